@@ -10,6 +10,44 @@ pub enum LogLevel {
     Trace,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum HookType {
+    PrePush,
+    PreCommit,
+}
+
+impl HookType {
+    /// File name of the hook inside the git hooks directory
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            HookType::PrePush => "pre-push",
+            HookType::PreCommit => "pre-commit",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, ValueEnum)]
+pub enum PlanFormat {
+    /// Plain markdown reports
+    #[default]
+    Markdown,
+    /// GitHub-flavored reports with collapsible sections, sized for PR comments
+    Github,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, ValueEnum)]
+pub enum ScanOutput {
+    /// Human-readable log output
+    #[default]
+    Text,
+    /// A JSON document on stdout, with all other output silenced
+    Json,
+    /// A YAML document on stdout, with all other output silenced
+    Yaml,
+    /// A GitHub Actions matrix with one entry per module and workspace, with all other output silenced
+    GhaMatrix,
+}
+
 #[derive(Parser)]
 #[command(
     author, 
@@ -24,8 +62,8 @@ pub struct Args {
         long,
         help = "Path to configuration file (solarboat.json)",
         long_help = "Specify a custom path to the configuration file. \
-                    If not provided, the tool will search for configuration files \
-                    in the current directory and parent directories."
+                    If not provided, or given a directory, the tool will search for configuration \
+                    files in that directory and its parents up to the root of the git repository."
     )]
     pub config: Option<String>,
 
@@ -39,6 +77,17 @@ pub struct Args {
     )]
     pub no_config: Option<String>,
 
+    #[clap(
+        long,
+        global = true,
+        value_name = "NAME",
+        help = "Configuration profile to apply",
+        long_help = "Apply the named entry of the configuration file's profiles section on top of its \
+                    other settings, e.g. --profile staging. The profile is deep-merged like a base file \
+                    of extends. Fails when the configuration file has no such profile."
+    )]
+    pub profile: Option<String>,
+
     #[clap(
         short,
         long,
@@ -59,6 +108,17 @@ pub struct Args {
     )]
     pub quiet: bool,
 
+    #[clap(
+        long,
+        global = true,
+        value_name = "N",
+        help = "Maximum directory depth of module discovery",
+        long_help = "Only look for modules up to N directory levels below the repository root, \
+                    so huge repositories or stray data directories don't make discovery walk the whole tree. \
+                    Overrides max_depth from the configuration file. Modules sourced from deeper directories are still found."
+    )]
+    pub max_depth: Option<usize>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -87,16 +147,128 @@ pub enum Commands {
                      Runs in dry-run mode by default for safety. Use --dry-run=false to apply actual changes."
     )]
     Apply(ApplyArgs),
+
+    #[command(
+        about = "Approve saved plans for apply",
+        long_about = "Marks module/workspace plans recorded in the plan manifest as approved. \
+                     When apply is given --plan-dir, only approved plans are applied unless --force is used."
+    )]
+    Approve(ApproveArgs),
+
+    #[command(
+        about = "Manage local git hooks",
+        long_about = "Installs git hooks that run 'solarboat scan' before code leaves your machine, \
+                     so you can see which modules CI will plan before pushing."
+    )]
+    Hooks(HooksArgs),
+
+    #[command(
+        about = "Check or regenerate dependency lock files",
+        long_about = "Checks .terraform.lock.hcl files of changed modules for being missing, uncommitted, \
+                     or inconsistent with required_providers, and regenerates them with \
+                     'terraform providers lock'."
+    )]
+    Lockfiles(LockfilesArgs),
+
+    #[command(
+        about = "Manage terraform workspaces across modules",
+        long_about = "Creates workspaces in every module matching a glob, for rolling out a new environment \
+                     across many modules at once."
+    )]
+    Workspaces(WorkspacesArgs),
 }
 
 #[derive(Parser)]
-pub struct ScanArgs {
+pub struct WorkspacesArgs {
+    #[command(subcommand)]
+    pub command: WorkspacesCommand,
+}
+
+#[derive(Subcommand)]
+pub enum WorkspacesCommand {
+    #[command(
+        about = "Create a workspace in matching modules",
+        long_about = "Creates the workspace with 'terraform workspace new' in each stateful module matching --modules \
+                     and selects it. Modules that already have the workspace only get it selected. \
+                     With --plan, each module is then planned in the new workspace with its workspace_var_files."
+    )]
+    Create(WorkspacesCreateArgs),
+}
+
+#[derive(Parser)]
+pub struct WorkspacesCreateArgs {
+    #[clap(
+        help = "Name of the workspace to create",
+        long_help = "Name of the workspace to create, as terraform lists it, including any workspace_prefix."
+    )]
+    pub name: String,
+
+    #[clap(
+        long,
+        required = true,
+        value_name = "GLOB",
+        value_delimiter = ',',
+        help = "Create the workspace in modules matching these globs",
+        long_help = "Stateful modules whose path matches one of the globs get the workspace. Globs work as for \
+                    plan --include-modules. Repeatable or comma-separated. Example: --modules 'stacks/**'"
+    )]
+    pub modules: Vec<String>,
+
     #[clap(
         long,
         default_value = ".",
-        help = "Root directory to scan for Terraform modules",
-        long_help = "The root directory where the scan will start looking for Terraform modules. \
-                    The scan will recursively search for .tf files in this directory and its subdirectories."
+        help = "Root directory containing Terraform modules",
+        long_help = "The root directory searched for the modules matching --modules."
+    )]
+    pub path: String,
+
+    #[clap(
+        long,
+        help = "Plan each module in the new workspace",
+        long_help = "After creating the workspace, plan each module in it with the workspace's var files \
+                    and save the plans to --output-dir."
+    )]
+    pub plan: bool,
+
+    #[clap(
+        long,
+        default_value = "terraform-plans",
+        help = "Directory to save the initial plans",
+        long_help = "The directory the plans made with --plan are saved to."
+    )]
+    pub output_dir: String,
+}
+
+#[derive(Parser)]
+pub struct LockfilesArgs {
+    #[command(subcommand)]
+    pub command: LockfilesCommand,
+}
+
+#[derive(Subcommand)]
+pub enum LockfilesCommand {
+    #[command(
+        about = "Flag modules with missing, uncommitted or inconsistent lock files",
+        long_about = "Checks each changed module's .terraform.lock.hcl. Fails if any lock file is missing, \
+                     not committed, has uncommitted changes, or does not match required_providers."
+    )]
+    Check(LockfilesCheckArgs),
+
+    #[command(
+        about = "Regenerate lock files for changed modules",
+        long_about = "Runs 'terraform providers lock' in each changed module, in parallel."
+    )]
+    Update(LockfilesUpdateArgs),
+}
+
+/// Module selection shared by the lockfiles subcommands
+#[derive(Parser)]
+pub struct LockfilesTargetArgs {
+    #[clap(
+        long,
+        default_value = ".",
+        help = "Root directory containing Terraform modules",
+        long_help = "The root directory containing Terraform modules whose lock files are processed."
     )]
     pub path: String,
 
@@ -105,9 +277,8 @@ pub struct ScanArgs {
         num_args = 0..=1,
         value_name = "BOOL",
         help = "Process all stateful modules regardless of changes",
-        long_help = "When enabled, this flag will process all stateful modules \
-                    in the specified directory, regardless of whether they have been changed. \
-                    Use --all=false to process only changed modules."
+        long_help = "When enabled, lock files of all stateful modules are processed, \
+                    regardless of whether the modules have been changed."
     )]
     pub all: Option<String>,
 
@@ -115,9 +286,7 @@ pub struct ScanArgs {
         long,
         default_value = "main",
         help = "Default branch to compare against for changes",
-        long_help = "Specify the default branch name to compare against when detecting changes. \
-                    This is used to determine which modules have been modified since the last \
-                    merge with the default branch. Default is 'main'."
+        long_help = "Specify the default branch name to compare against when detecting changes. Default is 'main'."
     )]
     pub default_branch: String,
 
@@ -126,42 +295,106 @@ pub struct ScanArgs {
         default_value = "5",
         help = "Number of recent commits to check when running locally (not in CD pipeline)",
         long_help = "When running locally (not in a CD pipeline), this specifies how many recent \
-                    commits to check for changes. Default is 5. This is ignored when running in \
-                    a CD pipeline (when SOLARBOAT_PR_NUMBER is set)."
+                    commits to check for changes. Default is 5."
     )]
     pub recent_commits: u32,
 }
 
 #[derive(Parser)]
-pub struct PlanArgs {
+pub struct LockfilesCheckArgs {
+    #[command(flatten)]
+    pub target: LockfilesTargetArgs,
+}
+
+#[derive(Parser)]
+pub struct LockfilesUpdateArgs {
+    #[command(flatten)]
+    pub target: LockfilesTargetArgs,
+
+    #[clap(
+        long,
+        default_value = "1",
+        help = "Number of modules to update in parallel (max 4)",
+        long_help = "Number of 'terraform providers lock' processes to run at once. Clamped to 4."
+    )]
+    pub parallel: u32,
+
+    #[clap(
+        long = "platform",
+        help = "Platform to record hashes for (repeatable)",
+        long_help = "Passed to 'terraform providers lock -platform'. Repeat for each platform, \
+                    e.g. --platform linux_amd64 --platform darwin_arm64. Defaults to the current platform."
+    )]
+    pub platforms: Vec<String>,
+}
+
+#[derive(Parser)]
+pub struct HooksArgs {
+    #[command(subcommand)]
+    pub command: HooksCommand,
+}
+
+#[derive(Subcommand)]
+pub enum HooksCommand {
+    #[command(
+        about = "Install a git hook that scans for changed modules",
+        long_about = "Writes a pre-push (default) or pre-commit hook into the repository's git hooks \
+                     directory. The hook runs 'solarboat scan' against the default branch and prints \
+                     the modules that will be planned in CI. It never blocks the push or commit."
+    )]
+    Install(HooksInstallArgs),
+}
+
+#[derive(Parser)]
+pub struct HooksInstallArgs {
+    #[clap(
+        long,
+        value_enum,
+        default_value = "pre-push",
+        help = "Git hook to install",
+        long_help = "Which git hook to install: pre-push (default) scans before pushing, \
+                    pre-commit scans before every commit."
+    )]
+    pub hook: HookType,
+
     #[clap(
         long,
         default_value = ".",
-        help = "Root directory containing Terraform modules",
-        long_help = "The root directory containing Terraform modules to be planned. \
-                    The command will recursively search for changed modules in this directory."
+        help = "Root directory the hook scans for Terraform modules",
+        long_help = "The root directory passed to 'solarboat scan --path' by the hook, \
+                    relative to the repository root."
     )]
     pub path: String,
-    
+
     #[clap(
         long,
-        default_value = "terraform-plans",
-        help = "Directory to save generated plan files",
-        long_help = "The directory where Terraform plan files will be saved. \
-                    Each module's plan will be saved as a separate file in this directory. \
-                    The directory will be created if it doesn't exist."
+        default_value = "main",
+        help = "Default branch the hook compares against",
+        long_help = "The default branch passed to 'solarboat scan --default-branch' by the hook."
     )]
-    pub output_dir: Option<String>,
+    pub default_branch: String,
 
     #[clap(
         long,
-        value_delimiter = ',',
-        help = "Comma-separated list of workspace names to ignore",
-        long_help = "Specify workspace names to skip during plan operation. \
-                    Multiple workspaces can be provided as comma-separated values. \
-                    Example: --ignore-workspaces dev,staging"
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Overwrite an existing hook",
+        long_help = "When enabled, an existing hook that was not installed by solarboat is \
+                    overwritten. Hooks previously installed by solarboat are always updated."
     )]
-    pub ignore_workspaces: Option<Vec<String>>,
+    pub force: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct ScanArgs {
+    #[clap(
+        long,
+        default_value = ".",
+        help = "Root directory to scan for Terraform modules",
+        long_help = "The root directory where the scan will start looking for Terraform modules. \
+                    The scan will recursively search for .tf files in this directory and its subdirectories."
+    )]
+    pub path: String,
 
     #[clap(
         long,
@@ -176,35 +409,65 @@ pub struct PlanArgs {
 
     #[clap(
         long,
-        help = "Comma-separated list of var files to use",
-        long_help = "Specify var files to use during plan operation. \
-                    Multiple var files can be provided as comma-separated values. \
-                    Example: --var-files var1.tfvars,var2.tfvars"
+        value_name = "GLOB",
+        value_delimiter = ',',
+        help = "Only process modules matching these globs",
+        long_help = "Keeps only the detected modules whose path matches one of the globs. Relative globs match \
+                    paths relative to the current directory; * and ? match within a directory name and ** \
+                    matches any number of directories. Repeatable or comma-separated. \
+                    Example: --include-modules '**/networking*'"
     )]
-    pub var_files: Option<Vec<String>>,
+    pub include_modules: Vec<String>,
 
     #[clap(
         long,
-        num_args = 0..=1,
-        value_name = "BOOL",
-        help = "Watch background Terraform operations and display real-time status",
-        long_help = "When enabled, Terraform operations will run in the background \
-                    and this CLI will display real-time status updates. \
-                    Without this flag, Terraform output is hidden until completion. \
-                    Use --watch=false to hide real-time output."
+        value_name = "GLOB",
+        value_delimiter = ',',
+        help = "Skip modules matching these globs",
+        long_help = "Drops the detected modules whose path matches one of the globs, after --include-modules. \
+                    Globs work as for --include-modules. Example: --exclude-modules 'legacy/**'"
     )]
-    pub watch: Option<String>,
+    pub exclude_modules: Vec<String>,
 
-    /// Number of modules to process in parallel (max 4). Default is 1. This value is clamped to prevent system overload.
     #[clap(
         long,
-        default_value = "1",
-        help = "Number of parallel module processes (max 4)",
-        long_help = "Specify the number of modules to process in parallel. \
-                    The value is clamped to a maximum of 4 to prevent system overload. \
-                    Default is 1 (sequential processing)."
+        value_name = "TAG",
+        value_delimiter = ',',
+        help = "Process all stateful modules with one of these tags, regardless of changes",
+        long_help = "Selects every stateful module tagged with one of the given tags in its \"tags\" configuration, \
+                    whether or not it changed. Repeatable or comma-separated. Example: --tags network,dns"
     )]
-    pub parallel: u32,
+    pub tags: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "TAG",
+        value_delimiter = ',',
+        help = "Skip modules with any of these tags",
+        long_help = "Drops the selected modules tagged with one of the given tags in their \"tags\" configuration. \
+                    Repeatable or comma-separated. Example: --exclude-tags prod-critical"
+    )]
+    pub exclude_tags: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "K/N",
+        help = "Only process shard K of N of the selected modules",
+        long_help = "Splits the selected modules into N stable buckets by a hash of their repository-relative \
+                    paths and keeps bucket K (1 to N), so N CI jobs given 1/N to N/N process every module exactly \
+                    once. Example: --shard 2/4"
+    )]
+    pub shard: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Keep dependent modules in the same shard",
+        long_help = "With --shard, selected modules that depend on each other, through module sources, remote \
+                    states or depends_on, are assigned to the same shard."
+    )]
+    pub shard_by_dependency: Option<String>,
 
     #[clap(
         long,
@@ -225,93 +488,875 @@ pub struct PlanArgs {
                     a CD pipeline (when SOLARBOAT_PR_NUMBER is set)."
     )]
     pub recent_commits: u32,
-}
 
-#[derive(Parser)]
-pub struct ApplyArgs {
     #[clap(
         long,
-        default_value = ".",
-        help = "Root directory containing Terraform modules",
-        long_help = "The root directory containing Terraform modules to be applied. \
-                    The command will recursively search for changed modules in this directory."
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Also process stateful modules that consume outputs of changed modules",
+        long_help = "By default only changed stateful modules (and stateful modules using a changed \
+                    stateless module) are processed. When enabled, stateful modules that use a changed \
+                    module or read its state through terraform_remote_state are queued too, up to \
+                    propagate_depth levels away. Can also be enabled with propagate_dependents in the configuration."
     )]
-    pub path: String,
+    pub propagate_dependents: Option<String>,
 
     #[clap(
         long,
-        default_value = "true",
-        value_name = "BOOL",
-        help = "Run in dry-run mode (no changes will be applied)",
-        long_help = "When enabled (default), this flag will run the apply command in dry-run mode, \
-                    showing what changes would be made without actually applying them. \
-                    Use --dry-run=false to apply actual changes."
+        value_name = "REF",
+        help = "Commit, tag or branch to diff against for change detection",
+        long_help = "Detect changes between this ref and --head-ref (default: HEAD) instead of using the \
+                    current branch, merge-base, recent commits or PR environment variables. Useful for merge \
+                    trains and release tags, e.g. --base-ref v1.4.0 --head-ref v1.5.0."
     )]
-    pub dry_run: String,
+    pub base_ref: Option<String>,
 
     #[clap(
         long,
-        value_delimiter = ',',
-        help = "Comma-separated list of workspace names to ignore",
-        long_help = "Specify workspace names to skip during apply operation. \
-                    Multiple workspaces can be provided as comma-separated values. \
-                    Example: --ignore-workspaces dev,staging"
+        value_name = "REF",
+        help = "Commit, tag or branch whose changes are detected (requires --base-ref or --since)",
+        long_help = "The end of the range diffed against --base-ref for change detection. Defaults to HEAD. \
+                    Uncommitted changes are not included when comparing explicit refs."
     )]
-    pub ignore_workspaces: Option<Vec<String>>,
+    pub head_ref: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "WINDOW",
+        help = "Detect changes made within a time window, e.g. 24h, 7d or 2025-01-01",
+        long_help = "Detect changes relative to the newest commit before the cutoff instead of \
+                    --recent-commits or branch detection. Accepts a duration (s, m, h, d or w, e.g. 24h, 7d) or a \
+                    date (2025-01-01, optionally with a time). Combines with --head-ref but not --base-ref."
+    )]
+    pub since: Option<String>,
 
     #[clap(
         long,
         num_args = 0..=1,
         value_name = "BOOL",
-        help = "Process all stateful modules regardless of changes",
-        long_help = "When enabled, this flag will process all stateful modules \
-                    in the specified directory, regardless of whether they have been changed. \
-                    Use --all=false to process only changed modules."
+        help = "Explain why each module was selected",
+        long_help = "When enabled, lists every discovered module as stateful or stateless and why (backend or \
+                    cloud block, module blocks, local state file, root_modules or a stateful setting) with its \
+                    backend type, bucket and key, and region, then for \
+                    each selected module the changed files that caused it to be included and the commits that \
+                    changed them, the stateless modules it was pulled in through, or the chain of modules it \
+                    depends on when added by --propagate-dependents."
     )]
-    pub all: Option<String>,
+    pub explain: Option<String>,
 
     #[clap(
         long,
-        help = "Comma-separated list of var files to use",
-        long_help = "Specify var files to use during apply operation. \
-                    Multiple var files can be provided as comma-separated values. \
-                    Example: --var-files var1.tfvars,var2.tfvars"
+        value_name = "FILE",
+        num_args = 1..,
+        help = "List the stateful modules changes to these files would affect",
+        long_help = "Skips git change detection and treats the given files as changed, then lists the stateful \
+                    modules that would be selected, directly or through the dependency graph, and why. \
+                    Paths are relative to the current directory. Honors --propagate-dependents. \
+                    Example: --impact modules/vpc/main.tf templates/user-data.sh"
     )]
-    pub var_files: Option<Vec<String>>,
+    pub impact: Vec<String>,
 
     #[clap(
         long,
-        num_args = 0..=1,
-        value_name = "BOOL",
-        help = "Watch background Terraform operations and display real-time status",
-        long_help = "When enabled, Terraform operations will run in the background \
-                    and this CLI will display real-time status updates. \
-                    Without this flag, Terraform output is hidden until completion. \
-                    Use --watch=false to hide real-time output."
+        value_enum,
+        default_value_t = ScanOutput::Text,
+        help = "Output format: text, json, yaml or gha-matrix",
+        long_help = "With json or yaml, prints a single document on stdout instead of the log output: each \
+                    selected module with its absolute and repository-relative path, whether it is stateful and \
+                    why, its backend, the files that triggered its selection, and the modules it depends on and \
+                    that use it. \
+                    With gha-matrix, prints {\"include\":[{\"module\":...,\"workspace\":...}]} on one line, with the \
+                    workspaces plan would run for each module, for a GitHub Actions job matrix. Listing workspaces \
+                    initializes the modules, so it needs access to their backends. \
+                    Errors are still reported on stderr and through the exit code."
     )]
-    pub watch: Option<String>,
+    pub output: ScanOutput,
 
-    /// Number of modules to process in parallel (max 4). Default is 1. This value is clamped to prevent system overload.
     #[clap(
         long,
-        default_value = "1",
-        help = "Number of parallel module processes (max 4)",
-        long_help = "Specify the number of modules to process in parallel. \
-                    The value is clamped to a maximum of 4 to prevent system overload. \
-                    Default is 1 (sequential processing)."
+        value_name = "FILE",
+        help = "Write the selected modules to a file for plan/apply --modules-file",
+        long_help = "Writes the selected modules to FILE, one path relative to the repository root per line, so a \
+                    later pipeline stage can plan or apply exactly these modules with --modules-file. An empty \
+                    file is written when no module is selected."
     )]
-    pub parallel: u32,
+    pub output_file: Option<String>,
+}
 
+#[derive(Parser)]
+pub struct PlanArgs {
     #[clap(
         long,
-        default_value = "main",
-        help = "Default branch to compare against for changes",
-        long_help = "Specify the default branch name to compare against when detecting changes. \
-                    This is used to determine which modules have been modified since the last \
-                    merge with the default branch. Default is 'main'."
+        default_value = ".",
+        help = "Root directory containing Terraform modules",
+        long_help = "The root directory containing Terraform modules to be planned. \
+                    The command will recursively search for changed modules in this directory."
     )]
-    pub default_branch: String,
-
+    pub path: String,
+    
+    #[clap(
+        long,
+        default_value = "terraform-plans",
+        help = "Directory to save generated plan files",
+        long_help = "The directory where Terraform plan files will be saved. \
+                    Each module's plan will be saved as a separate file in this directory. \
+                    The directory will be created if it doesn't exist."
+    )]
+    pub output_dir: Option<String>,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        help = "Comma-separated list of workspace names to ignore",
+        long_help = "Specify workspace names to skip during plan operation. \
+                    Multiple workspaces can be provided as comma-separated values. \
+                    Names may be globs, where * matches any characters and ? one character, \
+                    or regexes between slashes. \
+                    Example: --ignore-workspaces dev,staging,'pr-*'"
+    )]
+    pub ignore_workspaces: Option<Vec<String>>,
+
+    #[clap(
+        long,
+        value_name = "BOOL",
+        help = "Whether to plan the default workspace of modules with other workspaces",
+        long_help = "With false, the default workspace is skipped in modules that have other workspaces, \
+                    for teams that leave it empty. Modules with only the default workspace are always processed. \
+                    Overrides process_default_workspace from the configuration file (default: true)."
+    )]
+    pub process_default_workspace: Option<bool>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Process all stateful modules regardless of changes",
+        long_help = "When enabled, this flag will process all stateful modules \
+                    in the specified directory, regardless of whether they have been changed. \
+                    Use --all=false to process only changed modules."
+    )]
+    pub all: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "GLOB",
+        value_delimiter = ',',
+        help = "Only process modules matching these globs",
+        long_help = "Keeps only the detected modules whose path matches one of the globs. Relative globs match \
+                    paths relative to the current directory; * and ? match within a directory name and ** \
+                    matches any number of directories. Repeatable or comma-separated. \
+                    Example: --include-modules '**/networking*'"
+    )]
+    pub include_modules: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "GLOB",
+        value_delimiter = ',',
+        help = "Skip modules matching these globs",
+        long_help = "Drops the detected modules whose path matches one of the globs, after --include-modules. \
+                    Globs work as for --include-modules. Example: --exclude-modules 'legacy/**'"
+    )]
+    pub exclude_modules: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "TAG",
+        value_delimiter = ',',
+        help = "Process all stateful modules with one of these tags, regardless of changes",
+        long_help = "Selects every stateful module tagged with one of the given tags in its \"tags\" configuration, \
+                    whether or not it changed. Repeatable or comma-separated. Example: --tags network,dns"
+    )]
+    pub tags: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "TAG",
+        value_delimiter = ',',
+        help = "Skip modules with any of these tags",
+        long_help = "Drops the selected modules tagged with one of the given tags in their \"tags\" configuration. \
+                    Repeatable or comma-separated. Example: --exclude-tags prod-critical"
+    )]
+    pub exclude_tags: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "K/N",
+        help = "Only process shard K of N of the selected modules",
+        long_help = "Splits the selected modules into N stable buckets by a hash of their repository-relative \
+                    paths and keeps bucket K (1 to N), so N CI jobs given 1/N to N/N process every module exactly \
+                    once. Example: --shard 2/4"
+    )]
+    pub shard: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Keep dependent modules in the same shard",
+        long_help = "With --shard, selected modules that depend on each other, through module sources, remote \
+                    states or depends_on, are assigned to the same shard."
+    )]
+    pub shard_by_dependency: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "FILE",
+        help = "Process the modules listed in a file instead of detecting changes ('-' for stdin)",
+        long_help = "Skips change detection and processes the modules listed in FILE, one per line, as written by \
+                    scan --output-file. Paths are relative to the repository root or absolute; blank lines and \
+                    lines starting with # are ignored. Use - to read the list from stdin. Filters such as \
+                    --include-modules and --shard still apply."
+    )]
+    pub modules_file: Option<String>,
+
+    #[clap(
+        long = "module",
+        value_name = "PATH",
+        help = "Process this module instead of detecting changes (repeatable)",
+        long_help = "Skips change detection and processes exactly the given module directories, relative to the \
+                    current directory. Workspaces, var files and module settings are resolved as usual. Useful for \
+                    emergency fixes that shouldn't need a commit to be detected. \
+                    Example: --module stacks/networking --module stacks/dns"
+    )]
+    pub modules: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Comma-separated list of var files to use",
+        long_help = "Specify var files to use during plan operation. \
+                    Multiple var files can be provided as comma-separated values. \
+                    Example: --var-files var1.tfvars,var2.tfvars"
+    )]
+    pub var_files: Option<Vec<String>>,
+
+    #[clap(
+        long = "var",
+        value_name = "KEY=VALUE",
+        help = "Variable to pass to terraform plan (repeatable)",
+        long_help = "Passed to 'terraform plan -var'. Repeat for each variable, \
+                    e.g. --var region=eu-west-1 --var instance_count=3. Overrides variables \
+                    with the same key configured for a module."
+    )]
+    pub vars: Vec<String>,
+
+    #[clap(
+        long = "backend-config",
+        value_name = "KEY=VALUE|FILE",
+        help = "Partial backend configuration for terraform init (repeatable)",
+        long_help = "Passed to 'terraform init -backend-config'. Repeat for each setting or file, \
+                    e.g. --backend-config bucket=tf-state-prod --backend-config prod.tfbackend. \
+                    Files are relative to the module directory. Added after the backend configuration \
+                    of the module and workspace, so its settings win."
+    )]
+    pub backend_config: Vec<String>,
+
+    #[clap(
+        long = "target",
+        value_name = "ADDRESS",
+        help = "Resource address to plan (repeatable)",
+        long_help = "Passed to 'terraform plan -target'. Repeat for each resource, \
+                    e.g. --target aws_instance.web --target module.db. Overrides the targets \
+                    configured for a module."
+    )]
+    pub targets: Vec<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Skip refreshing state (-refresh=false)",
+        long_help = "When enabled, passes -refresh=false to terraform plan to speed up runs on large states \
+                    where refresh dominates runtime. Modules can also set \"refresh\": false in their configuration."
+    )]
+    pub no_refresh: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "N",
+        help = "Terraform's own resource concurrency (-parallelism)",
+        long_help = "Passed to terraform plan as -parallelism=N to tune how many resources terraform \
+                    handles at once within a module. This is separate from --parallel, which controls how many \
+                    modules solarboat runs at once. Overrides tf_parallelism from the configuration."
+    )]
+    pub tf_parallelism: Option<u32>,
+
+    #[clap(
+        long,
+        value_name = "DURATION",
+        help = "Wait this long for a busy state lock (-lock-timeout)",
+        long_help = "Passed to terraform plan as -lock-timeout, e.g. --lock-timeout 5m, so runs queue on a \
+                    state lock held by another pipeline instead of failing immediately. \
+                    Overrides lock_timeout from the configuration."
+    )]
+    pub lock_timeout: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Watch background Terraform operations and display real-time status",
+        long_help = "When enabled, Terraform operations will run in the background \
+                    and this CLI will display real-time status updates. \
+                    Without this flag, Terraform output is hidden until completion. \
+                    Use --watch=false to hide real-time output."
+    )]
+    pub watch: Option<String>,
+
+    /// Number of modules to process in parallel (max 4). Default is 1. This value is clamped to prevent system overload.
+    #[clap(
+        long,
+        default_value = "1",
+        help = "Number of parallel module processes (max 4)",
+        long_help = "Specify the number of modules to process in parallel. \
+                    The value is clamped to a maximum of 4 to prevent system overload. \
+                    Default is 1 (sequential processing)."
+    )]
+    pub parallel: u32,
+
+    #[clap(
+        long,
+        help = "Serve run progress over HTTP on this local port",
+        long_help = "When set, a read-only HTTP endpoint on 127.0.0.1:<port> serves /status (JSON) \
+                    and /metrics (Prometheus text) with the current run state while modules are processed."
+    )]
+    pub status_port: Option<u16>,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value = "markdown",
+        help = "Format of the saved plan reports (markdown, github)",
+        long_help = "Layout of the {stem}.tfplan.md reports saved in the output directory. \
+                    'github' wraps each plan in collapsible <details> sections with a change count table, \
+                    truncates output to fit GitHub's comment size limit, and also writes a combined \
+                    plan-comment.md with a summary table of all plans at the top."
+    )]
+    pub plan_format: PlanFormat,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Also write all plans into a single static HTML report",
+        long_help = "When enabled, renders every plan of the run into report.html in the output directory, \
+                    with per-module navigation, change counts and highlighted plan output. \
+                    Upload it as a CI artifact for reviewers without terminal access."
+    )]
+    pub html_report: Option<String>,
+
+    #[clap(
+        long,
+        default_value = "main",
+        help = "Default branch to compare against for changes",
+        long_help = "Specify the default branch name to compare against when detecting changes. \
+                    This is used to determine which modules have been modified since the last \
+                    merge with the default branch. Default is 'main'."
+    )]
+    pub default_branch: String,
+
+    #[clap(
+        long,
+        default_value = "5",
+        help = "Number of recent commits to check when running locally (not in CD pipeline)",
+        long_help = "When running locally (not in a CD pipeline), this specifies how many recent \
+                    commits to check for changes. Default is 5. This is ignored when running in \
+                    a CD pipeline (when SOLARBOAT_PR_NUMBER is set)."
+    )]
+    pub recent_commits: u32,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Also process stateful modules that consume outputs of changed modules",
+        long_help = "By default only changed stateful modules (and stateful modules using a changed \
+                    stateless module) are processed. When enabled, stateful modules that use a changed \
+                    module or read its state through terraform_remote_state are queued too, up to \
+                    propagate_depth levels away. Can also be enabled with propagate_dependents in the configuration."
+    )]
+    pub propagate_dependents: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "REF",
+        help = "Commit, tag or branch to diff against for change detection",
+        long_help = "Detect changes between this ref and --head-ref (default: HEAD) instead of using the \
+                    current branch, merge-base, recent commits or PR environment variables. Useful for merge \
+                    trains and release tags, e.g. --base-ref v1.4.0 --head-ref v1.5.0."
+    )]
+    pub base_ref: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "REF",
+        help = "Commit, tag or branch whose changes are detected (requires --base-ref or --since)",
+        long_help = "The end of the range diffed against --base-ref for change detection. Defaults to HEAD. \
+                    Uncommitted changes are not included when comparing explicit refs."
+    )]
+    pub head_ref: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "WINDOW",
+        help = "Detect changes made within a time window, e.g. 24h, 7d or 2025-01-01",
+        long_help = "Detect changes relative to the newest commit before the cutoff instead of \
+                    --recent-commits or branch detection. Accepts a duration (s, m, h, d or w, e.g. 24h, 7d) or a \
+                    date (2025-01-01, optionally with a time). Combines with --head-ref but not --base-ref."
+    )]
+    pub since: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Report state size and resource counts per module/workspace",
+        long_help = "When enabled, pulls the state of every planned module/workspace after planning \
+                    and reports resource counts and state sizes, compared against previous runs \
+                    recorded in .solarboat/state-history.jsonl."
+    )]
+    pub state_stats: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Generate destroy plans (terraform plan -destroy)",
+        long_help = "When enabled, plans the destruction of every resource in the selected modules. \
+                    Destroy plans are saved and recorded in the manifest like normal plans, so a teardown \
+                    goes through the same review and approval flow before 'apply --plan-dir' runs it."
+    )]
+    pub destroy: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Plan the destruction of stateful modules whose directories were deleted",
+        long_help = "When enabled, each module whose .tf files were all deleted is checked out from the last commit \
+                    that contained it into a temporary git worktree, and a destroy plan is generated from its \
+                    backend. Without it, removed modules are only reported."
+    )]
+    pub plan_removed: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Generate refresh-only plans (terraform plan -refresh-only)",
+        long_help = "When enabled, plans only show differences between the state and real infrastructure \
+                    (drift) without proposing configuration changes. Plans are saved and recorded like normal \
+                    plans; applying them only updates the state. Cannot be combined with --destroy or --no-refresh."
+    )]
+    pub refresh_only: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Write a triage-<runid>.tar.gz bundle to the output directory when modules fail",
+        long_help = "When enabled and any module fails, bundles results.json, per-module logs, the resolved \
+                    configuration, tool versions and the git context used for change detection into \
+                    triage-<runid>.tar.gz in the output directory, ready to hand over as a single artifact."
+    )]
+    pub triage_bundle: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Re-run only the module/workspace pairs that failed in the last plan",
+        long_help = "Every plan records the outcome of each module/workspace pair in .solarboat/runs/plan.json. \
+                    When enabled, change detection is skipped and only the pairs that failed or were cancelled \
+                    in the last plan are queued again."
+    )]
+    pub retry_failed: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Cancel queued operations as soon as one module fails",
+        long_help = "By default every queued module/workspace pair is processed even after a failure. \
+                    When enabled, the first failure cancels all operations that have not started yet; \
+                    running operations finish. Cancelled pairs are reported separately from failed ones \
+                    and are re-run by --retry-failed."
+    )]
+    pub fail_fast: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Keep processing queued operations after a failure (default)",
+        long_help = "Process every queued module/workspace pair regardless of failures and report all \
+                    failures at the end. This is the default behavior; the flag makes it explicit and \
+                    cannot be combined with --fail-fast."
+    )]
+    pub continue_on_error: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct ApproveArgs {
+    #[clap(
+        long,
+        default_value = "terraform-plans",
+        help = "Directory containing the plan manifest",
+        long_help = "The directory where plan files and the plan manifest (manifest.json) were saved \
+                    by a previous plan run."
+    )]
+    pub plan_dir: String,
+
+    #[clap(
+        long = "module",
+        help = "Module path or name to approve (repeatable)",
+        long_help = "Approve plans for the given module. Accepts the module path relative to the \
+                    repository root or just the module directory name. Can be repeated. \
+                    Example: --module infrastructure/networking --module webapp"
+    )]
+    pub modules: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Only approve plans for this workspace",
+        long_help = "Restrict approval to plans generated for the given workspace."
+    )]
+    pub workspace: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Approve every pending plan in the manifest",
+        long_help = "When enabled, approves all pending plans in the manifest. \
+                    Required when no --module is given."
+    )]
+    pub all: Option<String>,
+
+    #[clap(
+        long,
+        help = "Name recorded as the approver",
+        long_help = "Name recorded in the manifest as the approver. Defaults to the USER environment variable."
+    )]
+    pub approver: Option<String>,
+}
+
+#[derive(Parser)]
+pub struct ApplyArgs {
+    #[clap(
+        long,
+        default_value = ".",
+        help = "Root directory containing Terraform modules",
+        long_help = "The root directory containing Terraform modules to be applied. \
+                    The command will recursively search for changed modules in this directory."
+    )]
+    pub path: String,
+
+    #[clap(
+        long,
+        default_value = "true",
+        value_name = "BOOL",
+        help = "Run in dry-run mode (no changes will be applied)",
+        long_help = "When enabled (default), this flag will run the apply command in dry-run mode, \
+                    showing what changes would be made without actually applying them. \
+                    Use --dry-run=false to apply actual changes."
+    )]
+    pub dry_run: String,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        help = "Comma-separated list of workspace names to ignore",
+        long_help = "Specify workspace names to skip during apply operation. \
+                    Multiple workspaces can be provided as comma-separated values. \
+                    Names may be globs, where * matches any characters and ? one character, \
+                    or regexes between slashes. \
+                    Example: --ignore-workspaces dev,staging,'pr-*'"
+    )]
+    pub ignore_workspaces: Option<Vec<String>>,
+
+    #[clap(
+        long,
+        value_name = "BOOL",
+        help = "Whether to apply the default workspace of modules with other workspaces",
+        long_help = "With false, the default workspace is skipped in modules that have other workspaces, \
+                    for teams that leave it empty. Modules with only the default workspace are always processed. \
+                    Overrides process_default_workspace from the configuration file (default: true)."
+    )]
+    pub process_default_workspace: Option<bool>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Process all stateful modules regardless of changes",
+        long_help = "When enabled, this flag will process all stateful modules \
+                    in the specified directory, regardless of whether they have been changed. \
+                    Use --all=false to process only changed modules."
+    )]
+    pub all: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "GLOB",
+        value_delimiter = ',',
+        help = "Only process modules matching these globs",
+        long_help = "Keeps only the detected modules whose path matches one of the globs. Relative globs match \
+                    paths relative to the current directory; * and ? match within a directory name and ** \
+                    matches any number of directories. Repeatable or comma-separated. \
+                    Example: --include-modules '**/networking*'"
+    )]
+    pub include_modules: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "GLOB",
+        value_delimiter = ',',
+        help = "Skip modules matching these globs",
+        long_help = "Drops the detected modules whose path matches one of the globs, after --include-modules. \
+                    Globs work as for --include-modules. Example: --exclude-modules 'legacy/**'"
+    )]
+    pub exclude_modules: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "TAG",
+        value_delimiter = ',',
+        help = "Process all stateful modules with one of these tags, regardless of changes",
+        long_help = "Selects every stateful module tagged with one of the given tags in its \"tags\" configuration, \
+                    whether or not it changed. Repeatable or comma-separated. Example: --tags network,dns"
+    )]
+    pub tags: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "TAG",
+        value_delimiter = ',',
+        help = "Skip modules with any of these tags",
+        long_help = "Drops the selected modules tagged with one of the given tags in their \"tags\" configuration. \
+                    Repeatable or comma-separated. Example: --exclude-tags prod-critical"
+    )]
+    pub exclude_tags: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "K/N",
+        help = "Only process shard K of N of the selected modules",
+        long_help = "Splits the selected modules into N stable buckets by a hash of their repository-relative \
+                    paths and keeps bucket K (1 to N), so N CI jobs given 1/N to N/N process every module exactly \
+                    once. Example: --shard 2/4"
+    )]
+    pub shard: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Keep dependent modules in the same shard",
+        long_help = "With --shard, selected modules that depend on each other, through module sources, remote \
+                    states or depends_on, are assigned to the same shard."
+    )]
+    pub shard_by_dependency: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "FILE",
+        help = "Process the modules listed in a file instead of detecting changes ('-' for stdin)",
+        long_help = "Skips change detection and processes the modules listed in FILE, one per line, as written by \
+                    scan --output-file. Paths are relative to the repository root or absolute; blank lines and \
+                    lines starting with # are ignored. Use - to read the list from stdin. Filters such as \
+                    --include-modules and --shard still apply."
+    )]
+    pub modules_file: Option<String>,
+
+    #[clap(
+        long = "module",
+        value_name = "PATH",
+        help = "Process this module instead of detecting changes (repeatable)",
+        long_help = "Skips change detection and processes exactly the given module directories, relative to the \
+                    current directory. Workspaces, var files and module settings are resolved as usual. Useful for \
+                    emergency fixes that shouldn't need a commit to be detected. \
+                    Example: --module stacks/networking --module stacks/dns"
+    )]
+    pub modules: Vec<String>,
+
+    #[clap(
+        long,
+        help = "Comma-separated list of var files to use",
+        long_help = "Specify var files to use during apply operation. \
+                    Multiple var files can be provided as comma-separated values. \
+                    Example: --var-files var1.tfvars,var2.tfvars"
+    )]
+    pub var_files: Option<Vec<String>>,
+
+    #[clap(
+        long = "var",
+        value_name = "KEY=VALUE",
+        help = "Variable to pass to terraform apply (repeatable)",
+        long_help = "Passed to 'terraform apply -var'. Repeat for each variable, \
+                    e.g. --var region=eu-west-1 --var instance_count=3. Overrides variables \
+                    with the same key configured for a module."
+    )]
+    pub vars: Vec<String>,
+
+    #[clap(
+        long = "backend-config",
+        value_name = "KEY=VALUE|FILE",
+        help = "Partial backend configuration for terraform init (repeatable)",
+        long_help = "Passed to 'terraform init -backend-config'. Repeat for each setting or file, \
+                    e.g. --backend-config bucket=tf-state-prod --backend-config prod.tfbackend. \
+                    Files are relative to the module directory. Added after the backend configuration \
+                    of the module and workspace, so its settings win."
+    )]
+    pub backend_config: Vec<String>,
+
+    #[clap(
+        long = "target",
+        value_name = "ADDRESS",
+        help = "Resource address to apply (repeatable)",
+        long_help = "Passed to 'terraform apply -target'. Repeat for each resource, \
+                    e.g. --target aws_instance.web --target module.db. Overrides the targets \
+                    configured for a module. Ignored when applying saved plans."
+    )]
+    pub targets: Vec<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Skip refreshing state (-refresh=false)",
+        long_help = "When enabled, passes -refresh=false to terraform apply to speed up runs on large states \
+                    where refresh dominates runtime. Modules can also set \"refresh\": false in their configuration."
+    )]
+    pub no_refresh: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "N",
+        help = "Terraform's own resource concurrency (-parallelism)",
+        long_help = "Passed to terraform apply as -parallelism=N to tune how many resources terraform \
+                    handles at once within a module. This is separate from --parallel, which controls how many \
+                    modules solarboat runs at once. Overrides tf_parallelism from the configuration."
+    )]
+    pub tf_parallelism: Option<u32>,
+
+    #[clap(
+        long,
+        value_name = "DURATION",
+        help = "Wait this long for a busy state lock (-lock-timeout)",
+        long_help = "Passed to terraform apply as -lock-timeout, e.g. --lock-timeout 5m, so runs queue on a \
+                    state lock held by another pipeline instead of failing immediately. \
+                    Overrides lock_timeout from the configuration."
+    )]
+    pub lock_timeout: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Watch background Terraform operations and display real-time status",
+        long_help = "When enabled, Terraform operations will run in the background \
+                    and this CLI will display real-time status updates. \
+                    Without this flag, Terraform output is hidden until completion. \
+                    Use --watch=false to hide real-time output."
+    )]
+    pub watch: Option<String>,
+
+    /// Number of modules to process in parallel (max 4). Default is 1. This value is clamped to prevent system overload.
+    #[clap(
+        long,
+        default_value = "1",
+        help = "Number of parallel module processes (max 4)",
+        long_help = "Specify the number of modules to process in parallel. \
+                    The value is clamped to a maximum of 4 to prevent system overload. \
+                    Default is 1 (sequential processing)."
+    )]
+    pub parallel: u32,
+
+    #[clap(
+        long,
+        help = "Serve run progress over HTTP on this local port",
+        long_help = "When set, a read-only HTTP endpoint on 127.0.0.1:<port> serves /status (JSON) \
+                    and /metrics (Prometheus text) with the current run state while modules are processed."
+    )]
+    pub status_port: Option<u16>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Write a triage-<runid>.tar.gz bundle when modules fail",
+        long_help = "When enabled and any module fails, bundles results.json, per-module logs, the resolved \
+                    configuration, tool versions and the git context used for change detection into \
+                    triage-<runid>.tar.gz in the plan directory (or the current directory without --plan-dir)."
+    )]
+    pub triage_bundle: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Re-run only the module/workspace pairs that failed in the last apply",
+        long_help = "Every apply records the outcome of each module/workspace pair in .solarboat/runs/apply.json. \
+                    When enabled, change detection is skipped and only the pairs that failed or were cancelled \
+                    in the last apply are queued again."
+    )]
+    pub retry_failed: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Cancel queued operations as soon as one module fails",
+        long_help = "By default every queued module/workspace pair is processed even after a failure. \
+                    When enabled, the first failure cancels all operations that have not started yet; \
+                    running operations finish. Cancelled pairs are reported separately from failed ones \
+                    and are re-run by --retry-failed."
+    )]
+    pub fail_fast: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Keep processing queued operations after a failure (default)",
+        long_help = "Process every queued module/workspace pair regardless of failures and report all \
+                    failures at the end. This is the default behavior; the flag makes it explicit and \
+                    cannot be combined with --fail-fast."
+    )]
+    pub continue_on_error: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Resume an interrupted apply, skipping module/workspace pairs it already applied",
+        long_help = "A live apply writes each module/workspace pair to .solarboat/runs/apply.checkpoint.jsonl \
+                    as soon as it finishes. When enabled, pairs that the checkpoint records as successfully \
+                    applied are skipped and the rest are applied as usual. The checkpoint is removed once an \
+                    apply finishes without failures. Has no effect in dry-run mode."
+    )]
+    pub resume: Option<String>,
+
+    #[clap(
+        long,
+        help = "Directory to save apply output reports",
+        long_help = "When set, terraform apply output is captured and saved as \
+                    {module}/{workspace}/{timestamp}.apply.md in this directory (see artifact_template), and every apply is \
+                    recorded in its apply-manifest.json, so applies can be reviewed after the fact."
+    )]
+    pub output_dir: Option<String>,
+
+    #[clap(
+        long,
+        default_value = "main",
+        help = "Default branch to compare against for changes",
+        long_help = "Specify the default branch name to compare against when detecting changes. \
+                    This is used to determine which modules have been modified since the last \
+                    merge with the default branch. Default is 'main'."
+    )]
+    pub default_branch: String,
+
     #[clap(
         long,
         default_value = "5",
@@ -321,4 +1366,111 @@ pub struct ApplyArgs {
                     a CD pipeline (when SOLARBOAT_PR_NUMBER is set)."
     )]
     pub recent_commits: u32,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Also process stateful modules that consume outputs of changed modules",
+        long_help = "By default only changed stateful modules (and stateful modules using a changed \
+                    stateless module) are processed. When enabled, stateful modules that use a changed \
+                    module or read its state through terraform_remote_state are queued too, up to \
+                    propagate_depth levels away. Can also be enabled with propagate_dependents in the configuration."
+    )]
+    pub propagate_dependents: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "REF",
+        help = "Commit, tag or branch to diff against for change detection",
+        long_help = "Detect changes between this ref and --head-ref (default: HEAD) instead of using the \
+                    current branch, merge-base, recent commits or PR environment variables. Useful for merge \
+                    trains and release tags, e.g. --base-ref v1.4.0 --head-ref v1.5.0."
+    )]
+    pub base_ref: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "REF",
+        help = "Commit, tag or branch whose changes are detected (requires --base-ref or --since)",
+        long_help = "The end of the range diffed against --base-ref for change detection. Defaults to HEAD. \
+                    Uncommitted changes are not included when comparing explicit refs."
+    )]
+    pub head_ref: Option<String>,
+
+    #[clap(
+        long,
+        value_name = "WINDOW",
+        help = "Detect changes made within a time window, e.g. 24h, 7d or 2025-01-01",
+        long_help = "Detect changes relative to the newest commit before the cutoff instead of \
+                    --recent-commits or branch detection. Accepts a duration (s, m, h, d or w, e.g. 24h, 7d) or a \
+                    date (2025-01-01, optionally with a time). Combines with --head-ref but not --base-ref."
+    )]
+    pub since: Option<String>,
+
+    #[clap(
+        long,
+        help = "Directory containing approved plans from a previous plan run",
+        long_help = "When set, apply reads the plan manifest (manifest.json) from this directory \
+                    and refuses to apply module/workspace pairs whose plans have not been approved \
+                    with 'solarboat approve'."
+    )]
+    pub plan_dir: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Apply even if plans have not been approved",
+        long_help = "When enabled, unapproved plans are applied with a warning instead of \
+                    aborting the run. A live apply without --plan-dir is also refused unless \
+                    this is set."
+    )]
+    pub force: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Apply the saved binary plans from --plan-dir instead of re-planning",
+        long_help = "When enabled, apply uses the binary plans recorded in the plan manifest. \
+                    Each plan's SHA-256 checksum and the originating commit SHA are verified \
+                    before applying; if SOLARBOAT_PLAN_SIGNING_KEY is set, plan signatures are \
+                    verified too. Verification failures cannot be overridden with --force."
+    )]
+    pub use_saved_plans: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Allow applying plans that delete resources",
+        long_help = "Plans that delete or replace resources are checked against the \
+                    destroy_guard setting in solarboat.json (off, flag, confirm or fail; default flag). \
+                    Saved plans in --plan-dir are checked from their summaries; other modules are planned first. \
+                    This flag satisfies the 'flag' and 'confirm' guards."
+    )]
+    pub allow_destroy: Option<String>,
+
+    #[clap(
+        long,
+        num_args = 0..=1,
+        value_name = "BOOL",
+        help = "Ask before applying each module/workspace",
+        long_help = "When enabled, the plan summary of each module/workspace is shown and you are asked \
+                    whether to apply it: y (yes), n (no), skip-all (skip the rest) or apply-all (apply the rest). \
+                    Selected modules are applied sequentially. Requires a terminal; plan summaries are read from --plan-dir."
+    )]
+    pub interactive: Option<String>,
+
+    #[clap(
+        long,
+        default_value = "SOLARBOAT_OWNERS",
+        help = "Rules file mapping module paths to required approvals and labels",
+        long_help = "CODEOWNERS-style rules file. Each line is a path pattern followed by requirements: \
+                    @user, @org/team, approvals:N or label:name. When SOLARBOAT_PR_NUMBER is set, apply \
+                    queries the pull request on GitHub (GITHUB_REPOSITORY, GITHUB_TOKEN) and refuses \
+                    modules whose requirements are not met. A missing file means no rules."
+    )]
+    pub owners_file: String,
 }
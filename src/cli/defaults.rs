@@ -0,0 +1,95 @@
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+
+use crate::cli::args::{Args, Commands, HooksCommand, LockfilesCommand};
+use crate::config::CliDefaults;
+
+/// Replace arguments left at their built-in defaults with the configured `defaults`.
+/// Values given on the command line always win.
+pub fn apply_defaults(args: &mut Args, matches: &ArgMatches, defaults: &CliDefaults) {
+    let Some((_, matches)) = matches.subcommand() else {
+        return;
+    };
+    let parallel = defaults.parallel;
+    let output_dir = defaults.output_dir.clone().map(Some);
+    let default_branch = defaults.default_branch.clone();
+    let recent_commits = defaults.recent_commits;
+    let watch = defaults.watch.map(|watch| Some(watch.to_string()));
+
+    match &mut args.command {
+        Commands::Scan(scan) => {
+            seed(&mut scan.default_branch, matches, "default_branch", default_branch);
+            seed(&mut scan.recent_commits, matches, "recent_commits", recent_commits);
+        }
+        Commands::Plan(plan) => {
+            seed(&mut plan.parallel, matches, "parallel", parallel);
+            seed(&mut plan.output_dir, matches, "output_dir", output_dir);
+            seed(&mut plan.default_branch, matches, "default_branch", default_branch);
+            seed(&mut plan.recent_commits, matches, "recent_commits", recent_commits);
+            seed(&mut plan.watch, matches, "watch", watch);
+        }
+        Commands::Apply(apply) => {
+            seed(&mut apply.parallel, matches, "parallel", parallel);
+            seed(&mut apply.default_branch, matches, "default_branch", default_branch);
+            seed(&mut apply.recent_commits, matches, "recent_commits", recent_commits);
+            seed(&mut apply.watch, matches, "watch", watch);
+        }
+        Commands::Lockfiles(lockfiles) => {
+            let Some((_, matches)) = matches.subcommand() else {
+                return;
+            };
+            let target = match &mut lockfiles.command {
+                LockfilesCommand::Check(check) => &mut check.target,
+                LockfilesCommand::Update(update) => {
+                    seed(&mut update.parallel, matches, "parallel", parallel);
+                    &mut update.target
+                }
+            };
+            seed(&mut target.default_branch, matches, "default_branch", default_branch);
+            seed(&mut target.recent_commits, matches, "recent_commits", recent_commits);
+        }
+        Commands::Hooks(hooks) => {
+            let Some((_, matches)) = matches.subcommand() else {
+                return;
+            };
+            let HooksCommand::Install(install) = &mut hooks.command;
+            seed(&mut install.default_branch, matches, "default_branch", default_branch);
+        }
+        Commands::Approve(_) | Commands::Workspaces(_) => {}
+    }
+}
+
+/// Set an argument to `default` unless it was given on the command line
+fn seed<T>(arg: &mut T, matches: &ArgMatches, id: &str, default: Option<T>) {
+    if let Some(default) = default {
+        if matches.value_source(id) != Some(ValueSource::CommandLine) {
+            *arg = default;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    #[test]
+    fn test_apply_defaults() {
+        let defaults = CliDefaults {
+            parallel: Some(3),
+            output_dir: Some("plans".to_string()),
+            default_branch: Some("develop".to_string()),
+            recent_commits: None,
+            watch: Some(true),
+        };
+        let matches = Args::command().try_get_matches_from(["solarboat", "plan", "--parallel", "2"]).unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        apply_defaults(&mut args, &matches, &defaults);
+        let Commands::Plan(plan) = args.command else { panic!("expected plan") };
+        assert_eq!(plan.parallel, 2);
+        assert_eq!(plan.output_dir.as_deref(), Some("plans"));
+        assert_eq!(plan.default_branch, "develop");
+        assert_eq!(plan.recent_commits, 5);
+        assert_eq!(plan.watch.as_deref(), Some("true"));
+    }
+}
@@ -1,2 +1,4 @@
 mod args;
-pub use self::args::{Args, Commands, ScanArgs, PlanArgs, ApplyArgs, LogLevel};
+mod defaults;
+pub use self::args::{Args, Commands, ScanArgs, PlanArgs, ApplyArgs, ApproveArgs, HooksArgs, HooksCommand, HooksInstallArgs, HookType, LockfilesArgs, LockfilesCommand, LockfilesTargetArgs, LogLevel, PlanFormat, ScanOutput, WorkspacesArgs, WorkspacesCommand, WorkspacesCreateArgs};
+pub use self::defaults::apply_defaults;
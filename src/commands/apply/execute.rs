@@ -1,6 +1,13 @@
 use crate::cli::ApplyArgs;
 use crate::config::Settings;
 use crate::utils::logger;
+use crate::utils::scan_utils::{self, CompareRefs, DetectionReport, Shard};
+use crate::utils::terraform_operations::TerraformArgs;
+use crate::utils::plan_manifest::PlanManifest;
+use crate::utils::triage::TriageOptions;
+use crate::utils::run_state::RunState;
+use crate::utils::{interactive, plan_json};
+use crate::commands::plan::helpers as plan_helpers;
 use super::helpers;
 use std::time::Instant;
 
@@ -21,6 +28,37 @@ pub fn execute(args: ApplyArgs, settings: &Settings) -> anyhow::Result<()> {
         }),
         None => false,
     };
+    // Tagged modules are selected whether or not they changed
+    let all = all || !args.tags.is_empty();
+    
+    let propagate_dependents = match &args.propagate_dependents {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --propagate-dependents: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let compare = CompareRefs::from_args(args.base_ref.as_deref(), args.head_ref.as_deref(), args.since.as_deref())
+        .map_err(|e| {
+            logger::error_box("Invalid Git Refs", &e);
+            anyhow::anyhow!(e)
+        })?;
+
+    let shard = args.shard.as_deref()
+        .map(Shard::parse)
+        .transpose()
+        .map_err(|e| {
+            logger::error_box("Invalid Shard", &e);
+            anyhow::anyhow!(e)
+        })?;
+    let shard_by_dependency = match &args.shard_by_dependency {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --shard-by-dependency: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
     
     let watch = match &args.watch {
         Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
@@ -30,17 +68,198 @@ pub fn execute(args: ApplyArgs, settings: &Settings) -> anyhow::Result<()> {
         None => false,
     };
 
+    let force = match &args.force {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --force: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let use_saved_plans = match &args.use_saved_plans {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --use-saved-plans: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let allow_destroy = match &args.allow_destroy {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --allow-destroy: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let interactive_apply = match &args.interactive {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --interactive: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let retry_failed = match &args.retry_failed {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --retry-failed: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let resume = match &args.resume {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --resume: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let fail_fast = match &args.fail_fast {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --fail-fast: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let continue_on_error = match &args.continue_on_error {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --continue-on-error: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    if fail_fast && continue_on_error {
+        logger::error_box("Conflicting Flags", "--fail-fast cannot be combined with --continue-on-error");
+        return Err(anyhow::anyhow!("--fail-fast cannot be combined with --continue-on-error"));
+    }
+    if !args.modules.is_empty() && args.modules_file.is_some() {
+        logger::error_box("Conflicting Flags", "--module cannot be combined with --modules-file");
+        return Err(anyhow::anyhow!("--module cannot be combined with --modules-file"));
+    }
+
+    let triage_bundle = match &args.triage_bundle {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --triage-bundle: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    if use_saved_plans && args.plan_dir.is_none() {
+        logger::error_box("Missing Plan Directory", "--use-saved-plans requires --plan-dir");
+        return Err(anyhow::anyhow!("--use-saved-plans requires --plan-dir"));
+    }
+
     // Show configuration summary
+    let no_refresh = match &args.no_refresh {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --no-refresh: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    if let Some(invalid) = args.vars.iter().find(|var| !var.contains('=')) {
+        logger::error_box("Invalid Variable", &format!("--var expects KEY=VALUE, got '{}'", invalid));
+        return Err(anyhow::anyhow!("Invalid --var '{}': expected KEY=VALUE", invalid));
+    }
+    let terraform_args = TerraformArgs {
+        vars: args.vars.clone(),
+        targets: args.targets.clone(),
+        no_refresh,
+        parallelism: args.tf_parallelism,
+        lock_timeout: args.lock_timeout.clone(),
+        destroy: false,
+        refresh_only: false,
+        backend_config: args.backend_config.clone(),
+        ..Default::default()
+    };
+
     logger::config_summary(&[
         ("Apply Path", &args.path),
         ("Default Branch", &args.default_branch),
         ("Recent Commits", &args.recent_commits.to_string()),
         ("Process All", &all.to_string()),
+        ("Propagate Dependents", &propagate_dependents.to_string()),
+        ("Compare Refs", &compare.as_ref().map(|refs| format!("{}..{}", refs.base, refs.head)).unwrap_or_else(|| "auto".to_string())),
         ("Watch Mode", &watch.to_string()),
         ("Parallel Jobs", &args.parallel.to_string()),
         ("Dry Run", &dry_run.to_string()),
+        ("Plan Directory", args.plan_dir.as_deref().unwrap_or("none")),
+        ("Force", &force.to_string()),
+        ("Use Saved Plans", &use_saved_plans.to_string()),
+        ("Allow Destroy", &allow_destroy.to_string()),
+        ("Interactive", &interactive_apply.to_string()),
+        ("Triage Bundle", &triage_bundle.to_string()),
+        ("Retry Failed", &retry_failed.to_string()),
+        ("Resume", &resume.to_string()),
+        ("Fail Fast", &fail_fast.to_string()),
+        ("Output Directory", args.output_dir.as_deref().unwrap_or("none")),
+        ("Variables", &if args.vars.is_empty() { "configured".to_string() } else { args.vars.iter().filter_map(|v| v.split_once('=')).map(|(k, _)| k).collect::<Vec<_>>().join(", ") }),
+        ("Skip Refresh", &if no_refresh { "true".to_string() } else { "configured".to_string() }),
+        ("Terraform Parallelism", &args.tf_parallelism.map(|n| n.to_string()).unwrap_or_else(|| "configured".to_string())),
+        ("Lock Timeout", args.lock_timeout.as_deref().unwrap_or("configured")),
+        ("Targets", &if args.targets.is_empty() { "configured".to_string() } else { args.targets.join(", ") }),
     ]);
 
+    // Load the plan manifest so unapproved plans can be refused; a live apply without one has nothing approved
+    let manifest = match (&args.plan_dir, dry_run) {
+        (None, false) if force => {
+            logger::warn("No --plan-dir given - applying without plan approvals because --force is set");
+            None
+        }
+        (None, false) => {
+            logger::error_box(
+                "Plan Approval Required",
+                "A live apply needs --plan-dir pointing at approved plans. Run 'solarboat plan --output-dir <dir>' and 'solarboat approve', or use --force to override."
+            );
+            return Err(anyhow::anyhow!("Refusing to apply without approved plans"));
+        }
+        (Some(plan_dir), false) => match PlanManifest::load(plan_dir) {
+            Ok(manifest) => Some(manifest),
+            Err(e) if force && !use_saved_plans => {
+                logger::warn(&format!("{} - continuing because --force is set", e));
+                None
+            }
+            Err(e) => {
+                logger::error_box("Plan Manifest Not Found", &e);
+                return Err(anyhow::anyhow!(e));
+            }
+        },
+        _ => None,
+    };
+
+    // Pause before applying plans that modify the same resources
+    if let (Some(plan_dir), Some(manifest)) = (&args.plan_dir, &manifest) {
+        let conflicts = plan_helpers::find_plan_conflicts(plan_dir, manifest);
+        if !conflicts.is_empty() {
+            logger::warning_box(
+                "Overlapping Resource Changes",
+                &format!("{} resource(s) are modified by more than one plan:\n{}", conflicts.len(), plan_json::format_conflicts(&conflicts))
+            );
+
+            if force {
+                logger::warn("Continuing despite overlapping changes because --force is set");
+            } else {
+                match interactive::confirm("Apply these plans anyway?") {
+                    Some(true) => logger::info("Continuing with overlapping changes"),
+                    Some(false) => return Err(anyhow::anyhow!("Apply cancelled due to overlapping resource changes")),
+                    None => {
+                        logger::error_box(
+                            "Overlapping Resource Changes",
+                            "Refusing to apply overlapping plans non-interactively. Re-run with --force to override."
+                        );
+                        return Err(anyhow::anyhow!("Refusing to apply {} overlapping resource change(s)", conflicts.len()));
+                    }
+                }
+            }
+        }
+    }
+
     if dry_run {
         logger::info("Running in dry-run mode (default) - no changes will be applied");
     } else {
@@ -50,11 +269,62 @@ pub fn execute(args: ApplyArgs, settings: &Settings) -> anyhow::Result<()> {
         );
     }
 
+    // Dry runs are recorded as plans
+    let retry_command = if dry_run { "plan" } else { "apply" };
+    let retry = if retry_failed {
+        match RunState::load(retry_command) {
+            Ok(state) if state.failed_modules().is_empty() => {
+                logger::success_box("Nothing to Retry", &format!("No module failed in the last {} run", retry_command));
+                return Ok(());
+            }
+            Ok(state) => {
+                logger::info(&format!("Retrying {} failed module(s) from the last {} run", state.failed_modules().len(), retry_command));
+                Some(state)
+            }
+            Err(e) => {
+                logger::error_box("No Previous Run", &e);
+                return Err(anyhow::anyhow!(e));
+            }
+        }
+    } else {
+        None
+    };
+
+    let checkpoint = if resume && dry_run {
+        logger::warn("--resume has no effect in dry-run mode");
+        None
+    } else if resume {
+        match RunState::load_checkpoint("apply") {
+            Ok(state) => {
+                logger::info(&format!("Resuming apply - {} module/workspace pair(s) already finished", state.entries.len()));
+                Some(state)
+            }
+            Err(e) => {
+                logger::error_box("No Apply to Resume", &e);
+                return Err(anyhow::anyhow!(e));
+            }
+        }
+    } else {
+        None
+    };
+
     // Get changed modules
     logger::step(1, 4, "Detecting changed modules");
     let progress = logger::progress("Analyzing git changes and module dependencies");
+    let mut report = DetectionReport::default();
     
-                match helpers::get_changed_modules(&args.path, all, &args.default_branch, args.recent_commits) {
+    let detected = if let Some(state) = &retry {
+        Ok(state.failed_modules())
+    } else if !args.modules.is_empty() {
+        std::env::current_dir()
+            .map_err(|e| format!("Failed to get current directory: {}", e))
+            .and_then(|current_dir| scan_utils::resolve_module_dirs(args.modules.iter().map(String::as_str), &current_dir))
+    } else if let Some(file) = &args.modules_file {
+        scan_utils::read_modules_file(file)
+    } else {
+        helpers::get_changed_modules(&args.path, all, &args.default_branch, args.recent_commits, propagate_dependents, compare.as_ref(), &mut report, settings.resolver())
+    };
+                match detected {
                 Ok(modules) => {
                     if let Some(progress) = progress {
                         progress.complete(true);
@@ -67,11 +337,23 @@ pub fn execute(args: ApplyArgs, settings: &Settings) -> anyhow::Result<()> {
                     "All stateful modules will be applied regardless of changes"
                 );
             } else {
-                if modules.is_empty() {
-                    logger::success_box(
-                        "No Changes Detected", 
-                        "No modules were changed since the last merge with the default branch"
+                if !report.removed.is_empty() {
+                    logger::warning_box(
+                        "Removed Modules",
+                        &format!(
+                            "{} stateful module(s) were deleted while their infrastructure may still exist:\n{}\nApply does not destroy them. Review their destruction with `solarboat plan --plan-removed`.",
+                            report.removed.len(),
+                            report.removed.iter().map(|m| format!("  • {}", m.describe())).collect::<Vec<_>>().join("\n")
+                        )
                     );
+                }
+                if modules.is_empty() {
+                    if report.removed.is_empty() {
+                        logger::success_box(
+                            "No Changes Detected", 
+                            "No modules were changed since the last merge with the default branch"
+                        );
+                    }
                     return Ok(());
                 }
                 logger::changes_detected(modules.len(), &modules);
@@ -79,7 +361,7 @@ pub fn execute(args: ApplyArgs, settings: &Settings) -> anyhow::Result<()> {
             
             // Filter modules based on the path argument if it's not "."
             logger::step(2, 4, "Filtering modules by path");
-            let filtered_modules = if args.path != "." {
+            let mut filtered_modules = if args.path != "." {
                 logger::info(&format!("Filtering modules with path: {}", args.path));
                 modules.into_iter()
                     .filter(|path| {
@@ -91,6 +373,19 @@ pub fn execute(args: ApplyArgs, settings: &Settings) -> anyhow::Result<()> {
             } else {
                 modules
             };
+            if !args.include_modules.is_empty() || !args.exclude_modules.is_empty() {
+                filtered_modules = scan_utils::filter_modules(filtered_modules, &args.include_modules, &args.exclude_modules);
+                logger::info(&format!("{} modules match --include-modules/--exclude-modules", filtered_modules.len()));
+            }
+            if !args.tags.is_empty() || !args.exclude_tags.is_empty() {
+                filtered_modules = settings.resolver().select_tagged(filtered_modules, &args.tags, &args.exclude_tags);
+                logger::info(&format!("{} modules match --tags/--exclude-tags", filtered_modules.len()));
+            }
+            if let Some(shard) = shard {
+                let selected = filtered_modules.len();
+                filtered_modules = shard.select(filtered_modules, shard_by_dependency.then_some(&report.graph));
+                logger::info(&format!("Shard {}/{}: {} of {} modules", shard.index, shard.count, filtered_modules.len(), selected));
+            }
             
             if filtered_modules.is_empty() {
                 logger::warning_box(
@@ -107,8 +402,28 @@ pub fn execute(args: ApplyArgs, settings: &Settings) -> anyhow::Result<()> {
             logger::step(3, 4, "Executing Terraform apply");
             logger::info(&format!("Applying {} modules with {} parallel jobs", filtered_modules.len(), args.parallel));
             
-            match helpers::run_terraform_apply(&filtered_modules, dry_run, args.ignore_workspaces.as_deref(), args.var_files.as_deref(), settings.resolver(), watch, args.parallel) {
-                Ok(_) => {
+            if !dry_run {
+                helpers::check_owner_rules(&filtered_modules, &args.owners_file).map_err(|e| anyhow::anyhow!(e))?;
+            }
+            
+            let gate = helpers::PlanGate {
+                manifest: manifest.as_ref(),
+                plan_dir: args.plan_dir.as_deref(),
+                force,
+                use_saved_plans,
+                allow_destroy,
+                interactive: interactive_apply,
+            };
+            let triage = triage_bundle.then(|| TriageOptions {
+                output_dir: args.plan_dir.clone().unwrap_or_else(|| ".".to_string()),
+                root_dir: args.path.clone(),
+                default_branch: args.default_branch.clone(),
+                recent_commits: args.recent_commits,
+                all,
+            });
+            
+            match helpers::run_terraform_apply(&filtered_modules, dry_run, args.ignore_workspaces.as_deref(), args.var_files.as_deref(), &terraform_args, settings.resolver(), watch, args.parallel, args.status_port, &gate, args.output_dir.as_deref(), triage.as_ref(), retry.as_ref(), checkpoint.as_ref(), fail_fast) {
+                Ok(totals) => {
                     let duration = start_time.elapsed();
                     
                     if dry_run {
@@ -123,12 +438,17 @@ pub fn execute(args: ApplyArgs, settings: &Settings) -> anyhow::Result<()> {
                         );
                     }
                     
-                    logger::results_summary("Apply Results", &[
-                        ("Modules Applied", &filtered_modules.len().to_string()),
-                        ("Duration", &format!("{:.2}s", duration.as_secs_f64())),
-                        ("Parallel Jobs", &args.parallel.to_string()),
-                        ("Mode", if dry_run { "Dry Run" } else { "Live Apply" }),
-                    ]);
+                    let resources = totals.map(|t| t.headline());
+                    let mut summary = vec![
+                        ("Modules Applied", filtered_modules.len().to_string()),
+                        ("Duration", format!("{:.2}s", duration.as_secs_f64())),
+                        ("Parallel Jobs", args.parallel.to_string()),
+                        ("Mode", if dry_run { "Dry Run" } else { "Live Apply" }.to_string()),
+                    ];
+                    if let Some(resources) = resources {
+                        summary.push(("Resources", resources));
+                    }
+                    logger::results_summary("Apply Results", &summary.iter().map(|(k, v)| (*k, v.as_str())).collect::<Vec<_>>());
                 }
                 Err(e) => {
                     logger::error_box("Apply Failed", &e.to_string());
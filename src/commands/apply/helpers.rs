@@ -1,8 +1,18 @@
-use crate::utils::scan_utils;
+use crate::utils::scan_utils::{self, CompareRefs, DetectionReport};
 use crate::commands::plan::helpers as plan_helpers;
 use crate::utils::parallel_processor::ParallelProcessor;
-use crate::utils::terraform_operations::{TerraformOperation, OperationType};
-use crate::config::ConfigResolver;
+use crate::utils::terraform_operations::{self, TerraformArgs, TerraformOperation, OperationType, OperationResult, PlanFormat};
+use crate::utils::plan_manifest::{self, ApplyManifest, ChangeCounts, PlanManifest};
+use crate::utils::plan_integrity;
+use crate::config::{ConfigResolver, DestroyGuard};
+use crate::utils::interactive::{self, Choice};
+use crate::utils::plan_json::PlanSummary;
+use crate::utils::triage::TriageOptions;
+use crate::utils::run_state::{Checkpoint, RunState};
+use crate::utils::owners::{self, GitHubClient, OwnerRule, OwnerRules, Requirement};
+use crate::utils::error::ROLLBACK_CONTEXT;
+use std::collections::HashMap;
+use std::path::Path;
 use crate::utils::logger;
 use colored::*;
 
@@ -12,28 +22,96 @@ pub struct ModuleError {
     error: String,
 }
 
-pub fn get_changed_modules(root_dir: &str, force: bool, default_branch: &str, recent_commits: u32) -> Result<Vec<String>, String> {
-    scan_utils::get_changed_modules_clean(root_dir, force, default_branch, recent_commits)
+#[allow(clippy::too_many_arguments)]
+pub fn get_changed_modules(
+    root_dir: &str,
+    force: bool,
+    default_branch: &str,
+    recent_commits: u32,
+    propagate_dependents: bool,
+    compare: Option<&CompareRefs>,
+    report: &mut DetectionReport,
+    config_resolver: &ConfigResolver,
+) -> Result<Vec<String>, String> {
+    scan_utils::get_changed_modules_clean(
+        root_dir,
+        force,
+        default_branch,
+        recent_commits,
+        config_resolver.get_tracked_extensions().as_deref(),
+        &config_resolver.get_discovery_options(),
+        config_resolver.get_recurse_submodules(),
+        &config_resolver.get_graph_overrides(),
+        config_resolver.get_propagate_depth(propagate_dependents),
+        compare,
+        &config_resolver.get_detection_strategies(),
+    ).map(|scan| {
+        let (modules, details) = scan.into_parts();
+        *report = details;
+        modules
+    })
+}
+
+/// Plan manifest checks applied before a live apply
+pub struct PlanGate<'a> {
+    /// Manifest loaded from --plan-dir, if any
+    pub manifest: Option<&'a PlanManifest>,
+    /// Plan directory the manifest was loaded from
+    pub plan_dir: Option<&'a str>,
+    /// Apply unapproved plans with a warning instead of aborting
+    pub force: bool,
+    /// Apply the saved binary plans after verifying their integrity
+    pub use_saved_plans: bool,
+    /// Allow plans containing deletions past the destroy guard
+    pub allow_destroy: bool,
+    /// Show each module/workspace plan summary and ask before applying it
+    pub interactive: bool,
+}
+
+/// A module/workspace apply waiting to be queued, with its saved plan summary if any
+struct PendingApply {
+    label: String,
+    summary: Option<PlanSummary>,
+    operation: TerraformOperation,
 }
 
+/// Planned deletions for a module/workspace pair, with the guard that applies to it
+struct PlannedDestroys {
+    label: String,
+    guard: DestroyGuard,
+    addresses: Vec<String>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn run_terraform_apply(
     modules: &[String], 
     dry_run: bool,
     ignore_workspaces: Option<&[String]>,
     var_files: Option<&[String]>,
+    cli_args: &TerraformArgs,
     config_resolver: &ConfigResolver,
     watch: bool,
     parallel: u32,
-) -> Result<(), String> {
+    status_port: Option<u16>,
+    gate: &PlanGate,
+    output_dir: Option<&str>,
+    triage: Option<&TriageOptions>,
+    retry: Option<&RunState>,
+    resume: Option<&RunState>,
+    fail_fast: bool,
+) -> Result<Option<ChangeCounts>, String> {
     if dry_run {
         println!("🔍 Running in dry-run mode - executing plan instead of apply");
-        return plan_helpers::run_terraform_plan(modules, None, ignore_workspaces, var_files, config_resolver, watch, parallel);
+        return plan_helpers::run_terraform_plan(modules, None, ignore_workspaces, var_files, cli_args, config_resolver, watch, parallel, status_port, plan_helpers::PlanReports::default(), triage, retry, fail_fast, &[]).map(|_| None);
     }
 
     // Force parallel to 1 if watch mode is enabled
     let effective_parallel = if watch {
         println!("🔄 Watch mode enabled - forcing parallel processing to 1 for real-time output");
         1
+    } else if gate.interactive {
+        logger::info("Interactive mode enabled - applying modules sequentially");
+        1
     } else {
         parallel
     };
@@ -42,7 +120,28 @@ pub fn run_terraform_apply(
     let parallel_limit = effective_parallel.min(4) as usize;
     
     // Create parallel processor
-    let mut processor = ParallelProcessor::new(parallel_limit);
+    let processor = plan_helpers::with_group_limits(ParallelProcessor::new(parallel_limit).with_fail_fast(fail_fast), config_resolver, modules);
+    let mut processor = plan_helpers::with_parallel_workspaces(processor, config_resolver, modules);
+    
+    // Saved plans must come from the current commit and match their recorded checksums
+    let saved_plans = match (gate.use_saved_plans, gate.plan_dir) {
+        (true, Some(plan_dir)) => {
+            let manifest = gate.manifest.ok_or_else(|| "Applying saved plans requires a plan manifest".to_string())?;
+            plan_integrity::verify_commit(manifest).inspect_err(|e| {
+                logger::error_box("Plan Integrity Check Failed", e);
+            })?;
+            Some((plan_dir, manifest))
+        }
+        _ => None,
+    };
+    let signing_key = plan_integrity::signing_key();
+    
+    // Module/workspace pairs without an approved plan, with unverifiable saved plans, or with planned deletions
+    let mut unapproved = Vec::new();
+    let mut integrity_failures = Vec::new();
+    let mut destroys = Vec::new();
+    let mut unchecked = Vec::new();
+    let mut pending = Vec::new();
     
     // Build operations for all modules and workspaces
     for module in modules {
@@ -53,68 +152,217 @@ pub fn run_terraform_apply(
         
         logger::module_init_status(true);
         
-        let workspaces = plan_helpers::get_workspaces(module)?;
+        let workspaces = plan_helpers::module_workspaces(config_resolver, module, &cli_args.backend_config)?;
+        logger::workspace_discovery(&workspaces);
         
+        // A single workspace means the default workspace (None); environment directories take the var files of their environment
+        let mut targets = Vec::new();
         if workspaces.len() <= 1 {
-            // Single workspace (default)
-            let default_var_files = config_resolver.get_workspace_var_files(module, "default", var_files);
-            logger::workspace_discovery(&workspaces);
-            
-            let operation = TerraformOperation {
-                module_path: module.clone(),
-                workspace: None, // None means default workspace
-                var_files: default_var_files,
-                operation_type: OperationType::Apply,
-                watch,
-                skip_init: false, // Always initialize in parallel processor
-            };
-            processor.add_operation(operation).map_err(|e| format!("Failed to add operation: {}", e))?;
+            let environment = config_resolver.get_environment(module);
+            if let Some(environment) = environment.as_deref().filter(|e| config_resolver.should_ignore_workspace(module, e, ignore_workspaces)) {
+                logger::workspace_skip(environment, "configured");
+                continue;
+            }
+            if environment.as_deref().is_some_and(|e| !plan_helpers::is_listed_workspace(config_resolver, module, e)) {
+                continue;
+            }
+            targets.push((None, config_resolver.get_workspace_var_files(module, environment.as_deref().unwrap_or("default"), var_files)));
         } else {
-            logger::workspace_discovery(&workspaces);
-            
             for workspace in workspaces {
                 // Check if workspace should be ignored using config resolver
+                if workspace == "default" && !config_resolver.get_process_default_workspace(module) {
+                    logger::workspace_skip(&workspace, "auto-ignored");
+                    continue;
+                }
                 if config_resolver.should_ignore_workspace(module, &workspace, ignore_workspaces) {
-                    if workspace == "default" {
-                        logger::workspace_skip(&workspace, "auto-ignored");
-                        continue;
-                    } else {
-                        logger::workspace_skip(&workspace, "configured");
-                        continue;
-                    }
+                    logger::workspace_skip(&workspace, "configured");
+                    continue;
+                }
+                if !plan_helpers::is_listed_workspace(config_resolver, module, &workspace) {
+                    continue;
                 }
                 
                 // Get workspace-specific var files
                 let workspace_var_files = config_resolver.get_workspace_var_files(module, &workspace, var_files);
                 logger::workspace_processing(&workspace, workspace_var_files.len());
+                targets.push((Some(workspace), workspace_var_files));
+            }
+        }
+        
+        for (workspace, target_var_files) in targets {
+            let label = format!("{} ({})", plan_manifest::module_key(module), workspace.as_deref().unwrap_or("default"));
+            
+            if retry.is_some_and(|r| !r.is_failed(module, workspace.as_deref())) {
+                logger::info(&format!("Skipping {} - it succeeded in the last run", label));
+                continue;
+            }
+            
+            if resume.is_some_and(|c| c.is_succeeded(module, workspace.as_deref())) {
+                logger::info(&format!("Skipping {} - already applied by the interrupted run", label));
+                continue;
+            }
+            
+            if gate.manifest.and_then(|m| m.find(module, workspace.as_deref())).is_some_and(|e| e.clean) {
+                logger::info(&format!("Skipping {} - its plan reported no changes", label));
+                continue;
+            }
+            
+            let mut summary = None;
+            if let Some(manifest) = gate.manifest {
+                if !manifest.is_approved(module, workspace.as_deref()) {
+                    unapproved.push(label.clone());
+                }
                 
-                let operation = TerraformOperation {
-                    module_path: module.clone(),
-                    workspace: Some(workspace.clone()),
-                    var_files: workspace_var_files,
-                    operation_type: OperationType::Apply,
-                    watch,
-                    skip_init: false, // Always initialize in parallel processor
-                };
-                processor.add_operation(operation).map_err(|e| format!("Failed to add operation: {}", e))?;
+                if let (Some(plan_dir), Some(entry)) = (gate.plan_dir, manifest.find(module, workspace.as_deref())) {
+                    match entry.load_summary(plan_dir) {
+                        Ok(loaded) => summary = loaded,
+                        Err(e) => logger::warn(&format!("Could not check planned deletions for {}: {}", label, e)),
+                    }
+                }
+            }
+            let plan_file = match saved_plans {
+                Some((plan_dir, manifest)) => match verified_saved_plan(manifest, plan_dir, module, workspace.as_deref(), signing_key.as_deref()) {
+                    Ok(plan_file) => Some(plan_file),
+                    Err(e) => {
+                        integrity_failures.push(format!("{}: {}", label, e));
+                        None
+                    }
+                },
+                None => None,
+            };
+            
+            // Destroy and refresh-only plans keep their mode even without --use-saved-plans
+            let entry = gate.manifest.and_then(|m| m.find(module, workspace.as_deref()));
+            if let (None, Some(manifest), Some(entry)) = (saved_plans, gate.manifest, entry) {
+                if let Err(e) = plan_integrity::verify_signature(manifest, entry, signing_key.as_deref()) {
+                    integrity_failures.push(format!("{}: {}", label, e));
+                }
+            }
+            let destroy = entry.is_some_and(|e| e.destroy);
+            let refresh_only = entry.is_some_and(|e| e.refresh_only);
+            if destroy {
+                logger::warn(&format!("{} has a destroy plan - all of its resources will be destroyed", label));
+            }
+            
+            let operation = TerraformOperation {
+                module_path: module.clone(),
+                data_dir: plan_helpers::data_dir_for(config_resolver, module, workspace.as_deref()),
+                hooks: plan_helpers::hooks_for(config_resolver, module),
+                env: plan_helpers::env_for(config_resolver, module, workspace.as_deref())?,
+                args: TerraformArgs {
+                    destroy,
+                    refresh_only,
+                    ..plan_helpers::terraform_args_for(config_resolver, module, workspace.as_deref(), cli_args)
+                },
+                workspace,
+                var_files: target_var_files,
+                operation_type: OperationType::Apply {
+                    plan_file,
+                    report_dir: output_dir.map(|s| s.to_string()),
+                    snapshot: config_resolver.get_state_snapshots(module),
+                },
+                watch,
+                skip_init: false, // Always initialize in parallel processor
+            };
+            plan_helpers::register_sensitive_values(&operation);
+            
+            let guard = config_resolver.get_destroy_guard(module);
+            let summary = match guarded_summary(summary, guard, gate.allow_destroy, || preflight_summary(&operation)) {
+                Ok(summary) => summary,
+                Err(e) => {
+                    unchecked.push(format!("{}: {}", label, e));
+                    None
+                }
+            };
+            if let Some(summary) = summary.as_ref().filter(|s| !s.destroyed().is_empty()) {
+                destroys.push(PlannedDestroys {
+                    label: label.clone(),
+                    guard,
+                    addresses: summary.destroyed().iter().map(|a| a.to_string()).collect(),
+                });
             }
+            pending.push(PendingApply { label, summary, operation });
+        }
+    }
+    
+    if !integrity_failures.is_empty() {
+        let listing = integrity_failures.iter()
+            .map(|entry| format!("  • {}", entry))
+            .collect::<Vec<_>>()
+            .join("\n");
+        logger::error_box(
+            "Plan Integrity Check Failed",
+            &format!("{} plan(s) could not be verified:\n{}", integrity_failures.len(), listing)
+        );
+        return Err(format!("Refusing to apply {} unverified plan(s)", integrity_failures.len()));
+    }
+    
+    if !unapproved.is_empty() {
+        let listing = unapproved.iter()
+            .map(|entry| format!("  • {}", entry))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if gate.force {
+            logger::warning_box(
+                "Applying Unapproved Plans",
+                &format!("--force is set, applying {} unapproved plan(s):\n{}", unapproved.len(), listing)
+            );
+        } else {
+            logger::error_box(
+                "Unapproved Plans",
+                &format!("{} plan(s) have not been approved:\n{}\n\nRun 'solarboat approve' or use --force to override.", unapproved.len(), listing)
+            );
+            return Err(format!("Refusing to apply {} unapproved plan(s)", unapproved.len()));
         }
     }
     
+    if !unchecked.is_empty() {
+        logger::error_box(
+            "Destroy Guard",
+            &format!("Planned deletions of {} module(s) could not be checked:\n{}",
+                unchecked.len(), unchecked.iter().map(|entry| format!("  • {}", entry)).collect::<Vec<_>>().join("\n"))
+        );
+        return Err(format!("Refusing to apply {} module(s) with unchecked deletions", unchecked.len()));
+    }
+    check_destroy_guard(&destroys, gate.allow_destroy)?;
+    
+    if gate.interactive {
+        pending = confirm_each_apply(pending)?;
+        if pending.is_empty() {
+            logger::info("No modules selected for apply");
+            return Ok(Some(ChangeCounts::default()));
+        }
+    }
+    let checkpoint = Checkpoint::open("apply", resume.is_some())?;
+    processor = processor.with_checkpoint(checkpoint);
+    for apply in pending {
+        processor.add_operation(apply.operation).map_err(|e| format!("Failed to add operation: {}", e))?;
+    }
+    
     // Start processing
     logger::parallel_processing_start(parallel_limit);
     processor.start().map_err(|e| format!("Failed to start processor: {}", e))?;
+    let _status_server = status_port.and_then(|port| plan_helpers::start_status_server(port, "apply", &processor));
     
     // Wait for completion and collect results
     let results = processor.wait_for_completion().map_err(|e| format!("Failed to wait for completion: {}", e))?;
     let total_count = results.len();
+    plan_helpers::save_run_state("apply", &results);
+    
+    if let Some(output_dir) = output_dir {
+        record_applies(output_dir, &results);
+    }
+    let totals = report_apply_statistics(&results);
     
     // Process results and report failures
     let mut failed_modules = Vec::new();
+    let mut cancelled_modules = Vec::new();
     let mut successful_count = 0;
     
-    for result in results {
-        if !result.success {
+    for result in &results {
+        if result.cancelled {
+            cancelled_modules.push(plan_helpers::result_label(result));
+        } else if !result.success {
             let module_path = match &result.workspace {
                 Some(workspace) => format!("{}:{}", result.module_path, workspace),
                 None => result.module_path.clone(),
@@ -122,7 +370,7 @@ pub fn run_terraform_apply(
             
             failed_modules.push(ModuleError {
                 path: module_path,
-                error: result.error.unwrap_or_else(|| "Unknown error".to_string()),
+                error: result.error.clone().unwrap_or_else(|| "Unknown error".to_string()),
             });
         } else {
             successful_count += 1;
@@ -130,13 +378,13 @@ pub fn run_terraform_apply(
     }
     
     // Show processing summary
-    logger::processing_summary(total_count, successful_count, failed_modules.len());
+    logger::processing_summary(total_count, successful_count, failed_modules.len(), cancelled_modules.len());
     
     if !failed_modules.is_empty() {
         use crate::utils::logger;
         
-        // Show error summary
-        logger::error_summary("Apply Results", failed_modules.len(), total_count);
+        // Show error summary for the operations that ran
+        logger::error_summary("Apply Results", failed_modules.len(), total_count - cancelled_modules.len());
         
         println!("\n❌ Failed modules:");
         for failure in &failed_modules {
@@ -152,13 +400,317 @@ pub fn run_terraform_apply(
             
             println!("  • {}: {}", module_name.cyan(), friendly_error.dimmed());
         }
-        return Err(format!("Failed to process {} module(s)", failed_modules.len()));
+        plan_helpers::report_cancelled(&cancelled_modules);
+        offer_state_rollback(&results);
+        if let Some(triage) = triage {
+            plan_helpers::write_triage_bundle(triage, "apply", &results, config_resolver);
+        }
+        return Err(plan_helpers::failure_message(failed_modules.len(), cancelled_modules.len()));
     }
     
+    Checkpoint::clear("apply");
     println!("\n✅ All modules processed successfully!");
+    Ok(Some(totals))
+}
+
+/// Show each pending apply's plan summary and ask whether to apply it.
+/// `skip-all` and `apply-all` settle the remaining modules without asking again.
+fn confirm_each_apply(pending: Vec<PendingApply>) -> Result<Vec<PendingApply>, String> {
+    if !interactive::is_interactive() {
+        logger::error_box("Interactive Apply", "--interactive requires a terminal to answer prompts");
+        return Err("--interactive requires a terminal".to_string());
+    }
+    
+    let total = pending.len();
+    let mut selected = Vec::new();
+    let mut remaining = None;
+    
+    for (index, apply) in pending.into_iter().enumerate() {
+        let choice = match remaining {
+            Some(choice) => choice,
+            None => {
+                logger::section(&format!("Plan {}/{}: {}", index + 1, total, apply.label));
+                match &apply.summary {
+                    Some(summary) => {
+                        logger::info(&summary.headline());
+                        for resource in &summary.resources {
+                            println!("  {} {}", resource.action, resource.address);
+                        }
+                    }
+                    None => logger::info("No saved plan summary (run plan with --output-dir and pass it as --plan-dir)"),
+                }
+                interactive::choose(&format!("Apply {}?", apply.label))
+                    .ok_or_else(|| "Failed to read answer".to_string())?
+            }
+        };
+        
+        match choice {
+            Choice::Yes => selected.push(apply),
+            Choice::No => logger::info(&format!("Skipping {}", apply.label)),
+            Choice::SkipAll => {
+                logger::info(&format!("Skipping {}", apply.label));
+                remaining = Some(Choice::SkipAll);
+            }
+            Choice::ApplyAll => {
+                selected.push(apply);
+                remaining = Some(Choice::ApplyAll);
+            }
+        }
+    }
+    
+    logger::info(&format!("{} of {} module/workspace pair(s) selected for apply", selected.len(), total));
+    Ok(selected)
+}
+
+/// Report the pre-apply state snapshots of failed applies and, on a terminal, offer to push them back
+fn offer_state_rollback(results: &[OperationResult]) {
+    let snapshots: Vec<&OperationResult> = results.iter()
+        .filter(|r| !r.success && !r.cancelled && r.artifacts.state_snapshot.is_some())
+        .collect();
+    if snapshots.is_empty() {
+        return;
+    }
+    
+    let mut listing = Vec::new();
+    for result in snapshots {
+        let snapshot = result.artifacts.state_snapshot.clone().unwrap_or_default();
+        listing.push(format!("  • {}: {}", plan_helpers::result_label(result), snapshot));
+        
+        let module = result.module_path.clone();
+        let workspace = result.workspace.clone();
+        let data_dir = result.data_dir.clone();
+        let env = result.env.clone();
+        ROLLBACK_CONTEXT.add_operation(module.clone(), workspace.clone(), "apply".to_string(), move || {
+            terraform_operations::with_env(&env, || {
+                terraform_operations::restore_state(&module, workspace.as_deref(), data_dir.as_deref().map(Path::new), Path::new(&snapshot))
+            })
+        });
+    }
+    logger::warning_box(
+        "State Snapshots",
+        &format!("State saved before the failed applies:\n{}\n\nRestore one with 'terraform state push -force <snapshot>' in the module directory.", listing.join("\n"))
+    );
+    
+    let restore = interactive::confirm(
+        "Restore these snapshots? Resources the failed applies created or changed are not reverted and may no longer be tracked"
+    );
+    if restore != Some(true) {
+        ROLLBACK_CONTEXT.clear();
+        return;
+    }
+    for (label, result) in ROLLBACK_CONTEXT.execute_rollback() {
+        match result {
+            Ok(_) => logger::success(&format!("Restored state of {}", label)),
+            Err(e) => logger::error(&format!("Failed to restore state of {}: {}", label, e)),
+        }
+    }
+}
+
+/// Show the resources added, changed and destroyed per module/workspace and in total.
+/// Returns the totals.
+fn report_apply_statistics(results: &[OperationResult]) -> ChangeCounts {
+    let mut totals = ChangeCounts::default();
+    let mut rows = Vec::new();
+    
+    for result in results {
+        let Some(counts) = terraform_operations::parse_apply_counts(&result.output) else {
+            continue;
+        };
+        totals += counts;
+        rows.push((
+            format!("{} ({})", plan_manifest::module_key(&result.module_path), result.workspace.as_deref().unwrap_or("default")),
+            counts.headline(),
+        ));
+    }
+    
+    if rows.is_empty() {
+        return totals;
+    }
+    rows.push(("Total".to_string(), totals.headline()));
+    
+    logger::section("Apply Statistics");
+    logger::table(&rows.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect::<Vec<_>>());
+    totals
+}
+
+/// Append the outcome and report of every apply to the apply manifest
+fn record_applies(output_dir: &str, results: &[OperationResult]) {
+    let mut manifest = match ApplyManifest::load_or_default(output_dir) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            logger::warn(&e);
+            return;
+        }
+    };
+    for result in results {
+        manifest.record(&result.module_path, result.workspace.as_deref(), result.success, result.artifacts.report.clone());
+    }
+    match manifest.save(output_dir) {
+        Ok(_) => logger::info(&format!("Saved {} apply report(s) to {}", results.len(), output_dir)),
+        Err(e) => logger::warn(&format!("Failed to write apply manifest: {}", e)),
+    }
+}
+
+/// Check modules against the owners rules file in PR-integrated mode (SOLARBOAT_PR_NUMBER set).
+/// Modules whose required approvals or labels are missing on the pull request are refused.
+pub fn check_owner_rules(modules: &[String], owners_file: &str) -> Result<(), String> {
+    let check_failed = |e: String| {
+        logger::error_box("Owner Rules Check Failed", &e);
+        e
+    };
+    
+    let rules = OwnerRules::load(owners_file).map_err(check_failed)?;
+    if rules.rules.is_empty() {
+        return Ok(());
+    }
+    
+    let pr_number = match std::env::var("SOLARBOAT_PR_NUMBER") {
+        Ok(pr_number) => pr_number,
+        Err(_) => {
+            logger::debug(&format!("SOLARBOAT_PR_NUMBER is not set - skipping {} checks", owners_file));
+            return Ok(());
+        }
+    };
+    
+    let matched: Vec<(String, &OwnerRule)> = modules.iter()
+        .filter_map(|module| {
+            let key = plan_manifest::module_key(module);
+            rules.rule_for(&key).map(|rule| (key, rule))
+        })
+        .collect();
+    if matched.is_empty() {
+        return Ok(());
+    }
+    
+    logger::info(&format!("Checking {} module(s) against {} for PR #{}", matched.len(), owners_file, pr_number));
+    let client = GitHubClient::from_env().map_err(check_failed)?;
+    let state = client.pull_request_state(&pr_number).map_err(check_failed)?;
+    
+    let mut team_members = HashMap::new();
+    for (_, rule) in &matched {
+        for requirement in &rule.requirements {
+            if let Requirement::Team { org, team } = requirement {
+                if let std::collections::hash_map::Entry::Vacant(entry) = team_members.entry(format!("{}/{}", org, team)) {
+                    entry.insert(client.team_members(org, team).map_err(check_failed)?);
+                }
+            }
+        }
+    }
+    
+    let refused: Vec<String> = matched.iter()
+        .filter_map(|(module, rule)| {
+            let missing = owners::missing_requirements(rule, &state, &team_members);
+            if missing.is_empty() {
+                return None;
+            }
+            let missing = missing.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", ");
+            Some(format!("  • {} ({}): missing {}", module, rule.pattern, missing))
+        })
+        .collect();
+    
+    if !refused.is_empty() {
+        logger::error_box(
+            "Missing Required Approvals",
+            &format!("{} module(s) do not meet the rules in {}:\n{}", refused.len(), owners_file, refused.join("\n"))
+        );
+        return Err(format!("Refusing to apply {} module(s) without required approvals", refused.len()));
+    }
+    
+    logger::success(&format!("All {} module(s) meet their {} requirements", matched.len(), owners_file));
     Ok(())
 }
 
+/// Enforce the destroy guard on planned deletions.
+/// `flag` and `confirm` are satisfied by --allow-destroy; `confirm` can also be answered on a terminal.
+fn check_destroy_guard(destroys: &[PlannedDestroys], allow_destroy: bool) -> Result<(), String> {
+    let guarded: Vec<&PlannedDestroys> = destroys.iter().filter(|d| d.guard != DestroyGuard::Off).collect();
+    if guarded.is_empty() {
+        return Ok(());
+    }
+    
+    let listing = guarded.iter()
+        .map(|d| format!("  • {} [{}]\n{}", d.label, d.guard, d.addresses.iter().map(|a| format!("    - {}", a)).collect::<Vec<_>>().join("\n")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    logger::warning_box("Planned Deletions", &format!("{} plan(s) destroy resources:\n{}", guarded.len(), listing));
+    
+    let mut blocked: Vec<&str> = guarded.iter()
+        .filter(|d| d.guard == DestroyGuard::Fail || (d.guard == DestroyGuard::Flag && !allow_destroy))
+        .map(|d| d.label.as_str())
+        .collect();
+    
+    let needs_confirmation = guarded.iter().any(|d| d.guard == DestroyGuard::Confirm) && !allow_destroy;
+    if needs_confirmation && blocked.is_empty() {
+        match interactive::confirm("Apply plans that destroy resources?") {
+            Some(true) => logger::info("Deletions confirmed"),
+            Some(false) => return Err("Apply cancelled due to planned deletions".to_string()),
+            None => blocked.extend(guarded.iter().filter(|d| d.guard == DestroyGuard::Confirm).map(|d| d.label.as_str())),
+        }
+    }
+    
+    if !blocked.is_empty() {
+        logger::error_box(
+            "Destroy Guard",
+            &format!("Refusing to apply plans with deletions:\n{}\n\nReview the plans and re-run with --allow-destroy if the deletions are intended \
+                (modules with destroy_guard \"fail\" can never be applied with deletions).",
+                blocked.iter().map(|b| format!("  • {}", b)).collect::<Vec<_>>().join("\n"))
+        );
+        return Err(format!("Destroy guard blocked {} plan(s)", blocked.len()));
+    }
+    
+    Ok(())
+}
+
+/// Plan summary the destroy guard checks: the saved plan's, or else the one of a `preflight` plan when
+/// the guard could block the apply, so applies without --plan-dir can't bypass it
+fn guarded_summary(
+    summary: Option<PlanSummary>,
+    guard: DestroyGuard,
+    allow_destroy: bool,
+    preflight: impl FnOnce() -> Result<PlanSummary, String>,
+) -> Result<Option<PlanSummary>, String> {
+    if summary.is_some() || guard == DestroyGuard::Off || (allow_destroy && guard != DestroyGuard::Fail) {
+        return Ok(summary);
+    }
+    preflight().map(Some)
+}
+
+/// Plan an apply operation into a scratch directory and summarize what it would change
+fn preflight_summary(operation: &TerraformOperation) -> Result<PlanSummary, String> {
+    logger::info(&format!("Planning {} to check for deletions", plan_manifest::module_key(&operation.module_path)));
+    let plan_dir = std::env::temp_dir().join(format!("solarboat-preflight-{}", std::process::id()));
+    let data_dir = operation.data_dir.as_deref().map(Path::new);
+    let outcome = terraform_operations::with_env(&operation.env, || {
+        if let Some(workspace) = &operation.workspace {
+            terraform_operations::select_workspace(&operation.module_path, workspace, data_dir)?;
+        }
+        terraform_operations::run_single_plan(
+            &operation.module_path,
+            Some(&plan_dir.to_string_lossy()),
+            operation.workspace.as_deref(),
+            Some(&operation.var_files),
+            &operation.args,
+            PlanFormat::Markdown,
+            false,
+            data_dir,
+        )
+    });
+    let _ = std::fs::remove_dir_all(&plan_dir);
+    let outcome = outcome?;
+    if !outcome.success {
+        return Err(outcome.output.last().cloned().unwrap_or_else(|| "terraform plan failed".to_string()));
+    }
+    outcome.artifacts.summary.ok_or_else(|| "the plan could not be summarized".to_string())
+}
+
+/// Look up the saved plan for a module/workspace pair and verify its checksum and signature
+fn verified_saved_plan(manifest: &PlanManifest, plan_dir: &str, module: &str, workspace: Option<&str>, key: Option<&str>) -> Result<String, String> {
+    let entry = manifest.find(module, workspace)
+        .ok_or_else(|| "no saved plan in manifest".to_string())?;
+    plan_integrity::verify_entry(manifest, entry, plan_dir, key)
+        .map(|path| path.to_string_lossy().to_string())
+}
+
 /// Validate module configuration before processing
 fn validate_module_configuration(module_path: &str) -> Result<(), String> {
     // Check if terraform files exist
@@ -181,3 +733,35 @@ fn validate_module_configuration(module_path: &str) -> Result<(), String> {
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::plan_json::ResourceAction;
+
+    #[test]
+    fn test_destroy_guard_without_manifest() {
+        let deleting = || Ok(PlanSummary {
+            destroy: 1,
+            resources: vec![ResourceAction { address: "aws_vpc.main".to_string(), action: "delete".to_string() }],
+            ..Default::default()
+        });
+        let unexpected = || -> Result<PlanSummary, String> { panic!("planned without need") };
+
+        // Without a saved plan summary, guarded modules are planned first and their deletions blocked
+        let summary = guarded_summary(None, DestroyGuard::Flag, false, deleting).unwrap().unwrap();
+        let destroys = [PlannedDestroys {
+            label: "stacks/app (default)".to_string(),
+            guard: DestroyGuard::Flag,
+            addresses: summary.destroyed().iter().map(|a| a.to_string()).collect(),
+        }];
+        assert!(check_destroy_guard(&destroys, false).is_err());
+        assert!(guarded_summary(None, DestroyGuard::Fail, true, deleting).unwrap().is_some());
+        assert!(guarded_summary(None, DestroyGuard::Confirm, false, || Err("plan failed".to_string())).is_err());
+
+        // Nothing to plan when the guard can't block the apply or a summary is already known
+        assert!(guarded_summary(None, DestroyGuard::Off, false, unexpected).unwrap().is_none());
+        assert!(guarded_summary(None, DestroyGuard::Flag, true, unexpected).unwrap().is_none());
+        assert!(guarded_summary(Some(PlanSummary::default()), DestroyGuard::Fail, false, unexpected).unwrap().is_some());
+    }
+}
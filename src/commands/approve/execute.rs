@@ -0,0 +1,69 @@
+use crate::cli::ApproveArgs;
+use crate::config::Settings;
+use crate::utils::{logger, plan_integrity};
+use crate::utils::plan_manifest::PlanManifest;
+
+pub fn execute(args: ApproveArgs, _settings: &Settings) -> anyhow::Result<()> {
+    logger::section("Approve Plans");
+
+    let all = match &args.all {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --all: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    if !all && args.modules.is_empty() {
+        logger::error_box(
+            "Nothing To Approve",
+            "Specify one or more --module arguments, or use --all to approve every pending plan"
+        );
+        return Err(anyhow::anyhow!("No modules selected for approval"));
+    }
+
+    let approver = args.approver.clone()
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    logger::config_summary(&[
+        ("Plan Directory", &args.plan_dir),
+        ("Modules", &if all { "all".to_string() } else { args.modules.join(", ") }),
+        ("Workspace", args.workspace.as_deref().unwrap_or("all")),
+        ("Approver", &approver),
+    ]);
+
+    let mut manifest = PlanManifest::load(&args.plan_dir).map_err(|e| {
+        logger::error_box("Plan Manifest Not Found", &e);
+        anyhow::anyhow!(e)
+    })?;
+
+    // Signed manifests can only be approved with the signing key, and tampered entries are never re-signed
+    let signing_key = plan_integrity::signing_key();
+    plan_integrity::verify_signatures(&manifest, signing_key.as_deref()).map_err(|e| {
+        logger::error_box("Plan Integrity Check Failed", &e);
+        anyhow::anyhow!(e)
+    })?;
+
+    let modules: &[String] = if all { &[] } else { &args.modules };
+    let approved = manifest.approve(modules, args.workspace.as_deref(), Some(&approver));
+    plan_integrity::sign_manifest(&mut manifest, signing_key.as_deref());
+    manifest.save(&args.plan_dir).map_err(|e| anyhow::anyhow!(e))?;
+
+    let pending: Vec<String> = manifest.entries.iter()
+        .filter(|e| !e.approved)
+        .map(|e| format!("{} ({})", e.module, e.workspace))
+        .collect();
+
+    if approved == 0 {
+        logger::warning_box("No Plans Approved", "No pending plans matched the given selection");
+    } else {
+        logger::success_box("Plans Approved", &format!("Approved {} plan(s) as {}", approved, approver));
+    }
+
+    if !pending.is_empty() {
+        logger::list(&pending.iter().map(|s| s.as_str()).collect::<Vec<_>>(), Some("Still pending approval:"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,3 @@
+mod execute;
+
+pub use execute::execute;
@@ -0,0 +1,59 @@
+use crate::cli::{HooksArgs, HooksCommand, HooksInstallArgs};
+use crate::config::Settings;
+use crate::utils::logger;
+use super::helpers;
+
+pub fn execute(args: HooksArgs, _settings: &Settings) -> anyhow::Result<()> {
+    match args.command {
+        HooksCommand::Install(install_args) => install(install_args),
+    }
+}
+
+fn install(args: HooksInstallArgs) -> anyhow::Result<()> {
+    logger::section("Install Git Hook");
+
+    let force = match &args.force {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --force: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let hooks_dir = helpers::git_hooks_dir().map_err(|e| {
+        logger::error_box("Git Repository Not Found", &e);
+        anyhow::anyhow!(e)
+    })?;
+    let hook_path = hooks_dir.join(args.hook.file_name());
+
+    logger::config_summary(&[
+        ("Hook", args.hook.file_name()),
+        ("Hook Path", &hook_path.display().to_string()),
+        ("Scan Path", &args.path),
+        ("Default Branch", &args.default_branch),
+        ("Force", &force.to_string()),
+    ]);
+
+    if let Ok(existing) = std::fs::read_to_string(&hook_path) {
+        if !existing.contains(helpers::HOOK_MARKER) && !force {
+            logger::error_box(
+                "Hook Already Exists",
+                &format!("{} already exists and was not installed by solarboat. Use --force to overwrite it.", hook_path.display())
+            );
+            return Err(anyhow::anyhow!("Refusing to overwrite existing {} hook", args.hook.file_name()));
+        }
+    }
+
+    let content = helpers::render_hook(args.hook, &args.path, &args.default_branch);
+    helpers::write_hook(&hook_path, &content).map_err(|e| {
+        logger::error_box("Hook Installation Failed", &e);
+        anyhow::anyhow!(e)
+    })?;
+
+    logger::success_box(
+        "Hook Installed",
+        &format!("{} will now show the modules CI will plan", args.hook.file_name())
+    );
+
+    Ok(())
+}
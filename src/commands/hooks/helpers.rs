@@ -0,0 +1,103 @@
+use crate::cli::HookType;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Marker identifying hooks written by solarboat, so they can be updated safely
+pub const HOOK_MARKER: &str = "# Installed by solarboat hooks install";
+
+/// Resolve the git hooks directory, honouring core.hooksPath
+pub fn git_hooks_dir() -> Result<PathBuf, String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+    } else {
+        Err("Not inside a git repository".to_string())
+    }
+}
+
+/// Render the hook script. The scan is informational and never blocks git.
+pub fn render_hook(hook: HookType, path: &str, default_branch: &str) -> String {
+    let action = match hook {
+        HookType::PrePush => "push",
+        HookType::PreCommit => "commit",
+    };
+
+    format!(
+        r#"#!/bin/sh
+{marker}
+# Prints the Terraform modules CI will plan for this {action}.
+
+if ! command -v solarboat >/dev/null 2>&1; then
+    echo "solarboat: not found in PATH, skipping module scan" >&2
+    exit 0
+fi
+
+cd "$(git rev-parse --show-toplevel)" || exit 0
+
+echo "solarboat: modules affected by this {action}"
+solarboat scan --path '{path}' --default-branch '{default_branch}' || \
+    echo "solarboat: scan failed, continuing with {action}" >&2
+
+exit 0
+"#,
+        marker = HOOK_MARKER,
+        action = action,
+        path = path.replace('\'', ""),
+        default_branch = default_branch.replace('\'', ""),
+    )
+}
+
+/// Write the hook file and make it executable
+pub fn write_hook(hook_path: &std::path::Path, content: &str) -> Result<(), String> {
+    if let Some(parent) = hook_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create hooks directory: {}", e))?;
+    }
+
+    std::fs::write(hook_path, content)
+        .map_err(|e| format!("Failed to write {}: {}", hook_path.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(hook_path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to make {} executable: {}", hook_path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render_hook() {
+        let hook = render_hook(HookType::PrePush, "stacks/'app'", "main");
+        assert!(hook.starts_with("#!/bin/sh\n"));
+        assert!(hook.contains(HOOK_MARKER));
+        assert!(hook.contains("solarboat scan --path 'stacks/app' --default-branch 'main'"));
+        assert!(hook.contains("modules affected by this push"));
+        assert!(hook.trim_end().ends_with("exit 0"));
+        assert!(render_hook(HookType::PreCommit, ".", "main").contains("continuing with commit"));
+    }
+
+    #[test]
+    fn test_write_hook() {
+        let dir = TempDir::new().unwrap();
+        let hook_path = dir.path().join("hooks").join("pre-push");
+        write_hook(&hook_path, "#!/bin/sh\nexit 0\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&hook_path).unwrap(), "#!/bin/sh\nexit 0\n");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&hook_path).unwrap().permissions().mode() & 0o777, 0o755);
+        }
+    }
+}
@@ -0,0 +1,4 @@
+mod execute;
+mod helpers;
+
+pub use execute::execute;
@@ -0,0 +1,105 @@
+use crate::cli::{LockfilesArgs, LockfilesCommand, LockfilesTargetArgs};
+use crate::commands::plan::helpers as plan_helpers;
+use crate::config::Settings;
+use crate::utils::{lockfile, logger, scan_utils};
+use crate::utils::plan_manifest::module_key;
+
+pub fn execute(args: LockfilesArgs, settings: &Settings) -> anyhow::Result<()> {
+    match args.command {
+        LockfilesCommand::Check(check_args) => check(&check_args.target, settings),
+        LockfilesCommand::Update(update_args) => update(&update_args.target, update_args.parallel, &update_args.platforms, settings),
+    }
+}
+
+fn check(target: &LockfilesTargetArgs, settings: &Settings) -> anyhow::Result<()> {
+    logger::section("Lock File Check");
+
+    let modules = target_modules(target, settings)?;
+    if modules.is_empty() {
+        logger::success_box("No Changes Detected", "No modules to check");
+        return Ok(());
+    }
+
+    let problems: Vec<String> = modules.iter()
+        .filter_map(|module| {
+            let issues = lockfile::check_module(module);
+            if issues.is_empty() {
+                return None;
+            }
+            let issues = issues.iter().map(|i| format!("    - {}", i)).collect::<Vec<_>>().join("\n");
+            Some(format!("  • {}\n{}", module_key(module), issues))
+        })
+        .collect();
+
+    if !problems.is_empty() {
+        logger::error_box(
+            "Lock File Issues",
+            &format!("{} of {} module(s) have lock file issues:\n{}\n\nRun 'solarboat lockfiles update' to regenerate them.",
+                problems.len(), modules.len(), problems.join("\n"))
+        );
+        return Err(anyhow::anyhow!("{} module(s) have lock file issues", problems.len()));
+    }
+
+    logger::success_box("Lock Files Consistent", &format!("All {} module(s) have consistent lock files", modules.len()));
+    Ok(())
+}
+
+fn update(target: &LockfilesTargetArgs, parallel: u32, platforms: &[String], settings: &Settings) -> anyhow::Result<()> {
+    logger::section("Lock File Update");
+
+    let modules = target_modules(target, settings)?;
+    if modules.is_empty() {
+        logger::success_box("No Changes Detected", "No modules to update");
+        return Ok(());
+    }
+
+    logger::config_summary(&[
+        ("Modules", &modules.len().to_string()),
+        ("Parallel Jobs", &parallel.min(4).to_string()),
+        ("Platforms", &if platforms.is_empty() { "current".to_string() } else { platforms.join(", ") }),
+    ]);
+
+    let modules = modules.into_iter()
+        .map(|module| {
+            let env = plan_helpers::env_for(settings.resolver(), &module, None)?;
+            Ok((module, env))
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(anyhow::Error::msg)?;
+    let results = lockfile::update_lock_files(&modules, platforms, parallel as usize);
+    let failures: Vec<String> = results.iter()
+        .filter_map(|(module, result)| match result {
+            Ok(_) => {
+                logger::success(&format!("Updated {}", module_key(module)));
+                None
+            }
+            Err(e) => Some(format!("  • {}: {}", module_key(module), e)),
+        })
+        .collect();
+
+    if !failures.is_empty() {
+        logger::error_box("Lock File Update Failed", &failures.join("\n"));
+        return Err(anyhow::anyhow!("Failed to update {} lock file(s)", failures.len()));
+    }
+
+    logger::success_box("Lock Files Updated", &format!("Regenerated {} lock file(s). Review and commit the changes.", results.len()));
+    Ok(())
+}
+
+/// Resolve the modules selected by the shared target arguments
+fn target_modules(target: &LockfilesTargetArgs, settings: &Settings) -> anyhow::Result<Vec<String>> {
+    let all = match &target.all {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --all: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    scan_utils::get_changed_modules_clean(&target.path, all, &target.default_branch, target.recent_commits, settings.resolver().get_tracked_extensions().as_deref(), &settings.resolver().get_discovery_options(), settings.resolver().get_recurse_submodules(), &settings.resolver().get_graph_overrides(), settings.resolver().get_propagate_depth(false), None, &settings.resolver().get_detection_strategies())
+        .map(|scan| scan.paths())
+        .map_err(|e| {
+            logger::error_box("Module Detection Failed", &format!("Failed to get changed modules: {}", e));
+            anyhow::anyhow!("Failed to get changed modules: {}", e)
+        })
+}
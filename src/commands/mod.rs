@@ -1,13 +1,18 @@
 mod scan;
 mod plan;
 mod apply;
+mod approve;
+mod hooks;
+mod lockfiles;
+mod workspaces;
 
 use crate::cli::{Args, Commands};
 use crate::config::Settings;
 use anyhow::Result;
+use clap::ArgMatches;
 use std::path::PathBuf;
 
-pub fn handle_command(args: Args) -> Result<()> {
+pub fn handle_command(mut args: Args, matches: &ArgMatches) -> Result<()> {
     let no_config = match &args.no_config {
         Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
             eprintln!("Warning: Invalid value for --no-config: '{}'. Using default (true).", value);
@@ -17,22 +22,41 @@ pub fn handle_command(args: Args) -> Result<()> {
     };
     
     // Load configuration based on CLI arguments
-    let settings = if no_config {
+    let mut settings = if no_config {
         // Use default settings when config is disabled
         Settings {
             config_resolver: crate::config::ConfigResolver::new(None, PathBuf::from(".")),
         }
     } else if let Some(config_path) = &args.config {
         // Load from specified config file
-        Settings::load(config_path)?
+        Settings::load(config_path, args.profile.clone())?
     } else {
         // Auto-discover config file from current directory
-        Settings::load_from_current_dir()?
+        Settings::load_from_current_dir(args.profile.clone())?
     };
+    crate::cli::apply_defaults(&mut args, matches, &settings.config_resolver.get_cli_defaults());
+    settings.config_resolver = settings.config_resolver.with_max_depth(args.max_depth);
+    let process_default_workspace = match &args.command {
+        Commands::Plan(plan_args) => plan_args.process_default_workspace,
+        Commands::Apply(apply_args) => apply_args.process_default_workspace,
+        _ => None,
+    };
+    settings.config_resolver = settings.config_resolver.with_process_default_workspace(process_default_workspace);
+    if settings.config_resolver.has_branch_workspaces() {
+        let branch = crate::utils::scan_utils::get_current_branch(".").ok();
+        settings.config_resolver = settings.config_resolver.with_branch(branch);
+    }
+    crate::utils::redact::add_patterns(settings.config_resolver.get_redact_patterns());
+    crate::utils::env_passthrough::set_passthrough(settings.config_resolver.get_env_passthrough());
+    crate::utils::terraform_operations::set_artifact_template(settings.config_resolver.get_artifact_template());
 
     match args.command {
         Commands::Scan(scan_args) => scan::execute(scan_args, &settings),
         Commands::Plan(plan_args) => plan::execute(plan_args, &settings),
         Commands::Apply(apply_args) => apply::execute(apply_args, &settings),
+        Commands::Approve(approve_args) => approve::execute(approve_args, &settings),
+        Commands::Hooks(hooks_args) => hooks::execute(hooks_args, &settings),
+        Commands::Lockfiles(lockfiles_args) => lockfiles::execute(lockfiles_args, &settings),
+        Commands::Workspaces(workspaces_args) => workspaces::execute(workspaces_args, &settings),
     }
 }
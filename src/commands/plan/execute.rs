@@ -1,6 +1,10 @@
-use crate::cli::PlanArgs;
+use crate::cli::{self, PlanArgs};
 use crate::config::Settings;
 use crate::utils::logger;
+use crate::utils::scan_utils::{self, CompareRefs, DetectionReport, Shard};
+use crate::utils::terraform_operations::{PlanFormat, TerraformArgs};
+use crate::utils::triage::TriageOptions;
+use crate::utils::run_state::RunState;
 use super::helpers;
 use std::fs;
 use std::path::Path;
@@ -17,6 +21,37 @@ pub fn execute(args: PlanArgs, settings: &Settings) -> anyhow::Result<()> {
         }),
         None => false,
     };
+    // Tagged modules are selected whether or not they changed
+    let all = all || !args.tags.is_empty();
+    
+    let propagate_dependents = match &args.propagate_dependents {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --propagate-dependents: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let compare = CompareRefs::from_args(args.base_ref.as_deref(), args.head_ref.as_deref(), args.since.as_deref())
+        .map_err(|e| {
+            logger::error_box("Invalid Git Refs", &e);
+            anyhow::anyhow!(e)
+        })?;
+
+    let shard = args.shard.as_deref()
+        .map(Shard::parse)
+        .transpose()
+        .map_err(|e| {
+            logger::error_box("Invalid Shard", &e);
+            anyhow::anyhow!(e)
+        })?;
+    let shard_by_dependency = match &args.shard_by_dependency {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --shard-by-dependency: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
     
     let watch = match &args.watch {
         Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
@@ -26,20 +61,158 @@ pub fn execute(args: PlanArgs, settings: &Settings) -> anyhow::Result<()> {
         None => false,
     };
 
+    let state_stats = match &args.state_stats {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --state-stats: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let retry_failed = match &args.retry_failed {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --retry-failed: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let fail_fast = match &args.fail_fast {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --fail-fast: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let continue_on_error = match &args.continue_on_error {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --continue-on-error: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    if fail_fast && continue_on_error {
+        logger::error_box("Conflicting Flags", "--fail-fast cannot be combined with --continue-on-error");
+        return Err(anyhow::anyhow!("--fail-fast cannot be combined with --continue-on-error"));
+    }
+    if !args.modules.is_empty() && args.modules_file.is_some() {
+        logger::error_box("Conflicting Flags", "--module cannot be combined with --modules-file");
+        return Err(anyhow::anyhow!("--module cannot be combined with --modules-file"));
+    }
+
+    let triage_bundle = match &args.triage_bundle {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --triage-bundle: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let html_report = match &args.html_report {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --html-report: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let plan_format = match args.plan_format {
+        cli::PlanFormat::Markdown => PlanFormat::Markdown,
+        cli::PlanFormat::Github => PlanFormat::Github,
+    };
+
     let output_dir = args.output_dir.as_deref().unwrap_or("terraform-plans");
     let output_path = Path::new(output_dir);
 
     // Show configuration summary
+    let destroy = match &args.destroy {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --destroy: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let plan_removed = match &args.plan_removed {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --plan-removed: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let refresh_only = match &args.refresh_only {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --refresh-only: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let no_refresh = match &args.no_refresh {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --no-refresh: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    if refresh_only && (destroy || no_refresh) {
+        logger::error_box("Conflicting Flags", "--refresh-only cannot be combined with --destroy or --no-refresh");
+        return Err(anyhow::anyhow!("--refresh-only cannot be combined with --destroy or --no-refresh"));
+    }
+
+    if let Some(invalid) = args.vars.iter().find(|var| !var.contains('=')) {
+        logger::error_box("Invalid Variable", &format!("--var expects KEY=VALUE, got '{}'", invalid));
+        return Err(anyhow::anyhow!("Invalid --var '{}': expected KEY=VALUE", invalid));
+    }
+    let terraform_args = TerraformArgs {
+        vars: args.vars.clone(),
+        targets: args.targets.clone(),
+        no_refresh,
+        parallelism: args.tf_parallelism,
+        lock_timeout: args.lock_timeout.clone(),
+        destroy,
+        refresh_only,
+        backend_config: args.backend_config.clone(),
+        ..Default::default()
+    };
+
     logger::config_summary(&[
         ("Plan Path", &args.path),
         ("Output Directory", output_dir),
         ("Default Branch", &args.default_branch),
         ("Recent Commits", &args.recent_commits.to_string()),
         ("Process All", &all.to_string()),
+        ("Propagate Dependents", &propagate_dependents.to_string()),
+        ("Compare Refs", &compare.as_ref().map(|refs| format!("{}..{}", refs.base, refs.head)).unwrap_or_else(|| "auto".to_string())),
         ("Watch Mode", &watch.to_string()),
         ("Parallel Jobs", &args.parallel.to_string()),
+        ("State Stats", &state_stats.to_string()),
+        ("Triage Bundle", &triage_bundle.to_string()),
+        ("Retry Failed", &retry_failed.to_string()),
+        ("Fail Fast", &fail_fast.to_string()),
+        ("Plan Format", &format!("{:?}", plan_format).to_lowercase()),
+        ("HTML Report", &html_report.to_string()),
+        ("Variables", &if args.vars.is_empty() { "configured".to_string() } else { args.vars.iter().filter_map(|v| v.split_once('=')).map(|(k, _)| k).collect::<Vec<_>>().join(", ") }),
+        ("Skip Refresh", &if no_refresh { "true".to_string() } else { "configured".to_string() }),
+        ("Terraform Parallelism", &args.tf_parallelism.map(|n| n.to_string()).unwrap_or_else(|| "configured".to_string())),
+        ("Lock Timeout", args.lock_timeout.as_deref().unwrap_or("configured")),
+        ("Destroy Plan", &destroy.to_string()),
+        ("Plan Removed", &plan_removed.to_string()),
+        ("Refresh Only", &refresh_only.to_string()),
+        ("Targets", &if args.targets.is_empty() { "configured".to_string() } else { args.targets.join(", ") }),
     ]);
 
+    if destroy {
+        logger::warning_box(
+            "Destroy Plan Mode",
+            "Planning the destruction of every resource in the selected modules. Nothing is changed until the plans are applied."
+        );
+    }
+
     // Setup output directory
     logger::step(1, 4, "Setting up output directory");
     if output_path.exists() {
@@ -49,11 +222,42 @@ pub fn execute(args: PlanArgs, settings: &Settings) -> anyhow::Result<()> {
         fs::create_dir_all(output_dir)?;
     }
 
+    let retry = if retry_failed {
+        match RunState::load("plan") {
+            Ok(state) if state.failed_modules().is_empty() => {
+                logger::success_box("Nothing to Retry", &format!("No module failed in the last {} run", "plan"));
+                return Ok(());
+            }
+            Ok(state) => {
+                logger::info(&format!("Retrying {} failed module(s) from the last {} run", state.failed_modules().len(), "plan"));
+                Some(state)
+            }
+            Err(e) => {
+                logger::error_box("No Previous Run", &e);
+                return Err(anyhow::anyhow!(e));
+            }
+        }
+    } else {
+        None
+    };
+
     // Get changed modules
     logger::step(2, 4, "Detecting changed modules");
     let progress = logger::progress("Analyzing git changes and module dependencies");
+    let mut report = DetectionReport::default();
     
-                match helpers::get_changed_modules(&args.path, all, &args.default_branch, args.recent_commits) {
+    let detected = if let Some(state) = &retry {
+        Ok(state.failed_modules())
+    } else if !args.modules.is_empty() {
+        std::env::current_dir()
+            .map_err(|e| format!("Failed to get current directory: {}", e))
+            .and_then(|current_dir| scan_utils::resolve_module_dirs(args.modules.iter().map(String::as_str), &current_dir))
+    } else if let Some(file) = &args.modules_file {
+        scan_utils::read_modules_file(file)
+    } else {
+        helpers::get_changed_modules(&args.path, all, &args.default_branch, args.recent_commits, propagate_dependents, compare.as_ref(), &mut report, settings.resolver())
+    };
+                match detected {
                 Ok(modules) => {
                     if let Some(progress) = progress {
                         progress.complete(true);
@@ -66,11 +270,24 @@ pub fn execute(args: PlanArgs, settings: &Settings) -> anyhow::Result<()> {
                     "All stateful modules will be planned regardless of changes"
                 );
             } else {
-                if modules.is_empty() {
-                    logger::success_box(
-                        "No Changes Detected", 
-                        "No modules were changed since the last merge with the default branch"
+                if !report.removed.is_empty() {
+                    logger::warning_box(
+                        "Removed Modules",
+                        &format!(
+                            "{} stateful module(s) were deleted while their infrastructure may still exist:\n{}\n{}",
+                            report.removed.len(),
+                            report.removed.iter().map(|m| format!("  • {}", m.describe())).collect::<Vec<_>>().join("\n"),
+                            if plan_removed { "Destroy plans will be generated from their last commit." } else { "Pass --plan-removed to plan their destruction." }
+                        )
                     );
+                }
+                if modules.is_empty() && (report.removed.is_empty() || !plan_removed) {
+                    if report.removed.is_empty() {
+                        logger::success_box(
+                            "No Changes Detected", 
+                            "No modules were changed since the last merge with the default branch"
+                        );
+                    }
                     return Ok(());
                 }
                 logger::changes_detected(modules.len(), &modules);
@@ -78,7 +295,7 @@ pub fn execute(args: PlanArgs, settings: &Settings) -> anyhow::Result<()> {
             
             // Filter modules based on the path argument if it's not "."
             logger::step(3, 4, "Filtering modules by path");
-            let filtered_modules = if args.path != "." {
+            let mut filtered_modules = if args.path != "." {
                 logger::info(&format!("Filtering modules with path: {}", args.path));
                 modules.into_iter()
                     .filter(|path| {
@@ -90,24 +307,66 @@ pub fn execute(args: PlanArgs, settings: &Settings) -> anyhow::Result<()> {
             } else {
                 modules
             };
+            if !args.include_modules.is_empty() || !args.exclude_modules.is_empty() {
+                filtered_modules = scan_utils::filter_modules(filtered_modules, &args.include_modules, &args.exclude_modules);
+                logger::info(&format!("{} modules match --include-modules/--exclude-modules", filtered_modules.len()));
+            }
+            if !args.tags.is_empty() || !args.exclude_tags.is_empty() {
+                filtered_modules = settings.resolver().select_tagged(filtered_modules, &args.tags, &args.exclude_tags);
+                logger::info(&format!("{} modules match --tags/--exclude-tags", filtered_modules.len()));
+            }
+            if let Some(shard) = shard {
+                let selected = filtered_modules.len();
+                filtered_modules = shard.select(filtered_modules, shard_by_dependency.then_some(&report.graph));
+                logger::info(&format!("Shard {}/{}: {} of {} modules", shard.index, shard.count, filtered_modules.len(), selected));
+            }
             
-            if filtered_modules.is_empty() {
+            // Removed modules are planned from a worktree of their last commit, outside the scan path
+            let mut removed_paths = Vec::new();
+            if plan_removed {
+                for module in &report.removed {
+                    match scan_utils::checkout_removed_module(module) {
+                        Ok(path) => removed_paths.push(path),
+                        Err(e) => {
+                            logger::error_box("Removed Module Checkout Failed", &e);
+                            return Err(anyhow::anyhow!(e));
+                        }
+                    }
+                }
+            }
+            
+            if filtered_modules.is_empty() && removed_paths.is_empty() {
                 logger::warning_box(
                     "No Matching Modules", 
                     &format!("No modules match the specified path: {}", args.path)
                 );
                 return Ok(());
             }
+            filtered_modules.extend(removed_paths.iter().cloned());
             
             logger::section("Modules to Plan");
             logger::list(&filtered_modules.iter().map(|s| s.split('/').last().unwrap_or(s)).collect::<Vec<_>>(), None);
             
+            helpers::report_lock_issues(&filtered_modules);
+            
+            let triage = triage_bundle.then(|| TriageOptions {
+                output_dir: output_dir.to_string(),
+                root_dir: args.path.clone(),
+                default_branch: args.default_branch.clone(),
+                recent_commits: args.recent_commits,
+                all,
+            });
+            
             // Run terraform plan
             logger::step(4, 4, "Executing Terraform plans");
             logger::info(&format!("Planning {} modules with {} parallel jobs", filtered_modules.len(), args.parallel));
             
-            match helpers::run_terraform_plan(&filtered_modules, Some(output_dir), args.ignore_workspaces.as_deref(), args.var_files.as_deref(), settings.resolver(), watch, args.parallel) {
-                Ok(_) => {
+            match helpers::run_terraform_plan(&filtered_modules, Some(output_dir), args.ignore_workspaces.as_deref(), args.var_files.as_deref(), &terraform_args, settings.resolver(), watch, args.parallel, args.status_port, helpers::PlanReports { format: plan_format, html: html_report }, triage.as_ref(), retry.as_ref(), fail_fast, &removed_paths) {
+                Ok(results) => {
+                    if state_stats {
+                        helpers::report_state_stats(&results);
+                    }
+                    
                     let duration = start_time.elapsed();
                     logger::success_box(
                         "Plan Complete", 
@@ -1,7 +1,24 @@
-use crate::utils::scan_utils;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use regex::Regex;
+use crate::utils::scan_utils::{self, CompareRefs, DetectionReport};
 use crate::utils::parallel_processor::ParallelProcessor;
-use crate::utils::terraform_operations::{TerraformOperation, OperationType};
-use crate::config::ConfigResolver;
+use crate::utils::terraform_operations::{self, TerraformArgs, TerraformOperation, OperationType, OperationResult, PlanFormat};
+use crate::utils::state_stats;
+use crate::utils::plan_manifest::{self, PlanManifest, MANIFEST_FILE_NAME};
+use crate::utils::plan_json::{self, ResourceConflict};
+use crate::utils::plan_integrity;
+use crate::utils::status_server::StatusServer;
+use crate::utils::lockfile;
+use crate::utils::plan_report::{self, HtmlPlan};
+use crate::utils::triage::{self, TriageOptions};
+use crate::utils::run_state::RunState;
+use crate::utils::hooks::Hooks;
+use crate::utils::workspace_cache;
+use crate::utils::credentials;
+use crate::utils::secrets;
+use crate::utils::redact;
+use crate::config::{ConfigResolver, DataDirIsolation, WorkspaceSelection};
 use crate::utils::logger;
 
 #[derive(Debug)]
@@ -10,19 +27,62 @@ pub struct ModuleError {
     error: String,
 }
 
-pub fn get_changed_modules(root_dir: &str, force: bool, default_branch: &str, recent_commits: u32) -> Result<Vec<String>, String> {
-    scan_utils::get_changed_modules_clean(root_dir, force, default_branch, recent_commits)
+/// Reports written into the plan directory after planning
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlanReports {
+    /// Layout of the per-plan markdown reports
+    pub format: PlanFormat,
+    /// Also render all plans into a single static HTML page
+    pub html: bool,
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn get_changed_modules(
+    root_dir: &str,
+    force: bool,
+    default_branch: &str,
+    recent_commits: u32,
+    propagate_dependents: bool,
+    compare: Option<&CompareRefs>,
+    report: &mut DetectionReport,
+    config_resolver: &ConfigResolver,
+) -> Result<Vec<String>, String> {
+    scan_utils::get_changed_modules_clean(
+        root_dir,
+        force,
+        default_branch,
+        recent_commits,
+        config_resolver.get_tracked_extensions().as_deref(),
+        &config_resolver.get_discovery_options(),
+        config_resolver.get_recurse_submodules(),
+        &config_resolver.get_graph_overrides(),
+        config_resolver.get_propagate_depth(propagate_dependents),
+        compare,
+        &config_resolver.get_detection_strategies(),
+    ).map(|scan| {
+        let (modules, details) = scan.into_parts();
+        *report = details;
+        modules
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn run_terraform_plan(
     modules: &[String], 
     plan_dir: Option<&str>,
     ignore_workspaces: Option<&[String]>,
     var_files: Option<&[String]>,
+    cli_args: &TerraformArgs,
     config_resolver: &ConfigResolver,
     watch: bool,
     parallel: u32,
-) -> Result<(), String> {
+    status_port: Option<u16>,
+    reports: PlanReports,
+    triage: Option<&TriageOptions>,
+    retry: Option<&RunState>,
+    fail_fast: bool,
+    destroy_modules: &[String],
+) -> Result<Vec<OperationResult>, String> {
     // Force parallel to 1 if watch mode is enabled
     let effective_parallel = if watch {
         println!("🔄 Watch mode enabled - forcing parallel processing to 1 for real-time output");
@@ -35,7 +95,8 @@ pub fn run_terraform_plan(
     let parallel_limit = effective_parallel.min(4) as usize;
     
     // Create parallel processor
-    let mut processor = ParallelProcessor::new(parallel_limit);
+    let processor = with_group_limits(ParallelProcessor::new(parallel_limit).with_fail_fast(fail_fast), config_resolver, modules);
+    let mut processor = with_parallel_workspaces(processor, config_resolver, modules);
     
     // Build operations for all modules and workspaces
     for module in modules {
@@ -46,23 +107,43 @@ pub fn run_terraform_plan(
         
         logger::module_init_status(true);
         
-        let workspaces = get_workspaces(module)?;
+        let workspaces = module_workspaces(config_resolver, module, &cli_args.backend_config)?;
         
         if workspaces.len() <= 1 {
-            // Single workspace (default)
-            let default_var_files = config_resolver.get_workspace_var_files(module, "default", var_files);
+            // Single workspace (default); environment directories take the var files of their environment
+            let environment = config_resolver.get_environment(module);
+            if let Some(environment) = environment.as_deref().filter(|e| config_resolver.should_ignore_workspace(module, e, ignore_workspaces)) {
+                logger::workspace_skip(environment, "configured");
+                continue;
+            }
+            if environment.as_deref().is_some_and(|e| !is_listed_workspace(config_resolver, module, e)) {
+                continue;
+            }
+            let default_var_files = config_resolver.get_workspace_var_files(module, environment.as_deref().unwrap_or("default"), var_files);
             logger::workspace_discovery(&workspaces);
             
+            if retry.is_some_and(|r| !r.is_failed(module, None)) {
+                logger::workspace_skip("default", "succeeded in last run");
+                continue;
+            }
+            
             let operation = TerraformOperation {
                 module_path: module.clone(),
                 workspace: None, // None means default workspace
                 var_files: default_var_files,
+                args: TerraformArgs { destroy: cli_args.destroy || destroy_modules.contains(module), ..terraform_args_for(config_resolver, module, None, cli_args) },
                 operation_type: OperationType::Plan { 
-                    plan_dir: plan_dir.map(|s| s.to_string()) 
+                    plan_dir: plan_dir.map(|s| s.to_string()),
+                    format: reports.format,
+                    skip_empty: config_resolver.get_skip_empty_plans(module),
                 },
                 watch,
                 skip_init: false, // Always initialize in parallel processor
+                data_dir: data_dir_for(config_resolver, module, None),
+                hooks: hooks_for(config_resolver, module),
+                env: env_for(config_resolver, module, None)?,
             };
+            register_sensitive_values(&operation);
             processor.add_operation(operation).map_err(|e| format!("Failed to add operation: {}", e))?;
         } else {
             // Multiple workspaces
@@ -70,14 +151,20 @@ pub fn run_terraform_plan(
             
             for workspace in workspaces {
                 // Check if workspace should be ignored using config resolver
+                if workspace == "default" && !config_resolver.get_process_default_workspace(module) {
+                    logger::workspace_skip(&workspace, "auto-ignored");
+                    continue;
+                }
                 if config_resolver.should_ignore_workspace(module, &workspace, ignore_workspaces) {
-                    if workspace == "default" {
-                        logger::workspace_skip(&workspace, "auto-ignored");
-                        continue;
-                    } else {
-                        logger::workspace_skip(&workspace, "configured");
-                        continue;
-                    }
+                    logger::workspace_skip(&workspace, "configured");
+                    continue;
+                }
+                if !is_listed_workspace(config_resolver, module, &workspace) {
+                    continue;
+                }
+                if retry.is_some_and(|r| !r.is_failed(module, Some(&workspace))) {
+                    logger::workspace_skip(&workspace, "succeeded in last run");
+                    continue;
                 }
                 
                 // Get workspace-specific var files
@@ -88,13 +175,20 @@ pub fn run_terraform_plan(
                     module_path: module.clone(),
                     workspace: Some(workspace.clone()),
                     var_files: workspace_var_files,
+                    args: TerraformArgs { destroy: cli_args.destroy || destroy_modules.contains(module), ..terraform_args_for(config_resolver, module, Some(&workspace), cli_args) },
                     operation_type: OperationType::Plan { 
-                        plan_dir: plan_dir.map(|s| s.to_string()) 
+                        plan_dir: plan_dir.map(|s| s.to_string()),
+                        format: reports.format,
+                        skip_empty: config_resolver.get_skip_empty_plans(module),
                     },
                     watch,
                     skip_init: false, // Always initialize in parallel processor
+                    data_dir: data_dir_for(config_resolver, module, Some(&workspace)),
+                    hooks: hooks_for(config_resolver, module),
+                    env: env_for(config_resolver, module, Some(&workspace))?,
                 };
                 logger::debug(&format!("Adding operation for workspace: {}", workspace));
+                register_sensitive_values(&operation);
                 processor.add_operation(operation).map_err(|e| format!("Failed to add operation: {}", e))?;
             }
         }
@@ -103,15 +197,63 @@ pub fn run_terraform_plan(
     // Start processing
     logger::parallel_processing_start(parallel_limit);
     processor.start().map_err(|e| format!("Failed to start processor: {}", e))?;
+    let _status_server = status_port.and_then(|port| start_status_server(port, "plan", &processor));
     
     // Wait for completion and collect results
     let results = processor.wait_for_completion().map_err(|e| format!("Failed to wait for completion: {}", e))?;
+    save_run_state("plan", &results);
+    
+    report_plan_summaries(&results);
+    check_max_destroys(&results, config_resolver)?;
+    
+    // Record successful plans as pending approval in the plan manifest
+    if let Some(plan_dir) = plan_dir {
+        let mut manifest = PlanManifest::default();
+        for result in results.iter().filter(|r| r.success) {
+            manifest.add_pending(&result.module_path, result.workspace.as_deref(), &result.artifacts);
+        }
+        for entry in &mut manifest.entries {
+            entry.destroy = cli_args.destroy || destroy_modules.iter().any(|module| plan_manifest::module_key(module) == entry.module);
+            entry.refresh_only = cli_args.refresh_only;
+        }
+        if let Err(e) = plan_integrity::seal_manifest(&mut manifest, plan_dir, plan_integrity::signing_key().as_deref()) {
+            logger::warn(&format!("Failed to record plan checksums: {}", e));
+        }
+        match manifest.save(plan_dir) {
+            Ok(_) => logger::info(&format!("Recorded {} pending plan(s) in {}/{}", manifest.entries.len(), plan_dir, MANIFEST_FILE_NAME)),
+            Err(e) => logger::warn(&format!("Failed to write plan manifest: {}", e)),
+        }
+
+        if reports.format == PlanFormat::Github {
+            match write_github_comment(plan_dir, &results) {
+                Ok(path) => logger::info(&format!("Wrote GitHub plan comment to {}", path)),
+                Err(e) => logger::warn(&format!("Failed to write GitHub plan comment: {}", e)),
+            }
+        }
+        if reports.html {
+            match write_html_report(plan_dir, &results) {
+                Ok(path) => logger::info(&format!("Wrote HTML plan report to {}", path)),
+                Err(e) => logger::warn(&format!("Failed to write HTML plan report: {}", e)),
+            }
+        }
+
+        let conflicts = find_plan_conflicts(plan_dir, &manifest);
+        if !conflicts.is_empty() {
+            logger::warning_box(
+                "Overlapping Resource Changes",
+                &format!("{} resource(s) are modified by more than one plan:\n{}", conflicts.len(), plan_json::format_conflicts(&conflicts))
+            );
+        }
+    }
     
     // Process results and report failures
     let mut failed_modules = Vec::new();
+    let mut cancelled_modules = Vec::new();
     
-    for result in results {
-        if !result.success {
+    for result in &results {
+        if result.cancelled {
+            cancelled_modules.push(result_label(result));
+        } else if !result.success {
             let module_path = match &result.workspace {
                 Some(workspace) => format!("{}:{}", result.module_path, workspace),
                 None => result.module_path.clone(),
@@ -119,7 +261,7 @@ pub fn run_terraform_plan(
             
             failed_modules.push(ModuleError {
                 path: module_path,
-                error: result.error.unwrap_or_else(|| "Unknown error".to_string()),
+                error: result.error.clone().unwrap_or_else(|| "Unknown error".to_string()),
             });
         }
     }
@@ -129,21 +271,465 @@ pub fn run_terraform_plan(
         for failure in &failed_modules {
             println!("  ❌ {}: plan failed - {}", failure.path, failure.error);
         }
-        return Err(format!("Failed to process {} module(s)", failed_modules.len()));
+        report_cancelled(&cancelled_modules);
+        if let Some(triage) = triage {
+            write_triage_bundle(triage, "plan", &results, config_resolver);
+        }
+        return Err(failure_message(failed_modules.len(), cancelled_modules.len()));
     }
     
     println!("\n✅ All modules processed successfully!");
+    Ok(results)
+}
+
+/// `module:workspace` label of a result, or just the module for the default workspace
+pub fn result_label(result: &OperationResult) -> String {
+    match &result.workspace {
+        Some(workspace) => format!("{}:{}", result.module_path, workspace),
+        None => result.module_path.clone(),
+    }
+}
+
+/// List the module/workspace pairs that fail-fast mode cancelled
+pub fn report_cancelled(cancelled: &[String]) {
+    if cancelled.is_empty() {
+        return;
+    }
+    println!("\n⏹️  Cancelled after a failure (--fail-fast):");
+    for label in cancelled {
+        println!("  • {}", label);
+    }
+}
+
+/// Error returned when a run had failed operations
+pub fn failure_message(failed: usize, cancelled: usize) -> String {
+    if cancelled > 0 {
+        format!("Failed to process {} module(s), {} cancelled", failed, cancelled)
+    } else {
+        format!("Failed to process {} module(s)", failed)
+    }
+}
+
+/// Bundle the results, logs, resolved config and git context of a failed run for triage
+pub fn write_triage_bundle(options: &TriageOptions, command: &str, results: &[OperationResult], config_resolver: &ConfigResolver) {
+    match triage::write_bundle(options, command, results, &resolved_config_json(config_resolver, results)) {
+        Ok(path) => logger::info(&format!("Wrote triage bundle to {}", path.display())),
+        Err(e) => logger::warn(&format!("Failed to write triage bundle: {}", e)),
+    }
+}
+
+/// The loaded config plus the settings each module/workspace in the run resolved to
+fn resolved_config_json(config_resolver: &ConfigResolver, results: &[OperationResult]) -> serde_json::Value {
+    let modules: Vec<serde_json::Value> = results.iter()
+        .map(|result| {
+            let workspace = result.workspace.as_deref().unwrap_or("default");
+            serde_json::json!({
+                "module": plan_manifest::module_key(&result.module_path),
+                "workspace": workspace,
+                "var_files": config_resolver.get_workspace_var_files(&result.module_path, workspace, None),
+                "destroy_guard": config_resolver.get_destroy_guard(&result.module_path).to_string(),
+                "max_destroys": config_resolver.get_max_destroys(&result.module_path),
+                "data_dir_isolation": config_resolver.get_data_dir_isolation(&result.module_path).to_string(),
+                "targets": config_resolver.get_targets(&result.module_path, None),
+                "hooks": config_resolver.get_hooks(&result.module_path),
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "config": config_resolver.config(),
+        "modules": modules,
+    })
+}
+
+/// Warn about modules whose dependency lock files are missing, uncommitted or inconsistent
+pub fn report_lock_issues(modules: &[String]) {
+    for module in modules {
+        let issues = lockfile::check_module(module);
+        if !issues.is_empty() {
+            let issues = issues.iter().map(|i| i.to_string()).collect::<Vec<_>>().join("; ");
+            logger::warn(&format!("{}: {} (run 'solarboat lockfiles update')", plan_manifest::module_key(module), issues));
+        }
+    }
+}
+
+/// Serve the processor's progress over HTTP until the returned server is dropped
+pub fn start_status_server(port: u16, operation: &str, processor: &ParallelProcessor) -> Option<StatusServer> {
+    match StatusServer::start(port, operation, processor.progress_handle()) {
+        Ok(server) => {
+            logger::info(&format!("Serving run status at http://127.0.0.1:{}/status", server.port()));
+            Some(server)
+        }
+        Err(e) => {
+            logger::warn(&e);
+            None
+        }
+    }
+}
+
+/// Show the add/change/destroy summary of every plan that produced one
+fn report_plan_summaries(results: &[OperationResult]) {
+    let rows: Vec<(String, String)> = results.iter()
+        .filter_map(|result| {
+            let label = format!("{} ({})", plan_manifest::module_key(&result.module_path), result.workspace.as_deref().unwrap_or("default"));
+            if result.artifacts.clean {
+                return Some((label, "clean (no changes, no artifacts kept)".to_string()));
+            }
+            let summary = result.artifacts.summary.as_ref()?;
+            Some((label, summary.headline()))
+        })
+        .collect();
+    
+    if rows.is_empty() {
+        return;
+    }
+    
+    logger::section("Plan Summary");
+    logger::table(&rows.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect::<Vec<_>>());
+}
+
+/// Fail the plan phase when a plan deletes more resources than its max_destroys limit allows
+fn check_max_destroys(results: &[OperationResult], config_resolver: &ConfigResolver) -> Result<(), String> {
+    let violations: Vec<String> = results.iter()
+        .filter_map(|result| {
+            let summary = result.artifacts.summary.as_ref()?;
+            let limit = config_resolver.get_max_destroys(&result.module_path)?;
+            if summary.destroy <= limit {
+                return None;
+            }
+            let resources = summary.destroyed().iter()
+                .map(|address| format!("    - {}", address))
+                .collect::<Vec<_>>()
+                .join("\n");
+            Some(format!(
+                "  • {} ({}): {} deletion(s), limit {}\n{}",
+                plan_manifest::module_key(&result.module_path),
+                result.workspace.as_deref().unwrap_or("default"),
+                summary.destroy,
+                limit,
+                resources
+            ))
+        })
+        .collect();
+    
+    if violations.is_empty() {
+        return Ok(());
+    }
+    
+    logger::error_box(
+        "Destroy Limit Exceeded",
+        &format!("{} plan(s) exceed max_destroys:\n{}", violations.len(), violations.join("\n"))
+    );
+    Err(format!("{} plan(s) exceed the max_destroys limit", violations.len()))
+}
+
+/// Combine the GitHub-formatted reports of successful plans into a single PR comment body.
+/// Returns the path of the written file.
+pub fn write_github_comment(plan_dir: &str, results: &[OperationResult]) -> Result<String, String> {
+    let plans: Vec<_> = results.iter()
+        .filter(|r| r.success)
+        .filter_map(|r| {
+            let report = std::fs::read_to_string(Path::new(plan_dir).join(r.artifacts.report.as_ref()?)).ok()?;
+            Some((
+                plan_manifest::module_key(&r.module_path),
+                r.workspace.clone().unwrap_or_else(|| "default".to_string()),
+                r.artifacts.summary.clone(),
+                report,
+            ))
+        })
+        .collect();
+
+    let path = Path::new(plan_dir).join(plan_report::GITHUB_COMMENT_FILE_NAME);
+    std::fs::write(&path, plan_report::render_github_comment(&plans))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path.display().to_string())
+}
+
+/// Render every plan in the run, including failed ones, into a single static HTML page.
+/// Returns the path of the written file.
+pub fn write_html_report(plan_dir: &str, results: &[OperationResult]) -> Result<String, String> {
+    let plans: Vec<HtmlPlan> = results.iter()
+        .map(|r| HtmlPlan {
+            module: plan_manifest::module_key(&r.module_path),
+            workspace: r.workspace.clone().unwrap_or_else(|| "default".to_string()),
+            success: r.success,
+            summary: r.artifacts.summary.clone(),
+            output: r.output.iter().map(|line| terraform_operations::clean_terraform_output(line)).collect(),
+        })
+        .collect();
+
+    let path = Path::new(plan_dir).join(plan_report::HTML_REPORT_FILE_NAME);
+    std::fs::write(&path, plan_report::render_html_report(&plans))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path.display().to_string())
+}
+
+/// Find resources modified by more than one plan recorded in the manifest.
+/// Plans without a JSON export are skipped.
+pub fn find_plan_conflicts(plan_dir: &str, manifest: &PlanManifest) -> Vec<ResourceConflict> {
+    let mut plans = Vec::new();
+
+    for entry in &manifest.entries {
+        let Some(plan_json) = &entry.plan_json else {
+            continue;
+        };
+        match plan_json::load_plan_changes(&std::path::Path::new(plan_dir).join(plan_json)) {
+            Ok(changes) => plans.push((format!("{} ({})", entry.module, entry.workspace), changes)),
+            Err(e) => logger::warn(&format!("Skipping conflict check for {} ({}): {}", entry.module, entry.workspace, e)),
+        }
+    }
+
+    plan_json::find_conflicts(&plans)
+}
+
+/// Collect state size and resource counts for planned module/workspace pairs,
+/// compare them with the run history, and append the new measurements
+pub fn report_state_stats(results: &[OperationResult]) {
+    logger::section("State Statistics");
+    
+    let history = state_stats::load_history();
+    let mut collected = Vec::new();
+    let mut rows = Vec::new();
+    
+    for result in results.iter().filter(|r| r.success) {
+        let stats = terraform_operations::with_env(&result.env, || {
+            state_stats::collect_state_stats(&result.module_path, result.workspace.as_deref(), result.data_dir.as_deref().map(Path::new))
+        });
+        match stats {
+            Ok(stats) => {
+                let trend = match state_stats::previous_stats(&history, &stats.module, &stats.workspace) {
+                    Some(previous) => format!(
+                        " ({:+} resources, {:+} bytes since last run)",
+                        stats.resource_count as i64 - previous.resource_count as i64,
+                        stats.state_bytes as i64 - previous.state_bytes as i64
+                    ),
+                    None => String::new(),
+                };
+                rows.push((
+                    format!("{} ({})", stats.module, stats.workspace),
+                    format!("{} resources, {}{}", stats.resource_count, state_stats::format_bytes(stats.state_bytes), trend),
+                ));
+                collected.push(stats);
+            }
+            Err(e) => logger::warn(&format!("Failed to collect state statistics for {}: {}", result.module_path, e)),
+        }
+    }
+    
+    logger::table(&rows.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect::<Vec<_>>());
+    
+    if let Err(e) = state_stats::append_history(&collected) {
+        logger::warn(&format!("Failed to record state history: {}", e));
+    }
+}
+
+/// Cap the modules the processor runs at once per concurrency group, if any limits are configured
+pub fn with_group_limits(processor: ParallelProcessor, config_resolver: &ConfigResolver, modules: &[String]) -> ParallelProcessor {
+    let limits = config_resolver.get_concurrency_limits();
+    if limits.is_empty() {
+        return processor;
+    }
+    let groups: HashMap<String, String> = modules.iter()
+        .filter_map(|module| Some((module.clone(), config_resolver.get_concurrency_group(module)?)))
+        .collect();
+    let mut capped: Vec<(&String, &usize)> = limits.iter().collect();
+    capped.sort();
+    for (group, limit) in capped {
+        let count = groups.values().filter(|g| *g == group).count();
+        if count > *limit {
+            logger::info(&format!("Running at most {} of the {} modules in concurrency group '{}' at once", limit, count, group));
+        }
+    }
+    processor.with_group_limits(groups, limits)
+}
+
+/// Let modules configured with parallel_workspaces run their workspaces side by side
+pub fn with_parallel_workspaces(processor: ParallelProcessor, config_resolver: &ConfigResolver, modules: &[String]) -> ParallelProcessor {
+    let parallel: HashSet<String> = modules.iter()
+        .filter(|module| config_resolver.get_parallel_workspaces(module))
+        .cloned()
+        .collect();
+    processor.with_parallel_workspaces(parallel)
+}
+
+/// Isolated TF_DATA_DIR for a module/workspace according to its data_dir_isolation setting.
+/// `None` for the workspace means the module's default workspace.
+pub fn data_dir_for(config_resolver: &ConfigResolver, module_path: &str, workspace: Option<&str>) -> Option<String> {
+    let data_dir = match config_resolver.get_data_dir_isolation(module_path) {
+        DataDirIsolation::Off => return None,
+        DataDirIsolation::Module => terraform_operations::isolated_data_dir(module_path, None),
+        DataDirIsolation::Workspace => terraform_operations::isolated_data_dir(module_path, Some(workspace.unwrap_or("default"))),
+    };
+    Some(data_dir.to_string_lossy().to_string())
+}
+
+/// Record the outcome of every module/workspace pair so `--retry-failed` can re-queue the failures
+pub fn save_run_state(command: &str, results: &[OperationResult]) {
+    if let Err(e) = RunState::from_results(command, results).save() {
+        logger::warn(&format!("Failed to record run state: {}", e));
+    }
+}
+
+/// Hooks configured for a module
+pub fn hooks_for(config_resolver: &ConfigResolver, module: &str) -> Hooks {
+    let hooks = config_resolver.get_hooks(module);
+    Hooks {
+        pre_init: hooks.pre_init,
+        pre_plan: hooks.pre_plan,
+        post_plan: hooks.post_plan,
+        pre_apply: hooks.pre_apply,
+        post_apply: hooks.post_apply,
+    }
+}
+
+/// Environment variables of a module/workspace operation, with its cloud credentials added over the configured
+/// `env`, secret references resolved and secret `vars` added as `TF_VAR_` variables. Environment directories
+/// take those of their environment. Modules selecting workspaces through the environment also get `TF_WORKSPACE`.
+pub fn env_for(config_resolver: &ConfigResolver, module: &str, workspace: Option<&str>) -> Result<BTreeMap<String, String>, String> {
+    let selected = workspace;
+    let workspace = workspace.map(str::to_string)
+        .or_else(|| config_resolver.get_environment(module))
+        .unwrap_or_else(|| "default".to_string());
+    let mut env = config_resolver.get_env(module, &workspace);
+    
+    let credentials = config_resolver.get_credentials(module, &workspace);
+    for (name, source) in credentials.credentials_env {
+        let value = std::env::var(&source)
+            .map_err(|_| format!("{}: credentials_env reads {} from {}, which is not set", module, name, source))?;
+        redact::add_value(&value);
+        env.insert(name, value);
+    }
+    if let Some(profile) = credentials.aws_profile {
+        env.insert("AWS_PROFILE".to_string(), profile);
+    }
+    if let Some(role_arn) = credentials.assume_role_arn {
+        // The role is assumed with the profile, and terraform uses the role's credentials instead
+        let assumed = credentials::assume_role(&role_arn, &env).map_err(|e| format!("{}: {}", module, e))?;
+        env.remove("AWS_PROFILE");
+        env.extend(assumed);
+    }
+    
+    // Secret references are read with the module's credentials, leaving out the unresolved references
+    let (references, plain): (BTreeMap<String, String>, BTreeMap<String, String>) = env.into_iter()
+        .partition(|(_, value)| secrets::is_reference(value));
+    let secret_vars = config_resolver.get_secret_vars(module).into_iter().map(|(name, value)| (format!("TF_VAR_{}", name), value));
+    let mut env = plain.clone();
+    for (name, reference) in references.into_iter().chain(secret_vars) {
+        let value = secrets::resolve(&reference, &plain).map_err(|e| format!("{}: {}", module, e))?;
+        env.insert(name, value);
+    }
+    
+    if let Some(selected) = selected {
+        if config_resolver.get_workspace_selection(module) == WorkspaceSelection::Env {
+            env.insert("TF_WORKSPACE".to_string(), selected.to_string());
+        }
+    }
+    Ok(env)
+}
+
+/// Have output and reports mask the values an operation gives to the variables its module declares
+/// `sensitive = true`: string values set with `-var`, in var files or as `TF_VAR_` variables
+pub fn register_sensitive_values(operation: &TerraformOperation) {
+    let sensitive: Vec<String> = std::fs::read_dir(&operation.module_path).into_iter().flatten().flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "tf"))
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|content| scan_utils::find_sensitive_variables(&content))
+        .collect();
+    if sensitive.is_empty() {
+        return;
+    }
+    
+    let var_files: Vec<String> = operation.var_files.iter()
+        .filter_map(|file| std::fs::read_to_string(Path::new(&operation.module_path).join(file)).ok())
+        .collect();
+    for name in &sensitive {
+        let assignment = Regex::new(&format!(r#"(?m)^\s*{}\s*=\s*"([^"]*)""#, regex::escape(name))).unwrap();
+        let prefix = format!("{}=", name);
+        let values = operation.args.vars.iter().filter_map(|var| var.strip_prefix(prefix.as_str()).map(str::to_string))
+            .chain(var_files.iter().flat_map(|content| assignment.captures_iter(content).map(|caps| caps[1].to_string())))
+            .chain(operation.env.get(&format!("TF_VAR_{}", name)).cloned());
+        for value in values {
+            redact::add_value(&value);
+        }
+    }
+}
+
+/// Terraform arguments for a module and workspace: command line values merged with the module's configuration.
+/// Environment directories take the backend configuration of their environment.
+pub fn terraform_args_for(config_resolver: &ConfigResolver, module: &str, workspace: Option<&str>, cli_args: &TerraformArgs) -> TerraformArgs {
+    let workspace = workspace.map(str::to_string)
+        .or_else(|| config_resolver.get_environment(module))
+        .unwrap_or_else(|| "default".to_string());
+    let non_empty = |values: &[String]| (!values.is_empty()).then(|| values.to_vec());
+    let extra_args = config_resolver.get_extra_args(module);
+    TerraformArgs {
+        vars: config_resolver.get_vars(module, non_empty(&cli_args.vars).as_deref()),
+        targets: config_resolver.get_targets(module, non_empty(&cli_args.targets).as_deref()),
+        // terraform rejects -refresh=false together with -refresh-only
+        no_refresh: !cli_args.refresh_only && config_resolver.get_no_refresh(module, cli_args.no_refresh),
+        parallelism: config_resolver.get_tf_parallelism(module, cli_args.parallelism),
+        lock_timeout: config_resolver.get_lock_timeout(module, cli_args.lock_timeout.as_deref()),
+        destroy: cli_args.destroy,
+        refresh_only: cli_args.refresh_only,
+        backend_config: config_resolver.get_backend_config(module, &workspace, &cli_args.backend_config),
+        extra_init_args: extra_args.init,
+        extra_plan_args: extra_args.plan,
+        extra_apply_args: extra_args.apply,
+    }
+}
+
+/// Whether `only_workspaces` and `branch_workspaces` let a module run a workspace. Workspaces missing
+/// from `only_workspaces` are skipped with a warning, except the default workspace, which is usually left empty.
+pub fn is_listed_workspace(config_resolver: &ConfigResolver, module_path: &str, workspace: &str) -> bool {
+    if !config_resolver.is_workspace_allowed(module_path, workspace) {
+        if workspace == "default" {
+            logger::workspace_skip(workspace, "not in only_workspaces");
+        } else {
+            logger::warn(&format!("Skipping workspace '{}' of {}: not listed in only_workspaces", workspace, module_path));
+        }
+        return false;
+    }
+    if !config_resolver.is_branch_workspace(module_path, workspace) {
+        logger::workspace_skip(workspace, &format!("not mapped to branch {}", config_resolver.get_branch().unwrap_or_default()));
+        return false;
+    }
+    true
+}
+
+/// Workspaces of a module, listed with the backend configuration of its default workspace and
+/// `cli_backend_config`. Environment directories of the `directories` layout only use the default
+/// workspace, so terraform isn't asked for theirs. Listings are cached, see `workspace_cache_ttl`.
+pub fn module_workspaces(config_resolver: &ConfigResolver, module_path: &str, cli_backend_config: &[String]) -> Result<Vec<String>, String> {
+    if config_resolver.get_environment(module_path).is_some() {
+        return Ok(vec!["default".to_string()]);
+    }
+    let backend_config = config_resolver.get_backend_config(module_path, "default", cli_backend_config);
+    let data_dir = data_dir_for(config_resolver, module_path, None);
+    let env = env_for(config_resolver, module_path, None)?;
+    let extra_init_args = config_resolver.get_extra_args(module_path).init;
+    let key = workspace_cache::cache_key(module_path, data_dir.as_deref(), &backend_config, &env);
+    workspace_cache::cached_workspaces(&key, config_resolver.get_workspace_cache_ttl(), || {
+        terraform_operations::with_env(&env, || get_workspaces(module_path, data_dir.as_deref(), &backend_config, &extra_init_args))
+    })
+}
+
+/// Have the next `module_workspaces` call list a module's workspaces again, after they changed
+pub fn forget_module_workspaces(config_resolver: &ConfigResolver, module_path: &str) -> Result<(), String> {
+    let backend_config = config_resolver.get_backend_config(module_path, "default", &[]);
+    let data_dir = data_dir_for(config_resolver, module_path, None);
+    let env = env_for(config_resolver, module_path, None)?;
+    workspace_cache::forget(&workspace_cache::cache_key(module_path, data_dir.as_deref(), &backend_config, &env));
     Ok(())
 }
 
-pub fn get_workspaces(module_path: &str) -> Result<Vec<String>, String> {
+pub fn get_workspaces(module_path: &str, data_dir: Option<&str>, backend_config: &[String], extra_init_args: &[String]) -> Result<Vec<String>, String> {
+    let data_dir = data_dir.map(Path::new);
+    
     // Ensure module is initialized before listing workspaces
-    crate::utils::terraform_operations::ensure_module_initialized(module_path)?;
+    terraform_operations::ensure_module_initialized(module_path, data_dir, backend_config, extra_init_args)?;
     
-    let output = std::process::Command::new("terraform")
+    let output = terraform_operations::terraform_command(module_path, data_dir)
         .arg("workspace")
         .arg("list")
-        .current_dir(module_path)
         .output()
         .map_err(|e| e.to_string())?;
 
@@ -182,3 +768,29 @@ fn validate_module_configuration(module_path: &str) -> Result<(), String> {
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolarboatConfig;
+    use std::path::PathBuf;
+
+    fn resolver(config: serde_json::Value) -> ConfigResolver {
+        let config: SolarboatConfig = serde_json::from_value(config).unwrap();
+        ConfigResolver::new(Some(config), PathBuf::from("/tmp"))
+    }
+
+    #[test]
+    fn test_refresh_only_args() {
+        let resolver = resolver(serde_json::json!({"modules": {"stacks/app": {"refresh": false}}}));
+
+        let args = terraform_args_for(&resolver, "stacks/app", None, &TerraformArgs::default());
+        assert_eq!(args.plan_args(), vec!["-refresh=false"]);
+
+        // terraform rejects -refresh=false together with -refresh-only
+        let cli_args = TerraformArgs { refresh_only: true, ..Default::default() };
+        let args = terraform_args_for(&resolver, "stacks/app", None, &cli_args);
+        assert_eq!(args.plan_args(), vec!["-refresh-only"]);
+        assert!(args.apply_args(true).is_empty());
+    }
+}
@@ -1,12 +1,14 @@
-use crate::cli::ScanArgs;
-use crate::config::Settings;
-use crate::utils::scan_utils;
+use crate::cli::{ScanArgs, ScanOutput};
+use crate::commands::plan::helpers as plan_helpers;
+use crate::config::{ConfigResolver, Settings};
+use crate::utils::git;
+use crate::utils::scan_utils::{self, CompareRefs, DetectionReport, SelectionReason, Shard};
 use crate::utils::logger;
 use std::collections::HashSet;
 use std::process::Command;
 use std::time::Instant;
 
-pub fn execute(args: ScanArgs, _settings: &Settings) -> anyhow::Result<()> {
+pub fn execute(args: ScanArgs, settings: &Settings) -> anyhow::Result<()> {
     let start_time = Instant::now();
     
     logger::section("Terraform Scan");
@@ -19,6 +21,45 @@ pub fn execute(args: ScanArgs, _settings: &Settings) -> anyhow::Result<()> {
         }),
         None => false, // Flag not provided
     };
+    // Tagged modules are selected whether or not they changed
+    let all = all || !args.tags.is_empty();
+
+    let propagate_dependents = match &args.propagate_dependents {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --propagate-dependents: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let explain = match &args.explain {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --explain: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
+
+    let compare = CompareRefs::from_args(args.base_ref.as_deref(), args.head_ref.as_deref(), args.since.as_deref())
+        .map_err(|e| {
+            logger::error_box("Invalid Git Refs", &e);
+            anyhow::anyhow!(e)
+        })?;
+
+    let shard = args.shard.as_deref()
+        .map(Shard::parse)
+        .transpose()
+        .map_err(|e| {
+            logger::error_box("Invalid Shard", &e);
+            anyhow::anyhow!(e)
+        })?;
+    let shard_by_dependency = match &args.shard_by_dependency {
+        Some(value) => value.parse::<bool>().unwrap_or_else(|_| {
+            logger::warn(&format!("Invalid value for --shard-by-dependency: '{}'. Using default (true).", value));
+            true
+        }),
+        None => false,
+    };
 
     // Show configuration summary
     logger::config_summary(&[
@@ -26,8 +67,16 @@ pub fn execute(args: ScanArgs, _settings: &Settings) -> anyhow::Result<()> {
         ("Default Branch", &args.default_branch),
         ("Recent Commits", &args.recent_commits.to_string()),
         ("Process All", &all.to_string()),
+        ("Propagate Dependents", &propagate_dependents.to_string()),
+        ("Compare Refs", &compare.as_ref().map(|refs| format!("{}..{}", refs.base, refs.head)).unwrap_or_else(|| "auto".to_string())),
+        ("Explain", &explain.to_string()),
+        ("Impact Files", &if args.impact.is_empty() { "none".to_string() } else { args.impact.join(", ") }),
     ]);
 
+    if !args.impact.is_empty() {
+        return report_impact(&args, settings, propagate_dependents, start_time);
+    }
+
     // Check if the specified path is a git repository
     logger::step(1, 4, "Checking git repository");
     let git_check = Command::new("git")
@@ -42,12 +91,13 @@ pub fn execute(args: ScanArgs, _settings: &Settings) -> anyhow::Result<()> {
             // Scan for changed modules
             logger::step(2, 4, "Detecting changed modules");
             let progress = logger::progress("Analyzing git changes and module dependencies");
-            
-            match scan_utils::get_changed_modules_clean(&args.path, all, &args.default_branch, args.recent_commits) {
-                Ok(modules) => {
+            match scan_utils::get_changed_modules_clean(&args.path, all, &args.default_branch, args.recent_commits, settings.resolver().get_tracked_extensions().as_deref(), &settings.resolver().get_discovery_options(), settings.resolver().get_recurse_submodules(), &settings.resolver().get_graph_overrides(), settings.resolver().get_propagate_depth(propagate_dependents), compare.as_ref(), &settings.resolver().get_detection_strategies()) {
+                Ok(scan) => {
+                    let (modules, report) = scan.into_parts();
                     if let Some(progress) = progress {
                         progress.complete(true);
                     }
+                    check_shared_states(&report, settings)?;
                     
                     if all {
                         logger::info(&format!("Found {} stateful modules", modules.len()));
@@ -56,11 +106,24 @@ pub fn execute(args: ScanArgs, _settings: &Settings) -> anyhow::Result<()> {
                             "All stateful modules will be scanned regardless of changes"
                         );
                     } else {
-                        if modules.is_empty() {
-                            logger::success_box(
-                                "No Changes Detected", 
-                                "No modules were changed since the last merge with the default branch"
+                        if !report.removed.is_empty() {
+                            logger::warning_box(
+                                "Removed Modules",
+                                &format!(
+                                    "{} stateful module(s) were deleted while their infrastructure may still exist:\n{}\nRun `solarboat plan --plan-removed` to plan their destruction.",
+                                    report.removed.len(),
+                                    report.removed.iter().map(|m| format!("  • {}", m.describe())).collect::<Vec<_>>().join("\n")
+                                )
                             );
+                        }
+                        if modules.is_empty() {
+                            if report.removed.is_empty() {
+                                logger::success_box(
+                                    "No Changes Detected", 
+                                    "No modules were changed since the last merge with the default branch"
+                                );
+                            }
+                            write_results(&args, &report, &modules, settings)?;
                             return Ok(());
                         }
                         logger::changes_detected(modules.len(), &modules);
@@ -68,7 +131,7 @@ pub fn execute(args: ScanArgs, _settings: &Settings) -> anyhow::Result<()> {
                     
                     // Filter modules based on the path argument if it's not "."
                     logger::step(3, 4, "Filtering modules by path");
-                    let filtered_modules = if args.path != "." {
+                    let mut filtered_modules = if args.path != "." {
                         logger::info(&format!("Filtering modules with path: {}", args.path));
                         modules.into_iter()
                             .filter(|path| {
@@ -80,12 +143,26 @@ pub fn execute(args: ScanArgs, _settings: &Settings) -> anyhow::Result<()> {
                     } else {
                         modules
                     };
+                    if !args.include_modules.is_empty() || !args.exclude_modules.is_empty() {
+                        filtered_modules = scan_utils::filter_modules(filtered_modules, &args.include_modules, &args.exclude_modules);
+                        logger::info(&format!("{} modules match --include-modules/--exclude-modules", filtered_modules.len()));
+                    }
+                    if !args.tags.is_empty() || !args.exclude_tags.is_empty() {
+                        filtered_modules = settings.resolver().select_tagged(filtered_modules, &args.tags, &args.exclude_tags);
+                        logger::info(&format!("{} modules match --tags/--exclude-tags", filtered_modules.len()));
+                    }
+                    if let Some(shard) = shard {
+                        let selected = filtered_modules.len();
+                        filtered_modules = shard.select(filtered_modules, shard_by_dependency.then_some(&report.graph));
+                        logger::info(&format!("Shard {}/{}: {} of {} modules", shard.index, shard.count, filtered_modules.len(), selected));
+                    }
                     
                     if filtered_modules.is_empty() {
                         logger::warning_box(
                             "No Matching Modules", 
                             &format!("No modules match the specified path: {}", args.path)
                         );
+                        write_results(&args, &report, &filtered_modules, settings)?;
                         return Ok(());
                     }
                     
@@ -100,6 +177,13 @@ pub fn execute(args: ScanArgs, _settings: &Settings) -> anyhow::Result<()> {
                     
                     logger::section("Modules to Scan");
                     logger::list(&unique_modules.iter().map(|s| s.split('/').last().unwrap_or(s)).collect::<Vec<_>>(), None);
+
+                    if explain {
+                        print_classifications(&report);
+                    }
+                    if explain && !all {
+                        explain_selection(&report, &filtered_modules, &args.default_branch, compare.as_ref());
+                    }
                     
                     // Show results summary
                     logger::step(4, 4, "Generating scan report");
@@ -114,6 +198,7 @@ pub fn execute(args: ScanArgs, _settings: &Settings) -> anyhow::Result<()> {
                         ("Scan Path", &args.path),
                         ("Duration", &format!("{:.2}s", duration.as_secs_f64())),
                     ]);
+                    write_results(&args, &report, &filtered_modules, settings)?;
                 }
                 Err(e) => {
                     if let Some(progress) = progress {
@@ -134,3 +219,264 @@ pub fn execute(args: ScanArgs, _settings: &Settings) -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+/// Fail when modules write the same backend state, unless it is listed in `allowed_shared_states`
+fn check_shared_states(report: &DetectionReport, settings: &Settings) -> anyhow::Result<()> {
+    let allowed = settings.resolver().get_allowed_shared_states();
+    let conflicts: Vec<String> = report.shared_states.iter()
+        .filter(|(state, _)| !allowed.iter().any(|entry| *entry == state.to_string() || *entry == state.key))
+        .map(|(state, modules)| format!(
+            "  • {} is written by {}",
+            state,
+            modules.iter().map(|module| scan_utils::repo_relative_path(module)).collect::<Vec<_>>().join(", ")
+        ))
+        .collect();
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    logger::error_box("Shared Backend State", &format!(
+        "These backend states are configured in more than one module:\n{}\nModules sharing a state overwrite each other's resources. Give each module its own key,\nor list the state in allowed_shared_states if sharing it is intended.",
+        conflicts.join("\n")
+    ));
+    Err(anyhow::anyhow!("{} backend state(s) are shared between modules", conflicts.len()))
+}
+
+/// Print the stateful modules the `--impact` files affect and why, without git detection
+fn report_impact(args: &ScanArgs, settings: &Settings, propagate_dependents: bool, start_time: Instant) -> anyhow::Result<()> {
+    logger::step(1, 2, "Analyzing module dependencies");
+    let (modules, report) = scan_utils::get_impacted_modules(
+        &args.path,
+        &args.impact,
+        settings.resolver().get_tracked_extensions().as_deref(),
+        &settings.resolver().get_discovery_options(),
+        &settings.resolver().get_graph_overrides(),
+        settings.resolver().get_propagate_depth(propagate_dependents),
+    ).map_err(|e| {
+        logger::error_box("Impact Analysis Failed", &e);
+        anyhow::anyhow!("Impact analysis failed: {}", e)
+    })?.into_parts();
+
+    check_shared_states(&report, settings)?;
+
+    logger::step(2, 2, "Reporting affected modules");
+    if modules.is_empty() {
+        logger::success_box("No Modules Affected", "No stateful module would be selected for changes to these files");
+        write_results(args, &report, &modules, settings)?;
+        return Ok(());
+    }
+    logger::changes_detected(modules.len(), &modules);
+    logger::section("Why These Modules");
+    print_reasons(&report, &modules, &|_| String::new());
+
+    logger::results_summary("Impact Results", &[
+        ("Files", &args.impact.len().to_string()),
+        ("Affected Modules", &modules.len().to_string()),
+        ("Duration", &format!("{:.2}s", start_time.elapsed().as_secs_f64())),
+    ]);
+    write_results(args, &report, &modules, settings)?;
+    Ok(())
+}
+
+/// Write the selected modules to the `--output-file` and print them as the document requested with `--output`
+fn write_results(args: &ScanArgs, report: &DetectionReport, modules: &[String], settings: &Settings) -> anyhow::Result<()> {
+    if let Some(file) = &args.output_file {
+        scan_utils::write_modules_file(file, modules).map_err(|e| {
+            logger::error_box("Output File Failed", &e);
+            anyhow::anyhow!(e)
+        })?;
+        logger::info(&format!("Wrote {} modules to {}", modules.len(), file));
+    }
+    match args.output {
+        ScanOutput::Text => return Ok(()),
+        ScanOutput::GhaMatrix => return print_matrix(modules, settings),
+        ScanOutput::Json | ScanOutput::Yaml => {}
+    }
+    let relative = |path: &str| scan_utils::repo_relative_path(path);
+    let document = serde_json::json!({
+        "modules": modules.iter().map(|module| {
+            let node = report.graph.module(module);
+            let triggers: Vec<serde_json::Value> = report.reasons.iter()
+                .filter(|(path, _)| path == module)
+                .flat_map(|(_, reasons)| reasons.iter().map(|reason| match reason {
+                    SelectionReason::Changed(file) => serde_json::json!({"type": "changed", "file": file}),
+                    SelectionReason::Referenced(file) => serde_json::json!({"type": "referenced", "file": file}),
+                    SelectionReason::StatelessDependency { module, file } => {
+                        serde_json::json!({"type": "stateless_dependency", "module": module, "file": file})
+                    }
+                    SelectionReason::Dependent(chain) => serde_json::json!({"type": "dependent", "chain": chain}),
+                }))
+                .collect();
+            serde_json::json!({
+                "path": module,
+                "relative_path": relative(module),
+                "stateful": node.map(|node| node.stateful),
+                "classification": node.map(|node| node.classification.to_string()),
+                "backend": node.and_then(|node| node.backend.as_ref()).map(|backend| serde_json::json!({
+                    "type": backend.kind,
+                    "container": backend.container,
+                    "key": backend.key,
+                    "region": backend.region,
+                })),
+                "required_version": node.and_then(|node| node.required_version.as_ref()),
+                "providers": node.map(|node| node.providers.iter()
+                    .map(|provider| serde_json::json!({
+                        "name": provider.name,
+                        "source": provider.source,
+                        "version": provider.version,
+                    }))
+                    .collect::<Vec<_>>()),
+                "triggers": triggers,
+                "depends_on": report.graph.dependencies(module).map(relative).collect::<Vec<_>>(),
+                "used_by": report.graph.dependents(module).map(relative).collect::<Vec<_>>(),
+            })
+        }).collect::<Vec<_>>(),
+        "removed": report.removed.iter()
+            .map(|removed| serde_json::json!({"path": removed.path, "commit": removed.commit}))
+            .collect::<Vec<_>>(),
+    });
+    if args.output == ScanOutput::Yaml {
+        let yaml = serde_yaml_ng::to_string(&document)
+            .map_err(|e| anyhow::anyhow!("Failed to render the scan document as YAML: {}", e))?;
+        print!("{}", yaml);
+    } else {
+        println!("{}", serde_json::to_string_pretty(&document).unwrap_or_default());
+    }
+    Ok(())
+}
+
+/// Print a GitHub Actions matrix with an entry for each workspace plan would run for each module
+fn print_matrix(modules: &[String], settings: &Settings) -> anyhow::Result<()> {
+    let resolver = settings.resolver();
+    let mut workspaces = Vec::new();
+    for module in modules {
+        let module_workspaces = plan_helpers::module_workspaces(resolver, module, &[])
+            .map_err(|e| anyhow::anyhow!("Failed to list workspaces of {}: {}", module, e))?;
+        workspaces.push((module.clone(), module_workspaces));
+    }
+    println!("{}", gha_matrix(resolver, workspaces, scan_utils::repo_relative_path));
+    Ok(())
+}
+
+/// The GitHub Actions matrix for modules and their workspaces: `default` for modules without workspaces,
+/// and the workspaces plan would run otherwise
+fn gha_matrix(resolver: &ConfigResolver, workspaces: Vec<(String, Vec<String>)>, relative: impl Fn(&str) -> String) -> serde_json::Value {
+    let mut include = Vec::new();
+    for (module, workspaces) in workspaces {
+        let workspaces = if workspaces.len() <= 1 {
+            vec!["default".to_string()]
+        } else {
+            workspaces.into_iter()
+                .filter(|workspace| !resolver.should_ignore_workspace(&module, workspace, None) && resolver.is_workspace_allowed(&module, workspace) && resolver.is_branch_workspace(&module, workspace))
+                .collect()
+        };
+        let module = relative(&module);
+        for workspace in workspaces {
+            include.push(serde_json::json!({"module": module, "workspace": workspace}));
+        }
+    }
+    serde_json::json!({"include": include})
+}
+
+/// Print why each module was selected, with the commits that changed the files involved
+/// and the registry or git module pins they bumped
+fn explain_selection(report: &DetectionReport, modules: &[String], default_branch: &str, compare: Option<&CompareRefs>) {
+    let (base, head) = scan_utils::explanation_range(default_branch, compare);
+    let details = |file: &str| {
+        let path = scan_utils::repo_relative_path(file);
+        let commits = git::file_commits(".", &path, base.as_deref(), &head, 5).unwrap_or_default();
+        let mut details = if commits.is_empty() {
+            " (uncommitted)".to_string()
+        } else {
+            format!(" in {}", commits.iter()
+                .map(|(id, summary)| format!("{} \"{}\"", &id[..id.len().min(8)], summary))
+                .collect::<Vec<_>>()
+                .join(", "))
+        };
+        if file.ends_with(".tf") {
+            let old = git::file_content(".", base.as_deref().unwrap_or("HEAD"), &path).unwrap_or_default();
+            let new = if head == "HEAD" {
+                std::fs::read_to_string(file).unwrap_or_default()
+            } else {
+                git::file_content(".", &head, &path).unwrap_or_default()
+            };
+            for change in scan_utils::pin_changes(&old, &new) {
+                details.push_str(&format!("; bumps {}", change));
+            }
+        }
+        details
+    };
+
+    logger::section("Why These Modules");
+    print_reasons(report, modules, &details);
+}
+
+/// List every discovered module as stateful or stateless, with the reason, its backend,
+/// its required Terraform version and its providers
+fn print_classifications(report: &DetectionReport) {
+    let lines: Vec<String> = report.graph.modules()
+        .map(|(module, node)| format!(
+            "{}: {} ({}){}{}{}",
+            scan_utils::repo_relative_path(module),
+            if node.stateful { "stateful" } else { "stateless" },
+            node.classification,
+            node.backend.as_ref().map(|backend| format!(", backend {}", backend)).unwrap_or_default(),
+            node.required_version.as_ref().map(|version| format!(", terraform {}", version)).unwrap_or_default(),
+            if node.providers.is_empty() {
+                String::new()
+            } else {
+                format!(", providers {}", node.providers.iter()
+                    .map(|provider| match &provider.version {
+                        Some(version) => format!("{} {}", provider.name, version),
+                        None => provider.name.clone(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "))
+            }
+        ))
+        .collect();
+    logger::section("Module Classification");
+    logger::list(&lines.iter().map(String::as_str).collect::<Vec<_>>(), None);
+}
+
+/// List each module with the reasons it was selected; `details` describes a changed file,
+/// such as the commits behind it
+fn print_reasons(report: &DetectionReport, modules: &[String], details: &dyn Fn(&str) -> String) {
+    for (module, reasons) in report.reasons.iter().filter(|(module, _)| modules.contains(module)) {
+        let lines: Vec<String> = reasons.iter().map(|reason| match reason {
+            SelectionReason::Changed(file) => format!("{} changed{}", file, details(file)),
+            SelectionReason::Referenced(file) => format!("references {}, which changed{}", file, details(file)),
+            SelectionReason::StatelessDependency { module, file } => {
+                format!("uses stateless module {}: {} changed{}", module, file, details(file))
+            }
+            SelectionReason::Dependent(chain) => format!("depends on a changed module: {}", chain.join(" → ")),
+        }).collect();
+        let title = module.rsplit('/').next().unwrap_or(module);
+        logger::list(&lines.iter().map(String::as_str).collect::<Vec<_>>(), Some(title));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolarboatConfig;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_gha_matrix() {
+        let config: SolarboatConfig = serde_json::from_value(serde_json::json!({
+            "modules": {"stacks/app": {"ignore_workspaces": ["dev"]}},
+        })).unwrap();
+        let resolver = ConfigResolver::new(Some(config), PathBuf::from("/tmp"));
+        let workspaces = vec![
+            ("/tmp/stacks/app".to_string(), vec!["default".to_string(), "dev".to_string(), "prod".to_string()]),
+            ("/tmp/stacks/db".to_string(), vec!["default".to_string()]),
+        ];
+        let matrix = gha_matrix(&resolver, workspaces, |module| module.trim_start_matches("/tmp/").to_string());
+        assert_eq!(matrix, serde_json::json!({"include": [
+            {"module": "stacks/app", "workspace": "default"},
+            {"module": "stacks/app", "workspace": "prod"},
+            {"module": "stacks/db", "workspace": "default"},
+        ]}));
+        assert_eq!(gha_matrix(&resolver, Vec::new(), str::to_string), serde_json::json!({"include": []}));
+    }
+}
@@ -0,0 +1,176 @@
+use std::path::Path;
+
+use crate::cli::{WorkspacesArgs, WorkspacesCommand, WorkspacesCreateArgs};
+use crate::commands::plan::helpers as plan_helpers;
+use crate::config::{ConfigResolver, Settings};
+use crate::utils::{logger, scan_utils, terraform_operations};
+use crate::utils::plan_manifest::module_key;
+use crate::utils::terraform_operations::{OperationType, PlanFormat, TerraformArgs, TerraformOperation};
+
+pub fn execute(args: WorkspacesArgs, settings: &Settings) -> anyhow::Result<()> {
+    match args.command {
+        WorkspacesCommand::Create(create_args) => create(&create_args, settings),
+    }
+}
+
+fn create(args: &WorkspacesCreateArgs, settings: &Settings) -> anyhow::Result<()> {
+    logger::section("Workspace Creation");
+
+    let resolver = settings.resolver();
+    let modules = target_modules(args, resolver)?;
+    if modules.is_empty() {
+        logger::success_box("No Modules Matched", &format!("No stateful modules match {}", args.modules.join(", ")));
+        return Ok(());
+    }
+
+    logger::config_summary(&[
+        ("Workspace", &args.name),
+        ("Modules", &modules.len().to_string()),
+        ("Initial Plan", if args.plan { "yes" } else { "no" }),
+    ]);
+
+    let mut failures = Vec::new();
+    let mut ready = Vec::new();
+    for module in &modules {
+        match create_in_module(resolver, module, &args.name) {
+            Ok(true) => logger::success(&format!("Created {} in {}", args.name, module_key(module))),
+            Ok(false) => logger::info(&format!("{} already has {}, selected it", module_key(module), args.name)),
+            Err(e) => {
+                failures.push(format!("  • {}: {}", module_key(module), e));
+                continue;
+            }
+        }
+        if let Err(e) = plan_helpers::forget_module_workspaces(resolver, module) {
+            logger::warn(&format!("Failed to refresh cached workspaces of {}: {}", module_key(module), e));
+        }
+        ready.push(module.clone());
+    }
+
+    if args.plan {
+        for module in &ready {
+            match plan_in_workspace(resolver, module, &args.name, &args.output_dir) {
+                Ok(Some(report)) => logger::success(&format!("Planned {} ({}): {}", module_key(module), args.name, report)),
+                Ok(None) => logger::success(&format!("Planned {} ({})", module_key(module), args.name)),
+                Err(e) => failures.push(format!("  • {}: plan failed: {}", module_key(module), e)),
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        logger::error_box("Workspace Creation Failed", &failures.join("\n"));
+        return Err(anyhow::anyhow!("Failed in {} of {} module(s)", failures.len(), modules.len()));
+    }
+
+    logger::success_box("Workspace Created", &format!("{} is ready in {} module(s)", args.name, ready.len()));
+    Ok(())
+}
+
+/// Stateful modules under the root directory matching the `--modules` globs. Environment
+/// directories of the `directories` layout have no workspaces and are left out.
+fn target_modules(args: &WorkspacesCreateArgs, resolver: &ConfigResolver) -> anyhow::Result<Vec<String>> {
+    let scan = scan_utils::get_changed_modules_clean(&args.path, true, "main", 0, resolver.get_tracked_extensions().as_deref(), &resolver.get_discovery_options(), resolver.get_recurse_submodules(), &resolver.get_graph_overrides(), resolver.get_propagate_depth(false), None, &resolver.get_detection_strategies())
+        .map_err(|e| {
+            logger::error_box("Module Detection Failed", &format!("Failed to discover modules: {}", e));
+            anyhow::anyhow!("Failed to discover modules: {}", e)
+        })?;
+
+    Ok(scan_utils::filter_modules(scan.paths(), &args.modules, &[])
+        .into_iter()
+        .filter(|module| {
+            let environment = resolver.get_environment(module).is_some();
+            if environment {
+                logger::warn(&format!("Skipping {}: environment directories have no workspaces", module_key(module)));
+            }
+            !environment
+        })
+        .collect())
+}
+
+/// Create and select `workspace` in a module. Returns false when the module already had it.
+fn create_in_module(resolver: &ConfigResolver, module: &str, workspace: &str) -> Result<bool, String> {
+    let mut env = plan_helpers::env_for(resolver, module, Some(workspace))?;
+    // Terraform can't be pointed at a workspace that doesn't exist yet
+    env.remove("TF_WORKSPACE");
+    let data_dir = plan_helpers::data_dir_for(resolver, module, Some(workspace));
+    let backend_config = resolver.get_backend_config(module, workspace, &[]);
+    let extra_init_args = resolver.get_extra_args(module).init;
+
+    terraform_operations::with_env(&env, || {
+        let existing = plan_helpers::get_workspaces(module, data_dir.as_deref(), &backend_config, &extra_init_args)?;
+        if existing.iter().any(|name| name == workspace) {
+            terraform_operations::select_workspace(module, workspace, data_dir.as_deref().map(Path::new))?;
+            return Ok(false);
+        }
+        terraform_operations::create_workspace(module, workspace, data_dir.as_deref().map(Path::new))?;
+        Ok(true)
+    })
+}
+
+/// Plan a module in `workspace` with its var files, saving the plan to `output_dir`.
+/// Returns the saved report.
+fn plan_in_workspace(resolver: &ConfigResolver, module: &str, workspace: &str, output_dir: &str) -> Result<Option<String>, String> {
+    let operation = TerraformOperation {
+        module_path: module.to_string(),
+        workspace: Some(workspace.to_string()),
+        var_files: resolver.get_workspace_var_files(module, workspace, None),
+        args: plan_helpers::terraform_args_for(resolver, module, Some(workspace), &TerraformArgs::default()),
+        operation_type: OperationType::Plan { plan_dir: Some(output_dir.to_string()), format: PlanFormat::Markdown, skip_empty: false },
+        watch: false,
+        skip_init: false,
+        data_dir: plan_helpers::data_dir_for(resolver, module, Some(workspace)),
+        hooks: plan_helpers::hooks_for(resolver, module),
+        env: plan_helpers::env_for(resolver, module, Some(workspace))?,
+    };
+    plan_helpers::register_sensitive_values(&operation);
+
+    let data_dir = operation.data_dir.as_deref().map(Path::new);
+    let outcome = terraform_operations::with_env(&operation.env, || {
+        terraform_operations::select_workspace(module, workspace, data_dir)?;
+        terraform_operations::run_single_plan(module, Some(output_dir), Some(workspace), Some(&operation.var_files), &operation.args, PlanFormat::Markdown, false, data_dir)
+    })?;
+    if !outcome.success {
+        return Err(outcome.output.last().cloned().unwrap_or_else(|| "terraform plan failed".to_string()));
+    }
+    Ok(outcome.artifacts.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SolarboatConfig;
+    use std::fs;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_in_module() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::create_dir_all(root.join("stacks/app")).unwrap();
+        fs::write(root.join("stacks/app/main.tf"), "terraform {\n  backend \"s3\" {}\n}\n").unwrap();
+        // A terraform keeping the module's workspaces in a file
+        let terraform = root.join("bin/terraform");
+        fs::write(&terraform, "#!/bin/sh\ncase \"$1 $2\" in\n\
+            \"workspace list\") echo '* default'; cat workspaces 2>/dev/null || true ;;\n\
+            \"workspace new\") echo \"$3\" >> workspaces; echo \"$3\" > selected ;;\n\
+            \"workspace select\") echo \"$3\" > selected ;;\n\
+            \"workspace show\") cat selected 2>/dev/null || echo default ;;\n\
+            esac\n").unwrap();
+        fs::set_permissions(&terraform, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path = format!("{}:{}", root.join("bin").display(), std::env::var("PATH").unwrap_or_default());
+        let config: SolarboatConfig = serde_json::from_value(serde_json::json!({"global": {"env": {"PATH": path}}})).unwrap();
+        let resolver = ConfigResolver::new(Some(config), root.clone());
+        let module = root.join("stacks/app").to_string_lossy().to_string();
+
+        assert_eq!(create_in_module(&resolver, &module, "staging"), Ok(true));
+        assert_eq!(fs::read_to_string(root.join("stacks/app/workspaces")).unwrap(), "staging\n");
+        // An existing workspace is selected instead of created again
+        fs::write(root.join("stacks/app/selected"), "default\n").unwrap();
+        assert_eq!(create_in_module(&resolver, &module, "staging"), Ok(false));
+        assert_eq!(fs::read_to_string(root.join("stacks/app/workspaces")).unwrap(), "staging\n");
+        assert_eq!(fs::read_to_string(root.join("stacks/app/selected")).unwrap(), "staging\n");
+    }
+}
@@ -1,7 +1,8 @@
+use crate::config::resolver::workspace_pattern;
 use crate::config::types::SolarboatConfig;
 use crate::utils::logger;
 use anyhow::{Context, Result};
-use serde_json;
+use serde_json::{self, Value};
 use std::path::{Path, PathBuf};
 use std::env;
 
@@ -14,6 +15,8 @@ const CONFIG_FILE_NAMES: &[&str] = &[
 pub struct ConfigLoader {
     /// The directory where configuration files are searched
     pub search_dir: PathBuf,
+    /// Profile of the configuration file to apply, from `--profile`
+    pub profile: Option<String>,
 }
 
 impl ConfigLoader {
@@ -21,14 +24,37 @@ impl ConfigLoader {
     pub fn new<P: AsRef<Path>>(search_dir: P) -> Self {
         Self {
             search_dir: search_dir.as_ref().to_path_buf(),
+            profile: None,
         }
     }
     
-    /// Create a ConfigLoader for the current working directory
+    /// Apply the named profile of the configuration file on top of its other settings
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+    
+    /// Create a ConfigLoader for the current working directory (see `discover`)
     pub fn from_current_dir() -> Result<Self> {
         let current_dir = std::env::current_dir()
             .context("Failed to get current working directory")?;
-        Ok(Self::new(current_dir))
+        Self::discover(current_dir)
+    }
+    
+    /// Create a ConfigLoader for the nearest directory with a configuration file, searching from
+    /// `start_dir` up to the root of the git repository containing it. Falls back to `start_dir`.
+    pub fn discover<P: AsRef<Path>>(start_dir: P) -> Result<Self> {
+        let start_dir = start_dir.as_ref();
+        for dir in start_dir.ancestors() {
+            if Self::new(dir).find_config_file()?.is_some() {
+                return Ok(Self::new(dir));
+            }
+            // `.git` is a directory in a repository and a file in worktrees and submodules
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        Ok(Self::new(start_dir))
     }
     
     /// Find and load the configuration file
@@ -42,33 +68,55 @@ impl ConfigLoader {
                 Ok(Some(config))
             }
             None => {
+                if let Some(profile) = &self.profile {
+                    return Err(anyhow::anyhow!("Profile '{}' was given but no configuration file was found", profile));
+                }
                 logger::info("No configuration file found, using defaults");
                 Ok(None)
             }
         }
     }
     
-    /// Load configuration from a specific file path
+    /// Load configuration from a specific file path, merged over the files it `extends`, with
+    /// the selected profile applied
     pub fn load_from_path<P: AsRef<Path>>(&self, path: P) -> Result<SolarboatConfig> {
         let path = path.as_ref();
-        let content = std::fs::read_to_string(path)
-            .with_context(|| format!("Failed to read configuration file: {}", path.display()))?;
-        
-        match path.extension().and_then(|ext| ext.to_str()) {
-            Some("json") => {
-                serde_json::from_str(&content)
-                    .with_context(|| format!("Failed to parse JSON configuration: {}", path.display()))
-            }
-            _ => {
-                // Try to detect format by content
-                if content.trim().starts_with('{') {
-                    serde_json::from_str(&content)
-                        .with_context(|| format!("Failed to parse JSON configuration: {}", path.display()))
-                } else {
-                    Err(anyhow::anyhow!("Unsupported configuration file format. Only JSON files are supported."))
-                }
+        let mut value = load_extended(path, false, &mut Vec::new())?;
+        let profiles = value.as_object_mut().and_then(|object| object.remove(PROFILES_KEY));
+        self.apply_profile(&mut value, profiles, path)?;
+        serde_json::from_value(value)
+            .with_context(|| format!("Failed to parse JSON configuration: {}", path.display()))
+    }
+    
+    /// Merge the selected profile over `value`. Every profile is checked, so mistakes in one
+    /// surface before a run selects it.
+    fn apply_profile(&self, value: &mut Value, profiles: Option<Value>, path: &Path) -> Result<()> {
+        let profiles = match profiles {
+            None | Some(Value::Null) => serde_json::Map::new(),
+            Some(Value::Object(profiles)) => profiles,
+            Some(other) => return Err(anyhow::anyhow!("Invalid profiles {} in {}: expected an object", other, path.display())),
+        };
+        for (name, profile) in &profiles {
+            if !profile.is_object() {
+                return Err(anyhow::anyhow!("Invalid profile '{}' in {}: expected an object", name, path.display()));
             }
+            let mut applied = value.clone();
+            merge(&mut applied, profile.clone());
+            serde_json::from_value::<SolarboatConfig>(applied)
+                .with_context(|| format!("Failed to parse profile '{}' in {}", name, path.display()))?;
         }
+        
+        let Some(name) = &self.profile else {
+            return Ok(());
+        };
+        let Some(profile) = profiles.get(name) else {
+            let available = profiles.keys().map(String::as_str).collect::<Vec<_>>();
+            return Err(anyhow::anyhow!("Profile '{}' not found in {} ({})", name, path.display(),
+                if available.is_empty() { "it has no profiles".to_string() } else { format!("available: {}", available.join(", ")) }));
+        };
+        logger::info(&format!("Using configuration profile '{}'", name));
+        merge(value, profile.clone());
+        Ok(())
     }
     
     /// Find the first available configuration file
@@ -100,7 +148,7 @@ impl ConfigLoader {
     
     /// Validate the loaded configuration
     pub fn validate_config(&self, config: &SolarboatConfig) -> Result<()> {
-        let validation_errors: Vec<String> = Vec::new();
+        let mut validation_errors: Vec<String> = Vec::new();
         let mut validation_warnings: Vec<String> = Vec::new();
         
         // Validate module paths exist
@@ -112,6 +160,16 @@ impl ConfigLoader {
             }
         }
         
+        for (module_path, module_config) in &config.modules {
+            for dependency in &module_config.depends_on {
+                let full_path = self.search_dir.join(dependency);
+                if !full_path.exists() {
+                    validation_warnings.push(format!("Dependency '{}' of module '{}' does not exist (checked: {})",
+                        dependency, module_path, full_path.display()));
+                }
+            }
+        }
+        
         // Validate var file paths
         // Note: var_files field has been removed, only workspace_var_files are validated now
         
@@ -134,6 +192,55 @@ impl ConfigLoader {
         // Validate workspace names (basic sanity check)
         self.validate_workspace_names(config, &mut validation_warnings)?;
         
+        let workspace_lists = std::iter::once(("global", &config.global.ignore_workspaces, &config.global.only_workspaces))
+            .chain(config.modules.iter().map(|(path, module)| (path.as_str(), &module.ignore_workspaces, &module.only_workspaces)));
+        for (context, ignore, only) in workspace_lists {
+            for pattern in ignore.iter().chain(only) {
+                if let Err(e) = workspace_pattern(pattern) {
+                    validation_errors.push(format!("{} (in {})", e, context));
+                }
+            }
+        }
+        for (branch, workspaces) in config.global.branch_workspaces.iter().flatten() {
+            for pattern in std::iter::once(branch).chain(workspaces) {
+                if let Err(e) = workspace_pattern(pattern) {
+                    validation_errors.push(format!("{} (in branch_workspaces)", e));
+                }
+            }
+        }
+        
+        for pattern in &config.global.redact_patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                validation_errors.push(format!("Invalid redact pattern '{}': {} (in global)", pattern, e));
+            }
+        }
+        
+        for (module_path, module_config) in &config.modules {
+            let extra_args = &module_config.extra_args;
+            for arg in extra_args.init.iter().chain(&extra_args.plan).chain(&extra_args.apply) {
+                if !arg.starts_with('-') {
+                    validation_errors.push(format!("Extra argument '{}' is not a flag (in module '{}')", arg, module_path));
+                }
+            }
+        }
+        
+        if let Some(template) = &config.global.artifact_template {
+            if let Err(e) = crate::utils::terraform_operations::validate_artifact_template(template) {
+                validation_errors.push(format!("{} (in global)", e));
+            }
+        }
+        
+        let env_passthrough = &config.global.env_passthrough;
+        for pattern in env_passthrough.allow.iter().flatten().chain(&env_passthrough.deny) {
+            if let Err(e) = workspace_pattern(pattern) {
+                validation_errors.push(format!("{} (in env_passthrough)", e));
+            }
+        }
+        
+        if config.global.change_detection.strategies.as_ref().is_some_and(|strategies| strategies.is_empty()) {
+            validation_warnings.push("change_detection.strategies is empty; changes are only detected with --all or explicit refs".to_string());
+        }
+        
         // Print warnings and summary
         if !validation_warnings.is_empty() {
             logger::config_validation_warnings(&validation_warnings);
@@ -216,12 +323,119 @@ impl ConfigLoader {
     }
 }
 
+/// Key listing the base configuration files a configuration file extends
+const EXTENDS_KEY: &str = "extends";
+
+/// Key of the named profiles `--profile` selects from
+const PROFILES_KEY: &str = "profiles";
+
+/// Content of the configuration file at `path`, deep-merged over the base files listed in its
+/// `extends`. Bases are relative to the file naming them and may be YAML as well as JSON; later
+/// bases override earlier ones and the file itself overrides all of them. `chain` holds the files
+/// being loaded, to reject files extending themselves.
+fn load_extended(path: &Path, base: bool, chain: &mut Vec<PathBuf>) -> Result<Value> {
+    let canonical = std::fs::canonicalize(path)
+        .with_context(|| format!("Failed to read configuration file: {}", path.display()))?;
+    if chain.contains(&canonical) {
+        return Err(anyhow::anyhow!("Configuration file {} extends itself", path.display()));
+    }
+    
+    let mut value = parse_file(path, base)?;
+    let Some(object) = value.as_object_mut() else {
+        return Err(anyhow::anyhow!("Configuration file {} is not an object", path.display()));
+    };
+    let bases = match object.remove(EXTENDS_KEY) {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::String(base)) => vec![base],
+        Some(Value::Array(bases)) => bases.into_iter()
+            .map(|base| match base {
+                Value::String(base) => Ok(base),
+                other => Err(anyhow::anyhow!("Invalid entry {} in extends of {}", other, path.display())),
+            })
+            .collect::<Result<_>>()?,
+        Some(other) => return Err(anyhow::anyhow!("Invalid extends {} in {}: expected a path or a list of paths", other, path.display())),
+    };
+    if bases.is_empty() {
+        return Ok(value);
+    }
+    
+    chain.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut merged = Value::Object(serde_json::Map::new());
+    for base in bases {
+        merge(&mut merged, load_extended(&dir.join(&base), true, chain)?);
+    }
+    chain.pop();
+    merge(&mut merged, value);
+    Ok(merged)
+}
+
+/// Parse a configuration file. Only base files may be YAML.
+fn parse_file(path: &Path, base: bool) -> Result<Value> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read configuration file: {}", path.display()))?;
+    
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => {
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse JSON configuration: {}", path.display()))
+        }
+        Some("yml" | "yaml") if base => {
+            crate::utils::yaml::from_str(&content)
+                .map_err(|e| anyhow::anyhow!("Failed to parse YAML configuration {}: {}", path.display(), e))
+        }
+        _ => {
+            // Try to detect format by content
+            if content.trim().starts_with('{') {
+                serde_json::from_str(&content)
+                    .with_context(|| format!("Failed to parse JSON configuration: {}", path.display()))
+            } else {
+                Err(anyhow::anyhow!("Unsupported configuration file format. Only JSON files are supported."))
+            }
+        }
+    }
+}
+
+/// Merge `overlay` into `target`: objects are merged key by key, anything else is replaced
+fn merge(target: &mut Value, overlay: Value) {
+    match (target, overlay) {
+        (Value::Object(target), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, overlay) => *target = overlay,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
     use std::fs;
     
+    #[test]
+    fn test_discover_from_subdirectory() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("repo");
+        let subdir = repo.join("stacks/networking");
+        fs::create_dir_all(&subdir).unwrap();
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join("solarboat.json"), "{}").unwrap();
+        
+        assert_eq!(ConfigLoader::discover(&subdir).unwrap().search_dir, repo);
+        
+        // The search stops at the repository root
+        fs::remove_file(repo.join("solarboat.json")).unwrap();
+        fs::write(temp_dir.path().join("solarboat.json"), "{}").unwrap();
+        assert_eq!(ConfigLoader::discover(&subdir).unwrap().search_dir, subdir);
+    }
+    
     #[test]
     fn test_load_json_config() {
         let temp_dir = TempDir::new().unwrap();
@@ -253,6 +467,67 @@ mod tests {
         assert!(config.modules.contains_key("infrastructure/networking"));
     }
     
+    #[test]
+    fn test_extends() {
+        let temp_dir = TempDir::new().unwrap();
+        let shared = temp_dir.path().join("shared");
+        let repo = temp_dir.path().join("repo");
+        fs::create_dir_all(&shared).unwrap();
+        fs::create_dir_all(&repo).unwrap();
+        fs::write(shared.join("solarboat.base.yml"), r#"
+global:
+  ignore_workspaces: [dev]
+  tf_parallelism: 2
+  workspace_var_files:
+    default: [base.tfvars]
+modules:
+  stacks/app:
+    depends_on: [stacks/vpc]
+"#).unwrap();
+        fs::write(shared.join("security.json"), r#"{"global": {"redact_patterns": ["ghp_[A-Za-z0-9]+"], "tf_parallelism": 3}}"#).unwrap();
+        fs::write(repo.join("solarboat.json"), r#"{
+            "extends": ["../shared/solarboat.base.yml", "../shared/security.json"],
+            "global": {"ignore_workspaces": ["test"]},
+            "modules": {"stacks/app": {"ignore_workspaces": ["qa"]}}
+        }"#).unwrap();
+        
+        let config = ConfigLoader::new(&repo).load().unwrap().unwrap();
+        // Lists are replaced, objects merged, and later files win
+        assert_eq!(config.global.ignore_workspaces, vec!["test"]);
+        assert_eq!(config.global.tf_parallelism, Some(3));
+        assert_eq!(config.global.redact_patterns, vec!["ghp_[A-Za-z0-9]+"]);
+        assert!(config.global.workspace_var_files.is_some());
+        assert_eq!(config.modules["stacks/app"].depends_on, vec!["stacks/vpc"]);
+        assert_eq!(config.modules["stacks/app"].ignore_workspaces, vec!["qa"]);
+        
+        fs::write(shared.join("security.json"), r#"{"extends": "../repo/solarboat.json"}"#).unwrap();
+        let result = ConfigLoader::new(&repo).load();
+        assert!(result.unwrap_err().to_string().contains("extends itself"));
+    }
+    
+    #[test]
+    fn test_profiles() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("solarboat.json"), r#"{
+            "global": {"ignore_workspaces": ["dev"], "tf_parallelism": 4},
+            "profiles": {
+                "staging": {"global": {"only_workspaces": ["staging"]}},
+                "prod": {"global": {"ignore_workspaces": ["dev", "staging"]}, "defaults": {"parallel": 2}}
+            }
+        }"#).unwrap();
+        
+        let config = ConfigLoader::new(temp_dir.path()).load().unwrap().unwrap();
+        assert!(config.global.only_workspaces.is_empty());
+        
+        let config = ConfigLoader::new(temp_dir.path()).with_profile(Some("prod".to_string())).load().unwrap().unwrap();
+        assert_eq!(config.global.ignore_workspaces, vec!["dev", "staging"]);
+        assert_eq!(config.global.tf_parallelism, Some(4));
+        assert_eq!(config.defaults.parallel, Some(2));
+        
+        let result = ConfigLoader::new(temp_dir.path()).with_profile(Some("qa".to_string())).load();
+        assert!(result.unwrap_err().to_string().contains("available: prod, staging"));
+    }
+    
     #[test]
     fn test_unsupported_file_format() {
         let temp_dir = TempDir::new().unwrap();
@@ -4,6 +4,6 @@ mod loader;
 mod resolver;
 
 pub use settings::Settings;
-pub use types::{GlobalConfig, ModuleConfig, SolarboatConfig, WorkspaceVarFiles};
+pub use types::{ChangeDetectionConfig, CliDefaults, CredentialsConfig, DataDirIsolation, DestroyGuard, EnvPassthroughConfig, EnvironmentLayout, ExtraArgsConfig, GlobalConfig, HooksConfig, ModuleConfig, SolarboatConfig, WorkspaceSelection, WorkspaceVarFiles};
 pub use loader::ConfigLoader;
-pub use resolver::{ConfigResolver, ResolvedModuleConfig};
+pub use resolver::{workspace_pattern, ConfigResolver, ResolvedModuleConfig, MODULE_CONFIG_FILE};
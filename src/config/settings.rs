@@ -9,15 +9,15 @@ pub struct Settings {
 }
 
 impl Settings {
-    /// Load settings from configuration file
-    pub fn load<P: AsRef<std::path::Path>>(config_path: P) -> Result<Self> {
+    /// Load settings from configuration file, applying `profile` if given
+    pub fn load<P: AsRef<std::path::Path>>(config_path: P, profile: Option<String>) -> Result<Self> {
         let config_path = config_path.as_ref().to_path_buf();
         
         // Check if the path is a file or directory
         if config_path.is_file() {
             // Load from specific file
             let config_dir = config_path.parent().unwrap_or(&PathBuf::from(".")).to_path_buf();
-            let loader = ConfigLoader::new(&config_dir);
+            let loader = ConfigLoader::new(&config_dir).with_profile(profile);
             let config = loader.load_from_path(&config_path)?;
             
             // Validate configuration
@@ -27,8 +27,9 @@ impl Settings {
             let config_resolver = ConfigResolver::new(Some(config), config_dir);
             Ok(Self { config_resolver })
         } else {
-            // Load from directory (auto-discover)
-            let loader = ConfigLoader::new(&config_path);
+            // Load from directory (auto-discover, up to the repository root)
+            let loader = ConfigLoader::discover(&config_path)?.with_profile(profile);
+            let config_path = loader.search_dir.clone();
             let config = loader.load()?;
             
             // Validate configuration if loaded
@@ -42,9 +43,9 @@ impl Settings {
         }
     }
     
-    /// Load settings from current working directory
-    pub fn load_from_current_dir() -> Result<Self> {
-        let loader = ConfigLoader::from_current_dir()?;
+    /// Load settings from current working directory, applying `profile` if given
+    pub fn load_from_current_dir(profile: Option<String>) -> Result<Self> {
+        let loader = ConfigLoader::from_current_dir()?.with_profile(profile);
         let config_dir = loader.search_dir.clone();
         
         // Load configuration file
@@ -66,3 +67,30 @@ impl Settings {
         &self.config_resolver
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_load_from_subdirectory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = fs::canonicalize(temp_dir.path()).unwrap();
+        let subdir = repo.join("stacks/networking");
+        fs::create_dir_all(&subdir).unwrap();
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join("solarboat.json"), r#"{"modules": {"stacks/networking": {"ignore_workspaces": ["dev"]}}}"#).unwrap();
+
+        // Module paths in the configuration are relative to the directory it was found in
+        let settings = Settings::load(&subdir, None).unwrap();
+        let module = subdir.to_string_lossy().to_string();
+        assert!(settings.resolver().should_ignore_workspace(&module, "dev", None));
+        assert!(!settings.resolver().should_ignore_workspace(&module, "prod", None));
+
+        // Without a configuration file up to the repository root, defaults apply
+        fs::remove_file(repo.join("solarboat.json")).unwrap();
+        let settings = Settings::load(&subdir, None).unwrap();
+        assert!(!settings.resolver().should_ignore_workspace(&module, "dev", None));
+    }
+}
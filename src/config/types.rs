@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::utils::scan_utils::DetectionStrategy;
 
 /// Configuration for workspace-specific variable files
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,14 +11,295 @@ pub struct WorkspaceVarFiles {
     pub workspaces: HashMap<String, Vec<String>>,
 }
 
+/// How apply treats plans that delete resources
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DestroyGuard {
+    /// Deletions are applied without checks
+    Off,
+    /// Deletions require --allow-destroy
+    #[default]
+    Flag,
+    /// Deletions require --allow-destroy or interactive confirmation
+    Confirm,
+    /// Plans with deletions are never applied
+    Fail,
+}
+
+impl std::fmt::Display for DestroyGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DestroyGuard::Off => "off",
+            DestroyGuard::Flag => "flag",
+            DestroyGuard::Confirm => "confirm",
+            DestroyGuard::Fail => "fail",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Where terraform keeps its working data (TF_DATA_DIR) for each operation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataDirIsolation {
+    /// Terraform's default `.terraform` directory inside the module
+    #[default]
+    Off,
+    /// One directory per module under `.solarboat/data`
+    Module,
+    /// One directory per module and workspace under `.solarboat/data`
+    Workspace,
+}
+
+impl std::fmt::Display for DataDirIsolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DataDirIsolation::Off => "off",
+            DataDirIsolation::Module => "module",
+            DataDirIsolation::Workspace => "workspace",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How the environments of a stack are laid out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvironmentLayout {
+    /// Environments are terraform workspaces of a single module directory
+    #[default]
+    Workspaces,
+    /// Environments are sibling module directories, such as `envs/dev` and `envs/prod`
+    Directories,
+}
+
+/// How terraform is pointed at the workspace of an operation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceSelection {
+    /// `terraform workspace select`
+    #[default]
+    Select,
+    /// The `TF_WORKSPACE` environment variable, for backends where selecting doesn't work as usual
+    Env,
+}
+
+/// Shell commands run in the module directory around each terraform phase.
+/// They receive `SOLARBOAT_MODULE`, `SOLARBOAT_WORKSPACE` and `SOLARBOAT_PHASE`;
+/// post hooks also receive `SOLARBOAT_SUCCESS`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HooksConfig {
+    #[serde(default)]
+    pub pre_init: Option<String>,
+    #[serde(default)]
+    pub pre_plan: Option<String>,
+    #[serde(default)]
+    pub post_plan: Option<String>,
+    #[serde(default)]
+    pub pre_apply: Option<String>,
+    #[serde(default)]
+    pub post_apply: Option<String>,
+}
+
+/// Cloud credentials turned into environment variables for terraform and the hooks
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CredentialsConfig {
+    /// AWS profile, set as `AWS_PROFILE`
+    #[serde(default)]
+    pub aws_profile: Option<String>,
+    /// AWS role assumed with `aws sts assume-role`, whose temporary credentials are set
+    #[serde(default)]
+    pub assume_role_arn: Option<String>,
+    /// Variables copied from solarboat's own environment, by target name, e.g. `{"ARM_CLIENT_SECRET": "PROD_ARM_CLIENT_SECRET"}`
+    #[serde(default)]
+    pub credentials_env: BTreeMap<String, String>,
+}
+
+/// Values for command line arguments that aren't given, in every command taking them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CliDefaults {
+    /// `--parallel` of plan, apply and lockfiles update
+    #[serde(default)]
+    pub parallel: Option<u32>,
+    /// `--output-dir` of plan
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    /// `--default-branch` of scan, plan, apply, lockfiles and hooks install
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    /// `--recent-commits` of scan, plan, apply and lockfiles
+    #[serde(default)]
+    pub recent_commits: Option<u32>,
+    /// `--watch` of plan and apply
+    #[serde(default)]
+    pub watch: Option<bool>,
+}
+
+/// Which of solarboat's own environment variables terraform commands inherit. Names are globs,
+/// e.g. `AWS_*`, or regexes between slashes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnvPassthroughConfig {
+    /// Only these variables are inherited, besides PATH, HOME and the temp directory (default: all)
+    #[serde(default)]
+    pub allow: Option<Vec<String>>,
+    /// Variables never inherited, on top of CI tokens and library preloading
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// Extra arguments added to a module's terraform commands, after the ones solarboat passes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExtraArgsConfig {
+    /// Added to `terraform init`
+    #[serde(default)]
+    pub init: Vec<String>,
+    /// Added to `terraform plan`
+    #[serde(default)]
+    pub plan: Vec<String>,
+    /// Added to `terraform apply`
+    #[serde(default)]
+    pub apply: Vec<String>,
+}
+
+/// Which change detection strategies may be used, and their parameters
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeDetectionConfig {
+    /// Strategies change detection may use; they are always tried in their fixed order (default: all)
+    #[serde(default)]
+    pub strategies: Option<Vec<DetectionStrategy>>,
+    /// Recent commits checked in a pipeline when its commits and the merge base find no changes (default: 10)
+    #[serde(default)]
+    pub pipeline_recent_commits: Option<u32>,
+    /// How far back the reference strategy looks when there is no tag, e.g. `12h` or `7d` (default: `24h`)
+    #[serde(default)]
+    pub reference_window: Option<String>,
+}
+
 /// Global configuration settings applied to all modules
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GlobalConfig {
     /// Workspaces to ignore globally
     #[serde(default)]
     pub ignore_workspaces: Vec<String>,
+    /// Only process these workspaces, skipping any other with a warning (default: all)
+    #[serde(default)]
+    pub only_workspaces: Vec<String>,
+    /// Workspaces to process on each git branch, by branch name or pattern, e.g. `main: [prod]`
+    #[serde(default)]
+    pub branch_workspaces: Option<BTreeMap<String, Vec<String>>>,
     /// Global workspace variable file mappings
     pub workspace_var_files: Option<WorkspaceVarFiles>,
+    /// Pass each module's `<workspace>.tfvars` and `tfvars/<workspace>.tfvars` when present (default: false)
+    #[serde(default)]
+    pub auto_var_files: Option<bool>,
+    /// Partial backend configuration for `terraform init`: `KEY=VALUE` pairs or files
+    #[serde(default)]
+    pub backend_config: Vec<String>,
+    /// Partial backend configuration for each workspace, added after `backend_config`
+    #[serde(default)]
+    pub workspace_backend_config: BTreeMap<String, Vec<String>>,
+    /// How apply treats plans that delete resources (default: flag)
+    #[serde(default)]
+    pub destroy_guard: Option<DestroyGuard>,
+    /// Maximum planned deletions per plan before the plan phase fails
+    #[serde(default)]
+    pub max_destroys: Option<usize>,
+    /// TF_DATA_DIR isolation (default: off)
+    #[serde(default)]
+    pub data_dir_isolation: Option<DataDirIsolation>,
+    /// Run the workspaces of a module in parallel, each with its own TF_DATA_DIR (default: false)
+    #[serde(default)]
+    pub parallel_workspaces: Option<bool>,
+    /// Process the default workspace of modules that have other workspaces (default: true)
+    #[serde(default)]
+    pub process_default_workspace: Option<bool>,
+    /// Don't keep artifacts for plans that report no changes (default: false)
+    #[serde(default)]
+    pub skip_empty_plans: Option<bool>,
+    /// Terraform `-parallelism` for plan and apply (default: terraform's own)
+    #[serde(default)]
+    pub tf_parallelism: Option<u32>,
+    /// How long terraform waits for a busy state lock, e.g. `5m` (default: fail immediately)
+    #[serde(default)]
+    pub lock_timeout: Option<String>,
+    /// Whether environments are workspaces or directories (default: workspaces)
+    #[serde(default)]
+    pub environment_layout: Option<EnvironmentLayout>,
+    /// Name of the directory holding the environment directories in the `directories` layout (default: envs)
+    #[serde(default)]
+    pub environments_dir: Option<String>,
+    /// Hooks run for every module
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    /// Environment variables set for terraform and the hooks, e.g. `AWS_REGION`
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Environment variables for each workspace, added over `env`
+    #[serde(default)]
+    pub workspace_env: BTreeMap<String, BTreeMap<String, String>>,
+    /// Cloud credentials for terraform and the hooks
+    #[serde(default)]
+    pub credentials: CredentialsConfig,
+    /// Cloud credentials for each workspace, added over `credentials`
+    #[serde(default)]
+    pub workspace_credentials: BTreeMap<String, CredentialsConfig>,
+    /// How workspaces are selected (default: select)
+    #[serde(default)]
+    pub workspace_selection: Option<WorkspaceSelection>,
+    /// Prefix of workspace names left out when matching them against workspace settings
+    #[serde(default)]
+    pub workspace_prefix: Option<String>,
+    /// Save each workspace's state before a live apply (default: true)
+    #[serde(default)]
+    pub state_snapshots: Option<bool>,
+    /// Seconds workspace listings are reused by later runs (default: listed once per run)
+    #[serde(default)]
+    pub workspace_cache_ttl: Option<u64>,
+    /// Regular expressions whose matches are masked in output and reports, on top of sensitive values
+    #[serde(default)]
+    pub redact_patterns: Vec<String>,
+    /// Path of plan and apply artifacts in the output directory, without suffix (default: {module}/{workspace}/{timestamp})
+    #[serde(default)]
+    pub artifact_template: Option<String>,
+    /// Which environment variables terraform commands inherit from solarboat
+    #[serde(default)]
+    pub env_passthrough: EnvPassthroughConfig,
+    /// File suffixes whose changes mark a module as changed (default: all terraform file types)
+    #[serde(default)]
+    pub tracked_extensions: Option<Vec<String>>,
+    /// Directory names or paths module discovery skips (default: .terraform, .terragrunt-cache, .git, node_modules, vendor)
+    #[serde(default)]
+    pub excluded_dirs: Option<Vec<String>>,
+    /// Root module paths or globs, relative to the config file; when set, the repository is not walked for modules
+    #[serde(default)]
+    pub root_modules: Option<Vec<String>>,
+    /// Path globs, relative to the config file, of modules left out of discovery, change detection and `--all` runs
+    #[serde(default)]
+    pub ignore_modules: Option<Vec<String>>,
+    /// Descend into symlinked directories during module discovery (default: true)
+    #[serde(default)]
+    pub follow_symlinks: Option<bool>,
+    /// How many directory levels below the repository root module discovery descends (default: unlimited)
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// Maximum modules planned or applied at once per concurrency group, e.g. `s3:tf-state: 1`
+    #[serde(default)]
+    pub concurrency_limits: Option<HashMap<String, usize>>,
+    /// Backend states (`key` or `bucket/key`) several modules may write without failing scan
+    #[serde(default)]
+    pub allowed_shared_states: Option<Vec<String>>,
+    /// Change detection strategies and their parameters
+    #[serde(default)]
+    pub change_detection: ChangeDetectionConfig,
+    /// Diff inside changed git submodules instead of treating all their files as changed (default: false)
+    #[serde(default)]
+    pub recurse_submodules: Option<bool>,
+    /// Also queue stateful modules that consume outputs of changed modules (default: false)
+    #[serde(default)]
+    pub propagate_dependents: Option<bool>,
+    /// How many levels of dependents to queue when propagating (default: unlimited)
+    #[serde(default)]
+    pub propagate_depth: Option<u32>,
 }
 
 /// Module-specific configuration settings
@@ -25,13 +308,100 @@ pub struct ModuleConfig {
     /// Workspaces to ignore for this module
     #[serde(default)]
     pub ignore_workspaces: Vec<String>,
+    /// Workspace allowlist override for this module
+    #[serde(default)]
+    pub only_workspaces: Vec<String>,
     /// Module-specific workspace variable file mappings
     pub workspace_var_files: Option<WorkspaceVarFiles>,
+    /// Conventional variable file detection override for this module
+    #[serde(default)]
+    pub auto_var_files: Option<bool>,
+    /// Partial backend configuration for this module, added after the global one
+    #[serde(default)]
+    pub backend_config: Vec<String>,
+    /// Partial backend configuration for each workspace of this module, added last
+    #[serde(default)]
+    pub workspace_backend_config: BTreeMap<String, Vec<String>>,
+    /// Destroy guard override for this module
+    #[serde(default)]
+    pub destroy_guard: Option<DestroyGuard>,
+    /// Maximum planned deletions override for this module
+    #[serde(default)]
+    pub max_destroys: Option<usize>,
+    /// TF_DATA_DIR isolation override for this module
+    #[serde(default)]
+    pub data_dir_isolation: Option<DataDirIsolation>,
+    /// Parallel workspaces override for this module
+    #[serde(default)]
+    pub parallel_workspaces: Option<bool>,
+    /// Default workspace processing override for this module
+    #[serde(default)]
+    pub process_default_workspace: Option<bool>,
+    /// Empty plan artifact override for this module
+    #[serde(default)]
+    pub skip_empty_plans: Option<bool>,
+    /// Resource addresses passed as `-target` to plan and apply
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// Variables passed as `-var` to plan and apply. Non-string values are passed as JSON.
+    #[serde(default)]
+    pub vars: BTreeMap<String, serde_json::Value>,
+    /// Refresh state during plan and apply (default: true)
+    #[serde(default)]
+    pub refresh: Option<bool>,
+    /// Terraform `-parallelism` override for this module
+    #[serde(default)]
+    pub tf_parallelism: Option<u32>,
+    /// State lock timeout override for this module
+    #[serde(default)]
+    pub lock_timeout: Option<String>,
+    /// Hook overrides for this module, per phase
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    /// Environment variables for this module, added over the global ones
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Environment variables for each workspace of this module, added over every other `env`
+    #[serde(default)]
+    pub workspace_env: BTreeMap<String, BTreeMap<String, String>>,
+    /// Cloud credentials added over the global ones for this module
+    #[serde(default)]
+    pub credentials: CredentialsConfig,
+    /// Cloud credentials for each workspace of this module
+    #[serde(default)]
+    pub workspace_credentials: BTreeMap<String, CredentialsConfig>,
+    /// Workspace selection override for this module
+    #[serde(default)]
+    pub workspace_selection: Option<WorkspaceSelection>,
+    /// Workspace name prefix override for this module
+    #[serde(default)]
+    pub workspace_prefix: Option<String>,
+    /// State snapshot override for this module
+    #[serde(default)]
+    pub state_snapshots: Option<bool>,
+    /// Modules this module depends on beyond its `module` sources and remote states, relative to the config file
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Whether the module holds state, overriding the classification from its files
+    #[serde(default)]
+    pub stateful: Option<bool>,
+    /// Labels selecting the module with `--tags` and `--exclude-tags`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Concurrency group the module is scheduled in (default: inferred from its backend)
+    #[serde(default)]
+    pub concurrency_group: Option<String>,
+    /// Extra arguments for the module's terraform init, plan and apply
+    #[serde(default)]
+    pub extra_args: ExtraArgsConfig,
 }
 
 /// Root configuration structure for solarboat
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SolarboatConfig {
+    /// Default values of command line arguments
+    #[serde(default)]
+    pub defaults: CliDefaults,
     /// Global configuration settings
     #[serde(default)]
     pub global: GlobalConfig,
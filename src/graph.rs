@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use petgraph::algo::{tarjan_scc, toposort};
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::{Dfs, IntoNeighbors, NodeFiltered, Reversed, Visitable};
+use petgraph::Direction;
+
+use crate::utils::lockfile::ProviderRequirement;
+use crate::utils::scan_utils::{self, BackendInfo, Classification, DiscoveryOptions, GraphOverrides};
+
+/// A module of the graph
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModuleNode {
+    /// Whether the module holds state, so plan and apply run it
+    pub stateful: bool,
+    /// Why the module is stateful or stateless
+    pub classification: Classification,
+    /// The module's backend or cloud block, if it declares one
+    pub backend: Option<BackendInfo>,
+    /// Terraform version constraint set with `required_version`
+    pub required_version: Option<String>,
+    /// Providers declared in `required_providers`
+    pub providers: Vec<ProviderRequirement>,
+}
+
+/// The modules of a repository and the dependencies between them, keyed by absolute module path.
+/// A module depends on the modules it sources, the modules whose state it reads through
+/// `terraform_remote_state`, and the modules configured in its `depends_on`.
+/// Edges point from a module to its dependency.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleGraph {
+    graph: DiGraph<(String, ModuleNode), ()>,
+    indices: BTreeMap<String, NodeIndex>,
+}
+
+impl PartialEq for ModuleGraph {
+    fn eq(&self, other: &Self) -> bool {
+        self.modules().eq(other.modules()) && self.edges().eq(other.edges())
+    }
+}
+
+impl ModuleGraph {
+    /// Discover the modules under `root_dir` and link them, as change detection does
+    pub fn discover(root_dir: &str, discovery: &DiscoveryOptions, overrides: &GraphOverrides) -> Result<Self, String> {
+        scan_utils::discover_graph(root_dir, discovery, overrides)
+    }
+
+    /// Add a module, replacing the module with the same path but keeping its edges
+    pub fn add_module(&mut self, path: impl Into<String>, node: ModuleNode) {
+        let path = path.into();
+        match self.indices.get(&path) {
+            Some(&index) => self.graph[index].1 = node,
+            None => {
+                let index = self.graph.add_node((path.clone(), node));
+                self.indices.insert(path, index);
+            }
+        }
+    }
+
+    /// Record that `module` depends on `dependency`. Both must be in the graph.
+    pub fn add_dependency(&mut self, module: &str, dependency: &str) -> Result<(), String> {
+        let [module, dependency] = [module, dependency].map(|path| {
+            self.indices.get(path).copied().ok_or_else(|| format!("{} is not a module of the graph", path))
+        });
+        self.graph.update_edge(module?, dependency?, ());
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.graph.node_count() == 0
+    }
+
+    pub fn contains(&self, module: &str) -> bool {
+        self.indices.contains_key(module)
+    }
+
+    pub fn module(&self, module: &str) -> Option<&ModuleNode> {
+        self.indices.get(module).map(|&index| &self.graph[index].1)
+    }
+
+    /// Every module with its node, by path
+    pub fn modules(&self) -> impl Iterator<Item = (&str, &ModuleNode)> {
+        self.indices.values().map(|&index| {
+            let (path, node) = &self.graph[index];
+            (path.as_str(), node)
+        })
+    }
+
+    /// Every (module, dependency) edge, by module path
+    pub fn edges(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.indices.keys()
+            .flat_map(|module| self.dependencies(module).map(move |dependency| (module.as_str(), dependency)))
+    }
+
+    /// Modules `module` depends on directly
+    pub fn dependencies(&self, module: &str) -> impl Iterator<Item = &str> {
+        self.neighbors(module, Direction::Outgoing)
+    }
+
+    /// Modules depending directly on `module`
+    pub fn dependents(&self, module: &str) -> impl Iterator<Item = &str> {
+        self.neighbors(module, Direction::Incoming)
+    }
+
+    /// Modules `module` depends on directly or through other modules
+    pub fn transitive_dependencies(&self, module: &str) -> Vec<String> {
+        self.indices.get(module)
+            .map(|&start| self.reachable(&self.graph, start))
+            .unwrap_or_default()
+    }
+
+    /// Modules depending on `module` directly or through other modules
+    pub fn transitive_dependents(&self, module: &str) -> Vec<String> {
+        self.indices.get(module)
+            .map(|&start| self.reachable(Reversed(&self.graph), start))
+            .unwrap_or_default()
+    }
+
+    /// Stateful modules impacted by changes to the `changed` modules, as change detection selects them:
+    /// changed stateful modules, and the stateful modules using a changed stateless module directly or
+    /// through other stateless modules. Consumers of changed stateful modules are not included.
+    pub fn impacted(&self, changed: &[String]) -> Vec<String> {
+        // Walk towards dependents, stopping at stateful modules
+        let stateless = NodeFiltered::from_fn(Reversed(&self.graph), |index| !self.graph[index].1.stateful);
+        let mut impacted = BTreeSet::new();
+        for &start in changed.iter().filter_map(|module| self.indices.get(module)) {
+            if self.graph[start].1.stateful {
+                impacted.insert(self.path(start));
+                continue;
+            }
+            let mut dfs = Dfs::new(&stateless, start);
+            while let Some(index) = dfs.next(&stateless) {
+                impacted.extend(
+                    self.graph.neighbors_directed(index, Direction::Incoming)
+                        .filter(|&dependent| self.graph[dependent].1.stateful)
+                        .map(|dependent| self.path(dependent))
+                );
+            }
+        }
+        impacted.into_iter().map(str::to_string).collect()
+    }
+
+    /// Every module, each after the modules it depends on. The order only depends on the paths and
+    /// edges, not on the order modules were added, so it is stable.
+    /// Fails with the modules on or behind a dependency cycle when there is one.
+    pub fn topological_order(&self) -> Result<Vec<String>, Vec<String>> {
+        // Rebuild the graph in path order so the traversal does not depend on insertion order
+        let mut sorted = DiGraph::<&str, ()>::new();
+        let positions: BTreeMap<&str, NodeIndex> = self.indices.keys()
+            .map(|module| (module.as_str(), sorted.add_node(module.as_str())))
+            .collect();
+        for (module, dependency) in self.edges() {
+            sorted.add_edge(positions[module], positions[dependency], ());
+        }
+
+        match toposort(Reversed(&sorted), None) {
+            Ok(order) => Ok(order.into_iter().map(|index| sorted[index].to_string()).collect()),
+            Err(_) => Err(self.cyclic_modules()),
+        }
+    }
+
+    /// Modules on a dependency cycle, and the modules depending on them
+    fn cyclic_modules(&self) -> Vec<String> {
+        let mut found = BTreeSet::new();
+        for component in tarjan_scc(&self.graph) {
+            let on_cycle = component.len() > 1 || self.graph.contains_edge(component[0], component[0]);
+            if on_cycle {
+                for &index in &component {
+                    found.insert(self.path(index).to_string());
+                    found.extend(self.reachable(Reversed(&self.graph), index));
+                }
+            }
+        }
+        found.into_iter().collect()
+    }
+
+    fn neighbors(&self, module: &str, direction: Direction) -> impl Iterator<Item = &str> {
+        let mut neighbors: Vec<&str> = self.indices.get(module)
+            .into_iter()
+            .flat_map(|&index| self.graph.neighbors_directed(index, direction))
+            .map(|index| self.path(index))
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.into_iter()
+    }
+
+    /// Modules reachable from `start`, excluding `start` itself
+    fn reachable<G>(&self, graph: G, start: NodeIndex) -> Vec<String>
+    where
+        G: IntoNeighbors<NodeId = NodeIndex> + Visitable,
+    {
+        let mut found = BTreeSet::new();
+        let mut dfs = Dfs::new(graph, start);
+        while let Some(index) = dfs.next(graph) {
+            if index != start {
+                found.insert(self.path(index));
+            }
+        }
+        found.into_iter().map(str::to_string).collect()
+    }
+
+    fn path(&self, index: NodeIndex) -> &str {
+        &self.graph[index].0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(modules: &[(&str, bool)], edges: &[(&str, &str)]) -> ModuleGraph {
+        let mut graph = ModuleGraph::default();
+        for (path, stateful) in modules {
+            graph.add_module(*path, ModuleNode { stateful: *stateful, ..Default::default() });
+        }
+        for (module, dependency) in edges {
+            graph.add_dependency(module, dependency).unwrap();
+        }
+        graph
+    }
+
+    #[test]
+    fn test_module_graph() {
+        let graph = build(
+            &[("app", true), ("db", true), ("wrapper", false), ("vpc", false)],
+            &[("app", "wrapper"), ("wrapper", "vpc"), ("app", "db"), ("db", "vpc")],
+        );
+        assert_eq!(graph.dependencies("app").collect::<Vec<_>>(), vec!["db", "wrapper"]);
+        assert_eq!(graph.dependents("vpc").collect::<Vec<_>>(), vec!["db", "wrapper"]);
+        assert_eq!(graph.transitive_dependencies("app"), vec!["db", "vpc", "wrapper"]);
+        assert_eq!(graph.transitive_dependents("vpc"), vec!["app", "db", "wrapper"]);
+        assert_eq!(graph.impacted(&["vpc".to_string()]), vec!["app", "db"]);
+        assert_eq!(graph.impacted(&["db".to_string()]), vec!["db"]);
+        assert_eq!(graph.topological_order(), Ok(vec!["vpc".to_string(), "wrapper".to_string(), "db".to_string(), "app".to_string()]));
+        let reordered = build(
+            &[("vpc", false), ("wrapper", false), ("db", true), ("app", true)],
+            &[("db", "vpc"), ("app", "db"), ("wrapper", "vpc"), ("app", "wrapper")],
+        );
+        assert_eq!(reordered, graph);
+        assert_eq!(reordered.topological_order(), graph.topological_order());
+        assert!(graph.clone().add_dependency("app", "missing").is_err());
+
+        let cyclic = build(&[("a", true), ("b", true), ("c", true), ("d", true)], &[("a", "b"), ("b", "a"), ("c", "a"), ("c", "d")]);
+        assert_eq!(cyclic.topological_order(), Err(vec!["a".to_string(), "b".to_string(), "c".to_string()]));
+        assert_eq!(cyclic.transitive_dependencies("a"), vec!["b"]);
+    }
+}
@@ -1,9 +1,10 @@
 pub mod cli;
 pub mod commands;
 pub mod config;
+pub mod graph;
 pub mod utils;
 
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use colored::*;
 use std::env;
 
@@ -37,7 +38,8 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         print_banner();
     }
     
-    let cli = cli::Args::parse();
+    let matches = cli::Args::command().get_matches();
+    let cli = cli::Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
     
     // Initialize logger with CLI settings
     let log_level = match cli.log_level {
@@ -48,9 +50,12 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         cli::LogLevel::Debug => utils::logger::LogLevel::Debug,
         cli::LogLevel::Trace => utils::logger::LogLevel::Trace,
     };
-    utils::logger::init(log_level, cli.quiet);
+    // Machine-readable scan output is the only thing written to stdout
+    let machine_output = matches!(&cli.command, cli::Commands::Scan(args) if args.output != cli::ScanOutput::Text);
+    utils::logger::init(log_level, cli.quiet || machine_output);
+    utils::git::resolve_env_paths();
     
-    match commands::handle_command(cli) {
+    match commands::handle_command(cli, &matches) {
         Ok(_) => Ok(()),
         Err(e) => {
             utils::logger::error_box("Command Failed", &format!("{}", e));
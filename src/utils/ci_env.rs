@@ -0,0 +1,271 @@
+/// CI system a run was detected in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiProvider {
+    GithubActions,
+    GitlabCi,
+    CircleCi,
+    Jenkins,
+    AzurePipelines,
+}
+
+impl CiProvider {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CiProvider::GithubActions => "GitHub Actions",
+            CiProvider::GitlabCi => "GitLab CI",
+            CiProvider::CircleCi => "CircleCI",
+            CiProvider::Jenkins => "Jenkins",
+            CiProvider::AzurePipelines => "Azure Pipelines",
+        }
+    }
+}
+
+/// Pull request and commit information of the pipeline a run is part of.
+/// `SOLARBOAT_*` variables take precedence over values detected from the CI provider.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PipelineContext {
+    pub provider: Option<CiProvider>,
+    pub pr_number: Option<String>,
+    pub base_commit: Option<String>,
+    pub head_commit: Option<String>,
+    pub base_branch: Option<String>,
+    pub head_branch: Option<String>,
+    /// Branch being built, for detached checkouts where git can't tell
+    pub branch: Option<String>,
+}
+
+/// Git's placeholder for "no commit", e.g. the `before` of a newly pushed branch
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+
+impl PipelineContext {
+    /// Detect the pipeline context from the process environment
+    pub fn detect() -> Self {
+        Self::from_env(&|name| std::env::var(name).ok())
+    }
+
+    /// Detect the pipeline context from variables looked up with `var`
+    pub fn from_env(var: &dyn Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty() && v != NULL_SHA);
+
+        let mut context = if var("GITHUB_ACTIONS").is_some() {
+            github_actions(&var)
+        } else if var("GITLAB_CI").is_some() {
+            gitlab_ci(&var)
+        } else if var("CIRCLECI").is_some() {
+            circleci(&var)
+        } else if var("JENKINS_URL").is_some() {
+            jenkins(&var)
+        } else if var("TF_BUILD").is_some() {
+            azure_pipelines(&var)
+        } else {
+            Self::default()
+        };
+
+        let overrides = [
+            ("SOLARBOAT_PR_NUMBER", &mut context.pr_number),
+            ("SOLARBOAT_BASE_COMMIT", &mut context.base_commit),
+            ("SOLARBOAT_HEAD_COMMIT", &mut context.head_commit),
+            ("SOLARBOAT_BASE_BRANCH", &mut context.base_branch),
+            ("SOLARBOAT_HEAD_BRANCH", &mut context.head_branch),
+        ];
+        for (name, field) in overrides {
+            if let Some(value) = var(name) {
+                *field = Some(value);
+            }
+        }
+        context
+    }
+
+    /// Whether the pipeline supplied a pull request or commits to compare
+    pub fn has_changes_info(&self) -> bool {
+        self.pr_number.is_some() || self.base_commit.is_some()
+    }
+
+    /// Short description for logs, e.g. `PR #42` or `push`
+    pub fn describe(&self) -> String {
+        match &self.pr_number {
+            Some(pr_number) => format!("PR #{}", pr_number),
+            None => "push".to_string(),
+        }
+    }
+}
+
+fn github_actions(var: &dyn Fn(&str) -> Option<String>) -> PipelineContext {
+    let event = var("GITHUB_EVENT_PATH")
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .unwrap_or_default();
+    let event_str = |pointer: &str| event.pointer(pointer)
+        .and_then(|v| v.as_str())
+        .map(|v| v.to_string())
+        .filter(|v| !v.is_empty() && v != NULL_SHA);
+
+    // A merge queue builds a temporary branch that lands on the base branch as is, so it is treated as a
+    // build of the base branch comparing the queue entry with the commit it is built on
+    if var("GITHUB_EVENT_NAME").as_deref() == Some("merge_group") {
+        let head_branch = event_str("/merge_group/head_ref")
+            .map(|r| r.trim_start_matches("refs/heads/").to_string())
+            .or_else(|| var("GITHUB_REF_NAME"));
+        let queue = head_branch.as_deref().and_then(merge_queue_branch);
+        let base_branch = event_str("/merge_group/base_ref")
+            .map(|r| r.trim_start_matches("refs/heads/").to_string())
+            .or_else(|| queue.as_ref().map(|(base, _)| base.clone()));
+        return PipelineContext {
+            provider: Some(CiProvider::GithubActions),
+            pr_number: queue.map(|(_, pr_number)| pr_number),
+            base_commit: event_str("/merge_group/base_sha"),
+            head_commit: event_str("/merge_group/head_sha").or_else(|| var("GITHUB_SHA")),
+            branch: base_branch.clone(),
+            base_branch,
+            head_branch,
+        };
+    }
+
+    PipelineContext {
+        provider: Some(CiProvider::GithubActions),
+        pr_number: event.pointer("/pull_request/number").and_then(|n| n.as_u64()).map(|n| n.to_string())
+            .or_else(|| var("GITHUB_REF").and_then(|r| r.strip_prefix("refs/pull/").and_then(|r| r.split('/').next()).map(|n| n.to_string()))),
+        base_commit: event_str("/pull_request/base/sha").or_else(|| event_str("/before")),
+        head_commit: event_str("/pull_request/head/sha").or_else(|| var("GITHUB_SHA")),
+        base_branch: var("GITHUB_BASE_REF"),
+        head_branch: var("GITHUB_HEAD_REF"),
+        branch: var("GITHUB_HEAD_REF").or_else(|| var("GITHUB_REF_NAME")),
+    }
+}
+
+/// Base branch and PR number of a GitHub merge queue branch, `gh-readonly-queue/<base>/pr-<number>-<sha>`
+fn merge_queue_branch(branch: &str) -> Option<(String, String)> {
+    let (base, entry) = branch.strip_prefix("gh-readonly-queue/")?.rsplit_once("/pr-")?;
+    let pr_number = entry.split('-').next().filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))?;
+    Some((base.to_string(), pr_number.to_string()))
+}
+
+fn gitlab_ci(var: &dyn Fn(&str) -> Option<String>) -> PipelineContext {
+    PipelineContext {
+        provider: Some(CiProvider::GitlabCi),
+        pr_number: var("CI_MERGE_REQUEST_IID"),
+        base_commit: var("CI_MERGE_REQUEST_DIFF_BASE_SHA").or_else(|| var("CI_COMMIT_BEFORE_SHA")),
+        head_commit: var("CI_COMMIT_SHA"),
+        base_branch: var("CI_MERGE_REQUEST_TARGET_BRANCH_NAME"),
+        head_branch: var("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME"),
+        branch: var("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME").or_else(|| var("CI_COMMIT_BRANCH")),
+    }
+}
+
+fn circleci(var: &dyn Fn(&str) -> Option<String>) -> PipelineContext {
+    PipelineContext {
+        provider: Some(CiProvider::CircleCi),
+        pr_number: var("CIRCLE_PR_NUMBER")
+            .or_else(|| var("CIRCLE_PULL_REQUEST").and_then(|url| url.rsplit('/').next().map(|n| n.to_string()))),
+        base_commit: None,
+        head_commit: var("CIRCLE_SHA1"),
+        base_branch: None,
+        head_branch: var("CIRCLE_PULL_REQUEST").and(var("CIRCLE_BRANCH")),
+        branch: var("CIRCLE_BRANCH"),
+    }
+}
+
+fn jenkins(var: &dyn Fn(&str) -> Option<String>) -> PipelineContext {
+    PipelineContext {
+        provider: Some(CiProvider::Jenkins),
+        pr_number: var("CHANGE_ID"),
+        base_commit: var("GIT_PREVIOUS_SUCCESSFUL_COMMIT"),
+        head_commit: var("GIT_COMMIT"),
+        base_branch: var("CHANGE_TARGET"),
+        head_branch: var("CHANGE_BRANCH"),
+        branch: var("BRANCH_NAME")
+            .or_else(|| var("GIT_BRANCH").map(|b| b.strip_prefix("origin/").unwrap_or(&b).to_string())),
+    }
+}
+
+fn azure_pipelines(var: &dyn Fn(&str) -> Option<String>) -> PipelineContext {
+    let branch_name = |name: &str| var(name).map(|b| b.strip_prefix("refs/heads/").unwrap_or(&b).to_string());
+
+    PipelineContext {
+        provider: Some(CiProvider::AzurePipelines),
+        pr_number: var("SYSTEM_PULLREQUEST_PULLREQUESTNUMBER").or_else(|| var("SYSTEM_PULLREQUEST_PULLREQUESTID")),
+        base_commit: None,
+        head_commit: var("SYSTEM_PULLREQUEST_SOURCECOMMITID").or_else(|| var("BUILD_SOURCEVERSION")),
+        base_branch: branch_name("SYSTEM_PULLREQUEST_TARGETBRANCH"),
+        head_branch: branch_name("SYSTEM_PULLREQUEST_SOURCEBRANCH"),
+        branch: branch_name("SYSTEM_PULLREQUEST_SOURCEBRANCH").or_else(|| branch_name("BUILD_SOURCEBRANCH")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn detect(vars: &[(&str, &str)]) -> PipelineContext {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        PipelineContext::from_env(&|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_detect_providers() {
+        let gitlab = detect(&[
+            ("GITLAB_CI", "true"),
+            ("CI_MERGE_REQUEST_IID", "17"),
+            ("CI_MERGE_REQUEST_DIFF_BASE_SHA", "abc123"),
+            ("CI_COMMIT_SHA", "def456"),
+            ("CI_MERGE_REQUEST_TARGET_BRANCH_NAME", "main"),
+        ]);
+        assert_eq!(gitlab.provider, Some(CiProvider::GitlabCi));
+        assert_eq!(gitlab.pr_number.as_deref(), Some("17"));
+        assert_eq!(gitlab.base_commit.as_deref(), Some("abc123"));
+        assert_eq!(gitlab.head_commit.as_deref(), Some("def456"));
+        assert_eq!(gitlab.base_branch.as_deref(), Some("main"));
+
+        let circle = detect(&[
+            ("CIRCLECI", "true"),
+            ("CIRCLE_PULL_REQUEST", "https://github.com/acme/infra/pull/42"),
+            ("CIRCLE_BRANCH", "feature/vpc"),
+        ]);
+        assert_eq!(circle.pr_number.as_deref(), Some("42"));
+        assert_eq!(circle.branch.as_deref(), Some("feature/vpc"));
+
+        let azure = detect(&[
+            ("TF_BUILD", "True"),
+            ("BUILD_SOURCEBRANCH", "refs/heads/main"),
+            ("BUILD_SOURCEVERSION", "def456"),
+        ]);
+        assert_eq!(azure.branch.as_deref(), Some("main"));
+        assert_eq!(azure.pr_number, None);
+        assert!(!azure.has_changes_info());
+
+        assert_eq!(detect(&[]), PipelineContext::default());
+    }
+
+    #[test]
+    fn test_detect_merge_queue() {
+        let queue = detect(&[
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_EVENT_NAME", "merge_group"),
+            ("GITHUB_REF_NAME", "gh-readonly-queue/main/pr-42-0123abcd"),
+            ("GITHUB_SHA", "def456"),
+        ]);
+        assert_eq!(queue.branch.as_deref(), Some("main"));
+        assert_eq!(queue.base_branch.as_deref(), Some("main"));
+        assert_eq!(queue.pr_number.as_deref(), Some("42"));
+        assert_eq!(queue.head_commit.as_deref(), Some("def456"));
+        assert_eq!(merge_queue_branch("gh-readonly-queue/release/v2/pr-7-abc"), Some(("release/v2".to_string(), "7".to_string())));
+        assert_eq!(merge_queue_branch("feature/pr-7-abc"), None);
+    }
+
+    #[test]
+    fn test_solarboat_variables_take_precedence() {
+        let context = detect(&[
+            ("JENKINS_URL", "https://ci.example.com"),
+            ("CHANGE_ID", "7"),
+            ("GIT_PREVIOUS_SUCCESSFUL_COMMIT", NULL_SHA),
+            ("GIT_COMMIT", "def456"),
+            ("SOLARBOAT_BASE_COMMIT", "abc123"),
+            ("SOLARBOAT_PR_NUMBER", ""),
+        ]);
+        assert_eq!(context.provider, Some(CiProvider::Jenkins));
+        assert_eq!(context.pr_number.as_deref(), Some("7"));
+        assert_eq!(context.base_commit.as_deref(), Some("abc123"));
+        assert_eq!(context.describe(), "PR #7");
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crate::utils::{logger, redact, secrets};
+
+/// Assumed credentials are renewed before an operation that would start with less time left on them
+const REFRESH_MARGIN_SECS: u64 = 15 * 60;
+
+/// Lifetime of credentials whose expiration can't be read: the default session duration of STS
+const DEFAULT_SESSION_SECS: u64 = 3600;
+
+/// Credentials of a role assumed during this run
+struct Assumed {
+    role_arn: String,
+    /// Environment the role was assumed with
+    env: BTreeMap<String, String>,
+    credentials: BTreeMap<String, String>,
+    /// Seconds since the Unix epoch
+    expires_at: u64,
+}
+
+/// Roles assumed during this run, by role and the environment they were assumed with
+static ASSUMED: Mutex<BTreeMap<String, Assumed>> = Mutex::new(BTreeMap::new());
+
+/// Temporary credentials for `role_arn`, as `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+/// `AWS_SESSION_TOKEN`. The role is assumed with `aws sts assume-role`, using the credentials `env`
+/// points at, such as `AWS_PROFILE`. Credentials are reused for the same role and environment until
+/// they are about to expire.
+pub fn assume_role(role_arn: &str, env: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>, String> {
+    let key = std::iter::once(role_arn.to_string())
+        .chain(env.iter().map(|(name, value)| format!("{}={}", name, value)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let cached = ASSUMED.lock().ok().and_then(|assumed| {
+        assumed.get(&key).filter(|assumed| !expiring(assumed.expires_at)).map(|assumed| assumed.credentials.clone())
+    });
+    if let Some(credentials) = cached {
+        return Ok(credentials);
+    }
+
+    let aws = secrets::aws_cli(env).map_err(|e| format!("Cannot assume role {}: {}", role_arn, e))?;
+    let output = Command::new(aws)
+        .args(["sts", "assume-role", "--role-arn", role_arn, "--role-session-name", "solarboat", "--output", "json"])
+        .envs(env)
+        .output()
+        .map_err(|e| format!("Failed to run aws sts assume-role: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Failed to assume role {}: {}", role_arn, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let response = String::from_utf8_lossy(&output.stdout);
+    let credentials = parse_credentials(&response)
+        .map_err(|e| format!("Failed to assume role {}: {}", role_arn, e))?;
+    for value in credentials.values() {
+        redact::add_value(value);
+    }
+
+    let expires_at = parse_expiration(&response).unwrap_or_else(|| now() + DEFAULT_SESSION_SECS);
+    if let Ok(mut assumed) = ASSUMED.lock() {
+        assumed.insert(key, Assumed { role_arn: role_arn.to_string(), env: env.clone(), credentials: credentials.clone(), expires_at });
+    }
+    Ok(credentials)
+}
+
+/// `env` with credentials assumed during this run replaced by new ones when they are about to
+/// expire, so operations queued behind long applies don't start with stale credentials
+pub fn refresh(env: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let Some(key_id) = env.get("AWS_ACCESS_KEY_ID") else {
+        return env.clone();
+    };
+    let stale = ASSUMED.lock().ok().and_then(|assumed| {
+        assumed.values()
+            .find(|assumed| assumed.credentials.get("AWS_ACCESS_KEY_ID") == Some(key_id) && expiring(assumed.expires_at))
+            .map(|assumed| (assumed.role_arn.clone(), assumed.env.clone()))
+    });
+    let Some((role_arn, role_env)) = stale else {
+        return env.clone();
+    };
+
+    let mut env = env.clone();
+    match assume_role(&role_arn, &role_env) {
+        Ok(credentials) => {
+            logger::debug(&format!("Renewed credentials of {}", role_arn));
+            env.extend(credentials);
+        }
+        Err(e) => logger::warn(&format!("Failed to renew credentials of {}: {}", role_arn, e)),
+    }
+    env
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn expiring(expires_at: u64) -> bool {
+    now() + REFRESH_MARGIN_SECS >= expires_at
+}
+
+/// Environment variables for the credentials in the output of `aws sts assume-role`
+fn parse_credentials(output: &str) -> Result<BTreeMap<String, String>, String> {
+    let response: Value = serde_json::from_str(output).map_err(|e| format!("invalid response: {}", e))?;
+    [
+        ("AWS_ACCESS_KEY_ID", "AccessKeyId"),
+        ("AWS_SECRET_ACCESS_KEY", "SecretAccessKey"),
+        ("AWS_SESSION_TOKEN", "SessionToken"),
+    ]
+    .into_iter()
+    .map(|(name, field)| {
+        response["Credentials"][field].as_str()
+            .map(|value| (name.to_string(), value.to_string()))
+            .ok_or_else(|| format!("response has no {}", field))
+    })
+    .collect()
+}
+
+/// Expiration of the credentials in the output of `aws sts assume-role`, in seconds since the Unix
+/// epoch. The CLI prints it as an ISO 8601 time such as `2026-01-01T00:00:00+00:00`.
+fn parse_expiration(output: &str) -> Option<u64> {
+    let response: Value = serde_json::from_str(output).ok()?;
+    let expiration = response["Credentials"]["Expiration"].as_str()?;
+    let (date, time) = expiration.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+
+    // The time of day, then an optional fraction and the UTC offset
+    let clock = time.get(..8)?;
+    let mut clock = clock.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let seconds = clock.next()?? * 3600 + clock.next()?? * 60 + clock.next()??;
+    let zone = time[8..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let offset = match zone {
+        "" | "Z" => 0,
+        _ => {
+            let sign = if zone.starts_with('-') { -1 } else { 1 };
+            let (hours, minutes) = zone.get(1..)?.split_once(':')?;
+            sign * (hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60)
+        }
+    };
+
+    // Days since the epoch of a date in the proleptic Gregorian calendar
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    u64::try_from(days * 86400 + seconds - offset).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_credentials() {
+        let output = r#"{"Credentials": {"AccessKeyId": "ASIA1", "SecretAccessKey": "secret", "SessionToken": "token", "Expiration": "2026-01-01T00:00:00Z"}}"#;
+        let credentials = parse_credentials(output).unwrap();
+        assert_eq!(credentials.get("AWS_ACCESS_KEY_ID").map(String::as_str), Some("ASIA1"));
+        assert_eq!(credentials.get("AWS_SESSION_TOKEN").map(String::as_str), Some("token"));
+        assert!(parse_credentials(r#"{"Credentials": {"AccessKeyId": "ASIA1"}}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_expiration() {
+        let output = |expiration: &str| format!(r#"{{"Credentials": {{"Expiration": "{}"}}}}"#, expiration);
+        assert_eq!(parse_expiration(&output("2026-01-01T00:00:00Z")), Some(1767225600));
+        assert_eq!(parse_expiration(&output("2026-01-01T01:30:00+01:30")), Some(1767225600));
+        assert_eq!(parse_expiration(&output("2024-02-29T12:00:00.123-02:00")), Some(1709215200));
+        assert_eq!(parse_expiration(&output("tomorrow")), None);
+        assert!(expiring(now() + 60));
+        assert!(!expiring(now() + DEFAULT_SESSION_SECS));
+    }
+}
@@ -0,0 +1,80 @@
+use std::process::Command;
+use std::sync::Mutex;
+
+use crate::config::{workspace_pattern, EnvPassthroughConfig};
+
+/// Never inherited unless allowed by name: CI tokens and variables preloading libraries into terraform
+const DEFAULT_DENY: &[&str] = &[
+    "GITHUB_TOKEN",
+    "GH_TOKEN",
+    "ACTIONS_RUNTIME_TOKEN",
+    "ACTIONS_ID_TOKEN_REQUEST_TOKEN",
+    "CI_JOB_TOKEN",
+    "CI_JOB_JWT*",
+    "CI_REGISTRY_PASSWORD",
+    "SYSTEM_ACCESSTOKEN",
+    "NPM_TOKEN",
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "DYLD_*",
+];
+
+/// Always inherited, since terraform can't run without them
+const ESSENTIAL: &[&str] = &["PATH", "HOME", "TMPDIR", "TMP", "TEMP", "USERPROFILE", "SYSTEMROOT"];
+
+/// Configured passthrough, set once the configuration is loaded
+static PASSTHROUGH: Mutex<Option<EnvPassthroughConfig>> = Mutex::new(None);
+
+/// Use `config` for terraform commands started from now on
+pub fn set_passthrough(config: EnvPassthroughConfig) {
+    if let Ok(mut passthrough) = PASSTHROUGH.lock() {
+        *passthrough = Some(config);
+    }
+}
+
+fn matches(patterns: &[impl AsRef<str>], name: &str) -> bool {
+    patterns.iter().any(|pattern| workspace_pattern(pattern.as_ref()).is_ok_and(|regex| regex.is_match(name)))
+}
+
+/// Whether terraform commands inherit the variable `name` from solarboat
+pub fn passes(config: &EnvPassthroughConfig, name: &str) -> bool {
+    let allowed = config.allow.as_deref().is_some_and(|allow| matches(allow, name));
+    if config.allow.is_some() && !allowed && !ESSENTIAL.contains(&name) {
+        return false;
+    }
+    !matches(&config.deny, name) && (allowed || !matches(DEFAULT_DENY, name))
+}
+
+/// Keep `cmd` from inheriting the variables the passthrough leaves out. Variables set on the
+/// command itself, such as the configured `env`, are set after this and always reach it.
+pub fn sanitize(cmd: &mut Command) {
+    let config = PASSTHROUGH.lock().ok().and_then(|passthrough| passthrough.clone()).unwrap_or_default();
+    for (name, _) in std::env::vars_os() {
+        if !passes(&config, &name.to_string_lossy()) {
+            cmd.env_remove(name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passes() {
+        let default = EnvPassthroughConfig::default();
+        assert!(passes(&default, "AWS_REGION"));
+        assert!(!passes(&default, "GITHUB_TOKEN"));
+        assert!(!passes(&default, "DYLD_INSERT_LIBRARIES"));
+
+        let config = EnvPassthroughConfig {
+            allow: Some(vec!["AWS_*".to_string(), "GITHUB_TOKEN".to_string()]),
+            deny: vec!["AWS_SECRET_*".to_string()],
+        };
+        assert!(passes(&config, "AWS_REGION"));
+        assert!(passes(&config, "PATH"));
+        assert!(passes(&config, "GITHUB_TOKEN"));
+        assert!(!passes(&config, "AWS_SECRET_ACCESS_KEY"));
+        assert!(!passes(&config, "DATADOG_API_KEY"));
+    }
+}
@@ -0,0 +1,640 @@
+use git2::{Delta, DescribeFormatOptions, DescribeOptions, Diff, DiffFindOptions, DiffOptions, FileMode, Oid, Repository, Sort, StatusOptions, Tree};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::utils::logger;
+
+/// Git's empty tree, usable as a base that precedes every commit
+pub const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Variables git resolves relative to the directory it runs in
+const PATH_VARIABLES: [&str; 2] = ["GIT_DIR", "GIT_WORK_TREE"];
+
+/// Make relative `GIT_DIR` and `GIT_WORK_TREE` absolute, so git commands run from module or scan
+/// directories use the repository they name. Call before any thread is spawned.
+pub fn resolve_env_paths() {
+    let current_dir = match std::env::current_dir() {
+        Ok(dir) => dir,
+        Err(_) => return,
+    };
+    for name in PATH_VARIABLES {
+        if let Some(value) = std::env::var_os(name).filter(|value| !value.is_empty()) {
+            if Path::new(&value).is_relative() {
+                std::env::set_var(name, current_dir.join(value));
+            }
+        }
+    }
+}
+
+/// Open the repository containing `root_dir`, or the one named by `GIT_DIR` (and `GIT_WORK_TREE`) as git would
+fn open(root_dir: &str) -> Result<Repository, git2::Error> {
+    let repo = if std::env::var_os("GIT_DIR").is_some() {
+        let repo = Repository::open_from_env()?;
+        // libgit2 ignores GIT_WORK_TREE for bare repositories, which CI runners often share between checkouts
+        if let (true, Some(work_tree)) = (repo.is_bare(), std::env::var_os("GIT_WORK_TREE")) {
+            repo.set_workdir(Path::new(&work_tree), false)?;
+        }
+        repo
+    } else {
+        Repository::discover(root_dir)?
+    };
+
+    // libgit2 looks for the shallow boundary in a linked worktree's own git directory instead of the
+    // common one, so its history walks would run past the boundary
+    if repo.is_worktree() && repo.commondir().join("shallow").exists() {
+        return Err(git2::Error::from_str("linked worktree of a shallow clone"));
+    }
+    Ok(repo)
+}
+
+/// Run a libgit2 query, falling back to the git CLI if libgit2 can't open the repository or answer it
+fn with_fallback<T>(query: &str, native: impl FnOnce() -> Result<T, git2::Error>, cli: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    native().or_else(|e| {
+        logger::debug(&format!("libgit2 {} failed ({}), using the git CLI", query, e.message()));
+        cli()
+    })
+}
+
+fn cli_output(root_dir: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(root_dir)
+        .output()
+        .map_err(|e| format!("Failed to execute git {}: {}", args.first().unwrap_or(&""), e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+fn cli_lines(root_dir: &str, args: &[&str]) -> Result<Vec<String>, String> {
+    Ok(cli_output(root_dir, args)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.to_string())
+        .collect())
+}
+
+fn commit_id(repo: &Repository, spec: &str) -> Result<Oid, git2::Error> {
+    Ok(repo.revparse_single(spec)?.peel_to_commit()?.id())
+}
+
+/// Tree of a commit-ish, or None for the empty tree
+fn tree<'r>(repo: &'r Repository, spec: &str) -> Result<Option<Tree<'r>>, git2::Error> {
+    if spec == EMPTY_TREE {
+        return Ok(None);
+    }
+    Ok(Some(repo.revparse_single(spec)?.peel_to_tree()?))
+}
+
+/// Diff options limited to pathspecs relative to the repository root; no pathspecs means the whole repository
+fn diff_options(pathspecs: &[String]) -> DiffOptions {
+    let mut options = DiffOptions::new();
+    for pathspec in pathspecs {
+        options.pathspec(pathspec);
+    }
+    options
+}
+
+/// Diff between two commits, or between a commit and the working tree when `to` is None
+fn diff<'r>(repo: &'r Repository, from: &str, to: Option<&str>, pathspecs: &[String]) -> Result<Diff<'r>, git2::Error> {
+    let from_tree = tree(repo, from)?;
+    let mut options = diff_options(pathspecs);
+    match to {
+        Some(to) => repo.diff_tree_to_tree(from_tree.as_ref(), tree(repo, to)?.as_ref(), Some(&mut options)),
+        None => repo.diff_tree_to_workdir_with_index(from_tree.as_ref(), Some(&mut options)),
+    }
+}
+
+/// Arguments limiting a git command to pathspecs relative to the repository root, even though
+/// commands run from `root_dir`
+fn cli_pathspecs(pathspecs: &[String]) -> Vec<String> {
+    if pathspecs.is_empty() {
+        return Vec::new();
+    }
+    std::iter::once("--".to_string())
+        .chain(pathspecs.iter().map(|pathspec| format!(":(top,literal){}", pathspec)))
+        .collect()
+}
+
+fn diff_paths(diff: &git2::Diff) -> Vec<String> {
+    let mut paths: Vec<String> = diff.deltas()
+        .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()).map(|p| p.to_string_lossy().to_string()))
+        .collect();
+    paths.dedup();
+    paths
+}
+
+/// Full id of the commit a ref points to
+pub fn resolve_commit(root_dir: &str, spec: &str) -> Result<String, String> {
+    with_fallback(
+        "rev-parse",
+        || commit_id(&open(root_dir)?, spec).map(|id| id.to_string()),
+        || cli_output(root_dir, &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", spec)]).map(|out| out.trim().to_string()),
+    )
+}
+
+/// Full ids of a commit's parents, first parent first. Commits at a shallow clone's boundary have none.
+pub fn commit_parents(root_dir: &str, spec: &str) -> Result<Vec<String>, String> {
+    with_fallback(
+        "parents",
+        || {
+            let repo = open(root_dir)?;
+            let commit = repo.find_commit(commit_id(&repo, spec)?)?;
+            Ok(commit.parent_ids().map(|id| id.to_string()).collect())
+        },
+        // `rev-list --parents` prints the commit followed by its parents
+        || Ok(cli_output(root_dir, &["rev-list", "--parents", "-n", "1", spec])?
+            .split_whitespace()
+            .skip(1)
+            .map(|id| id.to_string())
+            .collect()),
+    )
+}
+
+/// Full message of a commit
+pub fn commit_message(root_dir: &str, spec: &str) -> Result<String, String> {
+    with_fallback(
+        "log",
+        || {
+            let repo = open(root_dir)?;
+            let commit = repo.find_commit(commit_id(&repo, spec)?)?;
+            Ok(String::from_utf8_lossy(commit.message_bytes()).to_string())
+        },
+        || cli_output(root_dir, &["log", "-1", "--format=%B", spec]),
+    )
+}
+
+/// Whether a ref resolves to a commit in the local repository
+pub fn has_commit(root_dir: &str, spec: &str) -> bool {
+    resolve_commit(root_dir, spec).is_ok()
+}
+
+/// Best common ancestor of two refs
+pub fn merge_base(root_dir: &str, one: &str, two: &str) -> Result<String, String> {
+    with_fallback(
+        "merge-base",
+        || {
+            let repo = open(root_dir)?;
+            let base = repo.merge_base(commit_id(&repo, one)?, commit_id(&repo, two)?)?;
+            Ok(base.to_string())
+        },
+        || cli_output(root_dir, &["merge-base", one, two]).map(|out| out.trim().to_string()),
+    )
+}
+
+/// Name of the checked-out branch, or `HEAD` when detached
+pub fn current_branch(root_dir: &str) -> Result<String, String> {
+    with_fallback(
+        "branch lookup",
+        || {
+            let repo = open(root_dir)?;
+            if repo.head_detached()? {
+                return Ok("HEAD".to_string());
+            }
+            let head = repo.head()?;
+            head.shorthand().map(|name| name.to_string())
+        },
+        || cli_output(root_dir, &["rev-parse", "--abbrev-ref", "HEAD"]).map(|out| out.trim().to_string()),
+    )
+}
+
+/// Ids of the last `count` commits reachable from HEAD, newest first
+pub fn recent_commits(root_dir: &str, count: usize) -> Result<Vec<String>, String> {
+    with_fallback(
+        "log",
+        || {
+            let repo = open(root_dir)?;
+            let mut walk = repo.revwalk()?;
+            walk.set_sorting(Sort::TIME)?;
+            walk.push_head()?;
+            walk.take(count).map(|id| id.map(|id| id.to_string())).collect()
+        },
+        || cli_lines(root_dir, &["log", "--format=%H", "-n", &count.to_string()]),
+    )
+}
+
+/// Newest commit reachable from HEAD that was committed before a unix timestamp
+pub fn commit_before(root_dir: &str, timestamp: i64) -> Result<Option<String>, String> {
+    with_fallback(
+        "rev-list",
+        || {
+            let repo = open(root_dir)?;
+            let mut walk = repo.revwalk()?;
+            walk.set_sorting(Sort::TIME)?;
+            walk.push_head()?;
+            for id in walk {
+                let id = id?;
+                if repo.find_commit(id)?.time().seconds() < timestamp {
+                    return Ok(Some(id.to_string()));
+                }
+            }
+            Ok(None)
+        },
+        || cli_output(root_dir, &["rev-list", "-n", "1", &format!("--before=@{}", timestamp), "HEAD"])
+            .map(|out| Some(out.trim().to_string()).filter(|commit| !commit.is_empty())),
+    )
+}
+
+/// Most recent tag reachable from HEAD
+pub fn latest_tag(root_dir: &str) -> Result<String, String> {
+    with_fallback(
+        "describe",
+        || {
+            let repo = open(root_dir)?;
+            let describe = repo.describe(DescribeOptions::new().describe_tags())?;
+            describe.format(Some(DescribeFormatOptions::new().abbreviated_size(0)))
+        },
+        || cli_output(root_dir, &["describe", "--tags", "--abbrev=0"]).map(|out| out.trim().to_string()),
+    )
+}
+
+/// Files that differ between two commits, or between a commit and the working tree when `to` is None.
+/// Paths are relative to the repository root, as with `git diff --name-only`. Only paths under
+/// `pathspecs` (relative to the repository root) are compared; none compares the whole repository.
+pub fn changed_files(root_dir: &str, from: &str, to: Option<&str>, pathspecs: &[String]) -> Result<Vec<String>, String> {
+    with_fallback(
+        "diff",
+        || Ok(diff_paths(&diff(&open(root_dir)?, from, to, pathspecs)?)),
+        || {
+            let pathspecs = cli_pathspecs(pathspecs);
+            let mut args = vec!["diff", "--name-only", from];
+            args.extend(to);
+            args.extend(pathspecs.iter().map(|p| p.as_str()));
+            cli_lines(root_dir, &args)
+        },
+    )
+}
+
+/// Submodule whose recorded commit differs between two commits or a commit and the working tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubmoduleUpdate {
+    /// Submodule directory relative to the repository root
+    pub path: String,
+    /// Recorded commit before the change, None for an added submodule
+    pub old: Option<String>,
+    /// Recorded commit after the change, None for a removed submodule or when git doesn't report it
+    pub new: Option<String>,
+}
+
+/// Submodules whose recorded commit (gitlink) changed between two commits, or between a commit and the
+/// working tree when `to` is None. Only submodules under `pathspecs` are compared, as with `changed_files`.
+pub fn submodule_updates(root_dir: &str, from: &str, to: Option<&str>, pathspecs: &[String]) -> Result<Vec<SubmoduleUpdate>, String> {
+    let commit = |id: String| Some(id).filter(|id| id.chars().any(|c| c != '0'));
+    with_fallback(
+        "submodule diff",
+        || {
+            let repo = open(root_dir)?;
+            let diff = diff(&repo, from, to, pathspecs)?;
+            Ok(diff.deltas()
+                .filter(|delta| delta.old_file().mode() == FileMode::Commit || delta.new_file().mode() == FileMode::Commit)
+                .filter_map(|delta| {
+                    let path = delta.new_file().path().or_else(|| delta.old_file().path())?;
+                    Some(SubmoduleUpdate {
+                        path: path.to_string_lossy().to_string(),
+                        old: commit(delta.old_file().id().to_string()),
+                        new: commit(delta.new_file().id().to_string()),
+                    })
+                })
+                .collect())
+        },
+        || {
+            let pathspecs = cli_pathspecs(pathspecs);
+            let mut args = vec!["diff", "--raw", "--no-abbrev", from];
+            args.extend(to);
+            args.extend(pathspecs.iter().map(|p| p.as_str()));
+            Ok(cli_lines(root_dir, &args)?
+                .iter()
+                .filter_map(|line| {
+                    // `:<old mode> <new mode> <old id> <new id> <status>\t<path>`, submodules have mode 160000
+                    let (meta, path) = line.split_once('\t')?;
+                    let fields: Vec<&str> = meta.trim_start_matches(':').split_whitespace().collect();
+                    if fields.len() < 4 || (fields[0] != "160000" && fields[1] != "160000") {
+                        return None;
+                    }
+                    Some(SubmoduleUpdate {
+                        path: path.to_string(),
+                        old: commit(fields[2].to_string()),
+                        new: commit(fields[3].to_string()),
+                    })
+                })
+                .collect())
+        },
+    )
+}
+
+/// Files renamed or moved between two commits, or between a commit and the working tree when `to` is None,
+/// as `(old, new)` pairs. Renames are detected by content similarity, as with `git diff --find-renames`.
+pub fn renamed_files(root_dir: &str, from: &str, to: Option<&str>) -> Result<Vec<(String, String)>, String> {
+    with_fallback(
+        "rename detection",
+        || {
+            let repo = open(root_dir)?;
+            let mut diff = diff(&repo, from, to, &[])?;
+            diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+            Ok(diff.deltas()
+                .filter(|delta| delta.status() == Delta::Renamed)
+                .filter_map(|delta| {
+                    let old = delta.old_file().path()?.to_string_lossy().to_string();
+                    let new = delta.new_file().path()?.to_string_lossy().to_string();
+                    Some((old, new))
+                })
+                .collect())
+        },
+        || {
+            let mut args = vec!["diff", "--name-status", "--find-renames", from];
+            args.extend(to);
+            Ok(cli_lines(root_dir, &args)?
+                .iter()
+                .filter_map(|line| {
+                    // Renames are reported as `R<similarity>\told\tnew`
+                    let mut fields = line.split('\t');
+                    let status = fields.next()?;
+                    match (fields.next(), fields.next()) {
+                        (Some(old), Some(new)) if status.starts_with('R') => Some((old.to_string(), new.to_string())),
+                        _ => None,
+                    }
+                })
+                .collect())
+        },
+    )
+}
+
+/// Newest commit reachable from HEAD whose tree contains a file
+pub fn last_commit_with(root_dir: &str, path: &str) -> Result<Option<String>, String> {
+    with_fallback(
+        "history lookup",
+        || {
+            let repo = open(root_dir)?;
+            let mut walk = repo.revwalk()?;
+            walk.set_sorting(Sort::TIME)?;
+            walk.push_head()?;
+            for id in walk {
+                let id = id?;
+                if repo.find_commit(id)?.tree()?.get_path(Path::new(path)).is_ok() {
+                    return Ok(Some(id.to_string()));
+                }
+            }
+            Ok(None)
+        },
+        || {
+            // The last commit touching a deleted file is the one deleting it
+            let last = cli_output(root_dir, &["rev-list", "-n", "1", "HEAD", "--", path])?.trim().to_string();
+            if last.is_empty() {
+                return Ok(None);
+            }
+            for commit in [last.clone(), format!("{}~1", last)] {
+                if cli_output(root_dir, &["cat-file", "-e", &format!("{}:{}", commit, path)]).is_ok() {
+                    return resolve_commit(root_dir, &commit).map(Some);
+                }
+            }
+            Ok(None)
+        },
+    )
+}
+
+/// Content of a file as of a commit
+pub fn file_content(root_dir: &str, commit: &str, path: &str) -> Result<String, String> {
+    with_fallback(
+        "blob lookup",
+        || {
+            let repo = open(root_dir)?;
+            let entry = repo.revparse_single(commit)?.peel_to_tree()?.get_path(Path::new(path))?;
+            let blob = repo.find_blob(entry.id())?;
+            Ok(String::from_utf8_lossy(blob.content()).to_string())
+        },
+        || cli_output(root_dir, &["show", &format!("{}:{}", commit, path)]),
+    )
+}
+
+/// Commits reachable from `to` but not from `from` that changed a file, newest first, as `(id, summary)` pairs
+pub fn file_commits(root_dir: &str, path: &str, from: Option<&str>, to: &str, limit: usize) -> Result<Vec<(String, String)>, String> {
+    with_fallback(
+        "file history",
+        || {
+            let repo = open(root_dir)?;
+            let mut walk = repo.revwalk()?;
+            walk.set_sorting(Sort::TIME)?;
+            walk.push(commit_id(&repo, to)?)?;
+            if let Some(from) = from {
+                walk.hide(commit_id(&repo, from)?)?;
+            }
+            let blob_id = |commit: &git2::Commit| commit.tree().ok().and_then(|tree| tree.get_path(Path::new(path)).ok()).map(|entry| entry.id());
+
+            let mut commits = Vec::new();
+            for id in walk {
+                let commit = repo.find_commit(id?)?;
+                let parent = commit.parents().next();
+                if blob_id(&commit) != parent.as_ref().and_then(blob_id) {
+                    commits.push((commit.id().to_string(), commit.summary().ok().flatten().unwrap_or_default().to_string()));
+                    if commits.len() == limit {
+                        break;
+                    }
+                }
+            }
+            Ok(commits)
+        },
+        || {
+            let range = match from {
+                Some(from) => format!("{}..{}", from, to),
+                None => to.to_string(),
+            };
+            Ok(cli_lines(root_dir, &["log", "-n", &limit.to_string(), "--format=%H%x09%s", &range, "--", path])?
+                .iter()
+                .filter_map(|line| line.split_once('\t').map(|(id, summary)| (id.to_string(), summary.to_string())))
+                .collect())
+        },
+    )
+}
+
+/// Files with staged changes
+pub fn staged_files(root_dir: &str, pathspecs: &[String]) -> Result<Vec<String>, String> {
+    with_fallback(
+        "diff --cached",
+        || {
+            let repo = open(root_dir)?;
+            let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+            let diff = repo.diff_tree_to_index(head.as_ref(), None, Some(&mut diff_options(pathspecs)))?;
+            Ok(diff_paths(&diff))
+        },
+        || {
+            let pathspecs = cli_pathspecs(pathspecs);
+            let mut args = vec!["diff", "--cached", "--name-only"];
+            args.extend(pathspecs.iter().map(|p| p.as_str()));
+            cli_lines(root_dir, &args)
+        },
+    )
+}
+
+/// Files with unstaged changes
+pub fn unstaged_files(root_dir: &str, pathspecs: &[String]) -> Result<Vec<String>, String> {
+    with_fallback(
+        "diff",
+        || {
+            let repo = open(root_dir)?;
+            let diff = repo.diff_index_to_workdir(None, Some(&mut diff_options(pathspecs)))?;
+            Ok(diff_paths(&diff))
+        },
+        || {
+            let pathspecs = cli_pathspecs(pathspecs);
+            let mut args = vec!["diff", "--name-only"];
+            args.extend(pathspecs.iter().map(|p| p.as_str()));
+            cli_lines(root_dir, &args)
+        },
+    )
+}
+
+/// Files `git status` reports as modified, staged or untracked. Untracked directories are
+/// reported as the directory, as with `git status --porcelain`. Only paths under `pathspecs` are reported.
+pub fn status_files(root_dir: &str, pathspecs: &[String]) -> Result<Vec<String>, String> {
+    with_fallback(
+        "status",
+        || {
+            let repo = open(root_dir)?;
+            let mut options = StatusOptions::new();
+            options.include_untracked(true).recurse_untracked_dirs(false);
+            for pathspec in pathspecs {
+                options.pathspec(pathspec);
+            }
+            let statuses = repo.statuses(Some(&mut options))?;
+            Ok(statuses.iter().filter_map(|entry| entry.path().ok().map(|p| p.to_string())).collect())
+        },
+        || {
+            let pathspecs = cli_pathspecs(pathspecs);
+            let mut args = vec!["status", "--porcelain"];
+            args.extend(pathspecs.iter().map(|p| p.as_str()));
+            Ok(cli_lines(root_dir, &args)?
+                .iter()
+                .filter(|line| line.len() > 3)
+                .map(|line| {
+                    // Renames are reported as `old -> new`
+                    let path = line[3..].trim();
+                    path.rsplit(" -> ").next().unwrap_or(path).to_string()
+                })
+                .collect())
+        },
+    )
+}
+
+/// Top-level directory of the working tree containing `root_dir`
+pub fn work_tree(root_dir: &str) -> Result<PathBuf, String> {
+    with_fallback(
+        "work tree lookup",
+        || open(root_dir)?.workdir()
+            .map(|dir| dir.to_path_buf())
+            .ok_or_else(|| git2::Error::from_str("repository has no working tree")),
+        || cli_output(root_dir, &["rev-parse", "--show-toplevel"]).map(|out| PathBuf::from(out.trim())),
+    )
+    .map(|dir| std::fs::canonicalize(&dir).unwrap_or(dir))
+}
+
+/// Whether the repository is a shallow clone
+pub fn is_shallow(root_dir: &str) -> bool {
+    with_fallback(
+        "shallow check",
+        || Ok(open(root_dir)?.is_shallow()),
+        || cli_output(root_dir, &["rev-parse", "--is-shallow-repository"]).map(|out| out.trim() == "true"),
+    )
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &std::path::Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_queries_match_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_str().unwrap();
+        git(dir.path(), &["init", "-q", "-b", "main"]);
+        std::fs::create_dir_all(dir.path().join("stacks/app")).unwrap();
+        std::fs::write(dir.path().join("stacks/app/main.tf"), "# v1\n").unwrap();
+        git(dir.path(), &["add", "-A"]);
+        git(dir.path(), &["commit", "-qm", "init"]);
+        git(dir.path(), &["tag", "v1"]);
+        git(dir.path(), &["checkout", "-qb", "feature"]);
+        std::fs::write(dir.path().join("stacks/app/main.tf"), "# v2\n").unwrap();
+        std::fs::write(dir.path().join("stacks/app/vars.tf"), "# new\n").unwrap();
+        git(dir.path(), &["add", "-A"]);
+        git(dir.path(), &["commit", "-qm", "change"]);
+        std::fs::write(dir.path().join("stacks/app/outputs.tf"), "# untracked\n").unwrap();
+
+        let main = resolve_commit(root, "main").unwrap();
+        assert_eq!(merge_base(root, "main", "HEAD").unwrap(), main);
+        assert_eq!(current_branch(root).unwrap(), "feature");
+        assert_eq!(recent_commits(root, 5).unwrap().len(), 2);
+        assert_eq!(latest_tag(root).unwrap(), "v1");
+        assert_eq!(changed_files(root, "main", Some("HEAD"), &[]).unwrap(), vec!["stacks/app/main.tf", "stacks/app/vars.tf"]);
+        assert_eq!(changed_files(root, EMPTY_TREE, Some("main"), &[]).unwrap(), vec!["stacks/app/main.tf"]);
+        assert_eq!(changed_files(root, "main", Some("HEAD"), &["stacks/app/vars.tf".to_string()]).unwrap(), vec!["stacks/app/vars.tf"]);
+        assert_eq!(status_files(root, &[]).unwrap(), vec!["stacks/app/outputs.tf"]);
+        assert!(staged_files(root, &[]).unwrap().is_empty());
+        assert!(!is_shallow(root));
+        assert!(!has_commit(root, "missing"));
+        assert_eq!(work_tree(&format!("{}/stacks/app", root)).unwrap(), std::fs::canonicalize(root).unwrap());
+
+        assert_eq!(commit_parents(root, "HEAD").unwrap(), vec![main.clone()]);
+        assert!(commit_parents(root, "main").unwrap().is_empty());
+        assert_eq!(commit_message(root, "HEAD").unwrap().trim(), "change");
+        assert_eq!(last_commit_with(root, "stacks/app/vars.tf").unwrap(), Some(resolve_commit(root, "HEAD").unwrap()));
+        assert_eq!(file_content(root, "main", "stacks/app/main.tf").unwrap(), "# v1\n");
+
+        let history = file_commits(root, "stacks/app/vars.tf", Some("main"), "HEAD", 5).unwrap();
+        assert_eq!(history, vec![(resolve_commit(root, "HEAD").unwrap(), "change".to_string())]);
+        assert_eq!(file_commits(root, "stacks/app/main.tf", None, "HEAD", 5).unwrap().len(), 2);
+
+        git(dir.path(), &["mv", "stacks/app", "stacks/web"]);
+        git(dir.path(), &["commit", "-qm", "move"]);
+        assert_eq!(
+            renamed_files(root, "HEAD~1", Some("HEAD")).unwrap(),
+            vec![
+                ("stacks/app/main.tf".to_string(), "stacks/web/main.tf".to_string()),
+                ("stacks/app/vars.tf".to_string(), "stacks/web/vars.tf".to_string()),
+            ]
+        );
+
+        git(dir.path(), &["rm", "-rq", "stacks/web"]);
+        git(dir.path(), &["commit", "-qm", "remove"]);
+        assert_eq!(last_commit_with(root, "stacks/web/main.tf").unwrap(), Some(resolve_commit(root, "HEAD~1").unwrap()));
+        assert_eq!(last_commit_with(root, "stacks/missing.tf").unwrap(), None);
+    }
+
+    #[test]
+    fn test_linked_worktree() {
+        let dir = tempfile::tempdir().unwrap();
+        let (origin, clone, worktree) = (dir.path().join("origin"), dir.path().join("clone"), dir.path().join("worktree"));
+        std::fs::create_dir_all(origin.join("stacks/app")).unwrap();
+        git(&origin, &["init", "-q", "-b", "main"]);
+        for version in ["v1", "v2"] {
+            std::fs::write(origin.join("stacks/app/main.tf"), version).unwrap();
+            git(&origin, &["add", "-A"]);
+            git(&origin, &["commit", "-qm", version]);
+        }
+        git(dir.path(), &["clone", "-q", "--depth=1", &format!("file://{}", origin.display()), "clone"]);
+        git(&clone, &["worktree", "add", "-q", "-b", "feature", worktree.to_str().unwrap()]);
+        std::fs::write(worktree.join("stacks/app/vars.tf"), "# new\n").unwrap();
+        git(&worktree, &["add", "-A"]);
+        git(&worktree, &["commit", "-qm", "change"]);
+
+        let root = worktree.to_str().unwrap();
+        assert_eq!(current_branch(root).unwrap(), "feature");
+        assert_eq!(work_tree(root).unwrap(), std::fs::canonicalize(&worktree).unwrap());
+        assert!(is_shallow(root));
+        assert_eq!(recent_commits(root, 5).unwrap().len(), 2);
+        assert_eq!(merge_base(root, "main", "HEAD").unwrap(), resolve_commit(clone.to_str().unwrap(), "main").unwrap());
+        assert_eq!(changed_files(root, "main", None, &[]).unwrap(), vec!["stacks/app/vars.tf"]);
+    }
+}
@@ -0,0 +1,111 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::utils::terraform_operations::apply_env;
+use crate::utils::redact;
+
+/// Point in a module operation where a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookPhase {
+    PreInit,
+    PrePlan,
+    PostPlan,
+    PreApply,
+    PostApply,
+}
+
+impl HookPhase {
+    /// Name of the phase as used in `solarboat.json`
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookPhase::PreInit => "pre_init",
+            HookPhase::PrePlan => "pre_plan",
+            HookPhase::PostPlan => "post_plan",
+            HookPhase::PreApply => "pre_apply",
+            HookPhase::PostApply => "post_apply",
+        }
+    }
+}
+
+/// Shell commands run around the terraform phases of a module operation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Hooks {
+    pub pre_init: Option<String>,
+    pub pre_plan: Option<String>,
+    pub post_plan: Option<String>,
+    pub pre_apply: Option<String>,
+    pub post_apply: Option<String>,
+}
+
+impl Hooks {
+    /// Command configured for a phase
+    pub fn command(&self, phase: HookPhase) -> Option<&str> {
+        match phase {
+            HookPhase::PreInit => self.pre_init.as_deref(),
+            HookPhase::PrePlan => self.pre_plan.as_deref(),
+            HookPhase::PostPlan => self.post_plan.as_deref(),
+            HookPhase::PreApply => self.pre_apply.as_deref(),
+            HookPhase::PostApply => self.post_apply.as_deref(),
+        }
+    }
+
+    /// Run the hook for a phase with `sh -c` in the module directory.
+    /// `success` is the outcome of the terraform phase and is only set for post hooks.
+    /// Returns the hook's output lines; an unconfigured phase returns no output.
+    pub fn run(&self, phase: HookPhase, module_path: &str, workspace: Option<&str>, data_dir: Option<&Path>, success: Option<bool>) -> Result<Vec<String>, String> {
+        let command = match self.command(phase) {
+            Some(command) => command,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut cmd = Command::new("sh");
+        apply_env(&mut cmd);
+        cmd.arg("-c")
+            .arg(command)
+            .current_dir(module_path)
+            .env("SOLARBOAT_MODULE", module_path)
+            .env("SOLARBOAT_WORKSPACE", workspace.unwrap_or("default"))
+            .env("SOLARBOAT_PHASE", phase.name());
+        if let Some(success) = success {
+            cmd.env("SOLARBOAT_SUCCESS", success.to_string());
+        }
+        if let Some(data_dir) = data_dir {
+            cmd.env("TF_DATA_DIR", data_dir);
+        }
+
+        let output = cmd.output()
+            .map_err(|e| format!("Failed to run {} hook: {}", phase.name(), e))?;
+        let lines: Vec<String> = String::from_utf8_lossy(&output.stdout).lines()
+            .chain(String::from_utf8_lossy(&output.stderr).lines())
+            .map(redact::redact)
+            .collect();
+
+        if output.status.success() {
+            Ok(lines)
+        } else {
+            let detail = lines.last().map(|line| format!(": {}", line)).unwrap_or_default();
+            Err(format!("{} hook failed ({}){}", phase.name(), output.status, detail))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_hook() {
+        let hooks = Hooks {
+            pre_plan: Some("echo \"$SOLARBOAT_PHASE $SOLARBOAT_WORKSPACE\"".to_string()),
+            post_plan: Some("echo \"post $SOLARBOAT_SUCCESS\"; exit 3".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(hooks.run(HookPhase::PrePlan, ".", Some("prod"), None, None).unwrap(), vec!["pre_plan prod"]);
+        assert!(hooks.run(HookPhase::PreApply, ".", None, None, None).unwrap().is_empty());
+
+        let error = hooks.run(HookPhase::PostPlan, ".", None, None, Some(true)).unwrap_err();
+        assert!(error.starts_with("post_plan hook failed"));
+        assert!(error.ends_with(": post true"));
+    }
+}
@@ -0,0 +1,79 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Whether solarboat can prompt the user (stdin is attached to a terminal)
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal()
+}
+
+/// Ask a yes/no question on the terminal. Anything other than `y`/`yes` is treated as no.
+/// Returns `None` when stdin is not a terminal, so callers can decide how to proceed unattended.
+pub fn confirm(question: &str) -> Option<bool> {
+    if !is_interactive() {
+        return None;
+    }
+
+    let answer = prompt(&format!("{} [y/N]: ", question))?;
+    Some(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Answer to a per-item prompt that can also settle every remaining item
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Choice {
+    Yes,
+    No,
+    SkipAll,
+    ApplyAll,
+}
+
+impl Choice {
+    /// Parse `y`/`n`/`skip-all`/`apply-all` and their short forms. An empty answer is no.
+    pub fn parse(answer: &str) -> Option<Self> {
+        match answer.to_lowercase().as_str() {
+            "y" | "yes" => Some(Choice::Yes),
+            "" | "n" | "no" => Some(Choice::No),
+            "s" | "skip-all" => Some(Choice::SkipAll),
+            "a" | "apply-all" => Some(Choice::ApplyAll),
+            _ => None,
+        }
+    }
+}
+
+/// Ask a y/n/skip-all/apply-all question on the terminal, repeating it until the answer is valid.
+/// Returns `None` when stdin is not a terminal or cannot be read.
+pub fn choose(question: &str) -> Option<Choice> {
+    if !is_interactive() {
+        return None;
+    }
+
+    loop {
+        let answer = prompt(&format!("{} [y/N/s(kip-all)/a(pply-all)]: ", question))?;
+        match Choice::parse(&answer) {
+            Some(choice) => return Some(choice),
+            None => println!("Please answer y, n, skip-all or apply-all"),
+        }
+    }
+}
+
+/// Print a prompt and read one trimmed line from stdin
+pub fn prompt(message: &str) -> Option<String> {
+    print!("{}", message);
+    io::stdout().flush().ok()?;
+
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).ok()?;
+    Some(line.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(Choice::parse("Y"), Some(Choice::Yes));
+        assert_eq!(Choice::parse(""), Some(Choice::No));
+        assert_eq!(Choice::parse("skip-all"), Some(Choice::SkipAll));
+        assert_eq!(Choice::parse("a"), Some(Choice::ApplyAll));
+        assert_eq!(Choice::parse("maybe"), None);
+    }
+}
@@ -0,0 +1,301 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::utils::terraform_operations;
+
+/// Dependency lock file written by `terraform init`
+pub const LOCK_FILE_NAME: &str = ".terraform.lock.hcl";
+
+/// A provider declared in a `required_providers` block
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ProviderRequirement {
+    /// Local name the module uses for the provider
+    pub name: String,
+    /// Fully qualified registry address
+    pub source: String,
+    /// Version constraint, if one is set
+    pub version: Option<String>,
+}
+
+/// A problem with a module's dependency lock file
+#[derive(Debug, Clone, PartialEq)]
+pub enum LockIssue {
+    /// No lock file in the module directory
+    Missing,
+    /// Lock file exists but is not tracked by git
+    NotCommitted,
+    /// Lock file has uncommitted changes
+    Modified,
+    /// Providers declared in required_providers but absent from the lock file
+    MissingProviders(Vec<String>),
+    /// Providers locked but no longer declared in required_providers
+    UnusedProviders(Vec<String>),
+}
+
+impl std::fmt::Display for LockIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockIssue::Missing => write!(f, "{} is missing", LOCK_FILE_NAME),
+            LockIssue::NotCommitted => write!(f, "{} is not committed", LOCK_FILE_NAME),
+            LockIssue::Modified => write!(f, "{} has uncommitted changes", LOCK_FILE_NAME),
+            LockIssue::MissingProviders(p) => write!(f, "providers not locked: {}", p.join(", ")),
+            LockIssue::UnusedProviders(p) => write!(f, "locked providers no longer required: {}", p.join(", ")),
+        }
+    }
+}
+
+/// Check a module's lock file for presence, git status and consistency with required_providers
+pub fn check_module(module_path: &str) -> Vec<LockIssue> {
+    let lock_path = Path::new(module_path).join(LOCK_FILE_NAME);
+    let content = match std::fs::read_to_string(&lock_path) {
+        Ok(content) => content,
+        Err(_) => return vec![LockIssue::Missing],
+    };
+
+    let mut issues = Vec::new();
+
+    let tracked = Command::new("git")
+        .args(["ls-files", "--error-unmatch", LOCK_FILE_NAME])
+        .current_dir(module_path)
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(true);
+    if !tracked {
+        issues.push(LockIssue::NotCommitted);
+    } else {
+        let modified = Command::new("git")
+            .args(["status", "--porcelain", "--", LOCK_FILE_NAME])
+            .current_dir(module_path)
+            .output()
+            .map(|o| !o.stdout.is_empty())
+            .unwrap_or(false);
+        if modified {
+            issues.push(LockIssue::Modified);
+        }
+    }
+
+    let required = required_providers(module_path);
+    let locked = locked_providers(&content);
+
+    let missing: Vec<String> = required.difference(&locked).cloned().collect();
+    if !missing.is_empty() {
+        issues.push(LockIssue::MissingProviders(missing));
+    }
+    let unused: Vec<String> = locked.difference(&required).cloned().collect();
+    if !unused.is_empty() && !required.is_empty() {
+        issues.push(LockIssue::UnusedProviders(unused));
+    }
+
+    issues
+}
+
+/// Collect provider sources declared in `required_providers` blocks of a module's .tf files
+pub fn required_providers(module_path: &str) -> BTreeSet<String> {
+    let mut providers = BTreeSet::new();
+    let entries = match std::fs::read_dir(module_path) {
+        Ok(entries) => entries,
+        Err(_) => return providers,
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "tf") {
+            if let Ok(content) = std::fs::read_to_string(&path) {
+                providers.extend(parse_required_providers(&content));
+            }
+        }
+    }
+
+    providers
+}
+
+/// Parse provider sources from `required_providers` blocks
+pub fn parse_required_providers(content: &str) -> BTreeSet<String> {
+    find_provider_requirements(content).into_iter()
+        .map(|provider| provider.source)
+        .collect()
+}
+
+/// Parse the providers declared in `required_providers` blocks, with their version constraints.
+/// Supports both `name = { source = "...", version = "..." }` and the legacy `name = "version"` form.
+pub fn find_provider_requirements(content: &str) -> Vec<ProviderRequirement> {
+    let block_re = Regex::new(r"required_providers\s*\{").unwrap();
+    let object_re = Regex::new(r#"(?s)([A-Za-z0-9_-]+)\s*=\s*\{([^}]*)\}"#).unwrap();
+    let source_re = Regex::new(r#"source\s*=\s*"([^"]+)""#).unwrap();
+    let version_re = Regex::new(r#"version\s*=\s*"([^"]+)""#).unwrap();
+    let legacy_re = Regex::new(r#"(?m)^\s*([A-Za-z0-9_-]+)\s*=\s*"([^"]*)""#).unwrap();
+
+    let mut providers = BTreeSet::new();
+
+    for block_start in block_re.find_iter(content) {
+        let body = match block_body(&content[block_start.end()..]) {
+            Some(body) => body,
+            None => continue,
+        };
+
+        for object in object_re.captures_iter(body) {
+            let source = source_re.captures(&object[2])
+                .map(|s| s[1].to_string())
+                .unwrap_or_else(|| object[1].to_string());
+            providers.insert(ProviderRequirement {
+                name: object[1].to_string(),
+                source: normalize_source(&source),
+                version: version_re.captures(&object[2]).map(|v| v[1].to_string()),
+            });
+        }
+        for legacy in legacy_re.captures_iter(&object_re.replace_all(body, "")) {
+            providers.insert(ProviderRequirement {
+                name: legacy[1].to_string(),
+                source: normalize_source(&legacy[1]),
+                version: Some(legacy[2].to_string()).filter(|version| !version.is_empty()),
+            });
+        }
+    }
+
+    providers.into_iter().collect()
+}
+
+/// Collect provider addresses recorded in a lock file
+pub fn locked_providers(content: &str) -> BTreeSet<String> {
+    let provider_re = Regex::new(r#"provider\s+"([^"]+)""#).unwrap();
+    provider_re.captures_iter(content)
+        .map(|c| normalize_source(&c[1]))
+        .collect()
+}
+
+/// Normalize a provider source to its fully qualified registry address
+pub fn normalize_source(source: &str) -> String {
+    let source = source.to_lowercase();
+    match source.split('/').count() {
+        1 => format!("registry.terraform.io/hashicorp/{}", source),
+        2 => format!("registry.terraform.io/{}", source),
+        _ => source,
+    }
+}
+
+/// Return the text inside the braces that open just before `rest`
+pub fn block_body(rest: &str) -> Option<&str> {
+    let mut depth = 1;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&rest[..i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Regenerate a module's lock file with `terraform providers lock`
+pub fn update_lock_file(module_path: &str, platforms: &[String]) -> Result<(), String> {
+    let mut cmd = terraform_operations::terraform_command(module_path, None);
+    cmd.arg("providers").arg("lock");
+    for platform in platforms {
+        cmd.arg(format!("-platform={}", platform));
+    }
+
+    let output = cmd.output()
+        .map_err(|e| format!("Failed to run terraform providers lock: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Regenerate lock files for several modules, each with its environment variables, using up to
+/// `parallel` worker threads. Returns `(module, result)` pairs in completion order.
+pub fn update_lock_files(modules: &[(String, BTreeMap<String, String>)], platforms: &[String], parallel: usize) -> Vec<(String, Result<(), String>)> {
+    let queue = Arc::new(Mutex::new(modules.iter().cloned().collect::<VecDeque<_>>()));
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let platforms = Arc::new(platforms.to_vec());
+
+    let workers: Vec<_> = (0..parallel.clamp(1, 4))
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let results = Arc::clone(&results);
+            let platforms = Arc::clone(&platforms);
+            thread::spawn(move || {
+                let next = || queue.lock().ok().and_then(|mut q| q.pop_front());
+                while let Some((module, env)) = next() {
+                    let result = terraform_operations::with_env(&env, || update_lock_file(&module, &platforms));
+                    if let Ok(mut results) = results.lock() {
+                        results.push((module, result));
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    Arc::try_unwrap(results)
+        .ok()
+        .and_then(|r| r.into_inner().ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_required_providers() {
+        let content = r#"
+terraform {
+  required_version = ">= 1.5"
+  required_providers {
+    aws = {
+      source  = "hashicorp/aws"
+      version = "~> 5.0"
+    }
+    cloudflare = {
+      source = "cloudflare/cloudflare"
+    }
+    random = "~> 3.0"
+  }
+}
+"#;
+        let providers: Vec<String> = parse_required_providers(content).into_iter().collect();
+        assert_eq!(providers, vec![
+            "registry.terraform.io/cloudflare/cloudflare",
+            "registry.terraform.io/hashicorp/aws",
+            "registry.terraform.io/hashicorp/random",
+        ]);
+
+        let requirements = find_provider_requirements(content);
+        assert_eq!(requirements[0], ProviderRequirement {
+            name: "aws".to_string(),
+            source: "registry.terraform.io/hashicorp/aws".to_string(),
+            version: Some("~> 5.0".to_string()),
+        });
+        assert_eq!(requirements[1].version, None);
+        assert_eq!(requirements[2].version.as_deref(), Some("~> 3.0"));
+    }
+
+    #[test]
+    fn test_locked_providers() {
+        let content = r#"
+provider "registry.terraform.io/hashicorp/aws" {
+  version     = "5.31.0"
+  constraints = "~> 5.0"
+  hashes = []
+}
+"#;
+        let locked = locked_providers(content);
+        assert!(locked.contains("registry.terraform.io/hashicorp/aws"));
+        assert_eq!(locked.len(), 1);
+    }
+}
@@ -360,7 +360,7 @@ impl Logger {
     }
 
     /// Print processing summary with better organization
-    pub fn processing_summary(&self, total_modules: usize, successful_modules: usize, failed_modules: usize) {
+    pub fn processing_summary(&self, total_modules: usize, successful_modules: usize, failed_modules: usize, cancelled_modules: usize) {
         if self.quiet || self.level < LogLevel::Info {
             return;
         }
@@ -371,6 +371,9 @@ impl Logger {
         if failed_modules > 0 {
             println!("  {} Failed: {}", "❌".red(), failed_modules.to_string().red());
         }
+        if cancelled_modules > 0 {
+            println!("  {} Cancelled: {}", "⏹️".yellow(), cancelled_modules.to_string().yellow());
+        }
     }
 
     /// Print module initialization status (simplified)
@@ -410,13 +413,14 @@ impl Logger {
     }
     
     /// Print pipeline detection info with enhanced styling
-    pub fn pipeline_info(&self, pr_number: &str, base: &str, head: &str) {
+    pub fn pipeline_info(&self, provider: &str, pr_number: &str, base: &str, head: &str) {
         if self.quiet || self.level < LogLevel::Info {
             return;
         }
         
         println!("{} Pipeline environment detected:", "🚀".blue().bold());
         self.table(&[
+            ("Provider", provider),
             ("PR Number", pr_number),
             ("Base Commit", &base[..7.min(base.len())]),
             ("Head Commit", &head[..7.min(head.len())]),
@@ -894,9 +898,9 @@ pub fn changes_detected(count: usize, modules: &[String]) {
     logger.changes_detected(count, modules);
 }
 
-pub fn pipeline_info(pr_number: &str, base: &str, head: &str) {
+pub fn pipeline_info(provider: &str, pr_number: &str, base: &str, head: &str) {
     let logger = get();
-    logger.pipeline_info(pr_number, base, head);
+    logger.pipeline_info(provider, pr_number, base, head);
 }
 
 pub fn step(step: usize, total: usize, description: &str) {
@@ -1051,8 +1055,8 @@ pub fn operation_completion(module: &str, workspace: Option<&str>, success: bool
     get().operation_completion(module, workspace, success);
 }
 
-pub fn processing_summary(total_modules: usize, successful_modules: usize, failed_modules: usize) {
-    get().processing_summary(total_modules, successful_modules, failed_modules);
+pub fn processing_summary(total_modules: usize, successful_modules: usize, failed_modules: usize, cancelled_modules: usize) {
+    get().processing_summary(total_modules, successful_modules, failed_modules, cancelled_modules);
 }
 
 pub fn module_init_status(success: bool) {
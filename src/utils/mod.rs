@@ -5,3 +5,24 @@ pub mod terraform_background;
 pub mod terraform_operations;
 pub mod display_utils;
 pub mod scan_utils;
+pub mod plan_manifest;
+pub mod state_stats;
+pub mod plan_json;
+pub mod interactive;
+pub mod plan_integrity;
+pub mod owners;
+pub mod status_server;
+pub mod lockfile;
+pub mod plan_report;
+pub mod triage;
+pub mod run_state;
+pub mod hooks;
+pub mod ci_env;
+pub mod git;
+pub mod module_cache;
+pub mod yaml;
+pub mod workspace_cache;
+pub mod credentials;
+pub mod secrets;
+pub mod redact;
+pub mod env_passthrough;
@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::utils::plan_integrity::{sha256, to_hex};
+use crate::utils::scan_utils::ParsedModule;
+
+/// Directory, relative to the repository root, holding what is reused between runs
+pub const CACHE_DIR: &str = ".solarboat/cache";
+
+/// Bumped whenever parsing changes, so modules cached by an older version are parsed again
+const CACHE_VERSION: u32 = 4;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Hash of the files the module was parsed from
+    hash: String,
+    module: ParsedModule,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    #[serde(default)]
+    modules: HashMap<String, CacheEntry>,
+}
+
+/// Modules parsed by earlier runs, by directory. An entry is only reused while the hash of the
+/// files it was parsed from is unchanged.
+#[derive(Debug, Default)]
+pub struct ModuleCache {
+    /// Cache file, or None for a cache that is never loaded or saved
+    path: Option<PathBuf>,
+    entries: HashMap<String, CacheEntry>,
+    used: HashSet<String>,
+    changed: bool,
+}
+
+/// Hash of a module's files, given as (file name, content) pairs
+pub fn module_hash(files: &[(String, String)]) -> String {
+    let mut data = Vec::new();
+    for (name, content) in files {
+        data.extend_from_slice(name.as_bytes());
+        data.push(0);
+        data.extend_from_slice(content.as_bytes());
+        data.push(0);
+    }
+    to_hex(&sha256(&data))
+}
+
+impl ModuleCache {
+    /// Load the cache of the repository at `repo_root`. A missing, unreadable or outdated cache starts empty.
+    pub fn load(repo_root: &Path) -> Self {
+        let path = repo_root.join(CACHE_DIR).join("modules.json");
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<CacheFile>(&content).ok())
+            .filter(|cache| cache.version == CACHE_VERSION)
+            .map(|cache| cache.modules)
+            .unwrap_or_default();
+        Self { path: Some(path), entries, ..Default::default() }
+    }
+
+    /// The cached module parsed from a directory, if its files still hash to `hash`
+    pub fn get(&mut self, dir: &str, hash: &str) -> Option<ParsedModule> {
+        let entry = self.entries.get(dir).filter(|entry| entry.hash == hash)?;
+        self.used.insert(dir.to_string());
+        Some(entry.module.clone())
+    }
+
+    pub fn insert(&mut self, dir: &str, hash: String, module: &ParsedModule) {
+        self.entries.insert(dir.to_string(), CacheEntry { hash, module: module.clone() });
+        self.used.insert(dir.to_string());
+        self.changed = true;
+    }
+
+    /// Write the modules used in this run, dropping directories that are no longer modules.
+    /// Nothing is written when every module came from the cache.
+    pub fn save(mut self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => return Ok(()),
+        };
+        if !self.changed && self.used.len() == self.entries.len() {
+            return Ok(());
+        }
+        self.entries.retain(|dir, _| self.used.contains(dir));
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let content = serde_json::to_string(&CacheFile { version: CACHE_VERSION, modules: self.entries })
+            .map_err(|e| format!("Failed to serialize module cache: {}", e))?;
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Failed to write module cache {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = vec![("main.tf".to_string(), "terraform {}\n".to_string())];
+        let hash = module_hash(&files);
+
+        let mut cache = ModuleCache::load(dir.path());
+        assert!(cache.get("/repo/stacks/app", &hash).is_none());
+        cache.insert("/repo/stacks/app", hash.clone(), &ParsedModule::default());
+        cache.save().unwrap();
+
+        let mut cache = ModuleCache::load(dir.path());
+        assert!(cache.get("/repo/stacks/app", &hash).is_some());
+        let changed = module_hash(&[("main.tf".to_string(), "terraform {\n}\n".to_string())]);
+        assert!(cache.get("/repo/stacks/app", &changed).is_none());
+    }
+}
@@ -0,0 +1,346 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Default rules file, read from the directory solarboat runs in
+pub const OWNERS_FILE_NAME: &str = "SOLARBOAT_OWNERS";
+
+/// A single requirement a pull request must satisfy before a module is applied
+#[derive(Debug, Clone, PartialEq)]
+pub enum Requirement {
+    /// Approval from a specific user (`@user`)
+    User(String),
+    /// Approval from any member of a team (`@org/team`)
+    Team { org: String, team: String },
+    /// Minimum number of approving reviews (`approvals:N`)
+    Approvals(usize),
+    /// Label present on the pull request (`label:name`)
+    Label(String),
+}
+
+impl std::fmt::Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Requirement::User(user) => write!(f, "approval from @{}", user),
+            Requirement::Team { org, team } => write!(f, "approval from @{}/{}", org, team),
+            Requirement::Approvals(count) => write!(f, "{} approval(s)", count),
+            Requirement::Label(label) => write!(f, "label '{}'", label),
+        }
+    }
+}
+
+/// A path pattern and the requirements for modules matching it
+#[derive(Debug, Clone)]
+pub struct OwnerRule {
+    pub pattern: String,
+    pub requirements: Vec<Requirement>,
+    matcher: Regex,
+}
+
+/// Rules parsed from a SOLARBOAT_OWNERS file.
+/// As with CODEOWNERS, the last matching rule wins.
+#[derive(Debug, Clone, Default)]
+pub struct OwnerRules {
+    pub rules: Vec<OwnerRule>,
+}
+
+impl OwnerRules {
+    /// Load rules from a file. A missing file means no rules.
+    pub fn load(path: &str) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path, e)),
+        }
+    }
+
+    /// Parse rules, one `pattern requirement...` entry per line; `#` starts a comment
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let pattern = parts.next().unwrap_or_default().to_string();
+            let requirements = parts
+                .map(|token| parse_requirement(token).map_err(|e| format!("Line {}: {}", index + 1, e)))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            rules.push(OwnerRule {
+                matcher: glob_to_regex(&pattern)?,
+                pattern,
+                requirements,
+            });
+        }
+
+        Ok(Self { rules })
+    }
+
+    /// Find the rule for a module path (relative to the rules file), if any
+    pub fn rule_for(&self, module_path: &str) -> Option<&OwnerRule> {
+        let module_path = module_path.trim_start_matches("./").trim_end_matches('/');
+        self.rules.iter().rev().find(|rule| rule.matcher.is_match(module_path))
+    }
+}
+
+fn parse_requirement(token: &str) -> Result<Requirement, String> {
+    if let Some(owner) = token.strip_prefix('@') {
+        return Ok(match owner.split_once('/') {
+            Some((org, team)) => Requirement::Team { org: org.to_string(), team: team.to_string() },
+            None => Requirement::User(owner.to_string()),
+        });
+    }
+    if let Some(count) = token.strip_prefix("approvals:") {
+        return count.parse::<usize>()
+            .map(Requirement::Approvals)
+            .map_err(|_| format!("Invalid approval count '{}'", count));
+    }
+    if let Some(label) = token.strip_prefix("label:") {
+        return Ok(Requirement::Label(label.to_string()));
+    }
+    Err(format!("Unknown requirement '{}' (expected @user, @org/team, approvals:N or label:name)", token))
+}
+
+/// Convert a CODEOWNERS-style glob into a regex.
+/// `*` matches within a path segment, `**` across segments, and a pattern also matches everything below it.
+fn glob_to_regex(pattern: &str) -> Result<Regex, String> {
+    let trimmed = pattern.trim_start_matches('/').trim_end_matches('/');
+    let mut regex = String::from("^");
+    let mut chars = trimmed.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push_str("(/.*)?$");
+
+    Regex::new(&regex).map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))
+}
+
+/// Approvals and labels on a pull request
+#[derive(Debug, Clone, Default)]
+pub struct PullRequestState {
+    /// Users whose latest review is an approval
+    pub approvers: Vec<String>,
+    pub labels: Vec<String>,
+}
+
+/// Requirements of a rule that the pull request does not satisfy.
+/// `team_members` maps `org/team` to its members.
+pub fn missing_requirements<'a>(
+    rule: &'a OwnerRule,
+    state: &PullRequestState,
+    team_members: &HashMap<String, Vec<String>>,
+) -> Vec<&'a Requirement> {
+    let approved_by = |user: &str| state.approvers.iter().any(|a| a.eq_ignore_ascii_case(user));
+
+    rule.requirements.iter()
+        .filter(|requirement| match requirement {
+            Requirement::User(user) => !approved_by(user),
+            Requirement::Team { org, team } => !team_members
+                .get(&format!("{}/{}", org, team))
+                .map(|members| members.iter().any(|m| approved_by(m)))
+                .unwrap_or(false),
+            Requirement::Approvals(count) => state.approvers.len() < *count,
+            Requirement::Label(label) => !state.labels.iter().any(|l| l == label),
+        })
+        .collect()
+}
+
+/// GitHub API access for the pull request solarboat is running for
+pub struct GitHubClient {
+    repository: String,
+    token: Option<String>,
+    api_url: String,
+}
+
+impl GitHubClient {
+    /// Build a client from GITHUB_REPOSITORY, GITHUB_TOKEN and GITHUB_API_URL
+    pub fn from_env() -> Result<Self, String> {
+        let repository = std::env::var("GITHUB_REPOSITORY")
+            .map_err(|_| "GITHUB_REPOSITORY is not set".to_string())?;
+        Ok(Self {
+            repository,
+            token: std::env::var("GITHUB_TOKEN").ok().filter(|t| !t.is_empty()),
+            api_url: std::env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".to_string()),
+        })
+    }
+
+    /// Fetch approving reviewers and labels for a pull request
+    pub fn pull_request_state(&self, pr_number: &str) -> Result<PullRequestState, String> {
+        let reviews = self.get_all(&format!("/repos/{}/pulls/{}/reviews?per_page=100", self.repository, pr_number))?;
+        let labels = self.get_all(&format!("/repos/{}/issues/{}/labels?per_page=100", self.repository, pr_number))?;
+
+        // Only the latest review of each user counts
+        let mut latest: Vec<(String, String)> = Vec::new();
+        for review in &reviews {
+            let user = review["user"]["login"].as_str().unwrap_or_default().to_string();
+            let state = review["state"].as_str().unwrap_or_default().to_string();
+            if state == "COMMENTED" {
+                continue;
+            }
+            latest.retain(|(u, _)| *u != user);
+            latest.push((user, state));
+        }
+
+        Ok(PullRequestState {
+            approvers: latest.into_iter().filter(|(_, s)| s == "APPROVED").map(|(u, _)| u).collect(),
+            labels: labels.iter()
+                .filter_map(|l| l["name"].as_str().map(|s| s.to_string()))
+                .collect(),
+        })
+    }
+
+    /// List the members of a team
+    pub fn team_members(&self, org: &str, team: &str) -> Result<Vec<String>, String> {
+        let members = self.get_all(&format!("/orgs/{}/teams/{}/members?per_page=100", org, team))?;
+        Ok(members.iter()
+            .filter_map(|m| m["login"].as_str().map(|s| s.to_string()))
+            .collect())
+    }
+
+    /// Fetch every item of a paginated list endpoint, following `Link: rel="next"`
+    fn get_all(&self, path: &str) -> Result<Vec<serde_json::Value>, String> {
+        let mut items = Vec::new();
+        let mut url = Some(format!("{}{}", self.api_url, path));
+        while let Some(page) = url {
+            let (headers, body) = self.get(&page)?;
+            match body {
+                serde_json::Value::Array(page_items) => items.extend(page_items),
+                _ => return Err(format!("GitHub API request {} did not return a list", path)),
+            }
+            url = next_link(&headers);
+        }
+        Ok(items)
+    }
+
+    /// GET a URL, returning the response headers and parsed body.
+    /// The token is passed to curl on stdin so it never appears in the process list.
+    fn get(&self, url: &str) -> Result<(String, serde_json::Value), String> {
+        let mut cmd = Command::new("curl");
+        cmd.args(["-sSfLi", "-H", "Accept: application/vnd.github+json"]);
+        if self.token.is_some() {
+            cmd.args(["-H", "@-"]);
+        }
+        cmd.arg(url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let mut child = cmd.spawn()
+            .map_err(|e| format!("Failed to run curl: {}", e))?;
+        if let (Some(token), Some(mut stdin)) = (&self.token, child.stdin.take()) {
+            writeln!(stdin, "Authorization: Bearer {}", token)
+                .map_err(|e| format!("Failed to pass the GitHub token to curl: {}", e))?;
+        }
+        let output = child.wait_with_output()
+            .map_err(|e| format!("Failed to run curl: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("GitHub API request {} failed: {}", url, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+
+        let (headers, body) = split_response(&String::from_utf8_lossy(&output.stdout));
+        let body = serde_json::from_str(&body)
+            .map_err(|e| format!("Failed to parse GitHub API response for {}: {}", url, e))?;
+        Ok((headers, body))
+    }
+}
+
+/// Split `curl -i` output into the headers of the final response and its body.
+/// Redirects and proxies add one header block per response, each starting with a status line.
+fn split_response(raw: &str) -> (String, String) {
+    let mut headers = String::new();
+    let mut rest = raw;
+    while rest.starts_with("HTTP/") {
+        let Some((block, body)) = rest.split_once("\r\n\r\n").or_else(|| rest.split_once("\n\n")) else {
+            break;
+        };
+        headers = block.to_string();
+        rest = body;
+    }
+    (headers, rest.to_string())
+}
+
+/// URL of the next page from a `Link` response header, if there is one
+fn next_link(headers: &str) -> Option<String> {
+    headers.lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("link"))
+        .flat_map(|(_, value)| value.split(','))
+        .find(|link| link.split(';').skip(1).any(|param| param.trim() == "rel=\"next\""))
+        .and_then(|link| {
+            let url = link.split(';').next()?.trim();
+            Some(url.strip_prefix('<')?.strip_suffix('>')?.to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = "
+# Everything under stacks needs one approval
+stacks/**            approvals:1
+stacks/networking    @acme/netops label:network-reviewed
+stacks/*/prod        @alice approvals:2
+";
+
+    #[test]
+    fn test_rule_matching() {
+        let rules = OwnerRules::parse(RULES).unwrap();
+        assert_eq!(rules.rule_for("stacks/webapp").unwrap().pattern, "stacks/**");
+        assert_eq!(rules.rule_for("./stacks/networking/").unwrap().pattern, "stacks/networking");
+        assert_eq!(rules.rule_for("stacks/networking/vpc").unwrap().pattern, "stacks/networking");
+        assert_eq!(rules.rule_for("stacks/webapp/prod").unwrap().pattern, "stacks/*/prod");
+        assert!(rules.rule_for("modules/vpc").is_none());
+        assert!(OwnerRules::parse("stacks bogus").is_err());
+    }
+
+    #[test]
+    fn test_missing_requirements() {
+        let rules = OwnerRules::parse(RULES).unwrap();
+        let state = PullRequestState {
+            approvers: vec!["bob".to_string()],
+            labels: vec!["network-reviewed".to_string()],
+        };
+        let mut teams = HashMap::new();
+        teams.insert("acme/netops".to_string(), vec!["bob".to_string()]);
+
+        let networking = rules.rule_for("stacks/networking").unwrap();
+        assert!(missing_requirements(networking, &state, &teams).is_empty());
+
+        let prod = rules.rule_for("stacks/webapp/prod").unwrap();
+        let missing = missing_requirements(prod, &state, &teams);
+        assert_eq!(missing, vec![&Requirement::User("alice".to_string()), &Requirement::Approvals(2)]);
+    }
+
+    #[test]
+    fn test_paginated_response() {
+        let raw = "HTTP/1.1 302 Found\r\nLocation: /next\r\n\r\n\
+                   HTTP/2 200\r\n\
+                   link: <https://api.github.com/repositories/1/pulls/7/reviews?per_page=100&page=2>; rel=\"next\", \
+                   <https://api.github.com/repositories/1/pulls/7/reviews?per_page=100&page=3>; rel=\"last\"\r\n\r\n\
+                   [{\"id\": 1}]";
+        let (headers, body) = split_response(raw);
+        assert_eq!(body, "[{\"id\": 1}]");
+        assert_eq!(
+            next_link(&headers).as_deref(),
+            Some("https://api.github.com/repositories/1/pulls/7/reviews?per_page=100&page=2")
+        );
+
+        let (headers, _) = split_response("HTTP/2 200\r\nlink: <https://x/?page=1>; rel=\"prev\"\r\n\r\n[]");
+        assert_eq!(next_link(&headers), None);
+    }
+}
@@ -1,19 +1,142 @@
-use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}};
 use std::thread;
 use std::time::Duration;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use serde::Serialize;
 
-use crate::utils::terraform_operations::{TerraformOperation, OperationResult};
+use crate::utils::terraform_operations::{TerraformOperation, OperationResult, PlanArtifacts};
 use crate::utils::error::{SolarboatError, SafeOperations};
 use crate::utils::logger;
+use crate::utils::run_state::Checkpoint;
+use crate::utils::hooks::HookPhase;
 
 pub struct ParallelProcessor {
     module_groups: Arc<Mutex<HashMap<String, VecDeque<TerraformOperation>>>>,
     results: Arc<Mutex<Vec<OperationResult>>>,
     total_modules: usize,
     completed_modules: Arc<AtomicUsize>,
+    active_modules: Arc<Mutex<HashMap<String, bool>>>,
     worker_handle: Option<thread::JoinHandle<()>>,
     parallel_limit: usize,
+    checkpoint: Option<Arc<Checkpoint>>,
+    /// Set on the first failure when running in fail-fast mode
+    cancel: Option<Arc<AtomicBool>>,
+    /// Modules of each concurrency group allowed to run at once
+    group_limits: Arc<GroupLimits>,
+    parallel_workspaces: HashSet<String>,
+}
+
+/// Separates the module from the workspace in the queue of a module running its workspaces in parallel
+const WORKSPACE_QUEUE_SEPARATOR: &str = "::";
+
+/// One lock per module directory, held while `terraform init` runs. Workspaces running in parallel
+/// have their own TF_DATA_DIR but share the module's `.terraform.lock.hcl`, so their inits take turns.
+static INIT_LOCKS: Mutex<BTreeMap<String, Arc<Mutex<()>>>> = Mutex::new(BTreeMap::new());
+
+fn init_lock(module_path: &str) -> Arc<Mutex<()>> {
+    let mut locks = INIT_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    Arc::clone(locks.entry(module_path.to_string()).or_default())
+}
+
+/// Module a queue belongs to
+fn queue_module(queue: &str) -> &str {
+    queue.split_once(WORKSPACE_QUEUE_SEPARATOR).map_or(queue, |(module, _)| module)
+}
+
+/// Concurrency group of each module and the groups whose modules are capped
+#[derive(Debug, Default)]
+struct GroupLimits {
+    groups: HashMap<String, String>,
+    limits: HashMap<String, usize>,
+}
+
+impl GroupLimits {
+    /// Whether the group of `module_path` already runs as many modules as it may
+    fn is_full(&self, module_path: &str, active: &HashMap<String, bool>) -> bool {
+        let Some(group) = self.groups.get(queue_module(module_path)) else {
+            return false;
+        };
+        let Some(limit) = self.limits.get(group) else {
+            return false;
+        };
+        active.keys().filter(|active| self.groups.get(queue_module(active)) == Some(group)).count() >= *limit
+    }
+}
+
+/// Read-only view of a processor's progress that can be shared with other threads
+#[derive(Clone)]
+pub struct ProgressHandle {
+    module_groups: Arc<Mutex<HashMap<String, VecDeque<TerraformOperation>>>>,
+    results: Arc<Mutex<Vec<OperationResult>>>,
+    completed_modules: Arc<AtomicUsize>,
+    active_modules: Arc<Mutex<HashMap<String, bool>>>,
+    total_modules: usize,
+}
+
+/// Point-in-time progress of a processor run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProgressSnapshot {
+    pub total_modules: usize,
+    pub completed_modules: usize,
+    /// Modules currently being processed
+    pub active_modules: Vec<String>,
+    /// Operations not yet started
+    pub pending_operations: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Operations cancelled after a failure in fail-fast mode
+    pub cancelled: usize,
+    /// Finished operations in completion order
+    pub results: Vec<OperationStatus>,
+}
+
+/// Outcome of a finished operation
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationStatus {
+    pub module: String,
+    pub workspace: String,
+    pub success: bool,
+    pub cancelled: bool,
+    pub error: Option<String>,
+}
+
+impl ProgressHandle {
+    /// Take a snapshot of the current progress
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        let mut active_modules: Vec<String> = self.active_modules.lock()
+            .map(|active| active.keys().cloned().collect())
+            .unwrap_or_default();
+        active_modules.sort();
+
+        let pending_operations = self.module_groups.lock()
+            .map(|groups| groups.values().map(|ops| ops.len()).sum())
+            .unwrap_or(0);
+
+        let results: Vec<OperationStatus> = self.results.lock()
+            .map(|results| {
+                results.iter()
+                    .map(|r| OperationStatus {
+                        module: r.module_path.clone(),
+                        workspace: r.workspace.clone().unwrap_or_else(|| "default".to_string()),
+                        success: r.success,
+                        cancelled: r.cancelled,
+                        error: r.error.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ProgressSnapshot {
+            total_modules: self.total_modules,
+            completed_modules: self.completed_modules.load(Ordering::Relaxed),
+            active_modules,
+            pending_operations,
+            succeeded: results.iter().filter(|r| r.success).count(),
+            failed: results.iter().filter(|r| !r.success && !r.cancelled).count(),
+            cancelled: results.iter().filter(|r| r.cancelled).count(),
+            results,
+        }
+    }
 }
 
 impl ParallelProcessor {
@@ -23,30 +146,69 @@ impl ParallelProcessor {
             results: Arc::new(Mutex::new(Vec::new())),
             total_modules: 0,
             completed_modules: Arc::new(AtomicUsize::new(0)),
+            active_modules: Arc::new(Mutex::new(HashMap::new())),
             worker_handle: None,
             parallel_limit: parallel_limit.clamp(1, 4),
+            checkpoint: None,
+            cancel: None,
+            group_limits: Arc::new(GroupLimits::default()),
+            parallel_workspaces: HashSet::new(),
         }
     }
 
+    /// Cancel all queued operations as soon as one operation fails
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.cancel = fail_fast.then(|| Arc::new(AtomicBool::new(false)));
+        self
+    }
+
+    /// Record each finished operation in a checkpoint as soon as it completes
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.checkpoint = Some(Arc::new(checkpoint));
+        self
+    }
+
+    /// Run at most `limits[group]` modules of a concurrency group at once, on top of the parallel
+    /// limit. `groups` maps module paths to their group; modules without one are never held back.
+    pub fn with_group_limits(mut self, groups: HashMap<String, String>, limits: HashMap<String, usize>) -> Self {
+        self.group_limits = Arc::new(GroupLimits { groups, limits });
+        self
+    }
+
+    /// Queue each workspace of these modules on its own, so their workspaces run in parallel
+    /// instead of one after another. Only safe when every workspace has its own TF_DATA_DIR; inits of
+    /// the same module still run one at a time.
+    pub fn with_parallel_workspaces(mut self, modules: HashSet<String>) -> Self {
+        self.parallel_workspaces = modules;
+        self
+    }
+
     pub fn add_operation(&mut self, operation: TerraformOperation) -> Result<(), SolarboatError> {
         let module_path = operation.module_path.clone();
         let workspace = operation.workspace.as_deref().unwrap_or("default");
         
         logger::debug(&format!("Adding operation: module={}, workspace={}", module_path, workspace));
         
+        let queue = match &operation.workspace {
+            Some(workspace) if self.parallel_workspaces.contains(&module_path) => {
+                format!("{}{}{}", module_path, WORKSPACE_QUEUE_SEPARATOR, workspace)
+            }
+            _ => module_path,
+        };
+        
         let mut groups = SafeOperations::lock_with_timeout(
             &self.module_groups,
             Duration::from_secs(5),
             "module_groups_add"
         )?;
         
-        groups.entry(module_path.clone())
+        groups.entry(queue.clone())
             .or_insert_with(VecDeque::new)
             .push_back(operation);
         
         logger::debug(&format!("Operation added. Total groups: {}, operations in group: {}", 
             groups.len(), 
-            groups.get(&module_path).map(|g| g.len()).unwrap_or(0)
+            groups.get(&queue).map(|g| g.len()).unwrap_or(0)
         ));
         
         Ok(())
@@ -72,16 +234,24 @@ impl ParallelProcessor {
         let module_groups = Arc::clone(&self.module_groups);
         let results = Arc::clone(&self.results);
         let completed_modules = Arc::clone(&self.completed_modules);
+        let active_modules = Arc::clone(&self.active_modules);
         let total_modules = self.total_modules;
         let parallel_limit = self.parallel_limit;
+        let checkpoint = self.checkpoint.clone();
+        let cancel = self.cancel.clone();
+        let group_limits = Arc::clone(&self.group_limits);
         
         let handle = thread::spawn(move || {
             Self::process_modules(
                 module_groups,
                 results,
                 completed_modules,
+                active_modules,
                 total_modules,
-                parallel_limit
+                parallel_limit,
+                checkpoint,
+                cancel,
+                group_limits
             );
         });
         
@@ -89,14 +259,18 @@ impl ParallelProcessor {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn process_modules(
         module_groups: Arc<Mutex<HashMap<String, VecDeque<TerraformOperation>>>>,
         results: Arc<Mutex<Vec<OperationResult>>>,
         completed_modules: Arc<AtomicUsize>,
+        active_modules: Arc<Mutex<HashMap<String, bool>>>,
         total_modules: usize,
         parallel_limit: usize,
+        checkpoint: Option<Arc<Checkpoint>>,
+        cancel: Option<Arc<AtomicBool>>,
+        group_limits: Arc<GroupLimits>,
     ) {
-        let active_modules = Arc::new(Mutex::new(HashMap::<String, bool>::new()));
         let start_time = std::time::Instant::now();
         let max_duration = Duration::from_secs(300);
         
@@ -115,6 +289,13 @@ impl ParallelProcessor {
                 break;
             }
             
+            // Modules that have not started yet are cancelled as a whole; running ones stop after their current operation
+            if cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed)) {
+                Self::cancel_pending_modules(&module_groups, &results, &completed_modules, &active_modules);
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            
             let can_start_more = {
                 let active = match active_modules.lock() {
                     Ok(active) => active,
@@ -144,7 +325,9 @@ impl ParallelProcessor {
                     
                     groups.iter()
                         .find(|(module_path, operations)| {
-                            !operations.is_empty() && !active.contains_key(*module_path)
+                            !operations.is_empty()
+                                && !active.contains_key(*module_path)
+                                && !group_limits.is_full(module_path, &active)
                         })
                         .map(|(module_path, _)| module_path.clone())
                 };
@@ -160,6 +343,8 @@ impl ParallelProcessor {
                     let results = Arc::clone(&results);
                     let completed_modules = Arc::clone(&completed_modules);
                     let active_modules_clone = Arc::clone(&active_modules);
+                    let checkpoint = checkpoint.clone();
+                    let cancel = cancel.clone();
                     
                     thread::spawn(move || {
                         Self::process_module_operations(
@@ -167,7 +352,9 @@ impl ParallelProcessor {
                             module_groups,
                             results,
                             completed_modules,
-                            active_modules_clone
+                            active_modules_clone,
+                            checkpoint,
+                            cancel
                         );
                     });
                 }
@@ -185,6 +372,8 @@ impl ParallelProcessor {
         results: Arc<Mutex<Vec<OperationResult>>>,
         completed_modules: Arc<AtomicUsize>,
         active_modules: Arc<Mutex<HashMap<String, bool>>>,
+        checkpoint: Option<Arc<Checkpoint>>,
+        cancel: Option<Arc<AtomicBool>>,
     ) {
         let display_path = format_module_path(&module_path);
         logger::debug(&format!("Processing module: {}", display_path));
@@ -206,6 +395,13 @@ impl ParallelProcessor {
                 };
                 
                 if let Some(operations) = groups.get_mut(&module_path) {
+                    if cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed)) {
+                        let cancelled: Vec<OperationResult> = operations.drain(..).map(|op| cancelled_result(&op)).collect();
+                        if let Ok(mut results) = results.lock() {
+                            results.extend(cancelled);
+                        }
+                        break;
+                    }
                     let op = operations.pop_front();
                     logger::debug(&format!("Module {}: took operation, remaining in group: {}", 
                         display_path, operations.len()));
@@ -221,7 +417,19 @@ impl ParallelProcessor {
                 logger::debug(&format!("Module {}: processing operation {} (workspace: {:?})", 
                     display_path, operation_count, op.workspace));
                 
-                let result = Self::process_single_operation(&op);
+                let result = crate::utils::terraform_operations::with_env(&op.env, || Self::process_single_operation(&op));
+                if !result.success {
+                    if let Some(cancel) = &cancel {
+                        if !cancel.swap(true, Ordering::Relaxed) {
+                            logger::warn(&format!("{} failed - cancelling queued operations (fail-fast)", display_path));
+                        }
+                    }
+                }
+                if let Some(checkpoint) = &checkpoint {
+                    if let Err(e) = checkpoint.record(&result) {
+                        logger::warn(&format!("Failed to update checkpoint: {}", e));
+                    }
+                }
                 
                 {
                     let mut results = match SafeOperations::lock_with_timeout(
@@ -262,17 +470,58 @@ impl ParallelProcessor {
         logger::debug(&format!("Module {} completed", display_path));
     }
 
+    /// Record every queued operation of modules that have not started as cancelled
+    fn cancel_pending_modules(
+        module_groups: &Arc<Mutex<HashMap<String, VecDeque<TerraformOperation>>>>,
+        results: &Arc<Mutex<Vec<OperationResult>>>,
+        completed_modules: &Arc<AtomicUsize>,
+        active_modules: &Arc<Mutex<HashMap<String, bool>>>,
+    ) {
+        let (mut groups, active) = match (module_groups.lock(), active_modules.lock()) {
+            (Ok(groups), Ok(active)) => (groups, active),
+            _ => return,
+        };
+        
+        let mut cancelled = Vec::new();
+        for (module_path, operations) in groups.iter_mut() {
+            if operations.is_empty() || active.contains_key(module_path) {
+                continue;
+            }
+            cancelled.extend(operations.drain(..).map(|op| cancelled_result(&op)));
+            completed_modules.fetch_add(1, Ordering::Relaxed);
+        }
+        
+        if let Ok(mut results) = results.lock() {
+            results.extend(cancelled);
+        }
+    }
+
     fn process_single_operation(operation: &TerraformOperation) -> OperationResult {
         let module_path = &operation.module_path;
         let workspace = &operation.workspace;
         let var_files = &operation.var_files;
+        let args = &operation.args;
         let operation_type = &operation.operation_type;
         let watch = operation.watch;
         let _skip_init = operation.skip_init;
+        let data_dir = operation.data_dir.as_deref().map(std::path::Path::new);
+        let hooks = &operation.hooks;
+        let (pre_hook, post_hook) = match operation_type {
+            crate::utils::terraform_operations::OperationType::Init => (None, None),
+            crate::utils::terraform_operations::OperationType::Plan { .. } => (Some(HookPhase::PrePlan), Some(HookPhase::PostPlan)),
+            crate::utils::terraform_operations::OperationType::Apply { .. } => (Some(HookPhase::PreApply), Some(HookPhase::PostApply)),
+        };
 
+        let mut hook_output = match hooks.run(HookPhase::PreInit, module_path, workspace.as_deref(), data_dir, None) {
+            Ok(output) => output,
+            Err(e) => return failed_result(operation, e),
+        };
+
+        let init_lock = init_lock(module_path);
+        let init_guard = init_lock.lock().unwrap_or_else(|e| e.into_inner());
         let init_success = if watch {
-            let mut background_tf = crate::utils::terraform_background::BackgroundTerraform::new();
-            match background_tf.init_background(module_path) {
+            let mut background_tf = crate::utils::terraform_background::BackgroundTerraform::new().with_data_dir(data_dir);
+            match background_tf.init_background(module_path, &args.backend_config, &args.extra_init_args) {
                 Ok(_) => {
                     match background_tf.wait_for_completion(300) {
                         Ok(success) => success,
@@ -282,11 +531,12 @@ impl ParallelProcessor {
                 Err(_) => false,
             }
         } else {
-            match crate::utils::terraform_operations::ensure_module_initialized(module_path) {
+            match crate::utils::terraform_operations::ensure_module_initialized(module_path, data_dir, &args.backend_config, &args.extra_init_args) {
                 Ok(_) => true,
                 Err(_) => false,
             }
         };
+        drop(init_guard);
 
         if !init_success {
             return OperationResult {
@@ -296,11 +546,15 @@ impl ParallelProcessor {
                 success: false,
                 error: Some("Initialization failed".to_string()),
                 output: Vec::new(),
+                artifacts: PlanArtifacts::default(),
+                data_dir: operation.data_dir.clone(),
+                env: operation.env.clone(),
+                cancelled: false,
             };
         }
 
         if let Some(ref workspace_name) = workspace {
-            if let Err(e) = crate::utils::terraform_operations::select_workspace(module_path, workspace_name) {
+            if let Err(e) = crate::utils::terraform_operations::select_workspace(module_path, workspace_name, data_dir) {
                 return OperationResult {
                     module_path: module_path.clone(),
                     workspace: workspace.clone(),
@@ -308,67 +562,104 @@ impl ParallelProcessor {
                     success: false,
                     error: Some(format!("Failed to select workspace {}: {}", workspace_name, e)),
                     output: Vec::new(),
+                    artifacts: PlanArtifacts::default(),
+                    data_dir: operation.data_dir.clone(),
+                    env: operation.env.clone(),
+                    cancelled: false,
                 };
             }
         }
 
+        if let Some(phase) = pre_hook {
+            match hooks.run(phase, module_path, workspace.as_deref(), data_dir, None) {
+                Ok(output) => hook_output.extend(output),
+                Err(e) => return failed_result(operation, e),
+            }
+        }
+
+        let mut artifacts = PlanArtifacts::default();
         let (success, error, output) = match operation_type {
             crate::utils::terraform_operations::OperationType::Init => {
                 (true, None, Vec::new())
             }
-            crate::utils::terraform_operations::OperationType::Plan { plan_dir } => {
+            crate::utils::terraform_operations::OperationType::Plan { plan_dir, format, skip_empty } => {
                 logger::operation_status("terraform plan", workspace.as_deref(), var_files.len());
 
                 if watch {
-                    let mut background_tf = crate::utils::terraform_background::BackgroundTerraform::new();
-                    match background_tf.plan_background(module_path, Some(var_files)) {
-                        Ok(_) => {
-                            match background_tf.wait_for_completion(600) {
-                                Ok(success) => {
-                                    if success {
-                                        logger::operation_completion(module_path, workspace.as_deref(), true);
-                                        if let Some(plan_dir) = plan_dir {
-                                            if let Ok(output) = background_tf.get_output() {
-                                                if let Err(e) = crate::utils::terraform_operations::save_plan_output(
-                                                    module_path, plan_dir, workspace.as_deref(), &output
-                                                ) {
-                                                    println!("  ⚠️  Failed to save plan output: {}", e);
+                    // Binary plans are only saved when a plan directory is configured
+                    let stem_and_out = match plan_dir {
+                        Some(plan_dir) => crate::utils::terraform_operations::plan_artifact_stem(module_path, workspace.as_deref())
+                            .and_then(|stem| {
+                                let out = crate::utils::terraform_operations::plan_dir_file(plan_dir, &format!("{}.tfplan", stem))?;
+                                Ok(Some((stem, out)))
+                            }),
+                        None => Ok(None),
+                    };
+                    
+                    match stem_and_out {
+                        Err(e) => {
+                            logger::operation_completion(module_path, workspace.as_deref(), false);
+                            (false, Some(format!("Plan error: {}", e)), Vec::new())
+                        }
+                        Ok(stem_and_out) => {
+                            let mut background_tf = crate::utils::terraform_background::BackgroundTerraform::new().with_data_dir(data_dir);
+                            match background_tf.plan_background(module_path, Some(var_files), args, stem_and_out.as_ref().map(|(_, out)| out.as_path())) {
+                                Ok(_) => {
+                                    match background_tf.wait_for_completion(600) {
+                                        Ok(success) => {
+                                            if success {
+                                                logger::operation_completion(module_path, workspace.as_deref(), true);
+                                                if let (Some(plan_dir), Some((stem, _))) = (plan_dir, &stem_and_out) {
+                                                    if let Ok(output) = background_tf.get_output() {
+                                                        artifacts = if *skip_empty && crate::utils::terraform_operations::plan_has_no_changes(&output) {
+                                                            crate::utils::terraform_operations::discard_empty_plan(plan_dir, stem)
+                                                        } else {
+                                                            crate::utils::terraform_operations::save_plan_artifacts(
+                                                            module_path, plan_dir, stem, workspace.as_deref(), &output, *format, data_dir
+                                                            )
+                                                        };
+                                                    }
                                                 }
+                                                let output = background_tf.get_output().unwrap_or_else(|_| Vec::new());
+                                                (true, None, output)
+                                            } else {
+                                                logger::operation_completion(module_path, workspace.as_deref(), false);
+                                                let output = background_tf.get_output().unwrap_or_else(|_| Vec::new());
+                                                (false, Some("Plan failed".to_string()), output)
                                             }
                                         }
-                                        let output = background_tf.get_output().unwrap_or_else(|_| Vec::new());
-                                        (true, None, output)
-                                    } else {
-                                        logger::operation_completion(module_path, workspace.as_deref(), false);
-                                        let output = background_tf.get_output().unwrap_or_else(|_| Vec::new());
-                                        (false, Some("Plan failed".to_string()), output)
+                                        Err(_) => {
+                                            logger::operation_completion(module_path, workspace.as_deref(), false);
+                                            (false, Some("Plan timeout".to_string()), Vec::new())
+                                        }
                                     }
                                 }
                                 Err(_) => {
                                     logger::operation_completion(module_path, workspace.as_deref(), false);
-                                    (false, Some("Plan timeout".to_string()), Vec::new())
+                                    (false, Some("Failed to start plan".to_string()), Vec::new())
                                 }
                             }
                         }
-                        Err(_) => {
-                            logger::operation_completion(module_path, workspace.as_deref(), false);
-                            (false, Some("Failed to start plan".to_string()), Vec::new())
-                        }
                     }
                 } else {
                     match crate::utils::terraform_operations::run_single_plan(
                         module_path, 
                         plan_dir.as_deref(), 
                         workspace.as_deref(), 
-                        Some(var_files)
+                        Some(var_files),
+                        args,
+                        *format,
+                        *skip_empty,
+                        data_dir
                     ) {
-                        Ok(success) => {
-                            if success {
+                        Ok(outcome) => {
+                            if outcome.success {
                                 logger::operation_completion(module_path, workspace.as_deref(), true);
-                                (true, None, Vec::new())
+                                artifacts = outcome.artifacts;
+                                (true, None, outcome.output)
                             } else {
                                 logger::operation_completion(module_path, workspace.as_deref(), false);
-                                (false, Some("Plan failed".to_string()), Vec::new())
+                                (false, Some("Plan failed".to_string()), outcome.output)
                             }
                         }
                         Err(e) => {
@@ -378,22 +669,34 @@ impl ParallelProcessor {
                     }
                 }
             }
-            crate::utils::terraform_operations::OperationType::Apply => {
+            crate::utils::terraform_operations::OperationType::Apply { plan_file, report_dir, snapshot } => {
                 logger::operation_status("terraform apply", workspace.as_deref(), var_files.len());
 
+                if *snapshot {
+                    match crate::utils::terraform_operations::snapshot_state(module_path, workspace.as_deref(), data_dir) {
+                        Ok(Some(path)) => artifacts.state_snapshot = Some(path.to_string_lossy().to_string()),
+                        Ok(None) => logger::debug(&format!("Module {}: no state to snapshot", module_path)),
+                        Err(e) => logger::warn(&format!("Failed to snapshot state before apply: {}", e)),
+                    }
+                }
+
                 if watch {
-                    let mut background_tf = crate::utils::terraform_background::BackgroundTerraform::new();
-                    match background_tf.apply_background(module_path, Some(var_files)) {
+                    let mut background_tf = crate::utils::terraform_background::BackgroundTerraform::new().with_data_dir(data_dir);
+                    match background_tf.apply_background(module_path, Some(var_files), args, plan_file.as_deref().map(std::path::Path::new)) {
                         Ok(_) => {
                             match background_tf.wait_for_completion(1800) {
                                 Ok(success) => {
+                                    logger::operation_completion(module_path, workspace.as_deref(), success);
+                                    let output = background_tf.get_output().unwrap_or_else(|_| Vec::new());
+                                    if let Some(report_dir) = report_dir {
+                                        match crate::utils::terraform_operations::save_apply_output(module_path, report_dir, workspace.as_deref(), &output, success) {
+                                            Ok(report) => artifacts.report = Some(report),
+                                            Err(e) => logger::warn(&format!("Failed to save apply output: {}", e)),
+                                        }
+                                    }
                                     if success {
-                                        logger::operation_completion(module_path, workspace.as_deref(), true);
-                                        let output = background_tf.get_output().unwrap_or_else(|_| Vec::new());
                                         (true, None, output)
                                     } else {
-                                        logger::operation_completion(module_path, workspace.as_deref(), false);
-                                        let output = background_tf.get_output().unwrap_or_else(|_| Vec::new());
                                         (false, Some("Apply failed".to_string()), output)
                                     }
                                 }
@@ -409,14 +712,22 @@ impl ParallelProcessor {
                         }
                     }
                 } else {
-                    match crate::utils::terraform_operations::run_single_apply(module_path, Some(var_files)) {
-                        Ok(success) => {
-                            if success {
-                                logger::operation_completion(module_path, workspace.as_deref(), true);
-                                (true, None, Vec::new())
+                    match crate::utils::terraform_operations::run_single_apply(
+                        module_path,
+                        workspace.as_deref(),
+                        Some(var_files),
+                        args,
+                        plan_file.as_deref().map(std::path::Path::new),
+                        report_dir.as_deref(),
+                        data_dir
+                    ) {
+                        Ok(outcome) => {
+                            logger::operation_completion(module_path, workspace.as_deref(), outcome.success);
+                            artifacts.report = outcome.report;
+                            if outcome.success {
+                                (true, None, outcome.output)
                             } else {
-                                logger::operation_completion(module_path, workspace.as_deref(), false);
-                                (false, Some("Apply failed".to_string()), Vec::new())
+                                (false, Some("Apply failed".to_string()), outcome.output)
                             }
                         }
                         Err(e) => {
@@ -428,13 +739,32 @@ impl ParallelProcessor {
             }
         };
 
+        hook_output.extend(output);
+        let (success, error) = match post_hook.map(|phase| hooks.run(phase, module_path, workspace.as_deref(), data_dir, Some(success))) {
+            Some(Ok(output)) => {
+                hook_output.extend(output);
+                (success, error)
+            }
+            // A failing post hook fails an otherwise successful operation
+            Some(Err(e)) if success => (false, Some(e)),
+            Some(Err(e)) => {
+                logger::warn(&e);
+                (success, error)
+            }
+            None => (success, error),
+        };
+
         OperationResult {
             module_path: module_path.clone(),
             workspace: workspace.clone(),
             operation_type: operation_type.clone(),
             success,
             error,
-            output,
+            output: hook_output,
+            artifacts,
+            data_dir: operation.data_dir.clone(),
+            env: operation.env.clone(),
+            cancelled: false,
         }
     }
 
@@ -471,11 +801,44 @@ impl ParallelProcessor {
         Ok(results.clone())
     }
 
+    /// Get a handle for observing progress from another thread. Call after `start`.
+    pub fn progress_handle(&self) -> ProgressHandle {
+        ProgressHandle {
+            module_groups: Arc::clone(&self.module_groups),
+            results: Arc::clone(&self.results),
+            completed_modules: Arc::clone(&self.completed_modules),
+            active_modules: Arc::clone(&self.active_modules),
+            total_modules: self.total_modules,
+        }
+    }
+
     pub fn get_parallel_limit(&self) -> usize {
         self.parallel_limit
     }
 }
 
+fn failed_result(operation: &TerraformOperation, error: String) -> OperationResult {
+    OperationResult {
+        module_path: operation.module_path.clone(),
+        workspace: operation.workspace.clone(),
+        operation_type: operation.operation_type.clone(),
+        success: false,
+        error: Some(error),
+        output: Vec::new(),
+        artifacts: PlanArtifacts::default(),
+        data_dir: operation.data_dir.clone(),
+        env: operation.env.clone(),
+        cancelled: false,
+    }
+}
+
+fn cancelled_result(operation: &TerraformOperation) -> OperationResult {
+    OperationResult {
+        cancelled: true,
+        ..failed_result(operation, "Cancelled after an earlier failure".to_string())
+    }
+}
+
 fn format_module_path(module_path: &str) -> String {
     if let Some(file_name) = std::path::Path::new(module_path).file_name() {
         if let Some(name) = file_name.to_str() {
@@ -484,3 +847,35 @@ fn format_module_path(module_path: &str) -> String {
     }
     module_path.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_limits() {
+        let limits = GroupLimits {
+            groups: HashMap::from([
+                ("a".to_string(), "s3:prod".to_string()),
+                ("b".to_string(), "s3:prod".to_string()),
+                ("c".to_string(), "s3:dev".to_string()),
+            ]),
+            limits: HashMap::from([("s3:prod".to_string(), 1)]),
+        };
+        let active = HashMap::from([("a".to_string(), true)]);
+        assert!(limits.is_full("b", &active));
+        assert!(!limits.is_full("c", &active));
+        assert!(!limits.is_full("d", &active));
+        assert!(!limits.is_full("b", &HashMap::new()));
+        assert!(limits.is_full("b::prod", &active));
+        assert!(limits.is_full("a", &HashMap::from([("b::staging".to_string(), true)])));
+    }
+
+    #[test]
+    fn test_init_lock_per_module() {
+        let lock = init_lock("stacks/app");
+        let _guard = lock.lock().unwrap();
+        assert!(init_lock("stacks/app").try_lock().is_err());
+        assert!(init_lock("stacks/db").try_lock().is_ok());
+    }
+}
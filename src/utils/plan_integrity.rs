@@ -0,0 +1,212 @@
+use std::path::Path;
+use std::process::Command;
+
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::utils::plan_manifest::{ManifestEntry, PlanManifest};
+
+/// Environment variable holding the key used to sign and verify plan checksums
+pub const SIGNING_KEY_ENV: &str = "SOLARBOAT_PLAN_SIGNING_KEY";
+
+/// Read the plan signing key from the environment, if configured
+pub fn signing_key() -> Option<String> {
+    std::env::var(SIGNING_KEY_ENV).ok().filter(|k| !k.is_empty())
+}
+
+/// Get the commit SHA of the current checkout
+pub fn current_commit() -> Result<String, String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err("Failed to get current commit".to_string())
+    }
+}
+
+/// Record the originating commit, binary plan checksums and (when a key is given)
+/// signatures for every manifest entry
+pub fn seal_manifest(manifest: &mut PlanManifest, plan_dir: &str, key: Option<&str>) -> Result<(), String> {
+    manifest.commit = Some(current_commit()?);
+
+    for entry in &mut manifest.entries {
+        if let Some(plan_file) = &entry.plan_file {
+            entry.checksum = Some(file_checksum(&Path::new(plan_dir).join(plan_file))?);
+        }
+    }
+    sign_manifest(manifest, key);
+
+    Ok(())
+}
+
+/// Re-sign every manifest entry after its flags changed (e.g. on approval).
+/// Does nothing without a key.
+pub fn sign_manifest(manifest: &mut PlanManifest, key: Option<&str>) {
+    let Some(key) = key else {
+        return;
+    };
+    let commit = manifest.commit.clone();
+    for entry in &mut manifest.entries {
+        entry.signature = Some(to_hex(&sign(key, commit.as_deref(), entry)));
+    }
+}
+
+/// Verify that the manifest was produced from the current commit
+pub fn verify_commit(manifest: &PlanManifest) -> Result<(), String> {
+    let expected = manifest.commit.as_deref()
+        .ok_or_else(|| "Plan manifest does not record the commit it was created from".to_string())?;
+    let current = current_commit()?;
+
+    if expected != current {
+        return Err(format!("Plans were created from commit {} but the current commit is {}", expected, current));
+    }
+    Ok(())
+}
+
+/// Verify the checksum and signature of a single saved plan.
+/// Returns the path of the verified binary plan.
+pub fn verify_entry(manifest: &PlanManifest, entry: &ManifestEntry, plan_dir: &str, key: Option<&str>) -> Result<std::path::PathBuf, String> {
+    let plan_file = entry.plan_file.as_deref()
+        .ok_or_else(|| "no binary plan file was saved".to_string())?;
+    let expected = entry.checksum.as_deref()
+        .ok_or_else(|| "no checksum recorded for the plan file".to_string())?;
+
+    let path = Path::new(plan_dir).join(plan_file);
+    let actual = file_checksum(&path)?;
+    if actual != expected {
+        return Err(format!("checksum mismatch for {} (expected {}, got {})", plan_file, expected, actual));
+    }
+
+    verify_signature(manifest, entry, key)?;
+
+    path.canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))
+}
+
+/// Verify the signature of an entry, covering its recorded checksum and every flag apply acts on
+pub fn verify_signature(manifest: &PlanManifest, entry: &ManifestEntry, key: Option<&str>) -> Result<(), String> {
+    let label = format!("{} ({})", entry.module, entry.workspace);
+    match (key, &entry.signature) {
+        (Some(key), Some(signature)) => {
+            let signature = from_hex(signature)
+                .ok_or_else(|| format!("signature of {} is not valid hex", label))?;
+            mac(key, manifest.commit.as_deref(), entry)
+                .verify_slice(&signature)
+                .map_err(|_| format!("signature mismatch for {}", label))
+        }
+        (Some(_), None) => Err(format!("{} is not signed", label)),
+        (None, Some(_)) => Err(format!("{} is signed but {} is not set", label, SIGNING_KEY_ENV)),
+        (None, None) => Ok(()),
+    }
+}
+
+/// Verify the signatures of every entry in the manifest
+pub fn verify_signatures(manifest: &PlanManifest, key: Option<&str>) -> Result<(), String> {
+    manifest.entries.iter().try_for_each(|entry| verify_signature(manifest, entry, key))
+}
+
+/// Compute the SHA-256 checksum of a file as lowercase hex
+pub fn file_checksum(path: &Path) -> Result<String, String> {
+    let content = std::fs::read(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(to_hex(&sha256(&content)))
+}
+
+/// Sign the commit, module, workspace, checksum and apply flags of a plan so entries
+/// cannot be swapped or have their mode or approval changed
+fn sign(key: &str, commit: Option<&str>, entry: &ManifestEntry) -> Vec<u8> {
+    mac(key, commit, entry).finalize().into_bytes().to_vec()
+}
+
+fn mac(key: &str, commit: Option<&str>, entry: &ManifestEntry) -> Hmac<Sha256> {
+    let message = format!(
+        "{}:{}:{}:{}:destroy={}:refresh_only={}:clean={}:approved={}",
+        commit.unwrap_or_default(),
+        entry.module,
+        entry.workspace,
+        entry.checksum.as_deref().unwrap_or_default(),
+        entry.destroy,
+        entry.refresh_only,
+        entry.clean,
+        entry.approved,
+    );
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    mac
+}
+
+/// SHA-256 digest of `data`
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::plan_manifest::PlanManifest;
+    use crate::utils::terraform_operations::PlanArtifacts;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sha256_vectors() {
+        assert_eq!(to_hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(to_hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    fn signed_manifest(plan_dir: &Path) -> PlanManifest {
+        std::fs::write(plan_dir.join("app.tfplan"), b"plan").unwrap();
+        let mut manifest = PlanManifest { commit: Some("abc123".to_string()), ..Default::default() };
+        manifest.add_pending("stacks/app", None, &PlanArtifacts::default());
+        manifest.entries[0].plan_file = Some("app.tfplan".to_string());
+        manifest.entries[0].checksum = Some(file_checksum(&plan_dir.join("app.tfplan")).unwrap());
+        sign_manifest(&mut manifest, Some("secret"));
+        manifest
+    }
+
+    #[test]
+    fn test_verify_entry_signature() {
+        let dir = TempDir::new().unwrap();
+        let plan_dir = dir.path().to_str().unwrap();
+        let manifest = signed_manifest(dir.path());
+
+        assert!(verify_entry(&manifest, &manifest.entries[0], plan_dir, Some("secret")).is_ok());
+        assert!(verify_entry(&manifest, &manifest.entries[0], plan_dir, Some("other")).is_err());
+        assert!(verify_entry(&manifest, &manifest.entries[0], plan_dir, None).is_err());
+    }
+
+    #[test]
+    fn test_tampered_flags_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let manifest = signed_manifest(dir.path());
+
+        let mut destroy = manifest.clone();
+        destroy.entries[0].destroy = true;
+        assert!(verify_signature(&destroy, &destroy.entries[0], Some("secret")).is_err());
+
+        let mut approved = manifest.clone();
+        approved.entries[0].approved = true;
+        assert!(verify_signatures(&approved, Some("secret")).is_err());
+
+        // Approving with the key re-signs the entry
+        sign_manifest(&mut approved, Some("secret"));
+        assert!(verify_signatures(&approved, Some("secret")).is_ok());
+    }
+}
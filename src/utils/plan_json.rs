@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A single resource change parsed from `terraform show -json` output
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceChange {
+    /// Resource address within the module, e.g. `aws_security_group.main`
+    pub address: String,
+    /// Resource type, e.g. `aws_security_group`
+    pub resource_type: String,
+    /// Planned actions, e.g. `["create"]`, `["delete", "create"]`, `["no-op"]`
+    pub actions: Vec<String>,
+    /// Provider-level identity of the real resource (ARN, ID or type/name), if known
+    pub identity: Option<String>,
+}
+
+impl ResourceChange {
+    /// Whether the change modifies real infrastructure
+    pub fn is_change(&self) -> bool {
+        self.actions.iter().any(|a| a != "no-op" && a != "read")
+    }
+
+    /// Single action name for the change: create, update, delete or replace
+    pub fn action(&self) -> &str {
+        let creates = self.actions.iter().any(|a| a == "create");
+        let deletes = self.actions.iter().any(|a| a == "delete");
+        match (creates, deletes) {
+            (true, true) => "replace",
+            (true, false) => "create",
+            (false, true) => "delete",
+            (false, false) if self.actions.iter().any(|a| a == "update") => "update",
+            _ => "no-op",
+        }
+    }
+}
+
+/// A changed resource and its planned action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceAction {
+    pub address: String,
+    /// One of create, update, delete or replace
+    pub action: String,
+}
+
+/// Add/change/destroy counts for a plan, counted the same way terraform does
+/// (a replacement counts as one add and one destroy)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlanSummary {
+    pub add: usize,
+    pub change: usize,
+    pub destroy: usize,
+    /// Every resource the plan modifies
+    pub resources: Vec<ResourceAction>,
+}
+
+impl PlanSummary {
+    /// Summarize parsed resource changes, ignoring no-op and read actions
+    pub fn from_changes(changes: &[ResourceChange]) -> Self {
+        let mut summary = PlanSummary::default();
+
+        for change in changes.iter().filter(|c| c.is_change()) {
+            let action = change.action();
+            match action {
+                "create" => summary.add += 1,
+                "update" => summary.change += 1,
+                "delete" => summary.destroy += 1,
+                "replace" => {
+                    summary.add += 1;
+                    summary.destroy += 1;
+                }
+                _ => continue,
+            }
+            summary.resources.push(ResourceAction {
+                address: change.address.clone(),
+                action: action.to_string(),
+            });
+        }
+
+        summary
+    }
+
+    /// Whether the plan modifies any resources
+    pub fn has_changes(&self) -> bool {
+        !self.resources.is_empty()
+    }
+
+    /// Addresses of resources the plan deletes, including replacements
+    pub fn destroyed(&self) -> Vec<&str> {
+        self.resources.iter()
+            .filter(|r| r.action == "delete" || r.action == "replace")
+            .map(|r| r.address.as_str())
+            .collect()
+    }
+
+    /// Terraform-style one line summary, e.g. `2 to add, 1 to change, 0 to destroy`
+    pub fn headline(&self) -> String {
+        format!("{} to add, {} to change, {} to destroy", self.add, self.change, self.destroy)
+    }
+
+    /// Markdown section listing the counts and changed addresses
+    pub fn to_markdown(&self) -> String {
+        let mut content = format!("## Summary\n\n**{}**\n\n", self.headline());
+        for resource in &self.resources {
+            content.push_str(&format!("- {} `{}`\n", resource.action, resource.address));
+        }
+        if self.has_changes() {
+            content.push('\n');
+        }
+        content
+    }
+}
+
+/// Two or more plans touching the same real resource
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceConflict {
+    /// Shared resource identity (ARN, ID or type/name)
+    pub identity: String,
+    /// Plans involved, as `label: address` strings
+    pub owners: Vec<String>,
+}
+
+/// Parse resource changes from a JSON plan document
+pub fn parse_plan_json(content: &str) -> Result<Vec<ResourceChange>, String> {
+    let plan: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| format!("Failed to parse plan JSON: {}", e))?;
+
+    let changes = plan["resource_changes"]
+        .as_array()
+        .map(|changes| {
+            changes.iter()
+                .map(|change| ResourceChange {
+                    address: change["address"].as_str().unwrap_or_default().to_string(),
+                    resource_type: change["type"].as_str().unwrap_or_default().to_string(),
+                    actions: change["change"]["actions"]
+                        .as_array()
+                        .map(|a| a.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+                        .unwrap_or_default(),
+                    identity: resource_identity(change),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(changes)
+}
+
+/// Load and parse resource changes from a JSON plan file
+pub fn load_plan_changes(path: &Path) -> Result<Vec<ResourceChange>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read plan JSON {}: {}", path.display(), e))?;
+    parse_plan_json(&content)
+}
+
+/// Determine the identity of the real resource behind a change.
+/// Addresses are only meaningful inside one state, so resources are matched
+/// across plans by ARN, then ID, then resource type and name.
+fn resource_identity(change: &serde_json::Value) -> Option<String> {
+    let values = [&change["change"]["after"], &change["change"]["before"]];
+
+    for key in ["arn", "id"] {
+        for value in values {
+            if let Some(identity) = value[key].as_str().filter(|s| !s.is_empty()) {
+                return Some(identity.to_string());
+            }
+        }
+    }
+
+    let resource_type = change["type"].as_str()?;
+    values.iter()
+        .find_map(|value| value["name"].as_str().filter(|s| !s.is_empty()))
+        .map(|name| format!("{}/{}", resource_type, name))
+}
+
+/// Find resources modified by more than one plan.
+/// Each plan is given as a label (e.g. `module (workspace)`) and its parsed changes.
+pub fn find_conflicts(plans: &[(String, Vec<ResourceChange>)]) -> Vec<ResourceConflict> {
+    let mut owners_by_identity: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+
+    for (label, changes) in plans {
+        for change in changes.iter().filter(|c| c.is_change()) {
+            if let Some(identity) = &change.identity {
+                let owners = owners_by_identity.entry(identity.clone()).or_default();
+                if !owners.iter().any(|(owner, _)| owner == label) {
+                    owners.push((label.clone(), change.address.clone()));
+                }
+            }
+        }
+    }
+
+    owners_by_identity
+        .into_iter()
+        .filter(|(_, owners)| owners.len() > 1)
+        .map(|(identity, owners)| ResourceConflict {
+            identity,
+            owners: owners.into_iter().map(|(label, address)| format!("{}: {}", label, address)).collect(),
+        })
+        .collect()
+}
+
+/// Format conflicts for display in a warning box
+pub fn format_conflicts(conflicts: &[ResourceConflict]) -> String {
+    conflicts.iter()
+        .map(|conflict| {
+            let owners = conflict.owners.iter()
+                .map(|owner| format!("    - {}", owner))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("  • {}\n{}", conflict.identity, owners)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = r#"{
+        "resource_changes": [
+            {
+                "address": "aws_security_group.shared",
+                "type": "aws_security_group",
+                "change": {
+                    "actions": ["update"],
+                    "before": {"arn": "arn:aws:ec2:eu-west-1:123:security-group/sg-1", "name": "shared"},
+                    "after": {"arn": "arn:aws:ec2:eu-west-1:123:security-group/sg-1", "name": "shared"}
+                }
+            },
+            {
+                "address": "aws_route53_record.www",
+                "type": "aws_route53_record",
+                "change": {
+                    "actions": ["create"],
+                    "before": null,
+                    "after": {"name": "www.example.com"}
+                }
+            },
+            {
+                "address": "aws_s3_bucket.logs",
+                "type": "aws_s3_bucket",
+                "change": {
+                    "actions": ["no-op"],
+                    "before": {"arn": "arn:aws:s3:::logs"},
+                    "after": {"arn": "arn:aws:s3:::logs"}
+                }
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_plan_json() {
+        let changes = parse_plan_json(PLAN).unwrap();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].actions, vec!["update"]);
+        assert_eq!(changes[0].identity.as_deref(), Some("arn:aws:ec2:eu-west-1:123:security-group/sg-1"));
+        assert_eq!(changes[1].identity.as_deref(), Some("aws_route53_record/www.example.com"));
+        assert!(!changes[2].is_change());
+    }
+
+    #[test]
+    fn test_plan_summary() {
+        let mut changes = parse_plan_json(PLAN).unwrap();
+        changes.push(ResourceChange {
+            address: "aws_instance.web".to_string(),
+            resource_type: "aws_instance".to_string(),
+            actions: vec!["delete".to_string(), "create".to_string()],
+            identity: None,
+        });
+
+        let summary = PlanSummary::from_changes(&changes);
+        assert_eq!((summary.add, summary.change, summary.destroy), (2, 1, 1));
+        assert_eq!(summary.resources.len(), 3);
+        assert_eq!(summary.resources[2].action, "replace");
+        assert_eq!(summary.headline(), "2 to add, 1 to change, 1 to destroy");
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let changes = parse_plan_json(PLAN).unwrap();
+        let plans = vec![
+            ("networking (prod)".to_string(), changes.clone()),
+            ("dns (prod)".to_string(), changes[1..].to_vec()),
+        ];
+
+        let conflicts = find_conflicts(&plans);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].identity, "aws_route53_record/www.example.com");
+        assert_eq!(conflicts[0].owners.len(), 2);
+    }
+}
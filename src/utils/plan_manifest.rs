@@ -0,0 +1,376 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::utils::plan_json::PlanSummary;
+use crate::utils::terraform_operations::PlanArtifacts;
+
+/// File name of the manifest written into the plan output directory
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// File name of the manifest written into the apply report directory
+pub const APPLY_MANIFEST_FILE_NAME: &str = "apply-manifest.json";
+
+/// Add/change/destroy counts of a plan
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChangeCounts {
+    pub add: usize,
+    pub change: usize,
+    pub destroy: usize,
+}
+
+impl ChangeCounts {
+    /// "X added, Y changed, Z destroyed"
+    pub fn headline(&self) -> String {
+        format!("{} added, {} changed, {} destroyed", self.add, self.change, self.destroy)
+    }
+}
+
+impl std::ops::AddAssign for ChangeCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.add += other.add;
+        self.change += other.change;
+        self.destroy += other.destroy;
+    }
+}
+
+/// A single planned module/workspace pair recorded by `plan`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Module path relative to the directory solarboat was run from
+    pub module: String,
+    /// Workspace the plan was generated for
+    pub workspace: String,
+    /// Unix timestamp of when the plan was recorded
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    /// Add/change/destroy counts, when the plan could be summarized
+    #[serde(default)]
+    pub changes: Option<ChangeCounts>,
+    /// Plan report file name, relative to the plan directory
+    pub artifact: Option<String>,
+    /// Binary plan file name, relative to the plan directory
+    #[serde(default)]
+    pub plan_file: Option<String>,
+    /// JSON plan file name, relative to the plan directory
+    #[serde(default)]
+    pub plan_json: Option<String>,
+    /// Change summary file name, relative to the plan directory
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Whether the plan has been approved for apply
+    #[serde(default)]
+    pub approved: bool,
+    /// Who approved the plan
+    #[serde(default)]
+    pub approved_by: Option<String>,
+    /// Unix timestamp of the approval
+    #[serde(default)]
+    pub approved_at: Option<u64>,
+    /// SHA-256 checksum of the binary plan file
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// HMAC-SHA256 signature over the commit, module, workspace, checksum and apply flags
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// The plan reported no changes, so no artifacts were kept and there is nothing to apply
+    #[serde(default)]
+    pub clean: bool,
+    /// Destroy plan generated with `plan --destroy`
+    #[serde(default)]
+    pub destroy: bool,
+    /// Refresh-only plan generated with `plan --refresh-only`
+    #[serde(default)]
+    pub refresh_only: bool,
+}
+
+impl ManifestEntry {
+    /// Load the change summary saved alongside the plan, if one was recorded
+    pub fn load_summary(&self, plan_dir: &str) -> Result<Option<PlanSummary>, String> {
+        let Some(summary) = &self.summary else {
+            return Ok(None);
+        };
+        let path = Path::new(plan_dir).join(summary);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read plan summary {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse plan summary {}: {}", path.display(), e))
+    }
+}
+
+/// Manifest of plans pending approval, stored as `manifest.json` in the plan directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlanManifest {
+    /// Commit SHA the plans were generated from
+    #[serde(default)]
+    pub commit: Option<String>,
+    /// Unix timestamp of when the manifest was written
+    #[serde(default)]
+    pub generated_at: Option<u64>,
+    #[serde(default)]
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl PlanManifest {
+    /// Load the manifest from a plan directory
+    pub fn load(plan_dir: &str) -> Result<Self, String> {
+        let path = Path::new(plan_dir).join(MANIFEST_FILE_NAME);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read plan manifest {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse plan manifest {}: {}", path.display(), e))
+    }
+
+    /// Write the manifest into a plan directory
+    pub fn save(&mut self, plan_dir: &str) -> Result<(), String> {
+        std::fs::create_dir_all(plan_dir)
+            .map_err(|e| format!("Failed to create plan directory: {}", e))?;
+        let path = Path::new(plan_dir).join(MANIFEST_FILE_NAME);
+        self.generated_at = unix_timestamp();
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize plan manifest: {}", e))?;
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Failed to write plan manifest {}: {}", path.display(), e))
+    }
+
+    /// Record a pending (unapproved) plan for a module/workspace pair
+    pub fn add_pending(&mut self, module_path: &str, workspace: Option<&str>, artifacts: &PlanArtifacts) {
+        let module = module_key(module_path);
+        let workspace = workspace.unwrap_or("default").to_string();
+        self.entries.retain(|e| !(e.module == module && e.workspace == workspace));
+        self.entries.push(ManifestEntry {
+            module,
+            workspace,
+            timestamp: unix_timestamp(),
+            changes: artifacts.summary.as_ref().map(|s| ChangeCounts {
+                add: s.add,
+                change: s.change,
+                destroy: s.destroy,
+            }),
+            artifact: artifacts.report.clone(),
+            plan_file: artifacts.plan_file.clone(),
+            plan_json: artifacts.plan_json.clone(),
+            summary: artifacts.summary_file.clone(),
+            approved: false,
+            approved_by: None,
+            approved_at: None,
+            checksum: None,
+            signature: None,
+            clean: artifacts.clean,
+            destroy: false,
+            refresh_only: false,
+        });
+    }
+
+    /// Find the entry for a module/workspace pair
+    pub fn find(&self, module_path: &str, workspace: Option<&str>) -> Option<&ManifestEntry> {
+        let module = module_key(module_path);
+        let workspace = workspace.unwrap_or("default");
+        self.entries.iter().find(|e| e.module == module && e.workspace == workspace)
+    }
+
+    /// Check whether a module/workspace pair has an approved plan
+    pub fn is_approved(&self, module_path: &str, workspace: Option<&str>) -> bool {
+        self.find(module_path, workspace).map(|e| e.approved).unwrap_or(false)
+    }
+
+    /// Approve entries matching the given modules and workspace.
+    /// An empty module list matches every module. Returns the number of entries approved.
+    pub fn approve(&mut self, modules: &[String], workspace: Option<&str>, approver: Option<&str>) -> usize {
+        let timestamp = unix_timestamp();
+        let modules: Vec<String> = modules.iter().map(|m| module_key(m)).collect();
+
+        let mut count = 0;
+        for entry in &mut self.entries {
+            let module_matches = modules.is_empty() || modules.iter().any(|m| entry_matches_module(&entry.module, m));
+            let workspace_matches = workspace.map(|w| entry.workspace == w).unwrap_or(true);
+            if module_matches && workspace_matches && !entry.approved {
+                entry.approved = true;
+                entry.approved_by = approver.map(|a| a.to_string());
+                entry.approved_at = timestamp;
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+/// A single applied module/workspace pair recorded by `apply`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplyRecord {
+    pub module: String,
+    pub workspace: String,
+    /// Unix timestamp of when the apply finished
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    pub success: bool,
+    /// Apply report file name, relative to the report directory
+    #[serde(default)]
+    pub report: Option<String>,
+}
+
+/// History of applies, stored as `apply-manifest.json` in the apply report directory.
+/// Records accumulate across runs so earlier applies stay reviewable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApplyManifest {
+    #[serde(default)]
+    pub entries: Vec<ApplyRecord>,
+}
+
+impl ApplyManifest {
+    /// Load the manifest from a report directory; a missing manifest is empty
+    pub fn load_or_default(report_dir: &str) -> Result<Self, String> {
+        let path = Path::new(report_dir).join(APPLY_MANIFEST_FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read apply manifest {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse apply manifest {}: {}", path.display(), e))
+    }
+
+    /// Write the manifest into a report directory
+    pub fn save(&self, report_dir: &str) -> Result<(), String> {
+        std::fs::create_dir_all(report_dir)
+            .map_err(|e| format!("Failed to create report directory: {}", e))?;
+        let path = Path::new(report_dir).join(APPLY_MANIFEST_FILE_NAME);
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize apply manifest: {}", e))?;
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Failed to write apply manifest {}: {}", path.display(), e))
+    }
+
+    /// Record the outcome of an apply
+    pub fn record(&mut self, module_path: &str, workspace: Option<&str>, success: bool, report: Option<String>) {
+        self.entries.push(ApplyRecord {
+            module: module_key(module_path),
+            workspace: workspace.unwrap_or("default").to_string(),
+            timestamp: unix_timestamp(),
+            success,
+            report,
+        });
+    }
+}
+
+fn unix_timestamp() -> Option<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .ok()
+}
+
+/// Normalize a module path into the key stored in the manifest.
+/// Absolute paths under the current directory are made relative so manifests
+/// remain valid when plan and apply run on different machines.
+pub fn module_key(module_path: &str) -> String {
+    let path = Path::new(module_path);
+    if path.is_absolute() {
+        if let Ok(current_dir) = std::env::current_dir() {
+            if let Ok(relative) = path.strip_prefix(&current_dir) {
+                return relative.to_string_lossy().to_string();
+            }
+        }
+    }
+    module_path.trim_start_matches("./").trim_end_matches('/').to_string()
+}
+
+/// The module key as a relative path, safe to join onto another directory. Modules outside the
+/// current directory keep their absolute path without its root, and `..` becomes `__`. The current
+/// directory itself is keyed by its name.
+pub fn module_dir_key(module_path: &str) -> PathBuf {
+    let key: PathBuf = Path::new(&module_key(module_path))
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_os_string()),
+            Component::ParentDir => Some("__".into()),
+            _ => None,
+        })
+        .collect();
+    if key.as_os_str().is_empty() {
+        let current_dir = std::env::current_dir().ok();
+        return PathBuf::from(current_dir.as_deref().and_then(Path::file_name).unwrap_or("root".as_ref()));
+    }
+    key
+}
+
+/// Match a manifest module key against a user-supplied module path or name
+fn entry_matches_module(entry_module: &str, requested: &str) -> bool {
+    entry_module == requested || entry_module.ends_with(&format!("/{}", requested))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_module_dir_key() {
+        assert_eq!(module_dir_key("./envs/prod/network/"), PathBuf::from("envs/prod/network"));
+        assert_eq!(module_dir_key("/elsewhere/envs/prod/network"), PathBuf::from("elsewhere/envs/prod/network"));
+        assert_eq!(module_dir_key("../shared/network"), PathBuf::from("__/shared/network"));
+        assert!(module_dir_key(".").is_relative());
+    }
+    
+    #[test]
+    fn test_approve_matching_entries() {
+        let mut manifest = PlanManifest::default();
+        manifest.add_pending("stacks/networking", Some("prod"), &PlanArtifacts::default());
+        manifest.add_pending("stacks/networking", Some("dev"), &PlanArtifacts::default());
+        manifest.add_pending("stacks/webapp", None, &PlanArtifacts::default());
+
+        let approved = manifest.approve(&["networking".to_string()], Some("prod"), Some("alice"));
+        assert_eq!(approved, 1);
+        assert!(manifest.is_approved("stacks/networking", Some("prod")));
+        assert!(!manifest.is_approved("stacks/networking", Some("dev")));
+        assert!(!manifest.is_approved("stacks/webapp", None));
+
+        let approved = manifest.approve(&[], None, None);
+        assert_eq!(approved, 2);
+        assert!(manifest.is_approved("stacks/webapp", Some("default")));
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let plan_dir = temp_dir.path().to_str().unwrap();
+
+        let mut manifest = PlanManifest::default();
+        let artifacts = PlanArtifacts {
+            report: Some("networking-prod-1.tfplan.md".to_string()),
+            summary: Some(PlanSummary { add: 2, change: 1, destroy: 0, resources: Vec::new() }),
+            ..Default::default()
+        };
+        manifest.add_pending("stacks/networking", Some("prod"), &artifacts);
+        manifest.save(plan_dir).unwrap();
+
+        let loaded = PlanManifest::load(plan_dir).unwrap();
+        assert_eq!(loaded.entries.len(), 1);
+        assert_eq!(loaded.entries[0].artifact.as_deref(), Some("networking-prod-1.tfplan.md"));
+        assert_eq!(loaded.entries[0].changes, Some(ChangeCounts { add: 2, change: 1, destroy: 0 }));
+        assert!(loaded.entries[0].timestamp.is_some());
+        assert!(loaded.generated_at.is_some());
+        assert!(!loaded.entries[0].approved);
+    }
+
+    #[test]
+    fn test_apply_manifest_accumulates() {
+        let temp_dir = TempDir::new().unwrap();
+        let report_dir = temp_dir.path().to_str().unwrap();
+
+        let mut manifest = ApplyManifest::load_or_default(report_dir).unwrap();
+        manifest.record("stacks/networking", Some("prod"), true, Some("networking-prod-1.apply.md".to_string()));
+        manifest.save(report_dir).unwrap();
+
+        let mut manifest = ApplyManifest::load_or_default(report_dir).unwrap();
+        manifest.record("stacks/webapp", None, false, None);
+        manifest.save(report_dir).unwrap();
+
+        let loaded = ApplyManifest::load_or_default(report_dir).unwrap();
+        assert_eq!(loaded.entries.len(), 2);
+        assert_eq!(loaded.entries[0].report.as_deref(), Some("networking-prod-1.apply.md"));
+        assert_eq!(loaded.entries[1].workspace, "default");
+        assert!(!loaded.entries[1].success);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::utils::plan_json::PlanSummary;
+
+/// Maximum size of a GitHub issue/PR comment body, in characters
+pub const GITHUB_COMMENT_LIMIT: usize = 65_536;
+
+/// Room kept free in a single report for headers and tags around the plan output
+const REPORT_OVERHEAD: usize = 4_096;
+
+/// File name of the combined GitHub comment written into the plan directory
+pub const GITHUB_COMMENT_FILE_NAME: &str = "plan-comment.md";
+
+/// Render a single plan as GitHub-flavored markdown: a heading, a change count table,
+/// and collapsible sections for changed resources and the raw plan output
+pub fn render_github_report(module_name: &str, workspace: &str, summary: Option<&PlanSummary>, output_lines: &[String]) -> String {
+    let mut content = format!("### {} (workspace: {})\n\n", module_name, workspace);
+
+    match summary {
+        Some(summary) => {
+            content.push_str("| Add | Change | Destroy |\n|---:|---:|---:|\n");
+            content.push_str(&format!("| {} | {} | {} |\n\n", summary.add, summary.change, summary.destroy));
+
+            if summary.has_changes() {
+                content.push_str(&format!("<details><summary>Changed resources ({})</summary>\n\n", summary.resources.len()));
+                for resource in &summary.resources {
+                    content.push_str(&format!("- {} `{}`\n", resource.action, resource.address));
+                }
+                content.push_str("\n</details>\n\n");
+            }
+        }
+        None => content.push_str("_Change summary unavailable._\n\n"),
+    }
+
+    let (lines, omitted) = truncate_lines(output_lines, GITHUB_COMMENT_LIMIT - REPORT_OVERHEAD);
+    content.push_str("<details><summary>Plan output</summary>\n\n```\n");
+    for line in lines {
+        content.push_str(line);
+        content.push('\n');
+    }
+    content.push_str("```\n");
+    if omitted > 0 {
+        content.push_str(&format!("\n_Output truncated: {} line(s) omitted. See the full plan artifact._\n", omitted));
+    }
+    content.push_str("\n</details>\n");
+
+    content
+}
+
+/// Combine per-plan GitHub reports into one comment with a summary table at the top.
+/// Each plan is `(module, workspace, summary, report)`. Reports that would push the
+/// comment past GitHub's size limit are left out and listed at the end.
+pub fn render_github_comment(plans: &[(String, String, Option<PlanSummary>, String)]) -> String {
+    let mut content = String::from("## Terraform Plan\n\n| Module | Workspace | Add | Change | Destroy |\n|---|---|---:|---:|---:|\n");
+    for (module, workspace, summary, _) in plans {
+        match summary {
+            Some(s) => content.push_str(&format!("| {} | {} | {} | {} | {} |\n", module, workspace, s.add, s.change, s.destroy)),
+            None => content.push_str(&format!("| {} | {} | ? | ? | ? |\n", module, workspace)),
+        }
+    }
+    content.push('\n');
+
+    let mut omitted = Vec::new();
+    for (module, workspace, _, report) in plans {
+        // Keep room for the omission note
+        if omitted.is_empty() && content.len() + report.len() + REPORT_OVERHEAD <= GITHUB_COMMENT_LIMIT {
+            content.push_str(report);
+            content.push('\n');
+        } else {
+            omitted.push(format!("{} ({})", module, workspace));
+        }
+    }
+
+    if !omitted.is_empty() {
+        content.push_str(&format!(
+            "_{} plan(s) omitted to stay within GitHub's comment size limit: {}. See the plan artifacts._\n",
+            omitted.len(),
+            omitted.join(", ")
+        ));
+    }
+
+    content
+}
+
+/// File name of the static HTML report written into the plan directory
+pub const HTML_REPORT_FILE_NAME: &str = "report.html";
+
+/// One module/workspace plan shown in the HTML report
+#[derive(Debug, Clone)]
+pub struct HtmlPlan {
+    pub module: String,
+    pub workspace: String,
+    pub success: bool,
+    pub summary: Option<PlanSummary>,
+    pub output: Vec<String>,
+}
+
+const HTML_STYLE: &str = "
+body { margin: 0; font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Helvetica, Arial, sans-serif; color: #1f2328; }
+nav { position: fixed; top: 0; bottom: 0; left: 0; width: 300px; overflow-y: auto; background: #f6f8fa; border-right: 1px solid #d0d7de; padding: 16px; box-sizing: border-box; }
+nav a { display: block; padding: 4px 0; color: #0969da; text-decoration: none; }
+main { margin-left: 300px; padding: 24px; }
+table { border-collapse: collapse; margin: 8px 0 16px; }
+th, td { border: 1px solid #d0d7de; padding: 4px 12px; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+pre { background: #0d1117; color: #c9d1d9; padding: 12px; overflow-x: auto; font-size: 13px; }
+.badge { display: inline-block; border-radius: 8px; padding: 0 6px; font-size: 12px; margin-left: 4px; }
+.badge.add { background: #dafbe1; color: #1a7f37; }
+.badge.change { background: #fff8c5; color: #9a6700; }
+.badge.destroy { background: #ffebe9; color: #cf222e; }
+.badge.failed { background: #cf222e; color: #fff; }
+.line-add { color: #3fb950; }
+.line-destroy { color: #f85149; }
+.line-change { color: #d29922; }
+.line-replace { color: #db61a2; }
+.line-comment { color: #8b949e; }
+";
+
+/// Render all plans into one self-contained HTML page with per-module navigation,
+/// change counts and highlighted plan output
+pub fn render_html_report(plans: &[HtmlPlan]) -> String {
+    let total = |count: fn(&PlanSummary) -> usize| -> usize {
+        plans.iter().filter_map(|p| p.summary.as_ref()).map(count).sum()
+    };
+
+    let mut nav = String::new();
+    let mut sections = String::new();
+    for (index, plan) in plans.iter().enumerate() {
+        let anchor = format!("plan-{}", index);
+        let title = format!("{} ({})", escape_html(&plan.module), escape_html(&plan.workspace));
+
+        nav.push_str(&format!("<a href=\"#{}\">{}{}</a>\n", anchor, title, badges(plan)));
+
+        sections.push_str(&format!("<section id=\"{}\">\n<h2>{}{}</h2>\n", anchor, title, badges(plan)));
+        if let Some(summary) = &plan.summary {
+            sections.push_str(&format!(
+                "<table><tr><th>Add</th><th>Change</th><th>Destroy</th></tr><tr><td>{}</td><td>{}</td><td>{}</td></tr></table>\n",
+                summary.add, summary.change, summary.destroy
+            ));
+        }
+        sections.push_str("<pre>");
+        for line in &plan.output {
+            sections.push_str(&highlight_line(line));
+            sections.push('\n');
+        }
+        sections.push_str("</pre>\n</section>\n");
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Terraform Plan Report</title>\n<style>{}</style>\n</head>\n<body>\n<nav>\n<h3>Plans</h3>\n{}</nav>\n<main>\n<h1>Terraform Plan Report</h1>\n<p>{} plan(s): {} to add, {} to change, {} to destroy. {} failed.</p>\n{}</main>\n</body>\n</html>\n",
+        HTML_STYLE,
+        nav,
+        plans.len(),
+        total(|s| s.add),
+        total(|s| s.change),
+        total(|s| s.destroy),
+        plans.iter().filter(|p| !p.success).count(),
+        sections
+    )
+}
+
+fn badges(plan: &HtmlPlan) -> String {
+    if !plan.success {
+        return "<span class=\"badge failed\">failed</span>".to_string();
+    }
+    match &plan.summary {
+        Some(s) => format!(
+            "<span class=\"badge add\">+{}</span><span class=\"badge change\">~{}</span><span class=\"badge destroy\">-{}</span>",
+            s.add, s.change, s.destroy
+        ),
+        None => String::new(),
+    }
+}
+
+/// Escape a plan output line and wrap it in a class for its change marker
+fn highlight_line(line: &str) -> String {
+    let escaped = escape_html(line);
+    let trimmed = line.trim_start();
+    let class = if trimmed.starts_with("-/+") || trimmed.starts_with("+/-") {
+        "line-replace"
+    } else if trimmed.starts_with('+') {
+        "line-add"
+    } else if trimmed.starts_with('-') {
+        "line-destroy"
+    } else if trimmed.starts_with('~') {
+        "line-change"
+    } else if trimmed.starts_with('#') {
+        "line-comment"
+    } else {
+        return escaped;
+    };
+    format!("<span class=\"{}\">{}</span>", class, escaped)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Keep as many leading lines as fit in `budget` characters.
+/// Returns the kept lines and the number of omitted lines.
+fn truncate_lines(lines: &[String], budget: usize) -> (&[String], usize) {
+    let mut used = 0;
+    for (i, line) in lines.iter().enumerate() {
+        used += line.len() + 1;
+        if used > budget {
+            return (&lines[..i], lines.len() - i);
+        }
+    }
+    (lines, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_report_truncates_output() {
+        let lines: Vec<String> = (0..20_000).map(|i| format!("  # resource {} will be created", i)).collect();
+        let report = render_github_report("networking", "prod", None, &lines);
+
+        assert!(report.len() <= GITHUB_COMMENT_LIMIT);
+        assert!(report.contains("<details><summary>Plan output</summary>"));
+        assert!(report.contains("line(s) omitted"));
+    }
+
+    #[test]
+    fn test_github_comment_omits_oversized_plans() {
+        let summary = PlanSummary { add: 1, ..Default::default() };
+        let small = "### small\n".to_string();
+        let large = "x".repeat(GITHUB_COMMENT_LIMIT);
+        let plans = vec![
+            ("small".to_string(), "prod".to_string(), Some(summary), small),
+            ("large".to_string(), "prod".to_string(), None, large),
+        ];
+
+        let comment = render_github_comment(&plans);
+        assert!(comment.contains("| small | prod | 1 | 0 | 0 |"));
+        assert!(comment.contains("| large | prod | ? | ? | ? |"));
+        assert!(comment.contains("1 plan(s) omitted"));
+        assert!(comment.len() <= GITHUB_COMMENT_LIMIT);
+    }
+
+    #[test]
+    fn test_html_report() {
+        let plans = vec![HtmlPlan {
+            module: "networking".to_string(),
+            workspace: "prod".to_string(),
+            success: true,
+            summary: Some(PlanSummary { add: 1, destroy: 1, ..Default::default() }),
+            output: vec![
+                "  # aws_vpc.main will be created".to_string(),
+                "  + resource \"aws_vpc\" \"main\" {".to_string(),
+                "  - tags = { \"Name\" = \"<old>\" }".to_string(),
+            ],
+        }];
+
+        let html = render_html_report(&plans);
+        assert!(html.contains("<a href=\"#plan-0\">networking (prod)"));
+        assert!(html.contains("<span class=\"line-add\">  + resource &quot;aws_vpc&quot;"));
+        assert!(html.contains("&lt;old&gt;"));
+        assert!(html.contains("1 plan(s): 1 to add, 0 to change, 1 to destroy. 0 failed."));
+    }
+}
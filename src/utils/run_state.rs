@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::utils::terraform_operations::OperationResult;
+
+/// Directory holding the result of the last run of each command
+pub const RUN_STATE_DIR: &str = ".solarboat/runs";
+
+/// Outcome of a module/workspace pair in a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Succeeded,
+    Failed,
+    /// Never started because an earlier operation failed in fail-fast mode
+    Cancelled,
+}
+
+/// A module/workspace pair processed in a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunEntry {
+    /// Module path as it was queued
+    pub module: String,
+    pub workspace: String,
+    pub status: RunStatus,
+}
+
+/// Per-run results of `plan` or `apply`, stored as `.solarboat/runs/<command>.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunState {
+    pub command: String,
+    /// Unix timestamp of when the run finished
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    #[serde(default)]
+    pub entries: Vec<RunEntry>,
+}
+
+/// Path of the run state file for a command
+pub fn run_state_path(command: &str) -> PathBuf {
+    Path::new(RUN_STATE_DIR).join(format!("{}.json", command))
+}
+
+/// Path of the checkpoint file of a command's current run
+pub fn checkpoint_path(command: &str) -> PathBuf {
+    Path::new(RUN_STATE_DIR).join(format!("{}.checkpoint.jsonl", command))
+}
+
+impl RunEntry {
+    fn from_result(result: &OperationResult) -> Self {
+        Self {
+            module: result.module_path.clone(),
+            workspace: result.workspace.clone().unwrap_or_else(|| "default".to_string()),
+            status: if result.success {
+                RunStatus::Succeeded
+            } else if result.cancelled {
+                RunStatus::Cancelled
+            } else {
+                RunStatus::Failed
+            },
+        }
+    }
+}
+
+impl RunState {
+    /// Record the outcome of every operation of a run
+    pub fn from_results(command: &str, results: &[OperationResult]) -> Self {
+        Self {
+            command: command.to_string(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).ok(),
+            entries: results.iter().map(RunEntry::from_result).collect(),
+        }
+    }
+
+    /// Load the operations an interrupted run of a command completed, from its checkpoint file
+    pub fn load_checkpoint(command: &str) -> Result<Self, String> {
+        let path = checkpoint_path(command);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("No {} checkpoint to resume ({}): {}", command, path.display(), e))?;
+        let entries = content.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| format!("Failed to parse checkpoint {}: {}", path.display(), e)))
+            .collect::<Result<Vec<RunEntry>, String>>()?;
+        Ok(Self {
+            command: command.to_string(),
+            timestamp: None,
+            entries,
+        })
+    }
+
+    /// Load the last run of a command
+    pub fn load(command: &str) -> Result<Self, String> {
+        let path = run_state_path(command);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("No previous {} run recorded ({}): {}", command, path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse run state {}: {}", path.display(), e))
+    }
+
+    /// Write the run state for its command
+    pub fn save(&self) -> Result<(), String> {
+        let path = run_state_path(&self.command);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize run state: {}", e))?;
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Failed to write run state {}: {}", path.display(), e))
+    }
+
+    /// Modules with at least one failed or cancelled workspace, in run order
+    pub fn failed_modules(&self) -> Vec<String> {
+        let mut modules: Vec<String> = Vec::new();
+        for entry in self.entries.iter().filter(|e| e.status != RunStatus::Succeeded) {
+            if !modules.contains(&entry.module) {
+                modules.push(entry.module.clone());
+            }
+        }
+        modules
+    }
+
+    /// Whether a module/workspace pair failed or was cancelled in this run. `None` means the default workspace.
+    pub fn is_failed(&self, module: &str, workspace: Option<&str>) -> bool {
+        self.has_status(module, workspace, RunStatus::Failed) || self.has_status(module, workspace, RunStatus::Cancelled)
+    }
+
+    /// Whether a module/workspace pair succeeded in this run. `None` means the default workspace.
+    pub fn is_succeeded(&self, module: &str, workspace: Option<&str>) -> bool {
+        self.has_status(module, workspace, RunStatus::Succeeded)
+    }
+
+    fn has_status(&self, module: &str, workspace: Option<&str>, status: RunStatus) -> bool {
+        let workspace = workspace.unwrap_or("default");
+        self.entries.iter().any(|e| e.module == module && e.workspace == workspace && e.status == status)
+    }
+}
+
+/// Append-only record of the operations a run has completed, written as each one finishes
+/// so an interrupted run can be resumed
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl Checkpoint {
+    /// Open the checkpoint of a command's run. Unless resuming, earlier entries are discarded.
+    pub fn open(command: &str, resume: bool) -> Result<Self, String> {
+        let path = checkpoint_path(command);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        if !resume {
+            std::fs::write(&path, "")
+                .map_err(|e| format!("Failed to reset checkpoint {}: {}", path.display(), e))?;
+        }
+        Ok(Self { path, lock: Mutex::new(()) })
+    }
+
+    /// Append a finished operation
+    pub fn record(&self, result: &OperationResult) -> Result<(), String> {
+        let line = serde_json::to_string(&RunEntry::from_result(result))
+            .map_err(|e| format!("Failed to serialize checkpoint entry: {}", e))?;
+        let _guard = self.lock.lock().map_err(|_| "Checkpoint lock poisoned".to_string())?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open checkpoint {}: {}", self.path.display(), e))?;
+        writeln!(file, "{}", line)
+            .map_err(|e| format!("Failed to write checkpoint {}: {}", self.path.display(), e))
+    }
+
+    /// Delete a command's checkpoint once its run no longer needs resuming
+    pub fn clear(command: &str) {
+        let _ = std::fs::remove_file(checkpoint_path(command));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_entries() {
+        let entry = |module: &str, workspace: &str, status| RunEntry {
+            module: module.to_string(),
+            workspace: workspace.to_string(),
+            status,
+        };
+        let state = RunState {
+            command: "plan".to_string(),
+            timestamp: None,
+            entries: vec![
+                entry("stacks/networking", "prod", RunStatus::Failed),
+                entry("stacks/networking", "dev", RunStatus::Succeeded),
+                entry("stacks/database", "default", RunStatus::Succeeded),
+                entry("stacks/networking", "staging", RunStatus::Failed),
+                entry("stacks/compute", "default", RunStatus::Cancelled),
+            ],
+        };
+
+        assert_eq!(state.failed_modules(), vec!["stacks/networking", "stacks/compute"]);
+        assert!(state.is_failed("stacks/compute", None));
+        assert!(state.is_failed("stacks/networking", Some("prod")));
+        assert!(!state.is_failed("stacks/networking", Some("dev")));
+        assert!(!state.is_failed("stacks/database", None));
+    }
+
+    #[test]
+    fn test_checkpoint_entries() {
+        let state = RunState {
+            command: "apply".to_string(),
+            timestamp: None,
+            entries: vec![
+                serde_json::from_str(r#"{"module":"stacks/networking","workspace":"prod","status":"succeeded"}"#).unwrap(),
+                serde_json::from_str(r#"{"module":"stacks/networking","workspace":"dev","status":"failed"}"#).unwrap(),
+            ],
+        };
+
+        assert!(state.is_succeeded("stacks/networking", Some("prod")));
+        assert!(!state.is_succeeded("stacks/networking", Some("dev")));
+        assert!(!state.is_succeeded("stacks/database", None));
+    }
+}
@@ -1,38 +1,687 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::process::Command;
+use std::time::Duration;
+use crate::graph::{ModuleGraph, ModuleNode};
 use crate::utils::logger;
-use crate::utils::error::{SolarboatError, SafeOperations};
+use crate::utils::lockfile::{self, block_body, ProviderRequirement};
+use crate::utils::ci_env::PipelineContext;
+use crate::utils::git;
+use crate::utils::plan_integrity::sha256;
+use crate::utils::module_cache::{self, ModuleCache};
+use crate::utils::error::SolarboatError;
+
+/// Compile a regex once, on first use, instead of on every call of the parsers run for each module file
+macro_rules! cached_regex {
+    ($pattern:expr) => {{
+        static REGEX: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+        REGEX.get_or_init(|| Regex::new($pattern).unwrap())
+    }};
+}
+
+/// What a module's files declare, from a single parse of each file. Cached between runs.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ParsedModule {
+    /// Whether the module declares a backend, calls modules or holds local state
+    pub is_stateful: bool,
+    /// Why the module is stateful or stateless
+    pub classification: Classification,
+    /// Local module directories the module's `module` blocks source
+    pub sources: Vec<String>,
+    /// Non-terraform files read by the module through file(), templatefile() or a local_file data source
+    pub referenced_files: Vec<PathBuf>,
+    /// States the module's backend writes to
+    pub states: Vec<StateAddress>,
+    /// The module's backend or cloud block, if it declares one
+    pub backend: Option<BackendInfo>,
+    /// Remote states the module reads through terraform_remote_state
+    pub remote_states: Vec<StateAddress>,
+    /// Terraform version constraint set with `required_version`
+    pub required_version: Option<String>,
+    /// Providers declared in `required_providers`, by name
+    pub providers: Vec<ProviderRequirement>,
+}
+
+impl ParsedModule {
+    /// Parse a module from the contents of its `.tf` files and its partial backend configurations
+    pub fn parse(dir: &Path, contents: &[String], backend_configs: &[String], has_local_state: bool) -> Self {
+        let dir_str = dir.to_string_lossy();
+        let classification = match state_declaration(contents) {
+            Some(declaration) => declaration,
+            None if has_local_state => Classification::LocalState,
+            None => Classification::NoState,
+        };
+        let mut parsed = Self {
+            is_stateful: classification != Classification::NoState,
+            classification,
+            states: find_backend_states(contents, backend_configs, dir),
+            backend: BackendInfo::parse(contents, backend_configs),
+            ..Default::default()
+        };
+        for content in contents {
+            parsed.sources.extend(find_module_dependencies(content, &dir_str));
+            parsed.referenced_files.extend(find_referenced_files(content, dir));
+            parsed.remote_states.extend(find_remote_states(content, dir));
+            parsed.required_version = parsed.required_version.or_else(|| find_required_version(content));
+            parsed.providers.extend(lockfile::find_provider_requirements(content));
+        }
+        parsed.providers.sort();
+        parsed.providers.dedup();
+        parsed
+    }
+}
+
+/// Why a module is stateful or stateless
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Classification {
+    /// Stateful: declares a `backend` or `cloud` block
+    Backend,
+    /// Stateful: calls other modules
+    ModuleCalls,
+    /// Stateful: holds a local `.tfstate` file
+    LocalState,
+    /// Stateless: none of the above
+    #[default]
+    NoState,
+    /// Stateful: listed in `root_modules`
+    RootModule,
+    /// Stateless: only sourced by the modules listed in `root_modules`
+    NotRootModule,
+    /// Set with `stateful` in the configuration
+    Configured,
+}
+
+impl std::fmt::Display for Classification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Classification::Backend => "backend or cloud block found",
+            Classification::ModuleCalls => "module blocks found",
+            Classification::LocalState => "local .tfstate file found",
+            Classification::NoState => "no backend, module blocks or .tfstate file",
+            Classification::RootModule => "listed in root_modules",
+            Classification::NotRootModule => "not listed in root_modules",
+            Classification::Configured => "set in the configuration",
+        })
+    }
+}
 
+/// A module in the dependency graph, linked by `build_dependency_graph`
 #[derive(Debug, Default)]
 pub struct Module {
+    parsed: ParsedModule,
     depends_on: Vec<String>,
     used_by: Vec<String>,
-    is_stateful: bool,
 }
 
-/// Cleaner version of get_changed_modules with better output
-pub fn get_changed_modules_clean(root_dir: &str, all: bool, default_branch: &str, recent_commits: u32) -> Result<Vec<String>, String> {
-    let mut modules = HashMap::new();
+/// Which changed files count as module changes: files with a tracked suffix, and files modules reference
+#[derive(Debug, Default)]
+pub struct TrackedFiles {
+    extensions: Vec<String>,
+    referenced: Vec<PathBuf>,
+    /// Diff inside changed submodules instead of counting every file in them as changed
+    recurse_submodules: bool,
+    /// Repository-relative paths git diffs are limited to, none for the whole repository
+    pathspecs: Vec<String>,
+}
+
+impl TrackedFiles {
+    /// Whether a changed file, given relative to the repository or as an absolute path, is tracked
+    pub fn matches(&self, path: &str) -> bool {
+        is_tracked_file(path, &self.extensions) || self.referenced.iter().any(|file| file.ends_with(path))
+    }
+}
+
+/// Stateful module whose directory was deleted, with the last commit that still contained it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemovedModule {
+    /// Module directory relative to the repository root
+    pub path: String,
+    pub commit: String,
+}
+
+impl RemovedModule {
+    /// `path (last in <commit>)` for reports
+    pub fn describe(&self) -> String {
+        format!("{} (last in {})", self.path, &self.commit[..self.commit.len().min(12)])
+    }
+}
+
+/// Why change detection selected a module. Paths are relative to the current directory.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectionReason {
+    /// A file in the module's directory changed
+    Changed(String),
+    /// A file the module reads from outside its directory changed
+    Referenced(String),
+    /// A file changed in a stateless module the module calls
+    StatelessDependency { module: String, file: String },
+    /// The module consumes a changed module, through this chain of modules (`--propagate-dependents`)
+    Dependent(Vec<String>),
+}
+
+/// What change detection found besides the selected modules
+#[derive(Debug, Default)]
+pub struct DetectionReport {
+    /// Stateful modules whose directories were deleted
+    pub removed: Vec<RemovedModule>,
+    /// Why each selected module was included, in selection order
+    pub reasons: Vec<(String, Vec<SelectionReason>)>,
+    /// Every discovered module, with whether it is stateful and why, its backend and its dependencies
+    pub graph: ModuleGraph,
+    /// States written by more than one module
+    pub shared_states: Vec<(StateAddress, Vec<String>)>,
+}
+
+impl DetectionReport {
+    fn record_graph(&mut self, modules: &HashMap<String, Module>) {
+        self.graph = module_graph(modules);
+        self.shared_states = find_shared_states(modules);
+    }
+
+    fn record(&mut self, root_dir: &str, changed_files: &[String], modules: &HashMap<String, Module>, affected_modules: &[String]) {
+        self.removed.extend(find_removed_modules(root_dir, changed_files));
+        self.record_reasons(changed_files, modules, affected_modules);
+    }
+
+    fn record_reasons(&mut self, changed_files: &[String], modules: &HashMap<String, Module>, affected_modules: &[String]) {
+        for module_path in affected_modules {
+            self.reasons.push((module_path.clone(), selection_reasons(module_path, changed_files, modules, affected_modules)));
+        }
+    }
+}
+
+/// A module selected by change detection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannedModule {
+    /// Absolute module directory
+    pub path: String,
+    pub stateful: bool,
+    /// Why the module is stateful or stateless
+    pub classification: Classification,
+    /// Terraform version constraint set with `required_version`
+    pub required_version: Option<String>,
+    /// Providers declared in `required_providers`
+    pub providers: Vec<ProviderRequirement>,
+    /// Why the module was selected, empty when every module was selected
+    pub triggers: Vec<SelectionReason>,
+    /// Modules it depends on
+    pub depends_on: Vec<String>,
+    /// Modules depending on it
+    pub used_by: Vec<String>,
+}
+
+/// Result of change detection: the selected modules, in selection order, and what else detection found
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    pub modules: Vec<ScannedModule>,
+    pub details: DetectionReport,
+}
+
+impl ScanReport {
+    fn new(paths: Vec<String>, details: DetectionReport) -> Self {
+        let modules = paths.into_iter()
+            .map(|path| {
+                let node = details.graph.module(&path).cloned().unwrap_or_default();
+                ScannedModule {
+                    stateful: node.stateful,
+                    classification: node.classification,
+                    required_version: node.required_version,
+                    providers: node.providers,
+                    triggers: details.reasons.iter()
+                        .filter(|(module, _)| *module == path)
+                        .flat_map(|(_, reasons)| reasons.iter().cloned())
+                        .collect(),
+                    depends_on: details.graph.dependencies(&path).map(str::to_string).collect(),
+                    used_by: details.graph.dependents(&path).map(str::to_string).collect(),
+                    path,
+                }
+            })
+            .collect();
+        Self { modules, details }
+    }
+
+    /// Paths of the selected modules, in selection order
+    pub fn paths(&self) -> Vec<String> {
+        self.modules.iter().map(|module| module.path.clone()).collect()
+    }
+
+    /// The selected module paths and the detection details, for callers that only need paths
+    pub fn into_parts(self) -> (Vec<String>, DetectionReport) {
+        let paths = self.paths();
+        (paths, self.details)
+    }
+}
+
+/// Corrections to the dependency graph from the configuration, by absolute module path
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphOverrides {
+    /// (module, dependency) pairs declared with `depends_on`
+    pub dependencies: Vec<(String, String)>,
+    /// Statefulness set with `stateful`, replacing the classification from the module's files
+    pub stateful: Vec<(String, bool)>,
+}
+
+/// Exact commits to diff for change detection, overriding branch, merge-base and PR detection
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompareRefs {
+    pub base: String,
+    pub head: String,
+}
+
+impl CompareRefs {
+    /// Build the refs to compare from `--base-ref`/`--head-ref`/`--since`. The head defaults to HEAD;
+    /// `--since` uses the newest commit before the cutoff as the base. Without a base or window,
+    /// detection falls back to its usual heuristics.
+    pub fn from_args(base: Option<&str>, head: Option<&str>, since: Option<&str>) -> Result<Option<Self>, String> {
+        let head = head.unwrap_or("HEAD").to_string();
+        match (base, since) {
+            (Some(_), Some(_)) => Err("--since cannot be combined with --base-ref".to_string()),
+            (Some(base), None) => Ok(Some(Self { base: base.to_string(), head })),
+            (None, Some(since)) => Ok(Some(Self { base: commit_before(".", &head, since)?, head })),
+            (None, None) if head != "HEAD" => Err("--head-ref requires --base-ref or --since".to_string()),
+            (None, None) => Ok(None),
+        }
+    }
+}
 
-    // Always discover modules from the root directory
+/// One of `count` stable buckets of the selected modules, from `--shard K/N`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shard {
+    /// Bucket to keep, from 1 to `count`
+    pub index: u32,
+    pub count: u32,
+}
+
+impl Shard {
+    /// Parse `K/N`, with 1 <= K <= N
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid shard '{}': expected K/N with 1 <= K <= N, e.g. 2/4", value);
+        let (index, count) = value.split_once('/').ok_or_else(invalid)?;
+        let index: u32 = index.trim().parse().map_err(|_| invalid())?;
+        let count: u32 = count.trim().parse().map_err(|_| invalid())?;
+        if index == 0 || index > count {
+            return Err(invalid());
+        }
+        Ok(Self { index, count })
+    }
+
+    /// Keep the modules in this shard. A module's bucket comes from a hash of its repository-relative path,
+    /// so every runner computes the same split. With a `graph`, selected modules linked by its edges form
+    /// a group that lands in the bucket of its first path.
+    pub fn select(&self, modules: Vec<String>, graph: Option<&ModuleGraph>) -> Vec<String> {
+        fn find(groups: &mut [usize], mut i: usize) -> usize {
+            while groups[i] != i {
+                groups[i] = groups[groups[i]];
+                i = groups[i];
+            }
+            i
+        }
+
+        let mut groups: Vec<usize> = (0..modules.len()).collect();
+        let positions: HashMap<&str, usize> = modules.iter().enumerate().map(|(i, module)| (module.as_str(), i)).collect();
+        for (module, dependency) in graph.into_iter().flat_map(ModuleGraph::edges) {
+            if let (Some(&a), Some(&b)) = (positions.get(module), positions.get(dependency)) {
+                let (a, b) = (find(&mut groups, a), find(&mut groups, b));
+                groups[a] = b;
+            }
+        }
+
+        let keys: Vec<String> = modules.iter().map(|module| repo_relative_path(module)).collect();
+        let roots: Vec<usize> = (0..modules.len()).map(|i| find(&mut groups, i)).collect();
+        let mut group_keys: HashMap<usize, &str> = HashMap::new();
+        for (key, root) in keys.iter().zip(&roots) {
+            let group_key = group_keys.entry(*root).or_insert(key);
+            if key.as_str() < *group_key {
+                *group_key = key;
+            }
+        }
+        let in_shard = |root: &usize| {
+            let hash = sha256(group_keys[root].as_bytes());
+            let bucket = u64::from_be_bytes(hash[..8].try_into().unwrap()) % u64::from(self.count);
+            bucket == u64::from(self.index - 1)
+        };
+        modules.into_iter().zip(&roots).filter(|(_, root)| in_shard(root)).map(|(module, _)| module).collect()
+    }
+}
+
+/// A way of finding changed files. Each environment tries the strategies that apply to it in a fixed order
+/// (pipeline commits, merge commit, merge base, recent commits, uncommitted changes, reference) until one finds changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionStrategy {
+    /// Base and head commits supplied by the CI pipeline
+    PipelineCommits,
+    /// In CI on the default branch, a merge or squash commit of a single pull request against its first parent
+    MergeCommit,
+    /// Merge base of HEAD and the default branch
+    MergeBase,
+    /// The last few commits
+    RecentCommits,
+    /// Staged, unstaged and untracked changes
+    Uncommitted,
+    /// The last tag, or the newest commit older than the reference window
+    Reference,
+}
+
+impl DetectionStrategy {
+    pub const ALL: [DetectionStrategy; 6] = [
+        DetectionStrategy::PipelineCommits,
+        DetectionStrategy::MergeCommit,
+        DetectionStrategy::MergeBase,
+        DetectionStrategy::RecentCommits,
+        DetectionStrategy::Uncommitted,
+        DetectionStrategy::Reference,
+    ];
+
+    /// Name of the strategy as used in `solarboat.json`
+    pub fn name(&self) -> &'static str {
+        match self {
+            DetectionStrategy::PipelineCommits => "pipeline_commits",
+            DetectionStrategy::MergeCommit => "merge_commit",
+            DetectionStrategy::MergeBase => "merge_base",
+            DetectionStrategy::RecentCommits => "recent_commits",
+            DetectionStrategy::Uncommitted => "uncommitted",
+            DetectionStrategy::Reference => "reference",
+        }
+    }
+}
+
+/// Recent commits checked in a pipeline when its commits and the merge base find no changes
+pub const DEFAULT_PIPELINE_RECENT_COMMITS: u32 = 10;
+
+/// How far back the reference strategy looks when there is no tag
+pub const DEFAULT_REFERENCE_WINDOW: u64 = 24 * 60 * 60;
+
+/// Detection strategies that may be used, and their parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectionStrategies {
+    pub enabled: Vec<DetectionStrategy>,
+    pub pipeline_recent_commits: u32,
+    /// Seconds
+    pub reference_window: u64,
+}
+
+impl Default for DetectionStrategies {
+    fn default() -> Self {
+        Self {
+            enabled: DetectionStrategy::ALL.to_vec(),
+            pipeline_recent_commits: DEFAULT_PIPELINE_RECENT_COMMITS,
+            reference_window: DEFAULT_REFERENCE_WINDOW,
+        }
+    }
+}
+
+impl DetectionStrategies {
+    /// Whether a strategy may be used. Skipped strategies are logged at debug level.
+    pub fn allows(&self, strategy: DetectionStrategy) -> bool {
+        let allowed = self.enabled.contains(&strategy);
+        if !allowed {
+            logger::debug(&format!("Skipping the {} change detection strategy, which is disabled in the configuration", strategy.name()));
+        }
+        allowed
+    }
+}
+
+/// Seconds in a duration such as `30m`, `24h`, `7d` or `2w`
+pub fn parse_duration(duration: &str) -> Option<u64> {
+    let c = Regex::new(r"^(\d+)([smhdw])$").unwrap().captures(duration.trim())?;
+    let amount: u64 = c[1].parse().ok()?;
+    let unit = match &c[2] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => 7 * 24 * 60 * 60,
+    };
+    amount.checked_mul(unit)
+}
+
+/// Turn a `--since` window into a git date: a duration such as `30m`, `24h`, `7d` or `2w`,
+/// or a date such as `2025-01-01` (optionally with a time)
+pub fn parse_since(since: &str) -> Result<String, String> {
+    let date_re = Regex::new(r"^\d{4}-\d{2}-\d{2}([ T]\d{2}:\d{2}(:\d{2})?)?$").unwrap();
+    let since = since.trim();
+
+    if let Some(seconds) = parse_duration(since) {
+        Ok(format!("{} seconds ago", seconds))
+    } else if date_re.is_match(since) {
+        Ok(since.to_string())
+    } else {
+        Err(format!("Invalid --since value '{}'. Use a duration like 24h or 7d, or a date like 2025-01-01", since))
+    }
+}
+
+/// Newest commit reachable from `head` that is older than the `--since` cutoff
+fn commit_before(root_dir: &str, head: &str, since: &str) -> Result<String, String> {
+    let cutoff = parse_since(since)?;
+    let output = Command::new("git")
+        .args(["rev-list", "-1", &format!("--before={}", cutoff), head])
+        .current_dir(root_dir)
+        .output()
+        .map_err(|e| format!("Failed to execute git rev-list: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!("Failed to find commits before {}: {}", since, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if commit.is_empty() {
+        logger::info(&format!("No commits older than {}; comparing against the empty tree", since));
+        return Ok(git::EMPTY_TREE.to_string());
+    }
+    Ok(commit)
+}
+
+/// File suffixes that count as module changes unless `tracked_extensions` is configured
+pub const DEFAULT_TRACKED_EXTENSIONS: &[&str] = &[
+    ".tf",
+    ".tf.json",
+    ".tfvars",
+    ".tfvars.json",
+    ".tftest.hcl",
+    ".tftest.json",
+    ".tfmock.hcl",
+    ".terraform.lock.hcl",
+    crate::config::MODULE_CONFIG_FILE,
+];
+
+/// Suffixes to track: the configured ones, with a leading dot added where missing, or the defaults
+pub fn tracked_extensions(configured: Option<&[String]>) -> Vec<String> {
+    match configured {
+        Some(extensions) => extensions.iter()
+            .map(|ext| if ext.starts_with('.') { ext.clone() } else { format!(".{}", ext) })
+            .collect(),
+        None => DEFAULT_TRACKED_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+    }
+}
+
+/// Whether a changed file ends with one of the tracked suffixes
+pub fn is_tracked_file(path: &str, extensions: &[String]) -> bool {
+    extensions.iter().any(|ext| path.ends_with(ext.as_str()))
+}
+
+/// Stateful modules under `root_dir` that changes to `files` would select, directly or through the
+/// dependency graph, without consulting git for changes. Files are relative to the current directory; ones
+/// that are neither tracked nor referenced by a module are ignored. The report says why each module is affected.
+pub fn get_impacted_modules(
+    root_dir: &str,
+    files: &[String],
+    tracked_extensions: Option<&[String]>,
+    discovery: &DiscoveryOptions,
+    overrides: &GraphOverrides,
+    propagate_depth: Option<u32>,
+) -> Result<ScanReport, String> {
+    let mut report = DetectionReport::default();
+    let mut modules = discover_repo_modules(root_dir, discovery)?;
+    let tracked = TrackedFiles {
+        extensions: self::tracked_extensions(tracked_extensions),
+        referenced: modules.values().flat_map(|m| m.parsed.referenced_files.iter().cloned()).collect(),
+        recurse_submodules: false,
+        pathspecs: Vec::new(),
+    };
+    build_dependency_graph(&mut modules, overrides)?;
+    report.record_graph(&modules);
+
+    let current_dir = std::env::current_dir().map_err(|e| format!("Failed to get current directory: {}", e))?;
+    let mut changed_files = Vec::new();
+    for file in files {
+        let path = fs::canonicalize(file).unwrap_or_else(|_| current_dir.join(file));
+        let path = path.to_string_lossy().to_string();
+        if !tracked.matches(&path) {
+            logger::warn(&format!("{} is not a tracked terraform file or referenced by a module; ignoring it", file));
+            continue;
+        }
+        changed_files.push(path);
+    }
+
+    let affected_modules = process_changed_modules(&changed_files, &mut modules, propagate_depth)?;
+    report.record_reasons(&changed_files, &modules, &affected_modules);
+    Ok(ScanReport::new(scope_to(root_dir, affected_modules), report))
+}
+
+/// Cleaner version of get_changed_modules with better output.
+/// Only files ending with one of `tracked_extensions` (default: `DEFAULT_TRACKED_EXTENSIONS`) count as changes.
+/// Modules are found as described by `discovery`.
+/// With `propagate_depth`, stateful modules consuming a changed module are queued up to that many levels away.
+/// With `compare`, exactly the changes between its two commits are used; otherwise only the enabled `strategies` are tried.
+/// A changed submodule counts as a change to every file in it, or with `recurse_submodules` to the files
+/// that differ between its recorded commits. `overrides` adds dependencies and sets statefulness from the configuration.
+/// The report holds the selected modules with why they were selected, deleted stateful modules and the dependency graph.
+/// Modules are discovered across the whole repository, so dependencies outside `root_dir` are seen,
+/// and only modules under `root_dir` are returned.
+#[allow(clippy::too_many_arguments)]
+pub fn get_changed_modules_clean(
+    root_dir: &str,
+    all: bool,
+    default_branch: &str,
+    recent_commits: u32,
+    tracked_extensions: Option<&[String]>,
+    discovery: &DiscoveryOptions,
+    recurse_submodules: bool,
+    overrides: &GraphOverrides,
+    propagate_depth: Option<u32>,
+    compare: Option<&CompareRefs>,
+    strategies: &DetectionStrategies,
+) -> Result<ScanReport, String> {
+    let mut report = DetectionReport::default();
+    let modules = detect_changed_modules(root_dir, all, default_branch, recent_commits, tracked_extensions, discovery, recurse_submodules, overrides, propagate_depth, compare, strategies, &mut report)?;
+    Ok(ScanReport::new(scope_to(root_dir, modules), report))
+}
+
+/// Repository-relative paths whose changes can select a module under `root_dir`: `root_dir` itself and,
+/// outside it, the modules those modules depend on and the files they reference. Empty when `root_dir` is
+/// the repository root, so that the whole repository is diffed.
+fn scan_pathspecs(root_dir: &str, modules: &HashMap<String, Module>) -> Vec<String> {
+    let work_tree = repo_root(root_dir);
+    let scope = fs::canonicalize(root_dir).unwrap_or_else(|_| PathBuf::from(root_dir));
+    if scope == work_tree || !scope.starts_with(&work_tree) {
+        return Vec::new();
+    }
+
+    let mut queue: Vec<&str> = modules.keys()
+        .filter(|path| Path::new(path).starts_with(&scope))
+        .map(|path| path.as_str())
+        .collect();
+    let mut visited: HashSet<&str> = queue.iter().copied().collect();
+    let mut paths = vec![scope.clone()];
+    while let Some(path) = queue.pop() {
+        if !Path::new(path).starts_with(&scope) {
+            paths.push(PathBuf::from(path));
+        }
+        let module = &modules[path];
+        paths.extend(module.parsed.referenced_files.iter().filter(|file| !file.starts_with(&scope)).cloned());
+        for dependency in &module.depends_on {
+            if modules.contains_key(dependency) && visited.insert(dependency) {
+                queue.push(dependency);
+            }
+        }
+    }
+
+    let mut pathspecs: Vec<String> = paths.iter()
+        .filter_map(|path| path.strip_prefix(&work_tree).ok())
+        .map(|relative| containing_submodule(&work_tree, relative).to_string_lossy().to_string())
+        .collect();
+    pathspecs.sort();
+    pathspecs.dedup();
+    pathspecs
+}
+
+/// The submodule a repository-relative path is in, or the path itself outside submodules.
+/// git reports changes in a submodule as a change to the submodule, so diffs must include it whole.
+fn containing_submodule(work_tree: &Path, relative: &Path) -> PathBuf {
+    let mut current = PathBuf::new();
+    for component in relative.components() {
+        current.push(component);
+        if work_tree.join(&current).join(".git").exists() {
+            return current;
+        }
+    }
+    relative.to_path_buf()
+}
+
+/// Modules located under `root_dir`
+fn scope_to(root_dir: &str, modules: Vec<String>) -> Vec<String> {
+    let scope = fs::canonicalize(root_dir).unwrap_or_else(|_| PathBuf::from(root_dir));
+    modules.into_iter().filter(|module| Path::new(module).starts_with(&scope)).collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn detect_changed_modules(
+    root_dir: &str,
+    all: bool,
+    default_branch: &str,
+    recent_commits: u32,
+    tracked_extensions: Option<&[String]>,
+    discovery: &DiscoveryOptions,
+    recurse_submodules: bool,
+    overrides: &GraphOverrides,
+    propagate_depth: Option<u32>,
+    compare: Option<&CompareRefs>,
+    strategies: &DetectionStrategies,
+    report: &mut DetectionReport,
+) -> Result<Vec<String>, String> {
+    // Always discover modules from the repository root
     logger::dependency_graph_progress("Discovering modules...");
-    discover_modules(root_dir, &mut modules)?;
+    let mut modules = discover_repo_modules(root_dir, discovery)?;
     
     logger::dependency_graph_progress("Building dependency graph...");
-    build_dependency_graph(&mut modules)?;
+    build_dependency_graph(&mut modules, overrides)?;
+    report.record_graph(&modules);
+
+    let tracked = &TrackedFiles {
+        extensions: self::tracked_extensions(tracked_extensions),
+        referenced: modules.values().flat_map(|m| m.parsed.referenced_files.iter().cloned()).collect(),
+        recurse_submodules,
+        pathspecs: scan_pathspecs(root_dir, &modules),
+    };
 
     if all {
         // If all is true, return all stateful modules
         let stateful_modules: Vec<String> = modules
             .iter()
-            .filter(|(_, module)| module.is_stateful)
+            .filter(|(_, module)| module.parsed.is_stateful)
             .map(|(path, _)| path.clone())
             .collect();
         return Ok(stateful_modules);
     }
 
+    if let Some(refs) = compare {
+        logger::environment_detection("refs", &format!("Comparing {}..{} as requested", refs.base, refs.head));
+        verify_commit(".", &refs.base)?;
+        verify_commit(".", &refs.head)?;
+        let changed_files = get_changes_between_commits(".", &refs.base, &refs.head, tracked)?;
+        let affected_modules = process_changed_modules(&changed_files, &mut modules, propagate_depth)?;
+        report.record(root_dir, &changed_files, &modules, &affected_modules);
+
+        if affected_modules.is_empty() {
+            logger::info(&format!("No changes detected between {} and {}", refs.base, refs.head));
+        }
+
+        return Ok(affected_modules);
+    }
+
     // Check if we're on the main branch and handle accordingly
     let current_branch = get_current_branch(root_dir)?;
     let is_on_main = current_branch == default_branch;
@@ -40,23 +689,48 @@ pub fn get_changed_modules_clean(root_dir: &str, all: bool, default_branch: &str
     if is_on_main {
         logger::environment_detection("branch", &format!("Currently on {} branch - using enhanced change detection", current_branch));
         
-        if let Ok(pr_number) = std::env::var("SOLARBOAT_PR_NUMBER") {
-            if !pr_number.is_empty() {
-                logger::environment_detection("pipeline", &format!("Detected CD pipeline environment (PR #{})", pr_number));
-                let changed_files = get_cd_pipeline_changes(root_dir, &pr_number, default_branch)?;
-                let affected_modules = process_changed_modules(&changed_files, &mut modules)?;
-                
+        let pipeline = PipelineContext::detect();
+        if pipeline.has_changes_info() {
+            logger::environment_detection("pipeline", &format!("Detected CD pipeline environment ({})", pipeline.describe()));
+            logger::pipeline_info(
+                pipeline.provider.map(|p| p.name()).unwrap_or("custom"),
+                pipeline.pr_number.as_deref().unwrap_or("-"),
+                pipeline.base_commit.as_deref().unwrap_or("-"),
+                pipeline.head_commit.as_deref().unwrap_or("HEAD"),
+            );
+            let changed_files = get_cd_pipeline_changes(root_dir, &pipeline, default_branch, tracked, strategies)?;
+            let affected_modules = process_changed_modules(&changed_files, &mut modules, propagate_depth)?;
+            report.record(root_dir, &changed_files, &modules, &affected_modules);
+            
+            if affected_modules.is_empty() {
+                logger::info(&format!("No changes detected in {}", pipeline.describe()));
+            }
+            
+            return Ok(affected_modules);
+        }
+
+        // CI builds of a merged pull request don't need the pipeline to say which commits to compare
+        let in_ci = pipeline.provider.is_some() || std::env::var_os("CI").is_some();
+        if in_ci && strategies.allows(DetectionStrategy::MergeCommit) {
+            if let Some((parent, pr_number)) = merge_commit_parent(root_dir) {
+                let merged = pr_number.map(|n| format!("PR #{}", n)).unwrap_or_else(|| "a branch".to_string());
+                logger::environment_detection("merge", &format!("HEAD merges {} - comparing with its first parent", merged));
+                let changed_files = get_changes_between_commits(root_dir, &parent, "HEAD", tracked)?;
+                let affected_modules = process_changed_modules(&changed_files, &mut modules, propagate_depth)?;
+                report.record(root_dir, &changed_files, &modules, &affected_modules);
+
                 if affected_modules.is_empty() {
-                    logger::info(&format!("No changes detected in PR #{}", pr_number));
+                    logger::info(&format!("No changes detected in the merge of {}", merged));
                 }
-                
+
                 return Ok(affected_modules);
             }
         }
 
         logger::environment_detection("local", &format!("Running in local environment - checking last {} commits", recent_commits));
-        let changed_files = get_main_branch_changes_local_clean(root_dir, recent_commits)?;
-        let affected_modules = process_changed_modules(&changed_files, &mut modules)?;
+        let changed_files = get_main_branch_changes_local_clean(root_dir, recent_commits, tracked, strategies)?;
+        let affected_modules = process_changed_modules(&changed_files, &mut modules, propagate_depth)?;
+        report.record(root_dir, &changed_files, &modules, &affected_modules);
         
         // Show git analysis summary with actual affected modules count
         logger::git_analysis_summary(recent_commits as usize, changed_files.len(), affected_modules.len());
@@ -64,7 +738,7 @@ pub fn get_changed_modules_clean(root_dir: &str, all: bool, default_branch: &str
         // If no changes detected on main, provide helpful message
         if affected_modules.is_empty() {
             logger::info("No changes detected on main branch. This could mean:");
-            logger::info("  • No recent commits with .tf changes");
+            logger::info("  • No recent commits with terraform file changes");
             logger::info("  • Changes were already applied");
             logger::info("  • Use --all flag to process all modules");
         }
@@ -72,248 +746,953 @@ pub fn get_changed_modules_clean(root_dir: &str, all: bool, default_branch: &str
         return Ok(affected_modules);
     }
 
-    let changed_files = get_git_changed_files(".", default_branch)?;
-    let affected_modules = process_changed_modules(&changed_files, &mut modules)?;
+    let changed_files = get_git_changed_files(".", default_branch, tracked, strategies)?;
+    let affected_modules = process_changed_modules(&changed_files, &mut modules, propagate_depth)?;
+    report.record(root_dir, &changed_files, &modules, &affected_modules);
+    
+    Ok(affected_modules)
+}
 
-    if root_dir != "." {
-        logger::info(&format!("Filtering modules with path: {}", root_dir));
-        
-        let filtered_modules: Vec<String> = affected_modules
-            .into_iter()
-            .filter(|path| {
-                // Check if the path contains the root_dir
-                let contains_path = path.contains(&format!("/{}/", root_dir)) || 
-                                   path.ends_with(&format!("/{}", root_dir));
-                
-                contains_path
+/// Directory names discovery doesn't descend into: caches of downloaded modules, git metadata and vendored code
+pub const DEFAULT_EXCLUDED_DIRS: &[&str] = &[".terraform", ".terragrunt-cache", ".git", "node_modules", "vendor"];
+
+/// Directory names or repository-relative paths skipped during module discovery
+pub fn excluded_dirs(configured: Option<&[String]>) -> Vec<String> {
+    match configured {
+        Some(dirs) => dirs.iter().map(|dir| dir.trim_end_matches('/').to_string()).collect(),
+        None => DEFAULT_EXCLUDED_DIRS.iter().map(|dir| dir.to_string()).collect(),
+    }
+}
+
+/// How modules are found
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveryOptions {
+    /// Directory names or repository-relative paths the filesystem walk skips
+    pub excluded_dirs: Vec<String>,
+    /// Absolute paths or globs of the root modules; when set, only these (and the modules they source)
+    /// are loaded instead of walking the repository
+    pub root_modules: Option<Vec<String>>,
+    /// Absolute path globs of modules left out of the run altogether
+    pub ignore_modules: Vec<String>,
+    /// Descend into symlinked directories; each directory is still walked once, by its canonical path
+    pub follow_symlinks: bool,
+    /// How many directory levels below the repository root the walk descends, or None for no limit
+    pub max_depth: Option<usize>,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self { excluded_dirs: excluded_dirs(None), root_modules: None, ignore_modules: Vec::new(), follow_symlinks: true, max_depth: None }
+    }
+}
+
+/// The directory a subdirectory entry is walked as: symlinks are resolved to their target, or None when
+/// they are not followed or can't be resolved
+fn walked_dir(path: PathBuf, follow_symlinks: bool) -> Option<PathBuf> {
+    if !path.is_symlink() {
+        return Some(path);
+    }
+    if !follow_symlinks {
+        logger::debug(&format!("Skipping symlinked directory {}", relative_path(&path.to_string_lossy())));
+        return None;
+    }
+    fs::canonicalize(&path)
+        .inspect_err(|e| logger::debug(&format!("Skipping symlink {}: {}", relative_path(&path.to_string_lossy()), e)))
+        .ok()
+}
+
+/// Regex matching a whole path against a glob: `*` and `?` match within one path segment and
+/// `**` matches any number of segments, so `legacy/**` matches `legacy` itself and everything below it
+fn glob_regex(pattern: &str) -> Regex {
+    let mut regex = String::from("^");
+    let mut rest = pattern;
+    while let Some(c) = rest.chars().next() {
+        let (matched, len) = if rest.starts_with("**/") {
+            ("(?:.*/)?".to_string(), 3)
+        } else if rest == "/**" {
+            ("(?:/.*)?".to_string(), 3)
+        } else if rest.starts_with("**") {
+            (".*".to_string(), 2)
+        } else if c == '*' {
+            ("[^/]*".to_string(), 1)
+        } else if c == '?' {
+            ("[^/]".to_string(), 1)
+        } else {
+            (regex::escape(&c.to_string()), c.len_utf8())
+        };
+        regex.push_str(&matched);
+        rest = &rest[len..];
+    }
+    regex.push('$');
+    Regex::new(&regex).expect("escaped glob is a valid regex")
+}
+
+/// Keep the modules matching one of the `include` globs (all of them when there are none) and none of the
+/// `exclude` globs. Relative globs match paths relative to the current directory, absolute ones whole paths.
+pub fn filter_modules(modules: Vec<String>, include: &[String], exclude: &[String]) -> Vec<String> {
+    let compile = |patterns: &[String]| -> Vec<Regex> { patterns.iter().map(|pattern| glob_regex(pattern.trim_end_matches('/'))).collect() };
+    let (include, exclude) = (compile(include), compile(exclude));
+    let matches = |patterns: &[Regex], module: &str| {
+        let relative = relative_path(module);
+        patterns.iter().any(|pattern| pattern.is_match(module) || pattern.is_match(&relative))
+    };
+    modules.into_iter()
+        .filter(|module| include.is_empty() || matches(&include, module))
+        .filter(|module| !matches(&exclude, module))
+        .collect()
+}
+
+/// Find the modules under `root_dir`: directories holding `.tf` files. Each directory is read once, and
+/// directories matching `excluded_dirs` by name or by path relative to `root_dir` are not descended into.
+/// With `root_modules`, no walk happens: only the listed modules are loaded and they are the only stateful ones.
+/// Symlinked directories are followed unless `follow_symlinks` is off, and a directory reached again through
+/// a link is skipped, so link cycles end and modules are not found twice. The walk stops `max_depth` levels
+/// below `root_dir`. A module in an excluded directory or below the maximum depth is still found when a discovered module sources it.
+/// Modules matching `ignore_modules` are never loaded, even when sourced.
+/// Modules whose files are unchanged are taken from `cache` instead of being parsed again.
+pub fn discover_modules(root_dir: &str, discovery: &DiscoveryOptions, cache: &mut ModuleCache, modules: &mut HashMap<String, Module>) -> Result<(), String> {
+    let root = fs::canonicalize(root_dir).map_err(|e| format!("Failed to resolve {}: {}", root_dir, e))?;
+    let excluded = |dir: &Path| discovery.excluded_dirs.iter().any(|excluded| {
+        dir.file_name().is_some_and(|name| name == excluded.as_str()) || dir.strip_prefix(&root).is_ok_and(|relative| relative == Path::new(excluded))
+    });
+    let ignore_patterns: Vec<Regex> = discovery.ignore_modules.iter().map(|pattern| glob_regex(pattern)).collect();
+    let ignored = |dir: &Path| ignore_patterns.iter().any(|pattern| pattern.is_match(&dir.to_string_lossy()));
+
+    match &discovery.root_modules {
+        Some(patterns) => {
+            for pattern in patterns {
+                let dirs = expand_module_pattern(pattern, &excluded, discovery.follow_symlinks);
+                if dirs.is_empty() {
+                    logger::warn(&format!("Root module {} matches no directory", relative_path(pattern)));
+                }
+                for dir in dirs.into_iter().filter(|dir| !ignored(dir)) {
+                    match load_module(&dir, &dir_entries(&dir)?, cache) {
+                        Some(mut module) => {
+                            module.parsed.is_stateful = true;
+                            module.parsed.classification = Classification::RootModule;
+                            modules.entry(dir.to_string_lossy().to_string()).or_insert(module);
+                        }
+                        None => logger::warn(&format!("Root module {} has no .tf files", relative_path(&dir.to_string_lossy()))),
+                    }
+                }
+            }
+        }
+        None => {
+            let mut visited = HashSet::from([root.clone()]);
+            let mut pending = vec![(root.clone(), 0)];
+            while let Some((dir, depth)) = pending.pop() {
+                let entries = dir_entries(&dir)?;
+                if dir != root && !ignored(&dir) {
+                    if let Some(module) = load_module(&dir, &entries, cache) {
+                        modules.entry(dir.to_string_lossy().to_string()).or_insert(module);
+                    }
+                }
+                if discovery.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                    logger::debug(&format!("Not descending into {}: maximum discovery depth reached", relative_path(&dir.to_string_lossy())));
+                    continue;
+                }
+                for entry in entries.into_iter().filter(|path| path.is_dir() && !excluded(path)) {
+                    // Only symlinked directories can lead back to a directory already walked
+                    let Some(path) = walked_dir(entry.clone(), discovery.follow_symlinks) else {
+                        continue;
+                    };
+                    if visited.insert(path.clone()) {
+                        pending.push((path, depth + 1));
+                    } else {
+                        logger::debug(&format!("Skipping {}: {} was already walked",
+                            relative_path(&entry.to_string_lossy()), relative_path(&path.to_string_lossy())));
+                    }
+                }
+            }
+        }
+    }
+
+    // Modules sourced from excluded directories, from outside `root_dir` or by the listed root modules
+    let mut sourced: Vec<String> = modules.values().flat_map(|module| module.parsed.sources.iter().cloned()).collect();
+    while let Some(dir) = sourced.pop() {
+        if modules.contains_key(&dir) || !Path::new(&dir).is_dir() || ignored(Path::new(&dir)) {
+            continue;
+        }
+        let entries = dir_entries(Path::new(&dir))?;
+        if let Some(mut module) = load_module(Path::new(&dir), &entries, cache) {
+            if discovery.root_modules.is_some() {
+                module.parsed.is_stateful = false;
+                module.parsed.classification = Classification::NotRootModule;
+            }
+            sourced.extend(module.parsed.sources.iter().cloned());
+            modules.insert(dir, module);
+        }
+    }
+    Ok(())
+}
+
+/// Directories matching an absolute root module pattern. `*` and `?` match within a path segment and
+/// `**` matches any number of directories, except excluded ones, walking each directory once.
+fn expand_module_pattern(pattern: &str, excluded: &dyn Fn(&Path) -> bool, follow_symlinks: bool) -> Vec<PathBuf> {
+    let mut matches = vec![PathBuf::from("/")];
+    for segment in Path::new(pattern).components().filter_map(|c| match c {
+        std::path::Component::Normal(segment) => Some(segment.to_string_lossy().to_string()),
+        _ => None,
+    }) {
+        let children = |dir: &PathBuf| -> Vec<PathBuf> {
+            dir_entries(dir).unwrap_or_default().into_iter()
+                .filter(|path| path.is_dir() && !excluded(path) && (follow_symlinks || !path.is_symlink()))
+                .collect()
+        };
+        matches = if segment == "**" {
+            let mut below = Vec::new();
+            let mut visited = HashSet::new();
+            let mut pending = matches;
+            while let Some(dir) = pending.pop() {
+                if !visited.insert(fs::canonicalize(&dir).unwrap_or_else(|_| dir.clone())) {
+                    continue;
+                }
+                pending.extend(children(&dir));
+                below.push(dir);
+            }
+            below
+        } else if segment.contains(['*', '?']) {
+            let segment_re = glob_regex(&segment);
+            matches.iter()
+                .flat_map(children)
+                .filter(|path| path.file_name().is_some_and(|name| segment_re.is_match(&name.to_string_lossy())))
+                .collect()
+        } else {
+            matches.into_iter().map(|dir| dir.join(&segment)).filter(|path| path.is_dir()).collect()
+        };
+    }
+    let mut matches: Vec<PathBuf> = matches.into_iter().map(|dir| fs::canonicalize(&dir).unwrap_or(dir)).collect();
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+/// Discover the modules of the repository containing `root_dir`, reusing and updating its module cache
+fn discover_repo_modules(root_dir: &str, discovery: &DiscoveryOptions) -> Result<HashMap<String, Module>, String> {
+    let root = repo_root(root_dir);
+    let mut cache = ModuleCache::load(&root);
+    let mut modules = HashMap::new();
+    discover_modules(&root.to_string_lossy(), discovery, &mut cache, &mut modules)?;
+    if let Err(e) = cache.save() {
+        logger::debug(&format!("Module cache not saved: {}", e));
+    }
+    Ok(modules)
+}
+
+fn dir_entries(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))? {
+        entries.push(entry.map_err(|e| e.to_string())?.path());
+    }
+    Ok(entries)
+}
+
+/// Parse the module in `dir` from its directory entries, or None if it holds no `.tf` files.
+/// Each `.tf` file is read once, and only parsed when `cache` has nothing for its contents.
+fn load_module(dir: &Path, entries: &[PathBuf], cache: &mut ModuleCache) -> Option<Module> {
+    let has_extension = |path: &PathBuf, extension: &str| path.extension().is_some_and(|ext| ext == extension);
+    let read = |extension: &str| {
+        let mut files: Vec<&PathBuf> = entries.iter().filter(|path| has_extension(path, extension) && path.is_file()).collect();
+        files.sort();
+        files.iter()
+            .filter_map(|file| Some((file.file_name()?.to_string_lossy().to_string(), fs::read_to_string(file).ok()?)))
+            .collect::<Vec<(String, String)>>()
+    };
+    let tf_files = read("tf");
+    if tf_files.is_empty() {
+        return None;
+    }
+    let backend_files = read("tfbackend");
+    // Local state files make a module stateful, so their names are part of the hash
+    let state_files: Vec<(String, String)> = entries.iter()
+        .filter(|path| has_extension(path, "tfstate") && path.is_file())
+        .filter_map(|path| Some((path.file_name()?.to_string_lossy().to_string(), String::new())))
+        .collect();
+    let dir_str = dir.to_string_lossy().to_string();
+    let hash = module_cache::module_hash(&[tf_files.as_slice(), &backend_files, &state_files].concat());
+    let parsed = match cache.get(&dir_str, &hash) {
+        Some(parsed) => parsed,
+        None => {
+            let contents: Vec<String> = tf_files.into_iter().map(|(_, content)| content).collect();
+            let backend_configs: Vec<String> = backend_files.into_iter().map(|(_, content)| content).collect();
+            let parsed = ParsedModule::parse(dir, &contents, &backend_configs, !state_files.is_empty());
+            cache.insert(&dir_str, hash, &parsed);
+            parsed
+        }
+    };
+    Some(Module { parsed, ..Default::default() })
+}
+
+/// Read the backend of the module in `dir` without going through the module cache
+pub fn read_backend(dir: &Path) -> Option<BackendInfo> {
+    let entries = dir_entries(dir).ok()?;
+    load_module(dir, &entries, &mut ModuleCache::default())?.parsed.backend
+}
+
+/// The typed graph of linked modules
+fn module_graph(modules: &HashMap<String, Module>) -> ModuleGraph {
+    let mut graph = ModuleGraph::default();
+    for (path, module) in modules {
+        graph.add_module(path.clone(), ModuleNode {
+            stateful: module.parsed.is_stateful,
+            classification: module.parsed.classification,
+            backend: module.parsed.backend.clone(),
+            required_version: module.parsed.required_version.clone(),
+            providers: module.parsed.providers.clone(),
+        });
+    }
+    for (path, module) in modules {
+        for dependency in module.depends_on.iter().filter(|dependency| modules.contains_key(*dependency)) {
+            let _ = graph.add_dependency(path, dependency);
+        }
+    }
+    graph
+}
+
+/// Discover the modules of the repository containing `root_dir` and link them
+pub fn discover_graph(root_dir: &str, discovery: &DiscoveryOptions, overrides: &GraphOverrides) -> Result<ModuleGraph, String> {
+    let mut modules = discover_repo_modules(root_dir, discovery)?;
+    build_dependency_graph(&mut modules, overrides)?;
+    Ok(module_graph(&modules))
+}
+
+/// Link modules to the modules they source and the remote states they read, and apply the `overrides`
+/// from the configuration: statefulness set with `stateful` and dependencies declared with `depends_on`
+pub fn build_dependency_graph(modules: &mut HashMap<String, Module>, overrides: &GraphOverrides) -> Result<(), String> {
+    for (path, stateful) in &overrides.stateful {
+        match modules.get_mut(path) {
+            Some(module) => {
+                module.parsed.is_stateful = *stateful;
+                module.parsed.classification = Classification::Configured;
+            }
+            None => logger::warn(&format!("Ignoring stateful setting of {}: it is not a terraform module", relative_path(path))),
+        }
+    }
+
+    let dependencies: Vec<(String, String)> = modules.iter()
+        .flat_map(|(path, module)| module.parsed.sources.iter().map(move |source| (path.clone(), source.clone())))
+        .collect();
+
+    for (path, dep) in dependencies {
+        if let Some(module) = modules.get_mut(&path) {
+            module.depends_on.push(dep.clone());
+        }
+        if let Some(dep_module) = modules.get_mut(&dep) {
+            dep_module.used_by.push(path.clone());
+        }
+    }
+
+    // Modules reading another module's outputs through terraform_remote_state depend on it too
+    let remote_states: Vec<(String, StateAddress)> = modules.iter()
+        .flat_map(|(path, module)| module.parsed.remote_states.iter().map(move |state| (path.clone(), state.clone())))
+        .collect();
+    for (path, state) in remote_states {
+        let producer = modules.iter()
+            .find(|(_, producer)| producer.parsed.states.iter().any(|produced| produced.matches(&state)))
+            .map(|(producer_path, _)| producer_path.clone());
+        if let Some(producer) = producer.filter(|producer| *producer != path) {
+            logger::debug(&format!("{} reads the state of {}", relative_path(&path), relative_path(&producer)));
+            if let Some(module) = modules.get_mut(&path) {
+                module.depends_on.push(producer.clone());
+            }
+            if let Some(producer_module) = modules.get_mut(&producer) {
+                producer_module.used_by.push(path);
+            }
+        }
+    }
+
+    for (path, dep) in &overrides.dependencies {
+        if !modules.contains_key(path) || !modules.contains_key(dep) {
+            logger::warn(&format!("Ignoring depends_on {} → {}: both must be terraform modules", relative_path(path), relative_path(dep)));
+            continue;
+        }
+        if path == dep || modules[path].depends_on.contains(dep) {
+            continue;
+        }
+        if let Some(module) = modules.get_mut(path) {
+            module.depends_on.push(dep.clone());
+        }
+        if let Some(dep_module) = modules.get_mut(dep) {
+            dep_module.used_by.push(path.clone());
+        }
+    }
+
+    for cycle in find_dependency_cycles(modules) {
+        let path: Vec<String> = cycle.iter().chain(cycle.first()).map(|path| relative_path(path)).collect();
+        logger::warn(&format!("Dependency cycle: {}. Check the module sources, remote states and depends_on entries involved.", path.join(" → ")));
+    }
+
+    logger::info(&format!("Found {} modules repo-wide", modules.len()));
+    Ok(())
+}
+
+/// Cycles in the dependency graph, at least one for each group of modules that depend on each other.
+/// Each cycle lists the modules along it, starting from its smallest path.
+pub fn find_dependency_cycles(modules: &HashMap<String, Module>) -> Vec<Vec<String>> {
+    let mut paths: Vec<&String> = modules.keys().collect();
+    paths.sort();
+
+    let mut cycles: Vec<Vec<String>> = Vec::new();
+    let mut done: HashSet<String> = HashSet::new();
+    for start in paths {
+        let mut stack = vec![start.clone()];
+        let mut next = vec![0usize];
+        while let Some(last) = stack.last().cloned() {
+            let index = next.last().copied().unwrap_or_default();
+            let dependency = modules.get(&last).and_then(|module| module.depends_on.get(index)).cloned();
+            let dependency = match dependency {
+                Some(dependency) => dependency,
+                None => {
+                    done.insert(last);
+                    stack.pop();
+                    next.pop();
+                    continue;
+                }
+            };
+            if let Some(index) = next.last_mut() {
+                *index += 1;
+            }
+            if let Some(position) = stack.iter().position(|path| *path == dependency) {
+                let mut cycle = stack[position..].to_vec();
+                let smallest = (0..cycle.len()).min_by_key(|&i| &cycle[i]).unwrap_or_default();
+                cycle.rotate_left(smallest);
+                if !cycles.contains(&cycle) {
+                    cycles.push(cycle);
+                }
+            } else if !done.contains(&dependency) && modules.contains_key(&dependency) {
+                stack.push(dependency);
+                next.push(0);
+            }
+        }
+    }
+    cycles.sort();
+    cycles
+}
+
+pub fn find_module_dependencies(content: &str, current_dir: &str) -> Vec<String> {
+    let mut deps = Vec::new();
+    let lines: Vec<&str> = content.lines().collect();
+    let mut in_module_block = false;
+
+    for line in lines {
+        let trimmed_line = line.trim();
+
+        if trimmed_line.starts_with("module") && trimmed_line.contains("{") {
+            in_module_block = true;
+            continue;
+        }
+
+        if in_module_block {
+            if trimmed_line.contains("source") {
+                let parts: Vec<&str> = trimmed_line.split('=').collect();
+                if parts.len() == 2 {
+                    let source = parts[1].trim().trim_matches(|c| c == '"' || c == '\'');
+                    // Only local paths are module directories; a source that doesn't exist yet is kept
+                    // so that a cached module links to it once it is created
+                    if source.starts_with("./") || source.starts_with("../") {
+                        let module_path = Path::new(current_dir).join(source);
+                        let abs_path = fs::canonicalize(&module_path).unwrap_or_else(|_| normalize_path(&module_path));
+                        deps.push(abs_path.to_string_lossy().to_string());
+                    }
+                }
+            }
+            if trimmed_line.contains("}") {
+                in_module_block = false;
+            }
+        }
+    }
+    deps
+}
+
+/// A module call sourced from a registry or a remote URL, with the version or git ref it pins
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModulePin {
+    /// Name of the module block
+    pub name: String,
+    /// Source address without its `ref` query parameter
+    pub source: String,
+    /// `version` attribute, or the `ref` of a git source
+    pub version: Option<String>,
+}
+
+/// Find module calls whose source is not a local path, with the version or git ref they pin
+pub fn find_module_pins(content: &str) -> Vec<ModulePin> {
+    let block_re = Regex::new(r#"module\s+"([^"]+)"\s*\{"#).unwrap();
+    let source_re = Regex::new(r#"(?m)^\s*source\s*=\s*"([^"]+)""#).unwrap();
+    let version_re = Regex::new(r#"(?m)^\s*version\s*=\s*"([^"]+)""#).unwrap();
+
+    block_re.captures_iter(content)
+        .filter_map(|block| {
+            let body = block_body(&content[block.get(0)?.end()..])?;
+            let source = source_re.captures(body)?[1].to_string();
+            if source.starts_with("./") || source.starts_with("../") {
+                return None;
+            }
+            let (source, git_ref) = split_git_ref(&source);
+            Some(ModulePin {
+                name: block[1].to_string(),
+                source,
+                version: version_re.captures(body).map(|v| v[1].to_string()).or(git_ref),
             })
-            .collect();
-            
-        return Ok(filtered_modules);
+        })
+        .collect()
+}
+
+/// Split the `ref` query parameter off a module source
+fn split_git_ref(source: &str) -> (String, Option<String>) {
+    let (address, query) = match source.split_once('?') {
+        Some(parts) => parts,
+        None => return (source.to_string(), None),
+    };
+    let (refs, params): (Vec<&str>, Vec<&str>) = query.split('&').partition(|param| param.starts_with("ref="));
+    let address = if params.is_empty() { address.to_string() } else { format!("{}?{}", address, params.join("&")) };
+    (address, refs.first().map(|r| r["ref=".len()..].to_string()))
+}
+
+/// Describe the module pins that differ between two versions of a file, e.g.
+/// `module "vpc" terraform-aws-modules/vpc/aws 5.1.0 → 5.2.0`. Added and removed calls are not pin changes.
+pub fn pin_changes(old_content: &str, new_content: &str) -> Vec<String> {
+    let old_pins = find_module_pins(old_content);
+    let describe = |pin: &ModulePin| match &pin.version {
+        Some(version) => format!("{} {}", pin.source, version),
+        None => pin.source.clone(),
+    };
+
+    find_module_pins(new_content).iter()
+        .filter_map(|pin| {
+            let old = old_pins.iter().find(|old| old.name == pin.name)?;
+            if old == pin {
+                None
+            } else if old.source == pin.source {
+                let version = |pin: &ModulePin| pin.version.clone().unwrap_or_else(|| "unpinned".to_string());
+                Some(format!("module \"{}\" {} {} → {}", pin.name, pin.source, version(old), version(pin)))
+            } else {
+                Some(format!("module \"{}\" {} → {}", pin.name, describe(old), describe(pin)))
+            }
+        })
+        .collect()
+}
+
+/// Find files a module reads through `file()`, `templatefile()` and similar functions, or through
+/// `data "local_file"` blocks. `${path.module}`, `${path.root}` and `${path.cwd}` resolve to the module
+/// directory; paths with other interpolations can't be resolved statically and are skipped.
+pub fn find_referenced_files(content: &str, module_dir: &Path) -> Vec<PathBuf> {
+    let function_re = cached_regex!(r#"\b(?:file|templatefile|filebase64|filesha1|filesha256|filesha512|filemd5|filebase64sha256|filebase64sha512)\(\s*"([^"]+)""#);
+    let local_file_re = cached_regex!(r#"(?s)data\s+"local_file"\s+"[^"]*"\s*\{[^}]*?\bfilename\s*=\s*"([^"]+)""#);
+
+    let mut files: Vec<PathBuf> = function_re.captures_iter(content)
+        .chain(local_file_re.captures_iter(content))
+        .filter_map(|c| {
+            let path = ["${path.module}", "${path.root}", "${path.cwd}"].iter()
+                .fold(c[1].to_string(), |path, var| path.replace(var, "."));
+            if path.contains("${") {
+                return None;
+            }
+            let path = module_dir.join(path);
+            Some(fs::canonicalize(&path).unwrap_or(path))
+        })
+        .collect();
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Where a backend keeps a state: the bucket, container or organization when known, and the key,
+/// prefix or workspace name. Local states are identified by the absolute path of their state file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StateAddress {
+    pub container: Option<String>,
+    pub key: String,
+}
+
+impl StateAddress {
+    /// Whether two addresses name the same state. A missing container matches any container.
+    fn matches(&self, other: &StateAddress) -> bool {
+        self.key == other.key && (self.container.is_none() || other.container.is_none() || self.container == other.container)
+    }
+
+    fn local(path: &Path) -> Self {
+        Self { container: None, key: normalize_path(path).to_string_lossy().to_string() }
+    }
+}
+
+impl std::fmt::Display for StateAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.container {
+            Some(container) => write!(f, "{}/{}", container, self.key),
+            None => f.write_str(&self.key),
+        }
     }
-    
-    Ok(affected_modules)
 }
 
-pub fn discover_modules(root_dir: &str, modules: &mut HashMap<String, Module>) -> Result<(), String> {
-    for entry in fs::read_dir(root_dir).map_err(|e| e.to_string())? {
-        let entry = entry.map_err(|e| e.to_string())?;
-        let path = entry.path();
+/// States written by more than one module directory, with the modules writing each. Modules sharing a
+/// state overwrite each other's resources.
+pub fn find_shared_states(modules: &HashMap<String, Module>) -> Vec<(StateAddress, Vec<String>)> {
+    let mut writers: HashMap<&StateAddress, Vec<String>> = HashMap::new();
+    for (path, module) in modules {
+        for state in &module.parsed.states {
+            writers.entry(state).or_default().push(path.clone());
+        }
+    }
+    let mut shared: Vec<(StateAddress, Vec<String>)> = writers.into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(state, mut paths)| {
+            paths.sort();
+            (state.clone(), paths)
+        })
+        .collect();
+    shared.sort_by(|a, b| a.1.cmp(&b.1));
+    shared
+}
 
-        if path.is_dir() {
-            discover_modules(path.to_str().ok_or("Invalid path")?, modules)?;
+/// Where a module's backend keeps its state, as declared in its files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackendInfo {
+    /// Backend type, e.g. `s3`, `gcs`, `azurerm` or `local`, or `cloud` for a `cloud` block
+    pub kind: String,
+    /// Bucket, container or organization
+    pub container: Option<String>,
+    /// Key, prefix or workspace name, or the state file path of the `local` backend
+    pub key: Option<String>,
+    pub region: Option<String>,
+}
 
-            let tf_files: Vec<_> = fs::read_dir(&path)
-                .map_err(|e| e.to_string())?
-                .filter_map(|e| e.ok())
-                .filter(|e| e.path().extension().map_or(false, |ext| ext == "tf"))
-                .collect();
+impl BackendInfo {
+    /// Read the `backend` or `cloud` block of a module. Settings missing from the block are taken from the
+    /// partial configurations in `backend_configs` when they all agree on them.
+    pub fn parse(contents: &[String], backend_configs: &[String]) -> Option<Self> {
+        let region_re = cached_regex!(r#"\bregion\s*=\s*"([^"]+)""#);
+        let path_re = cached_regex!(r#"\bpath\s*=\s*"([^"]+)""#);
+
+        let (kind, body) = backend_block(contents)?;
+        let kind = kind.unwrap_or_else(|| "cloud".to_string());
+        if kind == "local" {
+            let path = path_re.captures(&body).map(|c| c[1].to_string()).unwrap_or_else(|| "terraform.tfstate".to_string());
+            return Some(Self { kind, container: None, key: Some(path), region: None });
+        }
 
-            if !tf_files.is_empty() {
-                let abs_path = fs::canonicalize(&path).map_err(|e| e.to_string())?;
-                let abs_path_str = abs_path.to_str().ok_or("Invalid path")?.to_string();
+        let agreed = |values: Vec<Option<String>>| -> Option<String> {
+            let first = values.first()?.clone()?;
+            values.iter().all(|value| value.as_deref() == Some(first.as_str())).then_some(first)
+        };
+        let (container, key) = state_attributes(&body);
+        let region = region_re.captures(&body).map(|c| c[1].to_string());
+        let configs: Vec<(Option<String>, Option<String>, Option<String>)> = backend_configs.iter()
+            .map(|config| {
+                let (container, key) = state_attributes(config);
+                (container, key, region_re.captures(config).map(|c| c[1].to_string()))
+            })
+            .collect();
+        Some(Self {
+            kind,
+            container: container.or_else(|| agreed(configs.iter().map(|c| c.0.clone()).collect())),
+            key: key.or_else(|| agreed(configs.iter().map(|c| c.1.clone()).collect())),
+            region: region.or_else(|| agreed(configs.iter().map(|c| c.2.clone()).collect())),
+        })
+    }
+}
 
-                modules.entry(abs_path_str.clone()).or_insert(Module {
-                    is_stateful: has_backend_config(&tf_files),
-                    ..Default::default()
-                });
-            }
+impl BackendInfo {
+    /// Concurrency group of the modules writing to this backend: its type and bucket, container or
+    /// organization, e.g. `s3:tf-state`, or only its type when it names none
+    pub fn concurrency_group(&self) -> String {
+        match &self.container {
+            Some(container) => format!("{}:{}", self.kind, container),
+            None => self.kind.clone(),
         }
     }
-    Ok(())
 }
 
-pub fn build_dependency_graph(modules: &mut HashMap<String, Module>) -> Result<(), String> {
-    let dependencies = collect_dependencies(modules)?;
-
-    for (path, dep) in dependencies {
-        if let Some(module) = modules.get_mut(&path) {
-            module.depends_on.push(dep.clone());
+impl std::fmt::Display for BackendInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        match (&self.container, &self.key) {
+            (Some(container), Some(key)) => write!(f, " {}/{}", container, key)?,
+            (Some(value), None) | (None, Some(value)) => write!(f, " {}", value)?,
+            (None, None) => {}
         }
-        if let Some(dep_module) = modules.get_mut(&dep) {
-            dep_module.used_by.push(path.clone());
+        if let Some(region) = &self.region {
+            write!(f, " ({})", region)?;
         }
+        Ok(())
     }
+}
 
-    logger::info(&format!("Found {} modules repo-wide", modules.len()));
-    Ok(())
+/// Type (None for a `cloud` block) and body of the first `backend` or `cloud` block in a module's files
+fn backend_block(contents: &[String]) -> Option<(Option<String>, String)> {
+    let backend_re = cached_regex!(r#"(?m)\bbackend\s+"([^"]+)"\s*\{|^\s*cloud\s*\{"#);
+    contents.iter().find_map(|content| {
+        let start = backend_re.captures(content)?;
+        let body = block_body(&content[start.get(0)?.end()..])?;
+        Some((start.get(1).map(|kind| kind.as_str().to_string()), body.to_string()))
+    })
 }
 
-pub fn collect_dependencies(modules: &HashMap<String, Module>) -> Result<Vec<(String, String)>, String> {
-    let mut dependencies = Vec::new();
+/// Bucket/container/organization and key/prefix/workspace name set in a backend configuration
+fn state_attributes(body: &str) -> (Option<String>, Option<String>) {
+    let container_re = cached_regex!(r#"\b(?:bucket|container_name|organization)\s*=\s*"([^"]+)""#);
+    let key_re = cached_regex!(r#"\b(?:key|prefix)\s*=\s*"([^"]+)""#);
+    let workspace_re = cached_regex!(r#"(?s)\bworkspaces\s*=?\s*\{[^}]*?\bname\s*=\s*"([^"]+)""#);
 
-    for (path, _module) in modules {
-        let tf_files: Vec<_> = fs::read_dir(path)
-            .map_err(|e| e.to_string())?
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension().map_or(false, |ext| ext == "tf"))
-            .collect();
+    let container = container_re.captures(body).map(|c| c[1].to_string());
+    let key = key_re.captures(body).or_else(|| workspace_re.captures(body)).map(|c| c[1].to_string());
+    (container, key)
+}
 
-        for file in tf_files {
-            let content = fs::read_to_string(file.path()).map_err(|e| e.to_string())?;
-            let deps = find_module_dependencies(&content, path);
+/// States a module's backend writes to. The `backend` or `cloud` block is completed by the partial
+/// configurations in `backend_configs` (`*.tfbackend` files), one state per file. Without a backend,
+/// or with the `local` backend, the state is a file in the module directory. Keys with interpolations
+/// can't be resolved statically and are skipped.
+pub fn find_backend_states(contents: &[String], backend_configs: &[String], module_dir: &Path) -> Vec<StateAddress> {
+    let path_re = cached_regex!(r#"\bpath\s*=\s*"([^"]+)""#);
 
-            for dep in deps {
-                dependencies.push((path.clone(), dep));
-            }
-        }
+    let (kind, body) = match backend_block(contents) {
+        Some(backend) => backend,
+        None => return vec![StateAddress::local(&module_dir.join("terraform.tfstate"))],
+    };
+    if kind.as_deref() == Some("local") {
+        let path = path_re.captures(&body).map(|c| c[1].to_string()).unwrap_or_else(|| "terraform.tfstate".to_string());
+        return vec![StateAddress::local(&module_dir.join(path))];
     }
 
-    Ok(dependencies)
+    let (container, key) = state_attributes(&body);
+    let states: Vec<(Option<String>, Option<String>)> = if backend_configs.is_empty() {
+        vec![(container, key)]
+    } else {
+        backend_configs.iter()
+            .map(|config| {
+                let (config_container, config_key) = state_attributes(config);
+                (config_container.or(container.clone()), config_key.or(key.clone()))
+            })
+            .collect()
+    };
+    states.into_iter()
+        .filter_map(|(container, key)| Some(StateAddress { container, key: key? }))
+        .filter(|state| !state.key.contains("${") && !state.container.as_deref().is_some_and(|c| c.contains("${")))
+        .collect()
 }
 
-pub fn find_module_dependencies(content: &str, current_dir: &str) -> Vec<String> {
-    let mut deps = Vec::new();
-    let lines: Vec<&str> = content.lines().collect();
-    let mut in_module_block = false;
+/// Terraform version constraint set with `required_version` in a `terraform` block
+pub fn find_required_version(content: &str) -> Option<String> {
+    let terraform_re = cached_regex!(r#"(?m)^\s*terraform\s*\{"#);
+    let version_re = cached_regex!(r#"(?m)^\s*required_version\s*=\s*"([^"]+)""#);
 
-    for line in lines {
-        let trimmed_line = line.trim();
+    terraform_re.find_iter(content)
+        .filter_map(|start| block_body(&content[start.end()..]))
+        .find_map(|body| version_re.captures(body).map(|c| c[1].to_string()))
+}
 
-        if trimmed_line.starts_with("module") && trimmed_line.contains("{") {
-            in_module_block = true;
-            continue;
-        }
+/// Names of the variables declared with `sensitive = true`
+pub fn find_sensitive_variables(content: &str) -> Vec<String> {
+    let variable_re = cached_regex!(r#"(?m)^\s*variable\s+"([^"]+)"\s*\{"#);
+    let sensitive_re = cached_regex!(r#"(?m)^\s*sensitive\s*=\s*true\b"#);
+
+    variable_re.captures_iter(content)
+        .filter_map(|caps| {
+            let start = caps.get(0)?.end();
+            let body = block_body(&content[start..])?;
+            sensitive_re.is_match(body).then(|| caps[1].to_string())
+        })
+        .collect()
+}
 
-        if in_module_block {
-            if trimmed_line.contains("source") {
-                let parts: Vec<&str> = trimmed_line.split('=').collect();
-                if parts.len() == 2 {
-                    let source = parts[1].trim().trim_matches(|c| c == '"' || c == '\'');
-                    let module_path = Path::new(current_dir).join(source);
-                    if let Ok(abs_path) = fs::canonicalize(module_path) {
-                        if let Some(abs_path_str) = abs_path.to_str() {
-                            deps.push(abs_path_str.to_string());
-                        }
-                    }
-                }
+/// Remote states a module reads through `data "terraform_remote_state"` blocks. Local states
+/// (`path = ...`) resolve to the absolute path of the state file; other backends to their bucket,
+/// container or organization and their `key`, `prefix` or workspace name. Values with interpolations
+/// can't be resolved statically and are skipped.
+pub fn find_remote_states(content: &str, module_dir: &Path) -> Vec<StateAddress> {
+    let remote_state_re = cached_regex!(r#"data\s+"terraform_remote_state"\s+"[^"]*"\s*\{"#);
+    let path_re = cached_regex!(r#"\bpath\s*=\s*"([^"]+)""#);
+
+    remote_state_re.find_iter(content)
+        .filter_map(|start| block_body(&content[start.end()..]))
+        .filter_map(|body| {
+            if let Some(path) = path_re.captures(body) {
+                return Some(StateAddress::local(&module_dir.join(&path[1])));
             }
-            if trimmed_line.contains("}") {
-                in_module_block = false;
+            let (container, key) = state_attributes(body);
+            Some(StateAddress { container, key: key? })
+        })
+        .filter(|state| !state.key.contains("${"))
+        .map(|state| StateAddress { container: state.container.filter(|c| !c.contains("${")), ..state })
+        .collect()
+}
+
+/// Resolve `.` and `..` in a path without touching the filesystem
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
             }
+            component => normalized.push(component),
         }
     }
-    deps
+    normalized
 }
 
-pub fn has_backend_config(tf_files: &[fs::DirEntry]) -> bool {
-    let has_module_blocks = tf_files.iter().any(|file| {
-        if let Ok(content) = fs::read_to_string(file.path()) {
-            let lines: Vec<&str> = content.lines().collect();
-            for line in lines {
-                let trimmed_line = line.trim();
-                if trimmed_line.starts_with("module") && trimmed_line.contains("{") {
-                    return true;
-                }
+/// Whether terraform configuration calls modules or declares a backend
+fn declares_state(contents: &[String]) -> bool {
+    state_declaration(contents).is_some()
+}
+
+/// How a module's files make it stateful: a `backend` or `cloud` block, or calls to other modules
+fn state_declaration(contents: &[String]) -> Option<Classification> {
+    let has_module_blocks = contents.iter().any(|content| {
+        let lines: Vec<&str> = content.lines().collect();
+        for line in lines {
+            let trimmed_line = line.trim();
+            if trimmed_line.starts_with("module") && trimmed_line.contains("{") {
+                return true;
             }
         }
         false
     });
     
-    if has_module_blocks {
-        return true;
-    }
-    
-    for file in tf_files {
-        if let Ok(content) = fs::read_to_string(file.path()) {
-            let lines: Vec<&str> = content.lines().collect();
-            let mut in_terraform_block = false;
-            let mut brace_count = 0;
+    for content in contents {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut in_terraform_block = false;
+        let mut brace_count = 0;
+        
+        for line in lines {
+            let trimmed_line = line.trim();
             
-            for line in lines {
-                let trimmed_line = line.trim();
-                
-                if trimmed_line.is_empty() || trimmed_line.starts_with('#') || trimmed_line.starts_with("//") {
-                    continue;
-                }
-                
-                if trimmed_line.starts_with("terraform") && trimmed_line.contains("{") {
-                    in_terraform_block = true;
-                    brace_count += 1;
-                    continue;
-                }
-                
-                if in_terraform_block && trimmed_line.starts_with("backend") && trimmed_line.contains("\"") {
-                    return true;
-                }
-                
-                if trimmed_line.contains("{") {
-                    brace_count += 1;
-                }
-                if trimmed_line.contains("}") {
-                    brace_count -= 1;
-                    if brace_count == 0 {
-                        in_terraform_block = false;
-                    }
+            if trimmed_line.is_empty() || trimmed_line.starts_with('#') || trimmed_line.starts_with("//") {
+                continue;
+            }
+            
+            if trimmed_line.starts_with("terraform") && trimmed_line.contains("{") {
+                in_terraform_block = true;
+                brace_count += 1;
+                continue;
+            }
+            
+            if in_terraform_block && trimmed_line.starts_with("backend") && trimmed_line.contains("\"") {
+                return Some(Classification::Backend);
+            }
+            if in_terraform_block && trimmed_line.starts_with("cloud") && trimmed_line.contains("{") {
+                return Some(Classification::Backend);
+            }
+            
+            if trimmed_line.contains("{") {
+                brace_count += 1;
+            }
+            if trimmed_line.contains("}") {
+                brace_count -= 1;
+                if brace_count == 0 {
+                    in_terraform_block = false;
                 }
             }
         }
     }
     
-    if let Some(first_file) = tf_files.first() {
-        if let Some(dir_path) = first_file.path().parent() {
-            if let Ok(entries) = fs::read_dir(dir_path) {
-                for entry in entries.filter_map(|e| e.ok()) {
-                    let path = entry.path();
-                    if path.is_file() && path.extension().map_or(false, |ext| ext == "tfstate") {
-                        return true;
-                    }
-                }
+    has_module_blocks.then_some(Classification::ModuleCalls)
+}
+
+/// Whether a directory holds a local `.tfstate` file
+fn has_local_state(dir_path: &Path) -> bool {
+    if let Ok(entries) = fs::read_dir(dir_path) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.is_file() && path.extension().map_or(false, |ext| ext == "tfstate") {
+                return true;
             }
         }
     }
-    
     false
 }
 
+/// Fail unless a ref resolves to a commit (or the empty tree) in the local repository
+fn verify_commit(root_dir: &str, git_ref: &str) -> Result<(), String> {
+    if git_ref == git::EMPTY_TREE || ensure_commit(root_dir, git_ref) {
+        Ok(())
+    } else {
+        Err(format!("Git ref '{}' does not resolve to a commit", git_ref))
+    }
+}
+
 /// Get the current branch name
-fn get_current_branch(root_dir: &str) -> Result<String, String> {
-    // Try to get from environment first (for CI/CD)
-    if let Ok(branch) = std::env::var("GITHUB_REF_NAME") {
+pub fn get_current_branch(root_dir: &str) -> Result<String, String> {
+    // Try to get from the CI environment first, where checkouts are often detached
+    if let Some(branch) = PipelineContext::detect().branch {
         return Ok(branch);
     }
     
-    // Fallback to git command
-    let output = Command::new("git")
-        .args(&["rev-parse", "--abbrev-ref", "HEAD"])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
-        
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
-    } else {
-        Err("Failed to get current branch".to_string())
+    // Fallback to the repository's checked-out branch
+    git::current_branch(root_dir).map_err(|e| format!("Failed to get current branch: {}", e))
+}
+
+/// Number of the pull request a merge or squash commit message names, following the conventions of
+/// GitHub, GitLab, Azure DevOps and Bitbucket
+pub fn merged_pr_number(message: &str) -> Option<String> {
+    let summary = message.lines().next().unwrap_or("").trim();
+    let patterns = [
+        (summary, r"^Merge pull request #(\d+)"),
+        (summary, r"\(#(\d+)\)$"),
+        (summary, r"^Merged PR (\d+):"),
+        (summary, r"\(pull request #(\d+)\)"),
+        (message, r"See merge request \S*!(\d+)"),
+    ];
+    patterns.iter().find_map(|(text, pattern)| {
+        Regex::new(pattern).unwrap().captures(text).map(|c| c[1].to_string())
+    })
+}
+
+/// First parent of HEAD, and the pull request it merges, when HEAD merges a single pull request: a merge
+/// commit with two parents, or a squash commit whose message names a pull request
+fn merge_commit_parent(root_dir: &str) -> Option<(String, Option<String>)> {
+    let mut parents = git::commit_parents(root_dir, "HEAD").ok()?;
+    // A shallow clone often stops at HEAD; fetch its parents
+    if parents.is_empty() && git::is_shallow(root_dir) {
+        let head = git::resolve_commit(root_dir, "HEAD").ok()?;
+        if fetch_with_retries(root_dir, &["--deepen=1", "origin", &head]) {
+            parents = git::commit_parents(root_dir, "HEAD").ok()?;
+        }
+    }
+    let pr_number = merged_pr_number(&git::commit_message(root_dir, "HEAD").ok()?);
+
+    match parents.len() {
+        2 => Some((parents.swap_remove(0), pr_number)),
+        1 if pr_number.is_some() => Some((parents.swap_remove(0), pr_number)),
+        _ => None,
     }
 }
 
 /// Get changes specifically for main branch scenarios (local environment) - clean version
-fn get_main_branch_changes_local_clean(root_dir: &str, recent_commits: u32) -> Result<Vec<String>, String> {
+fn get_main_branch_changes_local_clean(root_dir: &str, recent_commits: u32, tracked: &TrackedFiles, strategies: &DetectionStrategies) -> Result<Vec<String>, String> {
     let mut total_changes = Vec::new();
     
     // Strategy 1: Check recent commits (configurable count)
-    let recent_changes = get_recent_commit_changes_clean(root_dir, recent_commits as usize)?;
-    total_changes.extend(recent_changes);
-    
-    if !total_changes.is_empty() {
-        logger::info("Found changes in recent commits");
-        return Ok(total_changes);
+    if strategies.allows(DetectionStrategy::RecentCommits) {
+        let recent_changes = get_recent_commit_changes_clean(root_dir, recent_commits as usize, tracked)?;
+        total_changes.extend(recent_changes);
+        
+        if !total_changes.is_empty() {
+            logger::info("Found changes in recent commits");
+            return Ok(total_changes);
+        }
     }
     
     // Strategy 2: Check if there are any staged or unstaged changes
-    let uncommitted_changes = get_uncommitted_changes(root_dir)?;
-    if !uncommitted_changes.is_empty() {
-        logger::info("Found uncommitted changes");
-        total_changes.extend(uncommitted_changes);
-        return Ok(total_changes);
+    if strategies.allows(DetectionStrategy::Uncommitted) {
+        let uncommitted_changes = get_uncommitted_changes(root_dir, tracked)?;
+        if !uncommitted_changes.is_empty() {
+            logger::info("Found uncommitted changes");
+            total_changes.extend(uncommitted_changes);
+            return Ok(total_changes);
+        }
     }
     
     // Strategy 3: Compare with a reference point (e.g., last tag or specific commit)
-    let reference_changes = get_reference_changes(root_dir)?;
-    if !reference_changes.is_empty() {
-        logger::info("Found changes compared to reference point");
-        total_changes.extend(reference_changes);
-        return Ok(total_changes);
+    if strategies.allows(DetectionStrategy::Reference) {
+        let reference_changes = get_reference_changes(root_dir, tracked, strategies.reference_window)?;
+        if !reference_changes.is_empty() {
+            logger::info("Found changes compared to reference point");
+            total_changes.extend(reference_changes);
+            return Ok(total_changes);
+        }
     }
     
     logger::info("No changes detected using any strategy");
@@ -322,23 +1701,23 @@ fn get_main_branch_changes_local_clean(root_dir: &str, recent_commits: u32) -> R
 
 /// Get changes specifically for main branch scenarios (local environment) - original version
 #[allow(dead_code)]
-fn get_main_branch_changes_local(root_dir: &str, recent_commits: u32) -> Result<Vec<String>, String> {
+fn get_main_branch_changes_local(root_dir: &str, recent_commits: u32, tracked: &TrackedFiles) -> Result<Vec<String>, String> {
     // Strategy 1: Check recent commits (configurable count)
-    let recent_changes = get_recent_commit_changes(root_dir, recent_commits as usize)?;
+    let recent_changes = get_recent_commit_changes(root_dir, recent_commits as usize, tracked)?;
     if !recent_changes.is_empty() {
         logger::info("Found changes in recent commits");
         return Ok(recent_changes);
     }
     
     // Strategy 2: Check if there are any staged or unstaged changes
-    let uncommitted_changes = get_uncommitted_changes(root_dir)?;
+    let uncommitted_changes = get_uncommitted_changes(root_dir, tracked)?;
     if !uncommitted_changes.is_empty() {
         logger::info("Found uncommitted changes");
         return Ok(uncommitted_changes);
     }
     
     // Strategy 3: Compare with a reference point (e.g., last tag or specific commit)
-    let reference_changes = get_reference_changes(root_dir)?;
+    let reference_changes = get_reference_changes(root_dir, tracked, DEFAULT_REFERENCE_WINDOW)?;
     if !reference_changes.is_empty() {
         logger::info("Found changes compared to reference point");
         return Ok(reference_changes);
@@ -349,58 +1728,73 @@ fn get_main_branch_changes_local(root_dir: &str, recent_commits: u32) -> Result<
 }
 
 /// Get changes for CD pipeline environment (Pipeline-supplied commits)
-fn get_cd_pipeline_changes(root_dir: &str, pr_number: &str, default_branch: &str) -> Result<Vec<String>, String> {
-    logger::info(&format!("Analyzing changes for PR #{} against {}", pr_number, default_branch));
+fn get_cd_pipeline_changes(root_dir: &str, pipeline: &PipelineContext, default_branch: &str, tracked: &TrackedFiles, strategies: &DetectionStrategies) -> Result<Vec<String>, String> {
+    logger::info(&format!("Analyzing changes for {} against {}", pipeline.describe(), default_branch));
     
     // Strategy 1: Use pipeline-supplied commit information (PRIORITY)
-    let pipeline_changes = get_pipeline_supplied_changes(root_dir, pr_number);
-    match pipeline_changes {
-        Ok(changes) if !changes.is_empty() => {
-            logger::info("Found changes using pipeline-supplied commits");
-            return Ok(changes);
-        }
-        Ok(_) => {
-            logger::info("Pipeline-supplied commits found but no changes detected");
-            return Ok(Vec::new());
-        }
-        Err(_) => {
-            logger::info("No pipeline-supplied commits available, using fallback strategies");
+    if strategies.allows(DetectionStrategy::PipelineCommits) {
+        let pipeline_changes = get_pipeline_supplied_changes(root_dir, pipeline, tracked);
+        match pipeline_changes {
+            Ok(changes) if !changes.is_empty() => {
+                logger::info("Found changes using pipeline-supplied commits");
+                return Ok(changes);
+            }
+            Ok(_) => {
+                logger::info("Pipeline-supplied commits found but no changes detected");
+                return Ok(Vec::new());
+            }
+            Err(e) => {
+                logger::warn(&format!("{}, using fallback strategies", e));
+            }
         }
     }
     
     // Strategy 2: Fallback to merge base detection (legacy)
-    if let Ok(changes) = get_pr_changes(root_dir, pr_number, default_branch) {
-        if !changes.is_empty() {
-            logger::info("Found changes using merge base detection (fallback)");
-            return Ok(changes);
+    if strategies.allows(DetectionStrategy::MergeBase) {
+        if let Ok(changes) = get_pr_changes(root_dir, &pipeline.describe(), default_branch, tracked) {
+            if !changes.is_empty() {
+                logger::info("Found changes using merge base detection (fallback)");
+                return Ok(changes);
+            }
         }
     }
     
     // Strategy 3: Fallback to recent commits in the PR
-    let recent_changes = get_recent_commit_changes(root_dir, 10)?;
-    if !recent_changes.is_empty() {
-        logger::info("Found changes in recent commits (fallback)");
-        return Ok(recent_changes);
+    if strategies.allows(DetectionStrategy::RecentCommits) {
+        let recent_changes = get_recent_commit_changes(root_dir, strategies.pipeline_recent_commits as usize, tracked)?;
+        if !recent_changes.is_empty() {
+            logger::info("Found changes in recent commits (fallback)");
+            return Ok(recent_changes);
+        }
     }
     
     // Strategy 4: Check for uncommitted changes
-    let uncommitted_changes = get_uncommitted_changes(root_dir)?;
-    if !uncommitted_changes.is_empty() {
-        logger::info("Found uncommitted changes");
-        return Ok(uncommitted_changes);
+    if strategies.allows(DetectionStrategy::Uncommitted) {
+        let uncommitted_changes = get_uncommitted_changes(root_dir, tracked)?;
+        if !uncommitted_changes.is_empty() {
+            logger::info("Found uncommitted changes");
+            return Ok(uncommitted_changes);
+        }
     }
     
-    logger::info(&format!("No changes detected for PR #{}", pr_number));
+    logger::info(&format!("No changes detected for {}", pipeline.describe()));
     Ok(Vec::new())
 }
 
 /// Get changes using pipeline-supplied commit information
-fn get_pipeline_supplied_changes(root_dir: &str, _pr_number: &str) -> Result<Vec<String>, String> {
+fn get_pipeline_supplied_changes(root_dir: &str, pipeline: &PipelineContext, tracked: &TrackedFiles) -> Result<Vec<String>, String> {
     // Check for pipeline-supplied commit information
-    let base_commit = std::env::var("SOLARBOAT_BASE_COMMIT").ok();
-    let head_commit = std::env::var("SOLARBOAT_HEAD_COMMIT").ok();
-    let base_branch = std::env::var("SOLARBOAT_BASE_BRANCH").ok();
-    let head_branch = std::env::var("SOLARBOAT_HEAD_BRANCH").ok();
+    let base_commit = pipeline.base_commit.clone();
+    let head_commit = pipeline.head_commit.clone();
+    let base_branch = pipeline.base_branch.clone();
+    let head_branch = pipeline.head_branch.clone();
+    
+    // Diffing against a commit missing from a shallow clone would silently find no changes
+    for commit in base_commit.iter().chain(head_commit.iter()) {
+        if !ensure_commit(root_dir, commit) {
+            return Err(format!("Pipeline-supplied commit {} is not available", commit));
+        }
+    }
     
     // If we have both base and head commits, use them directly
     if let (Some(base), Some(head)) = (base_commit.clone(), head_commit.clone()) {
@@ -414,20 +1808,20 @@ fn get_pipeline_supplied_changes(root_dir: &str, _pr_number: &str) -> Result<Vec
             logger::info(&format!("   • Head branch: {}", head_branch));
         }
         
-        return get_changes_between_commits(root_dir, &base, &head);
+        return get_changes_between_commits(root_dir, &base, &head, tracked);
     }
     
     // If we only have base commit, compare with HEAD
     if let Some(base) = base_commit {
         logger::info(&format!("Using pipeline-supplied base commit: {}", base));
-        return get_changes_between_commits(root_dir, &base, "HEAD");
+        return get_changes_between_commits(root_dir, &base, "HEAD", tracked);
     }
     
     // If we only have head commit, compare with default branch
     if let Some(head) = head_commit {
         logger::info(&format!("Using pipeline-supplied head commit: {}", head));
         // This is less ideal, but we can compare with the default branch
-        return get_changes_between_commits(root_dir, "main", &head);
+        return get_changes_between_commits(root_dir, "main", &head, tracked);
     }
     
     // No pipeline-supplied commits available
@@ -436,64 +1830,44 @@ fn get_pipeline_supplied_changes(root_dir: &str, _pr_number: &str) -> Result<Vec
 }
 
 /// Get changes between PR branch and default branch
-fn get_pr_changes(root_dir: &str, pr_number: &str, default_branch: &str) -> Result<Vec<String>, String> {
+fn get_pr_changes(root_dir: &str, change: &str, default_branch: &str, tracked: &TrackedFiles) -> Result<Vec<String>, String> {
     // Try to get the merge base between the current branch and the default branch
-    let merge_base_output = Command::new("git")
-        .args(&["merge-base", default_branch, "HEAD"])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
-    
-    if merge_base_output.status.success() {
-        let merge_base = String::from_utf8_lossy(&merge_base_output.stdout).trim().to_string();
+    if let Ok(merge_base) = git::merge_base(root_dir, default_branch, "HEAD") {
         logger::info(&format!("Using merge base: {}", merge_base));
-        return get_changes_between_commits(root_dir, &merge_base, "HEAD");
+        return get_changes_between_commits(root_dir, &merge_base, "HEAD", tracked);
     }
     
     // Fallback: try to get changes between origin/default_branch and HEAD
-    let origin_merge_base_output = Command::new("git")
-        .args(&["merge-base", &format!("origin/{}", default_branch), "HEAD"])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
-    
-    if origin_merge_base_output.status.success() {
-        let merge_base = String::from_utf8_lossy(&origin_merge_base_output.stdout).trim().to_string();
+    if let Ok(merge_base) = git::merge_base(root_dir, &format!("origin/{}", default_branch), "HEAD") {
         logger::info(&format!("Using origin merge base: {}", merge_base));
-        return get_changes_between_commits(root_dir, &merge_base, "HEAD");
+        return get_changes_between_commits(root_dir, &merge_base, "HEAD", tracked);
+    }
+    
+    // Shallow clones often lack the default branch or the history back to the merge base
+    if let Some(merge_base) = fetch_merge_base(root_dir, default_branch) {
+        logger::info(&format!("Using fetched merge base: {}", merge_base));
+        return get_changes_between_commits(root_dir, &merge_base, "HEAD", tracked);
     }
     
     // If we can't find a merge base, return empty list
-    logger::warn(&format!("Could not determine merge base for PR #{}", pr_number));
+    logger::warn(&format!("Could not determine merge base for {}", change));
     Ok(Vec::new())
 }
 
 /// Get changes from recent commits (clean version)
-fn get_recent_commit_changes_clean(root_dir: &str, commit_count: usize) -> Result<Vec<String>, String> {
+fn get_recent_commit_changes_clean(root_dir: &str, commit_count: usize, tracked: &TrackedFiles) -> Result<Vec<String>, String> {
     let mut changed_files = Vec::new();
 
     logger::info(&format!("Getting changes from last {} commits", commit_count));
     
     // Get the list of recent commits
-    let log_output = Command::new("git")
-        .args(&["log", "--oneline", "-n", &commit_count.to_string()])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| format!("Failed to execute git log: {}", e))?;
-
-    if log_output.status.success() {
-        let output_str = String::from_utf8_lossy(&log_output.stdout);
-        let commits: Vec<&str> = output_str
-            .lines()
-            .filter_map(|line| line.split_whitespace().next())
-            .collect();
-
+    if let Ok(commits) = git::recent_commits(root_dir, commit_count) {
         if commits.len() >= 2 {
             // Get changes between the first and last commit in the range
             let from_commit = commits.last().unwrap();
             let to_commit = commits.first().unwrap();
             
-            changed_files = get_changes_between_commits_clean(root_dir, from_commit, to_commit)
+            changed_files = get_changes_between_commits_clean(root_dir, from_commit, to_commit, tracked)
                 .map_err(|e| format!("Failed to get changes between commits: {}", e))?;
         }
     }
@@ -505,29 +1879,18 @@ fn get_recent_commit_changes_clean(root_dir: &str, commit_count: usize) -> Resul
 }
 
 /// Get changes from recent commits (original version)
-fn get_recent_commit_changes(root_dir: &str, commit_count: usize) -> Result<Vec<String>, String> {
+fn get_recent_commit_changes(root_dir: &str, commit_count: usize, tracked: &TrackedFiles) -> Result<Vec<String>, String> {
     let mut changed_files = Vec::new();
     
     // Get the last N commits
-    let log_output = Command::new("git")
-        .args(&["log", "--oneline", "-n", &commit_count.to_string()])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
-        
-    if !log_output.status.success() {
-        return Ok(Vec::new());
-    }
-    
-    let log_output_str = String::from_utf8_lossy(&log_output.stdout);
-    let commits: Vec<&str> = log_output_str
-        .lines()
-        .filter_map(|line| line.split_whitespace().next())
-        .collect();
+    let commits = match git::recent_commits(root_dir, commit_count) {
+        Ok(commits) => commits,
+        Err(_) => return Ok(Vec::new()),
+    };
     
     // Check changes in each commit
     for commit in commits {
-        let changes = get_changes_between_commits(root_dir, &format!("{}~1", commit), commit)?;
+        let changes = get_changes_between_commits(root_dir, &format!("{}~1", commit), &commit, tracked)?;
         changed_files.extend(changes);
     }
     
@@ -539,38 +1902,16 @@ fn get_recent_commit_changes(root_dir: &str, commit_count: usize) -> Result<Vec<
 }
 
 /// Get uncommitted changes (staged and unstaged)
-fn get_uncommitted_changes(root_dir: &str) -> Result<Vec<String>, String> {
+fn get_uncommitted_changes(root_dir: &str, tracked: &TrackedFiles) -> Result<Vec<String>, String> {
     let mut changed_files = Vec::new();
+    let work_tree = repo_root(root_dir);
     
-    // Get staged changes
-    let staged_output = Command::new("git")
-        .args(&["diff", "--cached", "--name-only"])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
-        
-    if staged_output.status.success() {
-        changed_files.extend(
-            String::from_utf8_lossy(&staged_output.stdout)
-                .lines()
-                .filter(|line| line.ends_with(".tf"))
-                .map(|line| Path::new(root_dir).join(line).to_string_lossy().to_string())
-        );
-    }
-    
-    // Get unstaged changes
-    let unstaged_output = Command::new("git")
-        .args(&["diff", "--name-only"])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
-        
-    if unstaged_output.status.success() {
+    // Get staged and unstaged changes
+    for files in [git::staged_files(root_dir, &tracked.pathspecs), git::unstaged_files(root_dir, &tracked.pathspecs)].into_iter().flatten() {
         changed_files.extend(
-            String::from_utf8_lossy(&unstaged_output.stdout)
-                .lines()
-                .filter(|line| line.ends_with(".tf"))
-                .map(|line| Path::new(root_dir).join(line).to_string_lossy().to_string())
+            files.iter()
+                .filter(|line| tracked.matches(line))
+                .map(|line| repo_file_path(&work_tree, line))
         );
     }
     
@@ -581,78 +1922,72 @@ fn get_uncommitted_changes(root_dir: &str) -> Result<Vec<String>, String> {
     Ok(changed_files)
 }
 
-/// Get changes compared to a reference point (last tag or specific commit)
-fn get_reference_changes(root_dir: &str) -> Result<Vec<String>, String> {
+/// Get changes compared to a reference point (last tag, or the newest commit older than `window` seconds)
+fn get_reference_changes(root_dir: &str, tracked: &TrackedFiles, window: u64) -> Result<Vec<String>, String> {
     // Try to find the last tag
-    let tag_output = Command::new("git")
-        .args(&["describe", "--tags", "--abbrev=0"])
-        .current_dir(root_dir)
-        .output();
-        
-    if let Ok(output) = tag_output {
-        if output.status.success() {
-            let tag = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            logger::info(&format!("Comparing with last tag: {}", tag));
-            return get_changes_between_commits(root_dir, &tag, "HEAD");
-        }
+    if let Ok(tag) = git::latest_tag(root_dir) {
+        logger::info(&format!("Comparing with last tag: {}", tag));
+        return get_changes_between_commits(root_dir, &tag, "HEAD", tracked);
     }
     
-    // Fallback: compare with a commit from 1 day ago
-    let date_output = Command::new("git")
-        .args(&["rev-list", "-n", "1", "--before=1 day ago", "HEAD"])
-        .current_dir(root_dir)
-        .output()
+    // Fallback: compare with the newest commit older than the window (1 day by default)
+    let cutoff = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|now| now.as_secs().saturating_sub(window) as i64)
         .map_err(|e| e.to_string())?;
-        
-    if date_output.status.success() {
-        let commit = String::from_utf8_lossy(&date_output.stdout).trim().to_string();
-        if !commit.is_empty() {
-            logger::info(&format!("Comparing with commit from 1 day ago: {}", commit));
-            return get_changes_between_commits(root_dir, &commit, "HEAD");
-        }
+    if let Some(commit) = git::commit_before(root_dir, cutoff)? {
+        logger::info(&format!("Comparing with the newest commit older than {}s: {}", window, commit));
+        return get_changes_between_commits(root_dir, &commit, "HEAD", tracked);
     }
     
     Ok(Vec::new())
 }
 
 /// Get changes between two specific commits (clean version)
-fn get_changes_between_commits_clean(root_dir: &str, from_commit: &str, to_commit: &str) -> Result<Vec<String>, SolarboatError> {
+fn get_changes_between_commits_clean(root_dir: &str, from_commit: &str, to_commit: &str, tracked: &TrackedFiles) -> Result<Vec<String>, SolarboatError> {
     let mut changed_files = Vec::new();
+    let work_tree = repo_root(root_dir);
 
     logger::info(&format!("Getting changes between {} and {}", from_commit, to_commit));
+    warn_module_moves(root_dir, from_commit, Some(to_commit));
     
     // Get changes between the two commits
-    let diff_output = Command::new("git")
-        .args(&["diff", "--name-only", from_commit, to_commit])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| SolarboatError::Process {
-            command: "git diff".to_string(),
-            args: vec!["diff".to_string(), "--name-only".to_string(), from_commit.to_string(), to_commit.to_string()],
-            cause: e.to_string(),
-            exit_code: None,
-        })?;
-
-    if diff_output.status.success() {
+    if let Ok(files) = diff_files(root_dir, from_commit, Some(to_commit), &tracked.pathspecs, tracked) {
         changed_files.extend(
-            String::from_utf8_lossy(&diff_output.stdout)
-                .lines()
-                .filter(|line| line.ends_with(".tf"))
-                .filter_map(|line| {
-                    // Use a more robust approach to handle paths that might not exist
-                    let file_path = Path::new(root_dir).join(line);
-                    if file_path.exists() {
-                        // If the file exists, canonicalize it
-                        SafeOperations::canonicalize(&file_path)
-                            .and_then(|canonical_path| SafeOperations::os_str_to_string(canonical_path.as_os_str()))
-                            .ok()
-                    } else {
-                        // If the file doesn't exist, use the absolute path from the current directory
-                        let current_dir = SafeOperations::current_dir().ok()?;
-                        let path = current_dir.join(root_dir).join(line);
-                        SafeOperations::os_str_to_string(path.as_os_str()).ok()
-                    }
-                })
+            files.iter()
+                .filter(|line| tracked.matches(line))
+                .map(|line| repo_file_path(&work_tree, line))
+        );
+    }
+
+    // Remove duplicates
+    changed_files.sort();
+    changed_files.dedup();
+
+    if !changed_files.is_empty() {
+        logger::info(&format!("Found {} changed terraform files", changed_files.len()));
+        logger::changed_files_summary(&changed_files);
+    } else {
+        logger::info("No terraform files changed between the commits");
+    }
+
+    Ok(changed_files)
+}
+
+/// Get changes between two specific commits (original version for backward compatibility)
+fn get_changes_between_commits(root_dir: &str, from_commit: &str, to_commit: &str, tracked: &TrackedFiles) -> Result<Vec<String>, String> {
+    let mut changed_files = Vec::new();
+    let work_tree = repo_root(root_dir);
+
+    logger::info(&format!("Getting changes between {} and {}", from_commit, to_commit));
+    warn_module_moves(root_dir, from_commit, Some(to_commit));
+    
+    // Get changes between the two commits
+    if let Ok(files) = diff_files(root_dir, from_commit, Some(to_commit), &tracked.pathspecs, tracked) {
+        changed_files.extend(
+            files.iter()
+                .filter(|line| tracked.matches(line))
+                .map(|line| repo_file_path(&work_tree, line))
         );
     }
 
@@ -661,168 +1996,341 @@ fn get_changes_between_commits_clean(root_dir: &str, from_commit: &str, to_commi
     changed_files.dedup();
 
     if !changed_files.is_empty() {
-        logger::info(&format!("Found {} changed .tf files", changed_files.len()));
+        logger::info(&format!("Found {} changed terraform files", changed_files.len()));
         logger::changed_files_summary(&changed_files);
     } else {
-        logger::info("No .tf files changed between the commits");
+        logger::info("No terraform files changed between the commits");
+    }
+
+    Ok(changed_files)
+}
+
+/// Files that differ between two commits, or between a commit and the working tree when `to` is None,
+/// relative to the repository root and limited to `pathspecs`. Changed submodules are replaced by the files changed in them.
+fn diff_files(root_dir: &str, from: &str, to: Option<&str>, pathspecs: &[String], tracked: &TrackedFiles) -> Result<Vec<String>, String> {
+    let mut files = git::changed_files(root_dir, from, to, pathspecs)?;
+    let updates = git::submodule_updates(root_dir, from, to, pathspecs).unwrap_or_else(|e| {
+        logger::debug(&format!("Submodule detection failed: {}", e));
+        Vec::new()
+    });
+    if updates.is_empty() {
+        return Ok(files);
+    }
+
+    let work_tree = repo_root(root_dir);
+    for update in updates {
+        files.retain(|file| file != &update.path);
+        files.extend(submodule_files(&work_tree, &update, tracked).into_iter().map(|file| format!("{}/{}", update.path, file)));
+    }
+    Ok(files)
+}
+
+/// Files of a changed submodule, relative to the submodule. With `recurse_submodules`, the files that differ
+/// between its old and new commit; otherwise, or when those commits aren't available, every file checked out.
+fn submodule_files(work_tree: &Path, update: &git::SubmoduleUpdate, tracked: &TrackedFiles) -> Vec<String> {
+    let dir = work_tree.join(&update.path);
+    let short = |commit: &Option<String>| commit.as_deref().map(|c| c[..c.len().min(12)].to_string()).unwrap_or_else(|| "none".to_string());
+    logger::info(&format!("Submodule {} changed ({} -> {})", update.path, short(&update.old), short(&update.new)));
+
+    // An uninitialized submodule is an empty directory, which git would resolve to the parent repository
+    if !dir.join(".git").exists() {
+        logger::warn(&format!("Submodule {} is not checked out; run `git submodule update --init` to detect changes in it", update.path));
+        return Vec::new();
+    }
+    let dir_str = dir.to_string_lossy().to_string();
+
+    if tracked.recurse_submodules {
+        let new = update.new.clone().or_else(|| git::resolve_commit(&dir_str, "HEAD").ok());
+        if let (Some(old), Some(new)) = (&update.old, &new) {
+            match diff_files(&dir_str, old, Some(new), &[], tracked) {
+                Ok(files) => return files,
+                Err(e) => logger::warn(&format!("Could not diff submodule {} ({}); treating all its files as changed", update.path, e)),
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    collect_files(&dir, &dir, &mut files);
+    files
+}
+
+/// Files under `dir`, relative to `base`, skipping git and terraform metadata
+fn collect_files(dir: &Path, base: &Path, files: &mut Vec<String>) {
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if entry.file_name() == ".git" || entry.file_name() == ".terraform" {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, base, files);
+        } else if let Ok(relative) = path.strip_prefix(base) {
+            files.push(relative.to_string_lossy().to_string());
+        }
+    }
+}
+
+/// Module directories that were moved, as `(old, new)` pairs, from the files renamed in a diff.
+/// A rename only counts as a module move when the old directory is no longer a module (`is_module_dir`).
+pub fn module_moves(renamed_files: &[(String, String)], is_module_dir: impl Fn(&str) -> bool) -> Vec<(String, String)> {
+    let parent = |path: &str| Path::new(path).parent().map(|dir| dir.to_string_lossy().to_string()).unwrap_or_default();
+    let mut moves: Vec<(String, String)> = Vec::new();
+
+    for (old, new) in renamed_files.iter().filter(|(_, new)| new.ends_with(".tf")) {
+        let (old_dir, new_dir) = (parent(old), parent(new));
+        if old_dir != new_dir && !is_module_dir(&old_dir) && !moves.contains(&(old_dir.clone(), new_dir.clone())) {
+            moves.push((old_dir, new_dir));
+        }
+    }
+    moves
+}
+
+/// Warn about modules moved between two commits, or between a commit and the working tree when `to` is None.
+/// The moved module is planned at its new path, but its state still belongs to the old one.
+fn warn_module_moves(root_dir: &str, from: &str, to: Option<&str>) {
+    let renamed = match git::renamed_files(root_dir, from, to) {
+        Ok(renamed) => renamed,
+        Err(e) => {
+            logger::debug(&format!("Rename detection failed: {}", e));
+            return;
+        }
+    };
+    let work_tree = repo_root(root_dir);
+    let is_module_dir = |dir: &str| fs::read_dir(work_tree.join(dir))
+        .map(|entries| entries.flatten().any(|e| e.path().extension().is_some_and(|ext| ext == "tf")))
+        .unwrap_or(false);
+
+    for (old_dir, new_dir) in module_moves(&renamed, is_module_dir) {
+        logger::warn(&format!(
+            "Module {} was moved to {}. It is planned at its new path; make sure it still uses the old state (backend key or state file) or migrate the state before applying.",
+            old_dir, new_dir
+        ));
+    }
+}
+
+/// Stateful modules whose `.tf` files were all deleted, from the changed files of a diff.
+/// Moved modules are left out; rename detection reports them.
+pub fn find_removed_modules(root_dir: &str, changed_files: &[String]) -> Vec<RemovedModule> {
+    let work_tree = repo_root(root_dir);
+    let repo = work_tree.to_string_lossy();
+    let mut deleted: Vec<(PathBuf, Vec<String>)> = Vec::new();
+    for file in changed_files.iter().filter(|file| file.ends_with(".tf")) {
+        let path = Path::new(file);
+        let (Some(dir), Ok(relative)) = (path.parent(), path.strip_prefix(&work_tree)) else {
+            continue;
+        };
+        if path.exists() {
+            continue;
+        }
+        let relative = relative.to_string_lossy().to_string();
+        match deleted.iter_mut().find(|(d, _)| d == dir) {
+            Some((_, files)) => files.push(relative),
+            None => deleted.push((dir.to_path_buf(), vec![relative])),
+        }
+    }
+
+    let mut removed = Vec::new();
+    for (dir, files) in deleted {
+        let still_configured = fs::read_dir(&dir)
+            .map(|entries| entries.flatten().any(|e| e.path().extension().is_some_and(|ext| ext == "tf")))
+            .unwrap_or(false);
+        if still_configured {
+            continue;
+        }
+        let Some(commit) = git::last_commit_with(&repo, &files[0]).ok().flatten() else {
+            continue;
+        };
+        let relative_dir = dir.strip_prefix(&work_tree).unwrap_or(&dir).to_string_lossy().to_string();
+        let moved = git::renamed_files(&repo, &commit, None)
+            .map(|renamed| renamed.iter().any(|(old, _)| Path::new(old).parent() == Some(Path::new(&relative_dir))))
+            .unwrap_or(false);
+        if moved {
+            continue;
+        }
+
+        let contents: Vec<String> = files.iter()
+            .filter_map(|file| git::file_content(&repo, &commit, file).ok())
+            .collect();
+        if declares_state(&contents) || has_local_state(&dir) {
+            removed.push(RemovedModule { path: relative_dir, commit });
+        }
+    }
+    removed
+}
+
+/// Check out the last version of a removed module into a temporary git worktree so it can be planned.
+/// Returns the module directory inside the worktree.
+pub fn checkout_removed_module(removed: &RemovedModule) -> Result<String, String> {
+    let short = &removed.commit[..removed.commit.len().min(12)];
+    let worktree = std::env::temp_dir().join(format!("solarboat-removed-{}", short));
+    if !worktree.exists() {
+        let output = Command::new("git")
+            .args(["worktree", "add", "--detach", "--force", "--quiet"])
+            .arg(&worktree)
+            .arg(&removed.commit)
+            .output()
+            .map_err(|e| format!("Failed to execute git worktree: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("Failed to check out {} at {}: {}", removed.path, short, String::from_utf8_lossy(&output.stderr).trim()));
+        }
     }
+    Ok(worktree.join(&removed.path).to_string_lossy().to_string())
+}
 
-    Ok(changed_files)
+/// Root of the git working tree containing `root_dir`, or `root_dir` itself outside a repository
+fn repo_root(root_dir: &str) -> PathBuf {
+    git::work_tree(root_dir).unwrap_or_else(|_| PathBuf::from(root_dir))
 }
 
-/// Get changes between two specific commits (original version for backward compatibility)
-fn get_changes_between_commits(root_dir: &str, from_commit: &str, to_commit: &str) -> Result<Vec<String>, String> {
-    let mut changed_files = Vec::new();
+/// Absolute path of a file git reports relative to the repository root, canonicalized when it exists
+fn repo_file_path(work_tree: &Path, file: &str) -> String {
+    let path = work_tree.join(file);
+    fs::canonicalize(&path).unwrap_or(path).to_string_lossy().to_string()
+}
 
-    logger::info(&format!("Getting changes between {} and {}", from_commit, to_commit));
-    
-    // Get changes between the two commits
-    let diff_output = Command::new("git")
-        .args(&["diff", "--name-only", from_commit, to_commit])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
+/// Times a fetch is attempted before giving up
+const FETCH_ATTEMPTS: u32 = 3;
 
-    if diff_output.status.success() {
-        changed_files.extend(
-            String::from_utf8_lossy(&diff_output.stdout)
-                .lines()
-                .filter(|line| line.ends_with(".tf"))
-                .map(|line| {
-                    // Use a more robust approach to handle paths that might not exist
-                    let file_path = Path::new(root_dir).join(line);
-                    if file_path.exists() {
-                        // If the file exists, canonicalize it
-                        fs::canonicalize(file_path)
-                            .map_err(|e| e.to_string())
-                            .unwrap()
-                            .to_str()
-                            .unwrap()
-                            .to_string()
-                    } else {
-                        // If the file doesn't exist, use the absolute path from the current directory
-                        let current_dir = std::env::current_dir().map_err(|e| e.to_string()).unwrap();
-                        current_dir.join(root_dir).join(line)
-                            .to_str()
-                            .unwrap()
-                            .to_string()
-                    }
-                })
-        );
-    }
+/// Commits added to a shallow clone's history by each `git fetch --deepen`
+const DEEPEN_STEP: u32 = 50;
 
-    // Remove duplicates
-    changed_files.sort();
-    changed_files.dedup();
+/// How many times a shallow clone is deepened while looking for a merge base
+const DEEPEN_ATTEMPTS: u32 = 5;
 
-    if !changed_files.is_empty() {
-        logger::info(&format!("Found {} changed .tf files", changed_files.len()));
-        logger::changed_files_summary(&changed_files);
-    } else {
-        logger::info("No .tf files changed between the commits");
+/// Run `git fetch` with the given arguments, retrying with backoff on failure
+fn fetch_with_retries(root_dir: &str, args: &[&str]) -> bool {
+    for attempt in 1..=FETCH_ATTEMPTS {
+        let fetched = Command::new("git")
+            .arg("fetch")
+            .args(["--no-tags", "--quiet"])
+            .args(args)
+            .current_dir(root_dir)
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false);
+        if fetched {
+            return true;
+        }
+        if attempt < FETCH_ATTEMPTS {
+            std::thread::sleep(Duration::from_secs(1 << attempt));
+        }
     }
+    false
+}
 
-    Ok(changed_files)
+/// Make sure a commit exists locally, fetching it from origin if it is missing (e.g. in a shallow clone)
+fn ensure_commit(root_dir: &str, commit: &str) -> bool {
+    if git::has_commit(root_dir, commit) {
+        return true;
+    }
+    logger::info(&format!("Commit {} is not available locally, fetching it from origin", commit));
+    if fetch_with_retries(root_dir, &["origin", commit]) && git::has_commit(root_dir, commit) {
+        return true;
+    }
+    logger::warn(&format!("Could not fetch commit {} from origin", commit));
+    false
 }
 
-pub fn get_git_changed_files(root_dir: &str, default_branch: &str) -> Result<Vec<String>, String> {
+/// Merge base of HEAD and the default branch: origin/{default_branch}, fetched if needed, or the local
+/// branch when the remote one is unavailable
+fn branch_merge_base(root_dir: &str, default_branch: &str) -> Option<String> {
     // First, try to get the merge-base with origin/{default_branch}
-    let merge_base_output = Command::new("git")
-        .args(&["merge-base", &format!("origin/{}", default_branch), "HEAD"])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
+    if let Ok(merge_base) = git::merge_base(root_dir, &format!("origin/{}", default_branch), "HEAD") {
+        return Some(merge_base);
+    }
+    if let Some(merge_base) = fetch_merge_base(root_dir, default_branch) {
+        return Some(merge_base);
+    }
 
-    let merge_base = if merge_base_output.status.success() {
-        String::from_utf8_lossy(&merge_base_output.stdout).trim().to_string()
-    } else {
-        // If origin/{default_branch} is not available, try with local {default_branch}
-        let local_merge_base = Command::new("git")
-            .args(&["merge-base", default_branch, "HEAD"])
-            .current_dir(root_dir)
-            .output()
-            .map_err(|e| e.to_string())?;
-            
-        if !local_merge_base.status.success() {
+    // If origin/{default_branch} is not available, try with local {default_branch}
+    match git::merge_base(root_dir, default_branch, "HEAD") {
+        Ok(merge_base) => {
+            logger::warn(&format!("Using merge base with local {} because origin/{} is unavailable", default_branch, default_branch));
+            Some(merge_base)
+        }
+        Err(_) => {
+            logger::warn(&format!("Could not determine a merge base with {}; no branch changes detected", default_branch));
+            None
+        }
+    }
+}
+
+/// Find the merge base of origin/{default_branch} and HEAD when it is missing locally: fetch the
+/// default branch if needed and deepen a shallow clone until the histories meet
+fn fetch_merge_base(root_dir: &str, default_branch: &str) -> Option<String> {
+    let remote_ref = format!("origin/{}", default_branch);
+    let refspec = format!("+refs/heads/{0}:refs/remotes/origin/{0}", default_branch);
+    let merge_base = || git::merge_base(root_dir, &remote_ref, "HEAD").ok();
+
+    if !git::has_commit(root_dir, &remote_ref) {
+        logger::info(&format!("{} is not available locally, fetching it", remote_ref));
+        let depth = format!("--depth={}", DEEPEN_STEP);
+        let mut args = vec!["origin", refspec.as_str()];
+        if git::is_shallow(root_dir) {
+            args.insert(0, depth.as_str());
+        }
+        if !fetch_with_retries(root_dir, &args) {
+            logger::warn(&format!("Could not fetch {} from origin", remote_ref));
+            return None;
+        }
+        if let Some(found) = merge_base() {
+            return Some(found);
+        }
+    }
+
+    let deepen = format!("--deepen={}", DEEPEN_STEP);
+    for _ in 0..DEEPEN_ATTEMPTS {
+        if !git::is_shallow(root_dir) {
+            break;
+        }
+        logger::info(&format!("Shallow clone: deepening history by {} commits to find the merge base", DEEPEN_STEP));
+        if !fetch_with_retries(root_dir, &[deepen.as_str(), "origin", refspec.as_str(), "HEAD"]) {
+            break;
+        }
+        if let Some(found) = merge_base() {
+            return Some(found);
+        }
+    }
+    None
+}
+
+pub fn get_git_changed_files(root_dir: &str, default_branch: &str, tracked: &TrackedFiles, strategies: &DetectionStrategies) -> Result<Vec<String>, String> {
+    let merge_base = if strategies.allows(DetectionStrategy::MergeBase) {
+        match branch_merge_base(root_dir, default_branch) {
+            Some(merge_base) => Some(merge_base),
             // If we can't find a merge base, return an empty list
-            return Ok(Vec::new());
+            None => return Ok(Vec::new()),
         }
-        String::from_utf8_lossy(&local_merge_base.stdout).trim().to_string()
+    } else {
+        None
     };
+    let uncommitted = strategies.allows(DetectionStrategy::Uncommitted);
 
     // Get both staged and unstaged changes
     let mut changed_files = Vec::new();
+    let work_tree = repo_root(root_dir);
 
     // Get uncommitted changes
-    let status_output = Command::new("git")
-        .arg("status")
-        .arg("--porcelain")
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
-
-    if status_output.status.success() {
-        changed_files.extend(
-            String::from_utf8_lossy(&status_output.stdout)
-                .lines()
-                .filter(|line| line.ends_with(".tf"))
-                .map(|line| {
-                    let file = line[3..].trim();
-                    // Use a more robust approach to handle paths that might not exist
-                    let file_path = Path::new(root_dir).join(file);
-                    if file_path.exists() {
-                        // If the file exists, canonicalize it
-                        fs::canonicalize(file_path)
-                            .map_err(|e| e.to_string())
-                            .unwrap()
-                            .to_str()
-                            .unwrap()
-                            .to_string()
-                    } else {
-                        // If the file doesn't exist, use the absolute path from the current directory
-                        let current_dir = std::env::current_dir().map_err(|e| e.to_string()).unwrap();
-                        current_dir.join(root_dir).join(file)
-                            .to_str()
-                            .unwrap()
-                            .to_string()
-                    }
-                })
-        );
+    if uncommitted {
+        if let Ok(files) = git::status_files(root_dir, &tracked.pathspecs) {
+            changed_files.extend(
+                files.iter()
+                    .filter(|file| tracked.matches(file))
+                    .map(|file| repo_file_path(&work_tree, file))
+            );
+        }
     }
 
-    // Get changes between current branch and merge-base
-    let diff_output = Command::new("git")
-        .args(&["diff", "--name-only", &merge_base])
-        .current_dir(root_dir)
-        .output()
-        .map_err(|e| e.to_string())?;
-
-    if diff_output.status.success() {
-        changed_files.extend(
-            String::from_utf8_lossy(&diff_output.stdout)
-                .lines()
-                .filter(|line| line.ends_with(".tf"))
-                .map(|line| {
-                    // Use a more robust approach to handle paths that might not exist
-                    let file_path = Path::new(root_dir).join(line);
-                    if file_path.exists() {
-                        // If the file exists, canonicalize it
-                        fs::canonicalize(file_path)
-                            .map_err(|e| e.to_string())
-                            .unwrap()
-                            .to_str()
-                            .unwrap()
-                            .to_string()
-                    } else {
-                        // If the file doesn't exist, use the absolute path from the current directory
-                        let current_dir = std::env::current_dir().map_err(|e| e.to_string()).unwrap();
-                        current_dir.join(root_dir).join(line)
-                            .to_str()
-                            .unwrap()
-                            .to_string()
-                    }
-                })
-        );
+    // Get changes between current branch and merge-base; diffing against the working tree includes uncommitted edits
+    if let Some(merge_base) = merge_base {
+        let to = if uncommitted { None } else { Some("HEAD") };
+        warn_module_moves(root_dir, &merge_base, to);
+        if let Ok(files) = diff_files(root_dir, &merge_base, to, &tracked.pathspecs, tracked) {
+            changed_files.extend(
+                files.iter()
+                    .filter(|line| tracked.matches(line))
+                    .map(|line| repo_file_path(&work_tree, line))
+            );
+        }
     }
 
     // Remove duplicates
@@ -832,7 +2340,7 @@ pub fn get_git_changed_files(root_dir: &str, default_branch: &str) -> Result<Vec
     Ok(changed_files)
 }
 
-pub fn process_changed_modules(changed_files: &[String], modules: &mut HashMap<String, Module>) -> Result<Vec<String>, String> {
+pub fn process_changed_modules(changed_files: &[String], modules: &mut HashMap<String, Module>, propagate_depth: Option<u32>) -> Result<Vec<String>, String> {
     let mut affected_modules = Vec::new();
     let mut processed = HashMap::new();
 
@@ -843,6 +2351,15 @@ pub fn process_changed_modules(changed_files: &[String], modules: &mut HashMap<S
     for file in changed_files {
         let file_path = Path::new(file);
         
+        // Files a module references can live outside its directory and belong to several modules
+        let referencing: Vec<String> = modules.iter()
+            .filter(|(_, module)| module.parsed.referenced_files.iter().any(|referenced| referenced.as_path() == file_path))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for module_path in referencing {
+            mark_module_changed(&module_path, modules, &mut affected_modules, &mut processed);
+        }
+        
         // Find the module this file belongs to
         for module_path in &module_paths {
             let module_path = Path::new(module_path);
@@ -855,9 +2372,164 @@ pub fn process_changed_modules(changed_files: &[String], modules: &mut HashMap<S
         }
     }
 
+    if let Some(max_depth) = propagate_depth {
+        propagate_dependents(&mut affected_modules, modules, max_depth);
+    }
+
     Ok(affected_modules)
 }
 
+/// Also queue stateful modules that consume changed modules, through module blocks or
+/// terraform_remote_state, up to `max_depth` levels away
+pub fn propagate_dependents(affected_modules: &mut Vec<String>, modules: &HashMap<String, Module>, max_depth: u32) {
+    let mut queue: VecDeque<(String, u32)> = affected_modules.iter().map(|path| (path.clone(), 0)).collect();
+    let mut visited: HashSet<String> = affected_modules.iter().cloned().collect();
+
+    while let Some((path, depth)) = queue.pop_front() {
+        if depth >= max_depth {
+            continue;
+        }
+        let users = modules.get(&path).map(|module| module.used_by.clone()).unwrap_or_default();
+        for user_path in users {
+            if !visited.insert(user_path.clone()) {
+                continue;
+            }
+            if modules.get(&user_path).is_some_and(|user| user.parsed.is_stateful) {
+                logger::info(&format!("Adding dependent of changed module: {}",
+                         user_path.rsplit('/').next().unwrap_or(&user_path)));
+                affected_modules.push(user_path.clone());
+            }
+            queue.push_back((user_path, depth + 1));
+        }
+    }
+}
+
+/// Why a selected module was included: its own or referenced files changed, a stateless module it calls
+/// changed, or it consumes another selected module
+pub fn selection_reasons(module_path: &str, changed_files: &[String], modules: &HashMap<String, Module>, affected_modules: &[String]) -> Vec<SelectionReason> {
+    let module = match modules.get(module_path) {
+        Some(module) => module,
+        None => return Vec::new(),
+    };
+    // Nested modules own the files in their directory
+    let owner = |file: &str| modules.keys()
+        .filter(|path| Path::new(file).starts_with(path))
+        .max_by_key(|path| path.len());
+
+    let mut reasons = Vec::new();
+    for file in changed_files {
+        if owner(file).is_some_and(|owner| owner == module_path) {
+            reasons.push(SelectionReason::Changed(relative_path(file)));
+        }
+        if module.parsed.referenced_files.iter().any(|referenced| referenced.as_path() == Path::new(file)) {
+            reasons.push(SelectionReason::Referenced(relative_path(file)));
+        }
+    }
+    for dependency in &module.depends_on {
+        if modules.get(dependency).is_some_and(|dependency| !dependency.parsed.is_stateful) {
+            for file in changed_files.iter().filter(|file| owner(file).is_some_and(|owner| owner == dependency)) {
+                reasons.push(SelectionReason::StatelessDependency {
+                    module: relative_path(dependency),
+                    file: relative_path(file),
+                });
+            }
+        }
+    }
+
+    if reasons.is_empty() {
+        if let Some(chain) = dependency_chain(module_path, modules, affected_modules) {
+            reasons.push(SelectionReason::Dependent(chain.iter().map(|path| relative_path(path)).collect()));
+        }
+    }
+    reasons
+}
+
+/// Shortest chain of dependencies from a module to another selected module
+fn dependency_chain(module_path: &str, modules: &HashMap<String, Module>, affected_modules: &[String]) -> Option<Vec<String>> {
+    let mut queue = VecDeque::from([vec![module_path.to_string()]]);
+    let mut visited = HashSet::from([module_path.to_string()]);
+
+    while let Some(chain) = queue.pop_front() {
+        let last = chain.last()?;
+        for dependency in modules.get(last).map(|module| module.depends_on.clone()).unwrap_or_default() {
+            if !visited.insert(dependency.clone()) {
+                continue;
+            }
+            let mut next = chain.clone();
+            next.push(dependency.clone());
+            if affected_modules.contains(&dependency) {
+                return Some(next);
+            }
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+/// Path relative to the current directory, or unchanged if it is outside it
+fn relative_path(path: &str) -> String {
+    std::env::current_dir().ok()
+        .and_then(|current_dir| Path::new(path).strip_prefix(current_dir).ok().map(|p| p.to_string_lossy().to_string()))
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Path of a file, given relative to the current directory, relative to the repository root
+pub fn repo_relative_path(path: &str) -> String {
+    let absolute = fs::canonicalize(path)
+        .or_else(|_| std::env::current_dir().map(|current_dir| current_dir.join(path)))
+        .unwrap_or_else(|_| PathBuf::from(path));
+    absolute.strip_prefix(repo_root(".")).unwrap_or(&absolute).to_string_lossy().to_string()
+}
+
+/// Write modules to a file, one repository-relative path per line, for `plan`/`apply --modules-file`
+pub fn write_modules_file(path: &str, modules: &[String]) -> Result<(), String> {
+    let content: String = modules.iter().map(|module| format!("{}\n", repo_relative_path(module))).collect();
+    fs::write(path, content).map_err(|e| format!("Failed to write modules file {}: {}", path, e))
+}
+
+/// Read a module list written by `scan --output-file`, or from stdin for `-`. Each line is a module directory
+/// relative to the repository root or absolute; blank lines and `#` comments are skipped.
+pub fn read_modules_file(path: &str) -> Result<Vec<String>, String> {
+    let content = if path == "-" {
+        std::io::read_to_string(std::io::stdin()).map_err(|e| format!("Failed to read modules from stdin: {}", e))?
+    } else {
+        fs::read_to_string(path).map_err(|e| format!("Failed to read modules file {}: {}", path, e))?
+    };
+    let lines = content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+    resolve_module_dirs(lines, &repo_root("."))
+}
+
+/// Absolute paths of module directories given relative to `base` or absolute, without duplicates.
+/// Fails for a path that is not an existing directory.
+pub fn resolve_module_dirs<'a>(paths: impl IntoIterator<Item = &'a str>, base: &Path) -> Result<Vec<String>, String> {
+    let mut modules = Vec::new();
+    for path in paths {
+        let dir = fs::canonicalize(base.join(path)).map_err(|_| format!("Module {} does not exist", path))?;
+        if !dir.is_dir() {
+            return Err(format!("Module {} is not a directory", path));
+        }
+        let dir = dir.to_string_lossy().to_string();
+        if !modules.contains(&dir) {
+            modules.push(dir);
+        }
+    }
+    Ok(modules)
+}
+
+/// Base and head of the commits `--explain` attributes changes to: the compared refs, or the commits
+/// since the merge base with the default branch. On the default branch itself, no base limits the history.
+pub fn explanation_range(default_branch: &str, compare: Option<&CompareRefs>) -> (Option<String>, String) {
+    if let Some(refs) = compare {
+        return (Some(refs.base.clone()), refs.head.clone());
+    }
+    let head = git::resolve_commit(".", "HEAD").ok();
+    let base = git::merge_base(".", &format!("origin/{}", default_branch), "HEAD")
+        .or_else(|_| git::merge_base(".", default_branch, "HEAD"))
+        .ok()
+        .filter(|base| Some(base) != head.as_ref());
+    (base, "HEAD".to_string())
+}
+
 pub fn mark_module_changed(module_path: &str, all_modules: &mut HashMap<String, Module>, affected_modules: &mut Vec<String>, processed: &mut HashMap<String, bool>) {
     if *processed.get(module_path).unwrap_or(&false) {
         return;
@@ -865,7 +2537,7 @@ pub fn mark_module_changed(module_path: &str, all_modules: &mut HashMap<String,
     processed.insert(module_path.to_string(), true);
 
     if let Some(module) = all_modules.get(module_path) {
-        if module.is_stateful {
+        if module.parsed.is_stateful {
             // Add this stateful module to affected modules if not already added
             if !affected_modules.contains(&module_path.to_string()) {
                 affected_modules.push(module_path.to_string());
@@ -882,7 +2554,7 @@ pub fn mark_module_changed(module_path: &str, all_modules: &mut HashMap<String,
                 // Check all modules that use this stateless module
                 for user_module_path in &module.used_by {
                     if let Some(user_module) = all_modules.get(user_module_path) {
-                        if user_module.is_stateful {
+                        if user_module.parsed.is_stateful {
                             // Mark this stateful module as affected since it uses a changed stateless module
                             // Only add and print if not already in the list
                             if !affected_modules.contains(user_module_path) {
@@ -897,3 +2569,878 @@ pub fn mark_module_changed(module_path: &str, all_modules: &mut HashMap<String,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_referenced_files() {
+        let content = r#"
+resource "aws_instance" "web" {
+  user_data = templatefile("${path.module}/templates/user-data.sh", { port = 80 })
+}
+
+resource "aws_iam_policy" "app" {
+  policy = file("../shared/policies/app.json")
+}
+
+data "local_file" "ca" {
+  filename = "certs/ca.pem"
+}
+
+locals {
+  dynamic = file("${var.config_dir}/settings.json")
+}
+"#;
+        let files = find_referenced_files(content, Path::new("/repo/stacks/app"));
+        assert_eq!(files, vec![
+            PathBuf::from("/repo/stacks/app/../shared/policies/app.json"),
+            PathBuf::from("/repo/stacks/app/certs/ca.pem"),
+            PathBuf::from("/repo/stacks/app/templates/user-data.sh"),
+        ]);
+    }
+
+    #[test]
+    fn test_pin_changes() {
+        let old = r#"
+module "vpc" {
+  source  = "terraform-aws-modules/vpc/aws"
+  version = "5.1.0"
+}
+
+module "dns" {
+  source = "git::https://github.com/acme/dns.git?ref=v1.2.0&depth=1"
+}
+
+module "shared" {
+  source = "../shared"
+}
+"#;
+        assert_eq!(find_module_pins(old), vec![
+            ModulePin { name: "vpc".to_string(), source: "terraform-aws-modules/vpc/aws".to_string(), version: Some("5.1.0".to_string()) },
+            ModulePin { name: "dns".to_string(), source: "git::https://github.com/acme/dns.git?depth=1".to_string(), version: Some("v1.2.0".to_string()) },
+        ]);
+
+        let new = old.replace("5.1.0", "5.2.0").replace("ref=v1.2.0", "ref=v1.3.0");
+        assert_eq!(pin_changes(old, &new), vec![
+            "module \"vpc\" terraform-aws-modules/vpc/aws 5.1.0 → 5.2.0",
+            "module \"dns\" git::https://github.com/acme/dns.git?depth=1 v1.2.0 → v1.3.0",
+        ]);
+        assert!(pin_changes(old, old).is_empty());
+    }
+
+    #[test]
+    fn test_find_remote_states() {
+        let producer = r#"
+terraform {
+  backend "s3" {
+    bucket = "acme-state"
+    key    = "networking/terraform.tfstate"
+  }
+}
+"#;
+        let consumer = r#"
+data "terraform_remote_state" "networking" {
+  backend = "s3"
+  config = {
+    bucket = "acme-state"
+    key    = "networking/terraform.tfstate"
+  }
+}
+
+data "terraform_remote_state" "dns" {
+  backend = "local"
+  config = {
+    path = "../dns/terraform.tfstate"
+  }
+}
+
+data "terraform_remote_state" "dynamic" {
+  backend = "s3"
+  config = {
+    key = "${var.env}/terraform.tfstate"
+  }
+}
+"#;
+        let module_dir = Path::new("/repo/stacks/app");
+        let address = |container: Option<&str>, key: &str| StateAddress { container: container.map(str::to_string), key: key.to_string() };
+        assert_eq!(find_backend_states(&[producer.to_string()], &[], module_dir), vec![address(Some("acme-state"), "networking/terraform.tfstate")]);
+        assert_eq!(find_backend_states(&[consumer.to_string()], &[], module_dir), vec![address(None, "/repo/stacks/app/terraform.tfstate")]);
+        assert_eq!(find_remote_states(consumer, module_dir), vec![
+            address(Some("acme-state"), "networking/terraform.tfstate"),
+            address(None, "/repo/stacks/dns/terraform.tfstate"),
+        ]);
+
+        // Partial configurations complete an empty backend block, one state per file
+        let partial = "terraform {\n  backend \"s3\" {\n    bucket = \"acme-state\"\n  }\n}\n".to_string();
+        let configs = ["key = \"dev/app.tfstate\"\n".to_string(), "bucket = \"acme-prod\"\nkey = \"prod/app.tfstate\"\n".to_string()];
+        assert_eq!(find_backend_states(&[partial], &configs, module_dir), vec![
+            address(Some("acme-state"), "dev/app.tfstate"),
+            address(Some("acme-prod"), "prod/app.tfstate"),
+        ]);
+
+        // Terraform Cloud workspaces, read through the remote backend
+        let cloud = "terraform {\n  cloud {\n    organization = \"acme\"\n    workspaces {\n      name = \"networking\"\n    }\n  }\n}\n".to_string();
+        let reader = "data \"terraform_remote_state\" \"net\" {\n  backend = \"remote\"\n  config = {\n    organization = \"acme\"\n    workspaces = {\n      name = \"networking\"\n    }\n  }\n}\n";
+        let states = find_backend_states(&[cloud], &[], module_dir);
+        assert_eq!(states, vec![address(Some("acme"), "networking")]);
+        assert!(states[0].matches(&find_remote_states(reader, module_dir)[0]));
+        assert!(!address(Some("other"), "networking").matches(&states[0]));
+    }
+
+    #[test]
+    fn test_remote_state_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let reader = |bucket: &str, key: &str| format!(
+            "data \"terraform_remote_state\" \"upstream\" {{\n  backend = \"s3\"\n  config = {{\n    bucket = \"{}\"\n    key    = \"{}\"\n  }}\n}}\n",
+            bucket, key
+        );
+        for (file, content) in [
+            ("stacks/networking/main.tf", "terraform {\n  backend \"s3\" {\n    bucket = \"acme-state\"\n    key    = \"networking.tfstate\"\n  }\n}\n".to_string()),
+            ("stacks/app/main.tf", format!("terraform {{\n  backend \"s3\" {{}}\n}}\n{}", reader("acme-state", "networking.tfstate"))),
+            ("stacks/other/main.tf", format!("terraform {{\n  backend \"s3\" {{}}\n}}\n{}", reader("other-state", "networking.tfstate"))),
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), content).unwrap();
+        }
+        let mut modules = HashMap::new();
+        discover_modules(root.to_str().unwrap(), &DiscoveryOptions::default(), &mut ModuleCache::default(), &mut modules).unwrap();
+        build_dependency_graph(&mut modules, &GraphOverrides::default()).unwrap();
+
+        let networking = root.join("stacks/networking").to_string_lossy().to_string();
+        let depends_on = |module: &str| modules[&root.join(module).to_string_lossy().to_string()].depends_on.clone();
+        assert!(depends_on("stacks/app").contains(&networking));
+        // Same key in another bucket is a different state
+        assert!(!depends_on("stacks/other").contains(&networking));
+    }
+
+    #[test]
+    fn test_find_dependency_cycles() {
+        let module = |depends_on: &[&str]| Module {
+            depends_on: depends_on.iter().map(|path| path.to_string()).collect(),
+            ..Default::default()
+        };
+        let modules = HashMap::from([
+            ("networking".to_string(), module(&["shared"])),
+            ("shared".to_string(), module(&["dns"])),
+            ("dns".to_string(), module(&["networking", "shared"])),
+            ("app".to_string(), module(&["networking", "app"])),
+            ("database".to_string(), module(&["networking"])),
+        ]);
+
+        assert_eq!(find_dependency_cycles(&modules), vec![
+            vec!["app"],
+            vec!["dns", "networking", "shared"],
+            vec!["dns", "shared"],
+        ]);
+        assert!(find_dependency_cycles(&HashMap::from([("app".to_string(), module(&["networking"]))])).is_empty());
+    }
+
+    #[test]
+    fn test_propagate_dependents() {
+        let module = |stateful: bool, used_by: &[&str]| Module {
+            parsed: ParsedModule { is_stateful: stateful, ..Default::default() },
+            used_by: used_by.iter().map(|path| path.to_string()).collect(),
+            ..Default::default()
+        };
+        let modules = HashMap::from([
+            ("networking".to_string(), module(true, &["shared", "database"])),
+            ("shared".to_string(), module(false, &["compute"])),
+            ("database".to_string(), module(true, &["app"])),
+            ("compute".to_string(), module(true, &[])),
+            ("app".to_string(), module(true, &["networking"])),
+        ]);
+
+        let mut affected = vec!["networking".to_string()];
+        propagate_dependents(&mut affected, &modules, 1);
+        assert_eq!(affected, vec!["networking", "database"]);
+
+        let mut affected = vec!["networking".to_string()];
+        propagate_dependents(&mut affected, &modules, u32::MAX);
+        assert_eq!(affected, vec!["networking", "database", "compute", "app"]);
+    }
+
+    #[test]
+    fn test_module_moves() {
+        let renamed = vec![
+            ("stacks/app/main.tf".to_string(), "stacks/web/main.tf".to_string()),
+            ("stacks/app/vars.tf".to_string(), "stacks/web/vars.tf".to_string()),
+            ("stacks/app/README.md".to_string(), "stacks/web/README.md".to_string()),
+            ("stacks/db/outputs.tf".to_string(), "stacks/api/outputs.tf".to_string()),
+            ("stacks/cache/main.tf".to_string(), "stacks/cache/cache.tf".to_string()),
+        ];
+
+        // stacks/db still has other .tf files, so only a file moved out of it
+        let moves = module_moves(&renamed, |dir| dir == "stacks/db");
+        assert_eq!(moves, vec![("stacks/app".to_string(), "stacks/web".to_string())]);
+    }
+
+    #[test]
+    fn test_selection_reasons() {
+        let module = |stateful: bool, depends_on: &[&str], used_by: &[&str]| Module {
+            parsed: ParsedModule { is_stateful: stateful, ..Default::default() },
+            depends_on: depends_on.iter().map(|path| path.to_string()).collect(),
+            used_by: used_by.iter().map(|path| path.to_string()).collect(),
+            ..Default::default()
+        };
+        let modules = HashMap::from([
+            ("/repo/stacks/app".to_string(), module(true, &["/repo/modules/vpc", "/repo/stacks/db"], &[])),
+            ("/repo/modules/vpc".to_string(), module(false, &[], &["/repo/stacks/app"])),
+            ("/repo/stacks/db".to_string(), module(true, &[], &["/repo/stacks/app"])),
+            ("/repo/stacks/db/replica".to_string(), module(true, &[], &[])),
+        ]);
+        let changed = vec!["/repo/modules/vpc/main.tf".to_string(), "/repo/stacks/db/replica/main.tf".to_string()];
+        let affected = vec!["/repo/stacks/app".to_string(), "/repo/stacks/db/replica".to_string()];
+
+        assert_eq!(
+            selection_reasons("/repo/stacks/app", &changed, &modules, &affected),
+            vec![SelectionReason::StatelessDependency {
+                module: "/repo/modules/vpc".to_string(),
+                file: "/repo/modules/vpc/main.tf".to_string(),
+            }]
+        );
+        assert_eq!(
+            selection_reasons("/repo/stacks/db/replica", &changed, &modules, &affected),
+            vec![SelectionReason::Changed("/repo/stacks/db/replica/main.tf".to_string())]
+        );
+        assert!(selection_reasons("/repo/stacks/db", &changed, &modules, &affected).is_empty());
+
+        let propagated = vec!["/repo/stacks/db/replica".to_string(), "/repo/stacks/db".to_string()];
+        let modules = HashMap::from([
+            ("/repo/stacks/db".to_string(), module(true, &["/repo/stacks/db/replica"], &[])),
+            ("/repo/stacks/db/replica".to_string(), module(true, &[], &["/repo/stacks/db"])),
+        ]);
+        assert_eq!(
+            selection_reasons("/repo/stacks/db", &changed, &modules, &propagated),
+            vec![SelectionReason::Dependent(vec!["/repo/stacks/db".to_string(), "/repo/stacks/db/replica".to_string()])]
+        );
+    }
+
+    #[test]
+    fn test_get_impacted_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        for (file, content) in [
+            ("modules/vpc/main.tf", "variable \"cidr\" {}\n"),
+            ("stacks/app/main.tf", "terraform {\n  backend \"s3\" {}\n}\nmodule \"vpc\" {\n  source = \"../../modules/vpc\"\n}\n"),
+            ("stacks/db/main.tf", "terraform {\n  backend \"s3\" {}\n}\n"),
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), content).unwrap();
+        }
+        let path = |file: &str| root.join(file).to_string_lossy().to_string();
+
+        let scan = get_impacted_modules(
+            root.to_str().unwrap(),
+            &[path("modules/vpc/main.tf"), path("stacks/db/README.md")],
+            None,
+            &DiscoveryOptions::default(),
+            &GraphOverrides::default(),
+            None,
+        ).unwrap();
+        assert_eq!(scan.paths(), vec![path("stacks/app")]);
+        let app = &scan.modules[0];
+        assert!(app.stateful);
+        assert_eq!(app.classification, Classification::Backend);
+        assert!(matches!(&app.triggers[..], [SelectionReason::StatelessDependency { .. }]));
+        assert_eq!(app.depends_on, vec![path("modules/vpc")]);
+        assert!(app.used_by.is_empty());
+
+        // A dependency declared in the configuration counts like a module source
+        let mut affected = get_impacted_modules(
+            root.to_str().unwrap(),
+            &[path("modules/vpc/main.tf")],
+            None,
+            &DiscoveryOptions::default(),
+            &GraphOverrides { dependencies: vec![(path("stacks/db"), path("modules/vpc"))], ..Default::default() },
+            None,
+        ).unwrap().paths();
+        affected.sort();
+        assert_eq!(affected, vec![path("stacks/app"), path("stacks/db")]);
+
+        // A shared module misclassified as stateful because it calls modules
+        let affected = get_impacted_modules(
+            root.to_str().unwrap(),
+            &[path("modules/vpc/main.tf")],
+            None,
+            &DiscoveryOptions::default(),
+            &GraphOverrides { stateful: vec![(path("stacks/app"), false), (path("modules/vpc"), true)], ..Default::default() },
+            None,
+        ).unwrap().paths();
+        assert_eq!(affected, vec![path("modules/vpc")]);
+    }
+
+    #[test]
+    fn test_scan_report() {
+        let mut details = DetectionReport::default();
+        details.graph.add_module("/repo/modules/vpc".to_string(), ModuleNode::default());
+        details.graph.add_module("/repo/stacks/app".to_string(), ModuleNode {
+            stateful: true,
+            classification: Classification::Backend,
+            required_version: Some(">= 1.5".to_string()),
+            ..Default::default()
+        });
+        details.graph.add_dependency("/repo/stacks/app", "/repo/modules/vpc").unwrap();
+        let trigger = SelectionReason::Changed("/repo/stacks/app/main.tf".to_string());
+        details.reasons.push(("/repo/stacks/app".to_string(), vec![trigger.clone()]));
+
+        let report = ScanReport::new(vec!["/repo/stacks/app".to_string(), "/repo/stacks/gone".to_string()], details);
+        let app = &report.modules[0];
+        assert!(app.stateful);
+        assert_eq!(app.classification, Classification::Backend);
+        assert_eq!(app.required_version.as_deref(), Some(">= 1.5"));
+        assert_eq!(app.triggers, vec![trigger]);
+        assert_eq!(app.depends_on, vec!["/repo/modules/vpc".to_string()]);
+        assert!(app.used_by.is_empty());
+        // A module missing from the graph is reported with defaults
+        assert!(!report.modules[1].stateful);
+        assert!(report.modules[1].triggers.is_empty());
+
+        // The CLI only takes the paths, in selection order
+        let (paths, details) = report.into_parts();
+        assert_eq!(paths, vec!["/repo/stacks/app".to_string(), "/repo/stacks/gone".to_string()]);
+        assert_eq!(details.graph.dependents("/repo/modules/vpc").collect::<Vec<_>>(), vec!["/repo/stacks/app"]);
+    }
+
+    #[test]
+    fn test_stateful_override() {
+        let module = |content: &str| Module { parsed: ParsedModule::parse(Path::new("/repo/modules/shared"), &[content.to_string()], &[], false), ..Default::default() };
+        let mut modules = HashMap::from([
+            ("/repo/modules/shared".to_string(), module("module \"vpc\" {\n  source = \"../vpc\"\n}\n")),
+            ("/repo/modules/vpc".to_string(), module("variable \"cidr\" {}\n")),
+        ]);
+        assert_eq!(modules["/repo/modules/shared"].parsed.classification, Classification::ModuleCalls);
+
+        let overrides = GraphOverrides {
+            stateful: vec![
+                ("/repo/modules/shared".to_string(), false),
+                ("/repo/modules/vpc".to_string(), true),
+                ("/repo/modules/missing".to_string(), true),
+            ],
+            ..Default::default()
+        };
+        build_dependency_graph(&mut modules, &overrides).unwrap();
+        assert!(!modules["/repo/modules/shared"].parsed.is_stateful);
+        assert!(modules["/repo/modules/vpc"].parsed.is_stateful);
+        assert_eq!(modules["/repo/modules/shared"].parsed.classification, Classification::Configured);
+        assert_eq!(modules["/repo/modules/vpc"].parsed.classification.to_string(), "set in the configuration");
+        // Settings for directories that aren't modules are ignored
+        assert!(!modules.contains_key("/repo/modules/missing"));
+        assert_eq!(modules["/repo/modules/shared"].depends_on, vec!["/repo/modules/vpc".to_string()]);
+    }
+
+    #[test]
+    fn test_discover_modules_skips_excluded_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        for (file, content) in [
+            ("stacks/app/main.tf", "terraform {\n  backend \"s3\" {}\n}\nmodule \"vpc\" {\n  source = \"../../vendor/vpc\"\n}\n"),
+            ("stacks/app/.terraform/modules/vpc/main.tf", "terraform {\n  backend \"s3\" {}\n}\n"),
+            ("vendor/vpc/main.tf", "variable \"cidr\" {}\n"),
+            ("vendor/unused/main.tf", "variable \"cidr\" {}\n"),
+            ("archive/old/main.tf", "terraform {\n  backend \"s3\" {}\n}\n"),
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), content).unwrap();
+        }
+        let path = |dir: &str| root.join(dir).to_string_lossy().to_string();
+        let discover = |excluded: &[String]| {
+            let mut modules = HashMap::new();
+            let discovery = DiscoveryOptions { excluded_dirs: excluded.to_vec(), ..Default::default() };
+            discover_modules(root.to_str().unwrap(), &discovery, &mut ModuleCache::default(), &mut modules).unwrap();
+            let mut paths: Vec<String> = modules.into_keys().collect();
+            paths.sort();
+            paths
+        };
+
+        // Vendored modules are only found when sourced
+        assert_eq!(discover(&excluded_dirs(None)), vec![path("archive/old"), path("stacks/app"), path("vendor/vpc")]);
+        assert_eq!(
+            discover(&excluded_dirs(Some(&[".terraform".to_string(), "vendor".to_string(), "archive/old/".to_string()]))),
+            vec![path("stacks/app"), path("vendor/vpc")]
+        );
+
+        // Ignored modules are left out even when sourced
+        let mut modules = HashMap::new();
+        let discovery = DiscoveryOptions { ignore_modules: vec![path("archive/**"), path("**/vpc")], ..Default::default() };
+        discover_modules(root.to_str().unwrap(), &discovery, &mut ModuleCache::default(), &mut modules).unwrap();
+        assert_eq!(modules.into_keys().collect::<Vec<_>>(), vec![path("stacks/app")]);
+
+        // The walk stops at the maximum depth
+        for (max_depth, count) in [(1, 0), (2, 3)] {
+            let mut modules = HashMap::new();
+            let discovery = DiscoveryOptions { max_depth: Some(max_depth), ..Default::default() };
+            discover_modules(root.to_str().unwrap(), &discovery, &mut ModuleCache::default(), &mut modules).unwrap();
+            assert_eq!(modules.len(), count);
+        }
+    }
+
+    #[test]
+    fn test_discover_modules_max_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        for (file, content) in [
+            ("app/main.tf", "terraform {\n  backend \"s3\" {}\n}\nmodule \"network\" {\n  source = \"../modules/aws/network\"\n}\n"),
+            ("modules/aws/network/main.tf", "variable \"cidr\" {}\n"),
+            ("modules/aws/dns/main.tf", "variable \"zone\" {}\n"),
+            ("data/exports/2024/01/main.tf", "variable \"unused\" {}\n"),
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), content).unwrap();
+        }
+        let path = |dir: &str| root.join(dir).to_string_lossy().to_string();
+        let discover = |max_depth: Option<usize>| {
+            let mut modules = HashMap::new();
+            let discovery = DiscoveryOptions { max_depth, ..Default::default() };
+            discover_modules(root.to_str().unwrap(), &discovery, &mut ModuleCache::default(), &mut modules).unwrap();
+            let mut paths: Vec<String> = modules.into_keys().collect();
+            paths.sort();
+            paths
+        };
+
+        // Deeper modules are only found when a discovered module sources them
+        assert_eq!(discover(Some(1)), vec![path("app"), path("modules/aws/network")]);
+        assert_eq!(discover(Some(3)), vec![path("app"), path("modules/aws/dns"), path("modules/aws/network")]);
+        assert_eq!(discover(None).len(), 4);
+        assert!(discover(Some(0)).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discover_modules_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        let external = fs::canonicalize(outside.path()).unwrap();
+        fs::create_dir_all(root.join("stacks/app")).unwrap();
+        fs::write(root.join("stacks/app/main.tf"), "terraform {}\n").unwrap();
+        fs::write(external.join("main.tf"), "terraform {}\n").unwrap();
+        // A link back to the root, a second link to a module and a link out of the repository
+        std::os::unix::fs::symlink(&root, root.join("stacks/app/loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("stacks/app"), root.join("app-link")).unwrap();
+        std::os::unix::fs::symlink(&external, root.join("external")).unwrap();
+
+        let discover = |discovery: DiscoveryOptions| {
+            let mut modules = HashMap::new();
+            discover_modules(root.to_str().unwrap(), &discovery, &mut ModuleCache::default(), &mut modules).unwrap();
+            let mut paths: Vec<String> = modules.into_keys().collect();
+            paths.sort();
+            paths
+        };
+        let app = root.join("stacks/app").to_string_lossy().to_string();
+        let mut expected = vec![app.clone(), external.to_string_lossy().to_string()];
+        expected.sort();
+        assert_eq!(discover(DiscoveryOptions::default()), expected);
+        assert_eq!(discover(DiscoveryOptions { follow_symlinks: false, ..Default::default() }), vec![app.clone()]);
+
+        let pattern = root.join("**").to_string_lossy().to_string();
+        assert_eq!(discover(DiscoveryOptions { root_modules: Some(vec![pattern]), follow_symlinks: false, ..Default::default() }), vec![app]);
+    }
+
+    #[test]
+    fn test_discover_root_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        for (file, content) in [
+            ("stacks/app/main.tf", "module \"vpc\" {\n  source = \"../../modules/vpc\"\n}\n"),
+            ("stacks/db/main.tf", "terraform {\n  backend \"s3\" {}\n}\n"),
+            ("live/eu/prod/main.tf", "terraform {}\n"),
+            ("live/us/east/prod/main.tf", "terraform {}\n"),
+            ("live/us/staging/main.tf", "terraform {}\n"),
+            ("modules/vpc/main.tf", "terraform {\n  backend \"s3\" {}\n}\n"),
+            ("modules/unused/main.tf", "variable \"cidr\" {}\n"),
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), content).unwrap();
+        }
+        let path = |dir: &str| root.join(dir).to_string_lossy().to_string();
+        let discovery = DiscoveryOptions {
+            root_modules: Some(vec![path("stacks/*"), path("live/**/prod"), path("missing")]),
+            ..Default::default()
+        };
+        let mut modules = HashMap::new();
+        discover_modules(root.to_str().unwrap(), &discovery, &mut ModuleCache::default(), &mut modules).unwrap();
+
+        let mut paths: Vec<&String> = modules.keys().collect();
+        paths.sort();
+        assert_eq!(paths, vec![&path("live/eu/prod"), &path("live/us/east/prod"), &path("modules/vpc"), &path("stacks/app"), &path("stacks/db")]);
+        // Listed modules are the stateful ones, whatever their backend
+        assert!(modules[&path("stacks/app")].parsed.is_stateful);
+        assert!(!modules[&path("modules/vpc")].parsed.is_stateful);
+    }
+
+    #[test]
+    fn test_classification() {
+        let classify = |content: &str, has_local_state: bool| {
+            ParsedModule::parse(Path::new("/repo/stacks/app"), &[content.to_string()], &[], has_local_state).classification
+        };
+        let backend = "terraform {\n  backend \"s3\" {}\n}\nmodule \"vpc\" {\n  source = \"../vpc\"\n}\n";
+        assert_eq!(classify(backend, true), Classification::Backend);
+        assert_eq!(classify("module \"vpc\" {\n  source = \"../vpc\"\n}\n", false), Classification::ModuleCalls);
+        assert_eq!(classify("resource \"null_resource\" \"a\" {}\n", true), Classification::LocalState);
+        assert_eq!(classify("variable \"cidr\" {}\n", false), Classification::NoState);
+    }
+
+    #[test]
+    fn test_parsed_module() {
+        let contents = vec![
+            "terraform {\n  backend \"s3\" {\n    bucket = \"tf-state\"\n    key    = \"app.tfstate\"\n  }\n}\nmodule \"vpc\" {\n  source = \"../../modules/vpc\"\n}\n".to_string(),
+            "locals {\n  policy = file(\"${path.module}/policy.json\")\n}\ndata \"terraform_remote_state\" \"db\" {\n  backend = \"s3\"\n  config = {\n    bucket = \"tf-state\"\n    key    = \"db.tfstate\"\n  }\n}\n".to_string(),
+        ];
+        let parsed = ParsedModule::parse(Path::new("/repo/stacks/app"), &contents, &[], false);
+        // Statefulness and dependencies come from the same parse of each file
+        assert!(parsed.is_stateful);
+        assert_eq!(parsed.classification, Classification::Backend);
+        assert_eq!(parsed.sources, vec!["/repo/modules/vpc".to_string()]);
+        assert_eq!(parsed.referenced_files, vec![PathBuf::from("/repo/stacks/app/policy.json")]);
+        assert_eq!(parsed.states, vec![StateAddress { container: Some("tf-state".to_string()), key: "app.tfstate".to_string() }]);
+        assert_eq!(parsed.remote_states, vec![StateAddress { container: Some("tf-state".to_string()), key: "db.tfstate".to_string() }]);
+        assert_eq!(parsed.backend.map(|backend| backend.to_string()).as_deref(), Some("s3 tf-state/app.tfstate"));
+    }
+
+    #[test]
+    fn test_shard() {
+        assert_eq!(Shard::parse("2/4"), Ok(Shard { index: 2, count: 4 }));
+        assert!(Shard::parse("0/4").is_err());
+        assert!(Shard::parse("5/4").is_err());
+        assert!(Shard::parse("4").is_err());
+
+        let modules: Vec<String> = (0..20).map(|i| format!("/repo/stacks/app{}", i)).collect();
+        let mut graph = ModuleGraph::default();
+        for module in &modules {
+            graph.add_module(module.clone(), ModuleNode::default());
+        }
+        graph.add_dependency(&modules[1], &modules[2]).unwrap();
+        let shards: Vec<Vec<String>> = (1..=3)
+            .map(|index| Shard { index, count: 3 }.select(modules.clone(), Some(&graph)))
+            .collect();
+        let mut all: Vec<String> = shards.concat();
+        all.sort();
+        let mut expected = modules.clone();
+        expected.sort();
+        assert_eq!(all, expected);
+        assert!(shards.iter().any(|shard| shard.contains(&modules[1]) && shard.contains(&modules[2])));
+        assert_eq!(Shard { index: 2, count: 3 }.select(modules.clone(), Some(&graph)), shards[1]);
+    }
+
+    #[test]
+    fn test_resolve_module_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("stacks/app")).unwrap();
+        let app = root.join("stacks/app").to_string_lossy().to_string();
+
+        assert_eq!(resolve_module_dirs(["stacks/app", "stacks/../stacks/app", app.as_str()], &root), Ok(vec![app]));
+        assert!(resolve_module_dirs(["stacks/db"], &root).is_err());
+    }
+
+    #[test]
+    fn test_resolve_module_dirs_from_subdirectory() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir_all(root.join("stacks/app")).unwrap();
+        fs::create_dir_all(root.join("stacks/dns")).unwrap();
+        fs::write(root.join("stacks/app/main.tf"), "").unwrap();
+        let path = |dir: &str| root.join(dir).to_string_lossy().to_string();
+
+        // `--module` paths are relative to the current directory and keep their order
+        let stacks = root.join("stacks");
+        assert_eq!(resolve_module_dirs(["dns", "app", "../stacks/dns"], &stacks), Ok(vec![path("stacks/dns"), path("stacks/app")]));
+        assert_eq!(resolve_module_dirs(["app/main.tf"], &stacks), Err("Module app/main.tf is not a directory".to_string()));
+        assert_eq!(resolve_module_dirs(["stacks/app"], &stacks), Err("Module stacks/app does not exist".to_string()));
+    }
+
+    #[test]
+    fn test_backend_info() {
+        let contents = vec!["terraform {\n  backend \"s3\" {\n    bucket = \"tf-state\"\n    region = \"eu-west-1\"\n  }\n}\n".to_string()];
+        let configs = vec!["key = \"app/prod.tfstate\"\n".to_string(), "key = \"app/dev.tfstate\"\n".to_string()];
+        let backend = BackendInfo::parse(&contents, &configs[..1]).unwrap();
+        assert_eq!(backend.to_string(), "s3 tf-state/app/prod.tfstate (eu-west-1)");
+        // Partial configurations disagreeing on the key leave it unknown
+        assert_eq!(BackendInfo::parse(&contents, &configs).unwrap().key, None);
+        assert_eq!(BackendInfo::parse(&["terraform {\n  backend \"local\" {}\n}\n".to_string()], &[]).unwrap().to_string(), "local terraform.tfstate");
+        assert_eq!(BackendInfo::parse(&["variable \"a\" {}\n".to_string()], &[]), None);
+        assert_eq!(backend.concurrency_group(), "s3:tf-state");
+    }
+
+    #[test]
+    fn test_module_metadata() {
+        let contents = vec![
+            "terraform {\n  required_version = \">= 1.5\"\n  required_providers {\n    aws = {\n      source  = \"hashicorp/aws\"\n      version = \"~> 5.0\"\n    }\n  }\n}\n".to_string(),
+            "variable \"required_version\" {}\n".to_string(),
+        ];
+        let parsed = ParsedModule::parse(Path::new("/repo/stacks/app"), &contents, &[], false);
+        assert_eq!(parsed.required_version.as_deref(), Some(">= 1.5"));
+        assert_eq!(parsed.providers.len(), 1);
+        assert_eq!(parsed.providers[0].source, "registry.terraform.io/hashicorp/aws");
+        assert_eq!(parsed.providers[0].version.as_deref(), Some("~> 5.0"));
+        assert_eq!(find_required_version("locals {\n  required_version = \"1.0\"\n}\n"), None);
+        assert_eq!(
+            find_sensitive_variables("variable \"db_password\" {\n  type      = string\n  sensitive = true\n}\nvariable \"region\" {\n  sensitive = false\n}\n"),
+            vec!["db_password"]
+        );
+    }
+
+    #[test]
+    fn test_find_shared_states() {
+        let module = |container: &str, key: &str| Module {
+            parsed: ParsedModule {
+                states: vec![StateAddress { container: Some(container.to_string()), key: key.to_string() }],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let modules = HashMap::from([
+            ("/repo/stacks/app".to_string(), module("tf-state", "app.tfstate")),
+            ("/repo/stacks/app-copy".to_string(), module("tf-state", "app.tfstate")),
+            ("/repo/stacks/db".to_string(), module("tf-state", "db.tfstate")),
+            ("/repo/other/db".to_string(), module("other-state", "db.tfstate")),
+        ]);
+
+        let shared = find_shared_states(&modules);
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].0.to_string(), "tf-state/app.tfstate");
+        assert_eq!(shared[0].1, vec!["/repo/stacks/app".to_string(), "/repo/stacks/app-copy".to_string()]);
+    }
+
+    #[test]
+    fn test_glob_regex() {
+        let examples = glob_regex("/repo/**/examples/**");
+        assert!(examples.is_match("/repo/modules/vpc/examples/basic"));
+        assert!(examples.is_match("/repo/examples"));
+        assert!(!examples.is_match("/repo/stacks/examples-app"));
+
+        let legacy = glob_regex("/repo/legacy/*");
+        assert!(legacy.is_match("/repo/legacy/app"));
+        assert!(!legacy.is_match("/repo/legacy/app/db"));
+        assert!(glob_regex("/repo/stack?").is_match("/repo/stack1"));
+    }
+
+    #[test]
+    fn test_filter_modules() {
+        let modules = vec!["/repo/stacks/networking-eu".to_string(), "/repo/stacks/db".to_string(), "/repo/legacy/networking".to_string()];
+        assert_eq!(
+            filter_modules(modules.clone(), &["**/networking*".to_string()], &["/repo/legacy/**".to_string()]),
+            vec!["/repo/stacks/networking-eu".to_string()]
+        );
+        assert_eq!(filter_modules(modules.clone(), &[], &["**/db".to_string()]).len(), 2);
+        assert_eq!(filter_modules(modules, &[], &[]).len(), 3);
+    }
+
+    #[test]
+    fn test_submodule_changes() {
+        let git = |dir: &Path, args: &[&str]| {
+            let output = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com", "-c", "protocol.file.allow=always"])
+                .args(args)
+                .current_dir(dir)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let (shared, parent) = (dir.path().join("shared"), dir.path().join("parent"));
+        fs::create_dir_all(shared.join("modules/vpc")).unwrap();
+        fs::create_dir_all(&parent).unwrap();
+        git(&shared, &["init", "-q"]);
+        fs::write(shared.join("modules/vpc/main.tf"), "# v1\n").unwrap();
+        fs::write(shared.join("README.md"), "shared\n").unwrap();
+        git(&shared, &["add", "-A"]);
+        git(&shared, &["commit", "-qm", "v1"]);
+        git(&parent, &["init", "-q"]);
+        git(&parent, &["submodule", "add", "-q", shared.to_str().unwrap(), "vendor/shared"]);
+        git(&parent, &["commit", "-qm", "add shared"]);
+
+        let submodule = parent.join("vendor/shared");
+        fs::write(submodule.join("modules/vpc/main.tf"), "# v2\n").unwrap();
+        git(&submodule, &["commit", "-qam", "v2"]);
+        git(&parent, &["commit", "-qam", "bump shared"]);
+
+        let root = parent.to_str().unwrap();
+        let mut tracked = TrackedFiles::default();
+        let mut files = diff_files(root, "HEAD~1", Some("HEAD"), &[], &tracked).unwrap();
+        files.sort();
+        assert_eq!(files, vec!["vendor/shared/README.md", "vendor/shared/modules/vpc/main.tf"]);
+
+        tracked.recurse_submodules = true;
+        assert_eq!(diff_files(root, "HEAD~1", Some("HEAD"), &[], &tracked).unwrap(), vec!["vendor/shared/modules/vpc/main.tf"]);
+    }
+
+    #[test]
+    fn test_shallow_clone_fetches() {
+        let git = |dir: &Path, args: &[&str]| -> String {
+            let output = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(dir)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let origin = dir.path().join("origin");
+        fs::create_dir_all(&origin).unwrap();
+        git(&origin, &["init", "-q", "-b", "main"]);
+        let mut main_commits = Vec::new();
+        for i in 0..3 {
+            fs::write(origin.join("main.tf"), format!("# main {}\n", i)).unwrap();
+            git(&origin, &["add", "-A"]);
+            git(&origin, &["commit", "-qm", &format!("main {}", i)]);
+            main_commits.push(git(&origin, &["rev-parse", "HEAD"]));
+        }
+        git(&origin, &["checkout", "-qb", "feature", &main_commits[1]]);
+        for i in 0..3 {
+            fs::write(origin.join("feature.tf"), format!("# feature {}\n", i)).unwrap();
+            git(&origin, &["add", "-A"]);
+            git(&origin, &["commit", "-qm", &format!("feature {}", i)]);
+        }
+
+        let clone = dir.path().join("clone");
+        let url = format!("file://{}", origin.display());
+        git(dir.path(), &["clone", "-q", "--depth=1", "--branch", "feature", &url, clone.to_str().unwrap()]);
+        let root = clone.to_str().unwrap();
+        assert!(git::is_shallow(root));
+
+        // A pipeline-supplied base commit missing from the clone is fetched
+        assert!(!git::has_commit(root, &main_commits[0]));
+        assert!(ensure_commit(root, &main_commits[0]));
+        assert!(git::has_commit(root, &main_commits[0]));
+
+        // The default branch is fetched and the history deepened until it meets HEAD
+        assert_eq!(fetch_merge_base(root, "main"), Some(main_commits[1].clone()));
+    }
+
+    #[test]
+    fn test_find_removed_modules() {
+        let git = |dir: &Path, args: &[&str]| -> String {
+            let output = Command::new("git")
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .current_dir(dir)
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        git(&root, &["init", "-q"]);
+        for (file, content) in [
+            ("stacks/app/main.tf", "terraform {\n  backend \"s3\" {}\n}\n"),
+            ("stacks/db/main.tf", "terraform {\n  backend \"s3\" {\n    key = \"db\"\n  }\n}\n"),
+            ("modules/vpc/main.tf", "variable \"cidr\" {}\n"),
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), content).unwrap();
+        }
+        git(&root, &["add", "-A"]);
+        git(&root, &["commit", "-qm", "add modules"]);
+        let last = git(&root, &["rev-parse", "HEAD"]);
+
+        // The stateful app and the stateless vpc are deleted; db only moves
+        git(&root, &["rm", "-rq", "stacks/app", "modules/vpc"]);
+        git(&root, &["mv", "stacks/db", "stacks/database"]);
+        git(&root, &["commit", "-qm", "remove modules"]);
+
+        let changed: Vec<String> = ["stacks/app/main.tf", "stacks/db/main.tf", "modules/vpc/main.tf", "stacks/database/main.tf"]
+            .iter()
+            .map(|file| root.join(file).to_string_lossy().to_string())
+            .collect();
+        let removed = find_removed_modules(root.to_str().unwrap(), &changed);
+        assert_eq!(removed, vec![RemovedModule { path: "stacks/app".to_string(), commit: last.clone() }]);
+        assert_eq!(removed[0].describe(), format!("stacks/app (last in {})", &last[..12]));
+    }
+
+    #[test]
+    fn test_scan_pathspecs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        assert!(Command::new("git").args(["init", "-q"]).current_dir(&root).status().unwrap().success());
+        for (file, content) in [
+            ("modules/vpc/main.tf", "module \"subnets\" {\n  source = \"../../vendor/shared/subnets\"\n}\n"),
+            ("vendor/shared/.git", "gitdir: ../../.git/modules/shared\n"),
+            ("vendor/shared/subnets/main.tf", "variable \"cidr\" {}\n"),
+            ("templates/user-data.sh", "#!/bin/sh\n"),
+            ("stacks/app/main.tf", "terraform {\n  backend \"s3\" {}\n}\nmodule \"vpc\" {\n  source = \"../../modules/vpc\"\n}\nlocals {\n  user_data = file(\"../../templates/user-data.sh\")\n}\n"),
+            ("stacks/db/main.tf", "terraform {\n  backend \"s3\" {}\n}\n"),
+        ] {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), content).unwrap();
+        }
+        let mut modules = HashMap::new();
+        discover_modules(root.to_str().unwrap(), &DiscoveryOptions::default(), &mut ModuleCache::default(), &mut modules).unwrap();
+        build_dependency_graph(&mut modules, &GraphOverrides::default()).unwrap();
+
+        assert_eq!(
+            scan_pathspecs(root.join("stacks/app").to_str().unwrap(), &modules),
+            vec!["modules/vpc", "stacks/app", "templates/user-data.sh", "vendor/shared"]
+        );
+        assert_eq!(scan_pathspecs(root.join("stacks/db").to_str().unwrap(), &modules), vec!["stacks/db"]);
+        assert!(scan_pathspecs(root.to_str().unwrap(), &modules).is_empty());
+    }
+
+    #[test]
+    fn test_merged_pr_number() {
+        assert_eq!(merged_pr_number("Merge pull request #42 from acme/feature\n\nAdd VPC"), Some("42".to_string()));
+        assert_eq!(merged_pr_number("Add VPC endpoints (#128)\n\n* wip"), Some("128".to_string()));
+        assert_eq!(merged_pr_number("Merge branch 'feature' into 'main'\n\nAdd VPC\n\nSee merge request infra/network!17"), Some("17".to_string()));
+        assert_eq!(merged_pr_number("Merged PR 311: Add VPC"), Some("311".to_string()));
+        assert_eq!(merged_pr_number("Merged in feature/vpc (pull request #9)"), Some("9".to_string()));
+        assert_eq!(merged_pr_number("Fix issue #12 in the VPC module"), None);
+    }
+
+    #[test]
+    fn test_compare_refs_from_args() {
+        assert_eq!(CompareRefs::from_args(None, None, None), Ok(None));
+        assert_eq!(
+            CompareRefs::from_args(Some("v1.2.0"), None, None),
+            Ok(Some(CompareRefs { base: "v1.2.0".to_string(), head: "HEAD".to_string() }))
+        );
+        assert_eq!(
+            CompareRefs::from_args(Some("abc123"), Some("def456"), None),
+            Ok(Some(CompareRefs { base: "abc123".to_string(), head: "def456".to_string() }))
+        );
+        assert!(CompareRefs::from_args(None, Some("def456"), None).is_err());
+        assert!(CompareRefs::from_args(Some("abc123"), None, Some("24h")).is_err());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("24h"), Ok("86400 seconds ago".to_string()));
+        assert_eq!(parse_since("2w"), Ok("1209600 seconds ago".to_string()));
+        assert_eq!(parse_since("2025-01-01"), Ok("2025-01-01".to_string()));
+        assert_eq!(parse_since("2025-01-01T08:30"), Ok("2025-01-01T08:30".to_string()));
+        assert!(parse_since("yesterday").is_err());
+        assert!(parse_since("24x").is_err());
+    }
+
+    #[test]
+    fn test_tracked_referenced_files() {
+        let tracked = TrackedFiles {
+            extensions: tracked_extensions(None),
+            referenced: vec![PathBuf::from("/repo/stacks/app/templates/user-data.sh")],
+            ..Default::default()
+        };
+        assert!(tracked.matches("stacks/app/templates/user-data.sh"));
+        assert!(tracked.matches("/repo/stacks/app/templates/user-data.sh"));
+        assert!(!tracked.matches("stacks/other/templates/user-data.sh"));
+    }
+
+    #[test]
+    fn test_tracked_files() {
+        let defaults = tracked_extensions(None);
+        assert!(is_tracked_file("stacks/networking/main.tf", &defaults));
+        assert!(is_tracked_file("stacks/networking/prod.tfvars", &defaults));
+        assert!(is_tracked_file("stacks/networking/variables.tf.json", &defaults));
+        assert!(is_tracked_file("stacks/networking/tests/vpc.tftest.hcl", &defaults));
+        assert!(!is_tracked_file("stacks/networking/README.md", &defaults));
+
+        let configured = tracked_extensions(Some(&["tf".to_string(), ".yaml".to_string()]));
+        assert_eq!(configured, vec![".tf", ".yaml"]);
+        assert!(is_tracked_file("stacks/networking/values.yaml", &configured));
+        assert!(!is_tracked_file("stacks/networking/prod.tfvars", &configured));
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+
+use crate::utils::redact;
+
+/// Values of references resolved during this run, by reference and the environment they were read with
+static RESOLVED: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Whether a configured value refers to a secret: `ssm:<parameter name>` for an SSM parameter, or
+/// `aws-secrets:<secret id>` for a Secrets Manager secret
+pub fn is_reference(value: &str) -> bool {
+    lookup_args(value).is_some()
+}
+
+/// Arguments of the `aws` command reading a reference, if `value` is one
+fn lookup_args(value: &str) -> Option<Vec<String>> {
+    let args: &[&str] = if let Some(name) = value.strip_prefix("ssm:").filter(|name| !name.is_empty()) {
+        &["ssm", "get-parameter", "--with-decryption", "--name", name, "--query", "Parameter.Value"]
+    } else if let Some(id) = value.strip_prefix("aws-secrets:").filter(|id| !id.is_empty()) {
+        &["secretsmanager", "get-secret-value", "--secret-id", id, "--query", "SecretString"]
+    } else {
+        return None;
+    };
+    Some(args.iter().chain(&["--output", "text"]).map(|arg| arg.to_string()).collect())
+}
+
+/// Value of a secret reference, read with the AWS CLI using the credentials `env` points at.
+/// Values that aren't references are returned as they are. Each reference is read once per run.
+pub fn resolve(value: &str, env: &BTreeMap<String, String>) -> Result<String, String> {
+    let Some(args) = lookup_args(value) else {
+        return Ok(value.to_string());
+    };
+    let key = std::iter::once(value.to_string())
+        .chain(env.iter().map(|(name, value)| format!("{}={}", name, value)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Some(resolved) = RESOLVED.lock().ok().and_then(|resolved| resolved.get(&key).cloned()) {
+        return Ok(resolved);
+    }
+
+    let aws = aws_cli(env).map_err(|e| format!("Cannot read {}: {}", value, e))?;
+    let output = Command::new(aws)
+        .args(&args)
+        .envs(env)
+        .output()
+        .map_err(|e| format!("Failed to run aws to read {}: {}", value, e))?;
+    if !output.status.success() {
+        return Err(format!("Failed to read {}: {}", value, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    // The CLI ends text output with a newline that isn't part of the value
+    let resolved = String::from_utf8_lossy(&output.stdout).trim_end_matches(['\r', '\n']).to_string();
+    redact::add_value(&resolved);
+
+    if let Ok(mut cache) = RESOLVED.lock() {
+        cache.insert(key, resolved.clone());
+    }
+    Ok(resolved)
+}
+
+/// Path of the AWS CLI on the `PATH` of `env`, falling back to solarboat's own `PATH`.
+/// Secret references and assumed roles are read with it.
+pub fn aws_cli(env: &BTreeMap<String, String>) -> Result<PathBuf, String> {
+    let path = env.get("PATH").map(OsString::from).or_else(|| std::env::var_os("PATH"));
+    find_executable("aws", path.as_deref()).ok_or_else(|| {
+        "aws CLI not found on PATH - install AWS CLI v2 to use secret references and assume_role \
+         (see \"AWS CLI Requirement\" in CONFIGURATION.md)".to_string()
+    })
+}
+
+fn find_executable(name: &str, path: Option<&OsStr>) -> Option<PathBuf> {
+    let names: Vec<String> = if cfg!(windows) {
+        ["exe", "cmd"].iter().map(|extension| format!("{}.{}", name, extension)).collect()
+    } else {
+        vec![name.to_string()]
+    };
+    std::env::split_paths(path?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references() {
+        assert!(is_reference("ssm:/app/prod/db-password"));
+        assert!(is_reference("aws-secrets:prod/api-key"));
+        assert!(!is_reference("ssm:"));
+        assert!(!is_reference("eu-west-1"));
+        assert_eq!(
+            lookup_args("aws-secrets:prod/api-key").unwrap(),
+            vec!["secretsmanager", "get-secret-value", "--secret-id", "prod/api-key", "--query", "SecretString", "--output", "text"]
+        );
+        assert_eq!(resolve("eu-west-1", &BTreeMap::new()).unwrap(), "eu-west-1");
+    }
+
+    #[test]
+    fn test_missing_aws_cli() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let env = BTreeMap::from([("PATH".to_string(), dir.path().to_string_lossy().to_string())]);
+
+        let error = resolve("ssm:/app/prod/db-password", &env).unwrap_err();
+        assert!(error.contains("aws CLI not found"), "{}", error);
+
+        let name = if cfg!(windows) { "aws.exe" } else { "aws" };
+        std::fs::write(dir.path().join(name), "").unwrap();
+        assert_eq!(aws_cli(&env).unwrap(), dir.path().join(name));
+    }
+}
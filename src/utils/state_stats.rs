@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::utils::plan_manifest::module_key;
+use crate::utils::terraform_operations::{select_workspace, terraform_command};
+
+/// Run history file recording state statistics over time
+pub const STATE_HISTORY_FILE: &str = ".solarboat/state-history.jsonl";
+
+/// Lightweight state metadata for a module/workspace pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateStats {
+    pub timestamp: u64,
+    pub module: String,
+    pub workspace: String,
+    /// Number of resource instances tracked in state
+    pub resource_count: usize,
+    /// Size of the raw state document in bytes
+    pub state_bytes: usize,
+    /// State serial, if reported by the backend
+    pub serial: Option<u64>,
+}
+
+/// Pull the state for a module/workspace and summarize it
+pub fn collect_state_stats(module_path: &str, workspace: Option<&str>, data_dir: Option<&Path>) -> Result<StateStats, String> {
+    if let Some(ws) = workspace {
+        select_workspace(module_path, ws, data_dir)?;
+    }
+
+    let output = terraform_command(module_path, data_dir)
+        .arg("state")
+        .arg("pull")
+        .output()
+        .map_err(|e| format!("Failed to run terraform state pull: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!("terraform state pull failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    let (resource_count, serial) = summarize_state(&output.stdout)?;
+
+    Ok(StateStats {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| format!("Failed to get timestamp: {}", e))?
+            .as_secs(),
+        module: module_key(module_path),
+        workspace: workspace.unwrap_or("default").to_string(),
+        resource_count,
+        state_bytes: output.stdout.len(),
+        serial,
+    })
+}
+
+/// Count resource instances and read the serial from a raw state document.
+/// An empty document (no state yet) counts as zero resources.
+pub fn summarize_state(raw_state: &[u8]) -> Result<(usize, Option<u64>), String> {
+    if raw_state.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok((0, None));
+    }
+
+    let state: serde_json::Value = serde_json::from_slice(raw_state)
+        .map_err(|e| format!("Failed to parse state JSON: {}", e))?;
+
+    let resource_count = state["resources"]
+        .as_array()
+        .map(|resources| {
+            resources.iter()
+                .map(|r| r["instances"].as_array().map(|i| i.len()).unwrap_or(0))
+                .sum()
+        })
+        .unwrap_or(0);
+
+    Ok((resource_count, state["serial"].as_u64()))
+}
+
+/// Load previously recorded statistics from the run history
+pub fn load_history() -> Vec<StateStats> {
+    std::fs::read_to_string(STATE_HISTORY_FILE)
+        .map(|content| {
+            content.lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Find the most recent recorded statistics for a module/workspace pair
+pub fn previous_stats<'a>(history: &'a [StateStats], module: &str, workspace: &str) -> Option<&'a StateStats> {
+    history.iter()
+        .filter(|s| s.module == module && s.workspace == workspace)
+        .max_by_key(|s| s.timestamp)
+}
+
+/// Append statistics to the run history
+pub fn append_history(stats: &[StateStats]) -> Result<(), String> {
+    if let Some(parent) = Path::new(STATE_HISTORY_FILE).parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create history directory: {}", e))?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(STATE_HISTORY_FILE)
+        .map_err(|e| format!("Failed to open state history: {}", e))?;
+
+    for entry in stats {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| format!("Failed to serialize state stats: {}", e))?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write state history: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Format a byte count for display
+pub fn format_bytes(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_state() {
+        let state = br#"{
+            "version": 4,
+            "serial": 12,
+            "resources": [
+                {"type": "aws_s3_bucket", "name": "a", "instances": [{}]},
+                {"type": "aws_instance", "name": "b", "instances": [{}, {}, {}]}
+            ]
+        }"#;
+        assert_eq!(summarize_state(state).unwrap(), (4, Some(12)));
+        assert_eq!(summarize_state(b"  \n").unwrap(), (0, None));
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(2048), "2.0 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MB");
+    }
+}
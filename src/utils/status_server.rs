@@ -0,0 +1,158 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::utils::parallel_processor::{ProgressHandle, ProgressSnapshot};
+
+/// Read-only HTTP endpoint serving `/status` (JSON) and `/metrics` (Prometheus text)
+/// for a running plan or apply. The server stops when dropped.
+pub struct StatusServer {
+    port: u16,
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl StatusServer {
+    /// Start serving progress on 127.0.0.1. Port 0 picks a free port.
+    pub fn start(port: u16, operation: &str, progress: ProgressHandle) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Failed to bind status endpoint on port {}: {}", port, e))?;
+        listener.set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure status endpoint: {}", e))?;
+        let port = listener.local_addr()
+            .map_err(|e| format!("Failed to read status endpoint address: {}", e))?
+            .port();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = Arc::clone(&stop);
+        let operation = operation.to_string();
+        let started = Instant::now();
+
+        let handle = thread::spawn(move || {
+            while !stop_flag.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let _ = handle_connection(stream, &operation, started, &progress);
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(Self { port, stop, handle: Some(handle) })
+    }
+
+    /// Port the endpoint is listening on
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, operation: &str, started: Instant, progress: &ProgressHandle) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let snapshot = progress.snapshot();
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/status") => ("200 OK", "application/json", render_status(operation, &snapshot, elapsed)),
+        ("GET", "/metrics") => ("200 OK", "text/plain; version=0.0.4", render_metrics(operation, &snapshot, elapsed)),
+        ("GET", _) => ("404 Not Found", "text/plain", "Not found. Try /status or /metrics\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Only GET is supported\n".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    )
+}
+
+/// Render the `/status` JSON document
+pub fn render_status(operation: &str, snapshot: &ProgressSnapshot, elapsed_secs: f64) -> String {
+    let running = snapshot.completed_modules < snapshot.total_modules;
+    let status = serde_json::json!({
+        "operation": operation,
+        "state": if running { "running" } else { "finished" },
+        "elapsed_seconds": elapsed_secs,
+        "progress": snapshot,
+    });
+    serde_json::to_string_pretty(&status).unwrap_or_default()
+}
+
+/// Render the `/metrics` Prometheus text exposition
+pub fn render_metrics(operation: &str, snapshot: &ProgressSnapshot, elapsed_secs: f64) -> String {
+    let metrics = [
+        ("solarboat_modules_total", "Modules in this run", snapshot.total_modules as f64),
+        ("solarboat_modules_completed", "Modules finished", snapshot.completed_modules as f64),
+        ("solarboat_modules_active", "Modules currently running", snapshot.active_modules.len() as f64),
+        ("solarboat_operations_pending", "Operations not yet started", snapshot.pending_operations as f64),
+        ("solarboat_operations_succeeded", "Operations that succeeded", snapshot.succeeded as f64),
+        ("solarboat_operations_failed", "Operations that failed", snapshot.failed as f64),
+        ("solarboat_operations_cancelled", "Operations cancelled after a failure", snapshot.cancelled as f64),
+        ("solarboat_run_elapsed_seconds", "Seconds since the run started", elapsed_secs),
+    ];
+
+    metrics.iter()
+        .map(|(name, help, value)| {
+            format!("# HELP {name} {help}\n# TYPE {name} gauge\n{name}{{operation=\"{operation}\"}} {value}\n")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parallel_processor::ParallelProcessor;
+    use std::io::Read;
+
+    #[test]
+    fn test_render_metrics() {
+        let snapshot = ProgressSnapshot {
+            total_modules: 3,
+            completed_modules: 1,
+            succeeded: 1,
+            ..Default::default()
+        };
+        let metrics = render_metrics("plan", &snapshot, 2.5);
+        assert!(metrics.contains("solarboat_modules_total{operation=\"plan\"} 3\n"));
+        assert!(metrics.contains("solarboat_run_elapsed_seconds{operation=\"plan\"} 2.5\n"));
+        assert!(render_status("plan", &snapshot, 2.5).contains("\"state\": \"running\""));
+    }
+
+    #[test]
+    fn test_serves_status() {
+        let processor = ParallelProcessor::new(1);
+        let server = StatusServer::start(0, "apply", processor.progress_handle()).unwrap();
+
+        let mut stream = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+        stream.write_all(b"GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\"operation\": \"apply\""));
+    }
+}
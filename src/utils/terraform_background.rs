@@ -5,6 +5,9 @@ use std::thread;
 use std::time::Duration;
 use std::path::{Path, PathBuf};
 use crate::utils::error::{SolarboatError, SafeOperations};
+use crate::utils::terraform_operations::{apply_env, forget_backend_config, init_args, terraform_command, TerraformArgs};
+use crate::utils::redact;
+use crate::utils::env_passthrough;
 
 #[derive(Debug, Clone)]
 pub enum TerraformStatus {
@@ -20,6 +23,7 @@ pub struct BackgroundTerraform {
     thread_handle: Option<thread::JoinHandle<()>>,
     status: Arc<Mutex<TerraformStatus>>,
     output: Arc<Mutex<Vec<String>>>,
+    data_dir: Option<PathBuf>,
 }
 
 impl Default for BackgroundTerraform {
@@ -34,9 +38,16 @@ impl BackgroundTerraform {
             thread_handle: None,
             status: Arc::new(Mutex::new(TerraformStatus::Initializing)),
             output: Arc::new(Mutex::new(Vec::new())),
+            data_dir: None,
         }
     }
 
+    /// Run terraform with TF_DATA_DIR pointed at an isolated directory
+    pub fn with_data_dir(mut self, data_dir: Option<&Path>) -> Self {
+        self.data_dir = data_dir.map(Path::to_path_buf);
+        self
+    }
+
     pub fn get_status(&self) -> Result<TerraformStatus, SolarboatError> {
         let status = SafeOperations::lock_with_timeout(
             &self.status,
@@ -63,10 +74,11 @@ impl BackgroundTerraform {
         }
     }
 
-    pub fn init_background(&mut self, module_path: &str) -> Result<(), SolarboatError> {
-        let mut cmd = Command::new("terraform");
-        cmd.arg("init")
-           .current_dir(module_path)
+    pub fn init_background(&mut self, module_path: &str, backend_config: &[String], extra_args: &[String]) -> Result<(), SolarboatError> {
+        // The outcome isn't known here, so a later foreground init doesn't trust the recorded configuration
+        forget_backend_config(module_path, self.data_dir.as_deref());
+        let mut cmd = terraform_command(module_path, self.data_dir.as_deref());
+        cmd.args(init_args(module_path, self.data_dir.as_deref(), backend_config, extra_args))
            .stdout(Stdio::piped())
            .stderr(Stdio::piped());
 
@@ -104,6 +116,7 @@ impl BackgroundTerraform {
             // Monitor stdout
             for line in stdout_reader.lines() {
                 if let Ok(line) = line {
+                    let line = redact::redact(&line);
                     if let Ok(mut output) = SafeOperations::lock_with_timeout(
                         &output,
                         Duration::from_secs(1),
@@ -118,6 +131,7 @@ impl BackgroundTerraform {
             // Monitor stderr
             for line in stderr_reader.lines() {
                 if let Ok(line) = line {
+                    let line = redact::redact(&line);
                     if let Ok(mut output) = SafeOperations::lock_with_timeout(
                         &output,
                         Duration::from_secs(1),
@@ -163,13 +177,17 @@ impl BackgroundTerraform {
         Ok(())
     }
 
-    pub fn plan_background(&mut self, module_path: &str, var_files: Option<&[String]>) -> Result<(), String> {
-        let mut cmd = Command::new("terraform");
+    pub fn plan_background(&mut self, module_path: &str, var_files: Option<&[String]>, args: &TerraformArgs, plan_out: Option<&Path>) -> Result<(), String> {
+        let mut cmd = terraform_command(module_path, self.data_dir.as_deref());
         cmd.arg("plan")
-           .current_dir(module_path)
            .stdout(Stdio::piped())
            .stderr(Stdio::piped());
 
+        // Save a binary plan if requested
+        if let Some(plan_out) = plan_out {
+            cmd.arg(format!("-out={}", plan_out.display()));
+        }
+
         // Add var files if provided
         if let Some(var_files) = var_files {
             for var_file in var_files {
@@ -227,6 +245,7 @@ impl BackgroundTerraform {
                 cmd.arg("-var-file").arg(&var_file_path);
             }
         }
+        cmd.args(args.plan_args());
 
         let mut child = cmd.spawn()
             .map_err(|e| format!("Failed to start terraform plan: {}", e))?;
@@ -248,6 +267,7 @@ impl BackgroundTerraform {
             // Monitor stdout
             for line in stdout_reader.lines() {
                 if let Ok(line) = line {
+                    let line = redact::redact(&line);
                     output.lock().unwrap().push(line.clone());
                     println!("  {}", line);
                 }
@@ -256,6 +276,7 @@ impl BackgroundTerraform {
             // Monitor stderr
             for line in stderr_reader.lines() {
                 if let Ok(line) = line {
+                    let line = redact::redact(&line);
                     output.lock().unwrap().push(format!("ERROR: {}", line));
                     eprintln!("  ERROR: {}", line);
                 }
@@ -278,17 +299,16 @@ impl BackgroundTerraform {
         Ok(())
     }
 
-    pub fn apply_background(&mut self, module_path: &str, var_files: Option<&[String]>) -> Result<(), String> {
-        let mut cmd = Command::new("terraform");
+    pub fn apply_background(&mut self, module_path: &str, var_files: Option<&[String]>, args: &TerraformArgs, plan_file: Option<&Path>) -> Result<(), String> {
+        let mut cmd = terraform_command(module_path, self.data_dir.as_deref());
         cmd.arg("apply")
            .arg("-auto-approve")
            .arg("-input=false")
-           .current_dir(module_path)
            .stdout(Stdio::piped())
            .stderr(Stdio::piped());
 
-        // Add var files if provided
-        if let Some(var_files) = var_files {
+        // A saved plan is applied as-is; var files are only used otherwise
+        if let (None, Some(var_files)) = (plan_file, var_files) {
             for var_file in var_files {
                 // Resolve var file path relative to module directory
                 let var_file_path = if Path::new(var_file).is_absolute() {
@@ -344,6 +364,11 @@ impl BackgroundTerraform {
                 cmd.arg("-var-file").arg(&var_file_path);
             }
         }
+        cmd.args(args.apply_args(plan_file.is_some()));
+        // The saved plan must come after all options
+        if let Some(plan_file) = plan_file {
+            cmd.arg(plan_file);
+        }
 
         let mut child = cmd.spawn()
             .map_err(|e| format!("Failed to start terraform apply: {}", e))?;
@@ -365,6 +390,7 @@ impl BackgroundTerraform {
             // Monitor stdout
             for line in stdout_reader.lines() {
                 if let Ok(line) = line {
+                    let line = redact::redact(&line);
                     output.lock().unwrap().push(line.clone());
                     println!("  {}", line);
                 }
@@ -373,6 +399,7 @@ impl BackgroundTerraform {
             // Monitor stderr
             for line in stderr_reader.lines() {
                 if let Ok(line) = line {
+                    let line = redact::redact(&line);
                     output.lock().unwrap().push(format!("ERROR: {}", line));
                     eprintln!("  ERROR: {}", line);
                 }
@@ -437,6 +464,8 @@ pub fn run_terraform_silent(
        .current_dir(module_path)
        .stdout(Stdio::null())
        .stderr(Stdio::null());
+    env_passthrough::sanitize(&mut cmd);
+    apply_env(&mut cmd);
 
     // Add var files if provided
     if let Some(var_files) = var_files {
@@ -1,26 +1,140 @@
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader};
+use std::sync::Mutex;
+use std::collections::BTreeMap;
 use std::process::{Command, Stdio};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use regex::Regex;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::time::Duration;
 use std::thread;
 
+use crate::utils::plan_json::{self, PlanSummary};
+use crate::utils::plan_report;
+use crate::utils::plan_manifest::ChangeCounts;
+use crate::utils::hooks::Hooks;
+use crate::utils::redact;
+use crate::utils::env_passthrough;
+
 /// Represents a single terraform operation to be processed
 #[derive(Debug, Clone)]
 pub struct TerraformOperation {
     pub module_path: String,
     pub workspace: Option<String>,
     pub var_files: Vec<String>,
+    /// Variables, targets and other flags forwarded to terraform
+    pub args: TerraformArgs,
     pub operation_type: OperationType,
     pub watch: bool,
     pub skip_init: bool, // Skip initialization if already done
+    /// Isolated TF_DATA_DIR for this operation, if configured
+    pub data_dir: Option<String>,
+    /// Shell commands run around the terraform phases
+    pub hooks: Hooks,
+    /// Environment variables set for terraform and the hooks
+    pub env: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone)]
 pub enum OperationType {
     Init,
-    Plan { plan_dir: Option<String> },
-    Apply,
+    /// Plan, saving artifacts into `plan_dir` when set. With `skip_empty`, plans
+    /// reporting no changes keep no artifacts and are marked clean instead.
+    Plan { plan_dir: Option<String>, format: PlanFormat, skip_empty: bool },
+    /// Apply, optionally from a verified saved plan file (absolute path).
+    /// Output is saved as a report into `report_dir` when set. With `snapshot`,
+    /// the workspace's state is saved with `terraform state pull` first.
+    Apply { plan_file: Option<String>, report_dir: Option<String>, snapshot: bool },
+}
+
+/// Extra arguments forwarded to terraform plan and apply
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TerraformArgs {
+    /// `KEY=VALUE` variables passed as `-var`
+    pub vars: Vec<String>,
+    /// Resource addresses passed as `-target`
+    pub targets: Vec<String>,
+    /// Skip refreshing state (`-refresh=false`)
+    pub no_refresh: bool,
+    /// Terraform's own resource concurrency (`-parallelism=N`)
+    pub parallelism: Option<u32>,
+    /// How long to wait for a busy state lock (`-lock-timeout`), e.g. `5m`
+    pub lock_timeout: Option<String>,
+    /// Plan or apply the destruction of every resource (`-destroy`)
+    pub destroy: bool,
+    /// Only sync state with real infrastructure, proposing no configuration changes (`-refresh-only`)
+    pub refresh_only: bool,
+    /// Partial backend configuration for `terraform init`, `KEY=VALUE` pairs or files passed as `-backend-config`
+    pub backend_config: Vec<String>,
+    /// Arguments added to `terraform init` as they are
+    pub extra_init_args: Vec<String>,
+    /// Arguments added to `terraform plan` as they are
+    pub extra_plan_args: Vec<String>,
+    /// Arguments added to `terraform apply` as they are
+    pub extra_apply_args: Vec<String>,
+}
+
+impl TerraformArgs {
+    /// Arguments for `terraform plan`
+    pub fn plan_args(&self) -> Vec<String> {
+        let mut args = self.to_args();
+        args.extend(self.extra_plan_args.iter().cloned());
+        args
+    }
+
+    /// Arguments for `terraform plan`, or `terraform apply` without a saved plan
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.destroy {
+            args.push("-destroy".to_string());
+        }
+        if self.refresh_only {
+            args.push("-refresh-only".to_string());
+        }
+        for var in &self.vars {
+            args.push("-var".to_string());
+            args.push(var.clone());
+        }
+        args.extend(self.targets.iter().map(|target| format!("-target={}", target)));
+        if self.no_refresh {
+            args.push("-refresh=false".to_string());
+        }
+        args.extend(self.run_args());
+        args
+    }
+
+    /// Arguments for `terraform apply`. Saved plans already carry their variables, targets, refresh and plan mode.
+    pub fn apply_args(&self, saved_plan: bool) -> Vec<String> {
+        let mut args = if saved_plan {
+            self.run_args()
+        } else {
+            self.to_args()
+        };
+        args.extend(self.extra_apply_args.iter().cloned());
+        args
+    }
+
+    /// Arguments that only affect how terraform runs, so they also apply to saved plans
+    fn run_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(parallelism) = self.parallelism {
+            args.push(format!("-parallelism={}", parallelism));
+        }
+        if let Some(lock_timeout) = &self.lock_timeout {
+            args.push(format!("-lock-timeout={}", lock_timeout));
+        }
+        args
+    }
+}
+
+/// Layout of the markdown plan reports saved into the plan directory
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PlanFormat {
+    /// Plain markdown with the plan output in a code block
+    #[default]
+    Markdown,
+    /// GitHub-flavored markdown with collapsible sections, sized for PR comments
+    Github,
 }
 
 /// Result of a terraform operation
@@ -32,18 +146,204 @@ pub struct OperationResult {
     pub success: bool,
     pub error: Option<String>,
     pub output: Vec<String>,
+    /// Files saved into the plan directory (for applies, the apply report and state snapshot)
+    pub artifacts: PlanArtifacts,
+    /// Isolated TF_DATA_DIR the operation ran with, if configured
+    pub data_dir: Option<String>,
+    /// Environment variables the operation ran with
+    pub env: BTreeMap<String, String>,
+    /// Never started because an earlier operation failed in fail-fast mode
+    pub cancelled: bool,
+}
+
+/// Files saved into the plan directory for a single plan run.
+/// All names are relative to the plan directory.
+#[derive(Debug, Clone, Default)]
+pub struct PlanArtifacts {
+    /// Markdown plan report
+    pub report: Option<String>,
+    /// Binary plan file written with `-out`
+    pub plan_file: Option<String>,
+    /// JSON rendering of the binary plan from `terraform show -json`
+    pub plan_json: Option<String>,
+    /// Machine-readable add/change/destroy summary
+    pub summary_file: Option<String>,
+    /// Add/change/destroy summary parsed from the JSON plan
+    pub summary: Option<PlanSummary>,
+    /// The plan reported no changes and its artifacts were skipped
+    pub clean: bool,
+    /// State saved before an apply (absolute path, outside the plan directory)
+    pub state_snapshot: Option<String>,
+}
+
+/// Outcome of a single terraform apply run
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOutcome {
+    pub success: bool,
+    /// Captured stdout and stderr
+    pub output: Vec<String>,
+    /// Apply report file name, relative to the report directory
+    pub report: Option<String>,
+}
+
+/// Outcome of a single terraform plan run
+#[derive(Debug, Clone, Default)]
+pub struct PlanOutcome {
+    pub success: bool,
+    pub artifacts: PlanArtifacts,
+    /// Terraform output lines (stdout, plus stderr for failed plans)
+    pub output: Vec<String>,
+}
+
+/// Root of the isolated TF_DATA_DIR directories, relative to where solarboat runs
+pub const DATA_DIR_ROOT: &str = ".solarboat/data";
+
+/// Absolute TF_DATA_DIR for a module, or for one workspace of a module when given.
+/// Keeps `.terraform` out of the module source tree.
+pub fn isolated_data_dir(module_path: &str, workspace: Option<&str>) -> PathBuf {
+    let mut data_dir = std::env::current_dir()
+        .unwrap_or_default()
+        .join(DATA_DIR_ROOT)
+        .join(crate::utils::plan_manifest::module_dir_key(module_path));
+    // Kept in a subdirectory so workspace names can't clash with terraform's own files
+    if let Some(workspace) = workspace {
+        data_dir.push("workspaces");
+        data_dir.push(workspace);
+    }
+    data_dir
+}
+
+/// Root of the state snapshots taken before live applies, relative to where solarboat runs
+pub const SNAPSHOT_ROOT: &str = ".solarboat/snapshots";
+
+/// Absolute directory of a module's state snapshots, under the snapshot root even for modules
+/// outside the current directory
+fn snapshot_dir(module_path: &str) -> PathBuf {
+    std::env::current_dir()
+        .unwrap_or_default()
+        .join(SNAPSHOT_ROOT)
+        .join(crate::utils::plan_manifest::module_dir_key(module_path))
+}
+
+/// Save the state of the module's selected workspace with `terraform state pull` as
+/// `.solarboat/snapshots/<module>/<workspace>-<timestamp>.tfstate`.
+/// Returns the absolute snapshot path, or None when the workspace has no state yet.
+pub fn snapshot_state(module_path: &str, workspace: Option<&str>, data_dir: Option<&Path>) -> Result<Option<PathBuf>, String> {
+    let output = terraform_command(module_path, data_dir)
+        .arg("state")
+        .arg("pull")
+        .output()
+        .map_err(|e| format!("Failed to run terraform state pull: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("terraform state pull failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    if output.stdout.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(None);
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| format!("Failed to get timestamp: {}", e))?
+        .as_secs();
+    let dir = snapshot_dir(module_path);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let path = dir.join(format!("{}-{}.tfstate", workspace.unwrap_or("default"), timestamp));
+    std::fs::write(&path, &output.stdout)
+        .map_err(|e| format!("Failed to write state snapshot {}: {}", path.display(), e))?;
+    Ok(Some(path))
+}
+
+/// Push a state snapshot back as the workspace's state with `terraform state push -force`.
+/// Only the state is restored; infrastructure changed by the apply is left as it is.
+pub fn restore_state(module_path: &str, workspace: Option<&str>, data_dir: Option<&Path>, snapshot: &Path) -> Result<(), String> {
+    if let Some(workspace) = workspace {
+        select_workspace(module_path, workspace, data_dir)?;
+    }
+    let output = terraform_command(module_path, data_dir)
+        .arg("state")
+        .arg("push")
+        .arg("-force")
+        .arg(snapshot)
+        .output()
+        .map_err(|e| format!("Failed to run terraform state push: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("terraform state push failed: {}", String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+thread_local! {
+    /// Environment variables of the operation running on this thread, set with `with_env`
+    static OPERATION_ENV: RefCell<BTreeMap<String, String>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Run `f` with `env` added to the environment of every terraform command and hook started on this thread.
+/// Assumed role credentials in `env` are renewed first when they are about to expire.
+pub fn with_env<T>(env: &BTreeMap<String, String>, f: impl FnOnce() -> T) -> T {
+    let previous = OPERATION_ENV.with(|current| current.replace(crate::utils::credentials::refresh(env)));
+    let result = f();
+    OPERATION_ENV.with(|current| current.replace(previous));
+    result
+}
+
+/// Add the environment variables set with `with_env` to a command
+pub fn apply_env(cmd: &mut Command) {
+    OPERATION_ENV.with(|env| {
+        cmd.envs(env.borrow().iter());
+    });
 }
 
-/// Ensure terraform module is initialized before operations
-pub fn ensure_module_initialized(module_path: &str) -> Result<(), String> {    
-    // Check if .terraform directory exists to avoid unnecessary init
-    let terraform_dir = std::path::Path::new(module_path).join(".terraform");
-    if terraform_dir.exists() {
+/// Build a terraform command running in the module directory, with the operation's environment
+/// variables and TF_DATA_DIR set when isolated
+pub fn terraform_command(module_path: &str, data_dir: Option<&Path>) -> Command {
+    let mut cmd = Command::new("terraform");
+    cmd.current_dir(module_path);
+    env_passthrough::sanitize(&mut cmd);
+    apply_env(&mut cmd);
+    if let Some(data_dir) = data_dir {
+        cmd.env("TF_DATA_DIR", data_dir);
+    }
+    cmd
+}
+
+/// File in the terraform data directory recording the `-backend-config` values and extra arguments of the last init
+const BACKEND_CONFIG_MARKER: &str = "solarboat-backend-config";
+
+/// Data directory terraform initializes a module into
+fn terraform_dir(module_path: &str, data_dir: Option<&Path>) -> PathBuf {
+    data_dir
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| Path::new(module_path).join(".terraform"))
+}
+
+/// Arguments for `terraform init` with a partial backend configuration and the module's extra init
+/// arguments. An initialized module is reconfigured, since its backend configuration may have changed.
+pub fn init_args(module_path: &str, data_dir: Option<&Path>, backend_config: &[String], extra_args: &[String]) -> Vec<String> {
+    let mut args = vec!["init".to_string()];
+    if !backend_config.is_empty() && terraform_dir(module_path, data_dir).exists() {
+        args.push("-reconfigure".to_string());
+    }
+    args.extend(backend_config.iter().map(|config| format!("-backend-config={}", config)));
+    args.extend(extra_args.iter().cloned());
+    args
+}
+
+/// Ensure terraform module is initialized before operations. A module initialized with a different
+/// backend configuration or different extra init arguments is initialized again.
+pub fn ensure_module_initialized(module_path: &str, data_dir: Option<&Path>, backend_config: &[String], extra_args: &[String]) -> Result<(), String> {    
+    // Check if the data directory exists to avoid unnecessary init
+    let terraform_dir = terraform_dir(module_path, data_dir);
+    let marker = terraform_dir.join(BACKEND_CONFIG_MARKER);
+    let recorded = std::fs::read_to_string(&marker).unwrap_or_default();
+    let init_config = backend_config.iter().chain(extra_args).cloned().collect::<Vec<_>>().join("\n");
+    if terraform_dir.exists() && recorded == init_config {
         // Check if it's properly initialized by trying to list workspaces
-        let workspace_check = Command::new("terraform")
+        let workspace_check = terraform_command(module_path, data_dir)
             .arg("workspace")
             .arg("list")
-            .current_dir(module_path)
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .status();
@@ -54,27 +354,44 @@ pub fn ensure_module_initialized(module_path: &str) -> Result<(), String> {
     }
     
     // Initialize if needed
-    let output = Command::new("terraform")
-        .arg("init")
-        .current_dir(module_path)
+    let output = terraform_command(module_path, data_dir)
+        .args(init_args(module_path, data_dir, backend_config, extra_args))
         .output()
         .map_err(|e| format!("Failed to run terraform init: {}", e))?;
 
     if !output.status.success() {
-        let error_msg = String::from_utf8_lossy(&output.stderr);
+        let error_msg = redact::redact(&String::from_utf8_lossy(&output.stderr));
         return Err(format!("Terraform init failed: {}", error_msg));
     }
 
+    if init_config.is_empty() {
+        let _ = std::fs::remove_file(&marker);
+    } else {
+        let _ = std::fs::write(&marker, init_config);
+    }
     Ok(())
 }
 
+/// Forget the backend configuration of the last init, so the next `ensure_module_initialized` initializes again
+pub fn forget_backend_config(module_path: &str, data_dir: Option<&Path>) {
+    let _ = std::fs::remove_file(terraform_dir(module_path, data_dir).join(BACKEND_CONFIG_MARKER));
+}
+
 /// Select a terraform workspace
-pub fn select_workspace(module_path: &str, workspace: &str) -> Result<(), String> {
+pub fn select_workspace(module_path: &str, workspace: &str, data_dir: Option<&Path>) -> Result<(), String> {
+    // Terraform refuses to select a workspace while TF_WORKSPACE overrides it
+    if let Some(selected) = OPERATION_ENV.with(|env| env.borrow().get("TF_WORKSPACE").cloned()) {
+        return if selected == workspace {
+            Ok(())
+        } else {
+            Err(format!("Failed to select workspace {}: TF_WORKSPACE is set to {}", workspace, selected))
+        };
+    }
+    
     // First check if we're already in the correct workspace
-    let current_workspace = Command::new("terraform")
+    let current_workspace = terraform_command(module_path, data_dir)
         .arg("workspace")
         .arg("show")
-        .current_dir(module_path)
         .output()
         .map_err(|e| format!("Failed to get current workspace: {}", e))?;
 
@@ -86,11 +403,10 @@ pub fn select_workspace(module_path: &str, workspace: &str) -> Result<(), String
     }
 
     // Only select if we're not already in the correct workspace
-    let mut cmd = Command::new("terraform");
+    let mut cmd = terraform_command(module_path, data_dir);
     cmd.arg("workspace")
        .arg("select")
        .arg(workspace)
-       .current_dir(module_path)
        .stdout(Stdio::null())
        .stderr(Stdio::null());
 
@@ -104,39 +420,275 @@ pub fn select_workspace(module_path: &str, workspace: &str) -> Result<(), String
     }
 }
 
-/// Save plan output to a markdown file
-/// Uses naming convention: {module_name}-{workspace}-{timestamp}.tfplan.md
-pub fn save_plan_output(module_path: &str, plan_dir: &str, workspace: Option<&str>, output_lines: &[String]) -> Result<(), String> {
-    // Create the plan directory if it doesn't exist
+/// Create a workspace with `terraform workspace new`, which also selects it
+pub fn create_workspace(module_path: &str, workspace: &str, data_dir: Option<&Path>) -> Result<(), String> {
+    let output = terraform_command(module_path, data_dir)
+        .arg("workspace")
+        .arg("new")
+        .arg(workspace)
+        .output()
+        .map_err(|e| format!("Failed to create workspace {}: {}", workspace, e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("Failed to create workspace {}: {}", workspace, redact::redact(String::from_utf8_lossy(&output.stderr).trim())))
+    }
+}
+
+/// Default layout of plan and apply artifacts in the output directory
+pub const DEFAULT_ARTIFACT_TEMPLATE: &str = "{module}/{workspace}/{timestamp}";
+
+/// Configured layout of artifacts, set once the configuration is loaded
+static ARTIFACT_TEMPLATE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Lay out artifacts saved from now on with `template` instead of the default one
+pub fn set_artifact_template(template: Option<String>) {
+    if let Ok(mut current) = ARTIFACT_TEMPLATE.lock() {
+        *current = template;
+    }
+}
+
+/// Build the shared stem of a plan's artifacts, relative to the output directory, from the
+/// artifact template with `{module}` (the module's path, as keyed in the manifest), `{workspace}`
+/// and `{timestamp}` filled in
+pub fn plan_artifact_stem(module_path: &str, workspace: Option<&str>) -> Result<String, String> {
+    let module_key = crate::utils::plan_manifest::module_dir_key(module_path);
+    let module_key = module_key.to_str()
+        .ok_or_else(|| format!("Invalid module path: {}", module_path))?;
+    
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| format!("Failed to get timestamp: {}", e))?
+        .as_secs();
+    
+    let template = ARTIFACT_TEMPLATE.lock().ok()
+        .and_then(|template| template.clone())
+        .unwrap_or_else(|| DEFAULT_ARTIFACT_TEMPLATE.to_string());
+    Ok(artifact_stem(&template, module_key, workspace.unwrap_or("default"), timestamp))
+}
+
+fn artifact_stem(template: &str, module_key: &str, workspace: &str, timestamp: u64) -> String {
+    template
+        .replace("{module}", module_key)
+        .replace("{workspace}", workspace)
+        .replace("{timestamp}", &timestamp.to_string())
+}
+
+/// Check an artifact template: its stems must stay inside the output directory
+pub fn validate_artifact_template(template: &str) -> Result<(), String> {
+    let path = Path::new(template);
+    if template.trim().is_empty() || path.is_absolute()
+        || path.components().any(|component| !matches!(component, std::path::Component::Normal(_))) {
+        return Err(format!("Invalid artifact template '{}': must be a relative path inside the output directory", template));
+    }
+    Ok(())
+}
+
+/// Absolute path of a file inside the plan directory, creating the directories leading to it.
+/// Terraform runs inside the module directory, so relative plan paths must be resolved first.
+pub fn plan_dir_file(plan_dir: &str, file_name: &str) -> Result<PathBuf, String> {
     std::fs::create_dir_all(plan_dir)
         .map_err(|e| format!("Failed to create plan directory: {}", e))?;
-        
-    if let Some(module_name) = Path::new(module_path).file_name().and_then(|n| n.to_str()) {
-        // Get current timestamp
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .map_err(|e| format!("Failed to get timestamp: {}", e))?
-            .as_secs();
-        
-        // Create filename with workspace and timestamp
-        let workspace_name = workspace.unwrap_or("default");
-        let filename = format!("{}-{}-{}.tfplan.md", module_name, workspace_name, timestamp);
-        let plan_file = Path::new(plan_dir).join(filename);
-        
-        // Format the output
-        let mut content = format!("# Terraform Plan Output for {} (workspace: {})\n\n", module_name, workspace_name);
-        content.push_str("```\n");
-        for line in output_lines {
-            content.push_str(&clean_terraform_output(line));
-            content.push('\n');
-        }
-        content.push_str("```\n");
-        
-        std::fs::write(&plan_file, content)
-            .map_err(|e| format!("Failed to write plan file: {}", e))?;
+    let plan_dir = std::fs::canonicalize(plan_dir)
+        .map_err(|e| format!("Failed to resolve plan directory: {}", e))?;
+    let file = plan_dir.join(file_name);
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create plan directory: {}", e))?;
     }
+    Ok(file)
+}
 
-    Ok(())
+/// Copy the report, JSON plan and summary of a plan next to it as `latest.*`, so the latest plan of
+/// a module is found without knowing its timestamp. Stems without a directory get no copies, since
+/// the copies of different modules would overwrite each other.
+fn save_latest_artifacts(plan_dir: &str, stem: &str, artifacts: &PlanArtifacts) {
+    let Some(dir) = Path::new(stem).parent().filter(|dir| !dir.as_os_str().is_empty()) else {
+        return;
+    };
+    let saved = [&artifacts.report, &artifacts.plan_json, &artifacts.summary_file];
+    for file in saved.into_iter().flatten() {
+        let Some(suffix) = file.strip_prefix(stem) else { continue };
+        let latest = dir.join(format!("latest{}", suffix));
+        let copied = plan_dir_file(plan_dir, file).and_then(|from| {
+            let to = plan_dir_file(plan_dir, &latest.to_string_lossy())?;
+            std::fs::copy(from, to).map_err(|e| e.to_string())
+        });
+        if let Err(e) = copied {
+            eprintln!("Warning: Failed to copy {} to {}: {}", file, latest.display(), e);
+        }
+    }
+}
+
+/// Save plan output to a markdown file named {stem}.tfplan.md, prefixed with the change summary when available
+/// Returns the file name of the saved report.
+pub fn save_plan_output(module_path: &str, plan_dir: &str, stem: &str, workspace: Option<&str>, output_lines: &[String], summary: Option<&PlanSummary>, format: PlanFormat) -> Result<String, String> {
+    let module_name = Path::new(module_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format!("Invalid module path: {}", module_path))?;
+    
+    let filename = format!("{}.tfplan.md", stem);
+    let plan_file = plan_dir_file(plan_dir, &filename)?;
+    
+    // Format the output
+    let workspace_name = workspace.unwrap_or("default");
+    let content = match format {
+        PlanFormat::Markdown => {
+            let mut content = format!("# Terraform Plan Output for {} (workspace: {})\n\n", module_name, workspace_name);
+            if let Some(summary) = summary {
+                content.push_str(&summary.to_markdown());
+            }
+            content.push_str("```\n");
+            for line in output_lines {
+                content.push_str(&clean_terraform_output(line));
+                content.push('\n');
+            }
+            content.push_str("```\n");
+            content
+        }
+        PlanFormat::Github => {
+            let cleaned: Vec<String> = output_lines.iter().map(|line| clean_terraform_output(line)).collect();
+            plan_report::render_github_report(module_name, workspace_name, summary, &cleaned)
+        }
+    };
+    
+    std::fs::write(&plan_file, content)
+        .map_err(|e| format!("Failed to write plan file: {}", e))?;
+
+    Ok(filename)
+}
+
+/// Save apply output to a markdown file named {stem}.apply.md
+/// Returns the file name of the saved report.
+pub fn save_apply_output(module_path: &str, report_dir: &str, workspace: Option<&str>, output_lines: &[String], success: bool) -> Result<String, String> {
+    let module_name = Path::new(module_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format!("Invalid module path: {}", module_path))?;
+    
+    let filename = format!("{}.apply.md", plan_artifact_stem(module_path, workspace)?);
+    let report_file = plan_dir_file(report_dir, &filename)?;
+    
+    let workspace_name = workspace.unwrap_or("default");
+    let mut content = format!("# Terraform Apply Output for {} (workspace: {})\n\n", module_name, workspace_name);
+    content.push_str(&format!("**Status:** {}\n\n", if success { "succeeded" } else { "failed" }));
+    content.push_str("```\n");
+    for line in output_lines {
+        content.push_str(&clean_terraform_output(line));
+        content.push('\n');
+    }
+    content.push_str("```\n");
+    
+    std::fs::write(&report_file, content)
+        .map_err(|e| format!("Failed to write apply report: {}", e))?;
+
+    Ok(filename)
+}
+
+/// Render a binary plan file as JSON with `terraform show -json` and save it as {stem}.tfplan.json
+/// Returns the file name of the saved JSON plan.
+pub fn export_plan_json(module_path: &str, plan_dir: &str, stem: &str, data_dir: Option<&Path>) -> Result<String, String> {
+    let plan_file = plan_dir_file(plan_dir, &format!("{}.tfplan", stem))?;
+    
+    let output = terraform_command(module_path, data_dir)
+        .arg("show")
+        .arg("-json")
+        .arg(&plan_file)
+        .output()
+        .map_err(|e| format!("Failed to run terraform show: {}", e))?;
+    
+    if !output.status.success() {
+        return Err(format!("terraform show -json failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    
+    let filename = format!("{}.tfplan.json", stem);
+    std::fs::write(plan_dir_file(plan_dir, &filename)?, &output.stdout)
+        .map_err(|e| format!("Failed to write plan JSON: {}", e))?;
+    
+    Ok(filename)
+}
+
+/// Parse the JSON plan {stem}.tfplan.json and save its change summary as {stem}.summary.json
+/// Returns the summary and the file name it was saved to.
+pub fn save_plan_summary(plan_dir: &str, stem: &str, plan_json_file: &str) -> Result<(PlanSummary, String), String> {
+    let changes = plan_json::load_plan_changes(&plan_dir_file(plan_dir, plan_json_file)?)?;
+    let summary = PlanSummary::from_changes(&changes);
+    
+    let filename = format!("{}.summary.json", stem);
+    let content = serde_json::to_string_pretty(&summary)
+        .map_err(|e| format!("Failed to serialize plan summary: {}", e))?;
+    std::fs::write(plan_dir_file(plan_dir, &filename)?, content)
+        .map_err(|e| format!("Failed to write plan summary: {}", e))?;
+    
+    Ok((summary, filename))
+}
+
+/// Save the JSON rendering, change summary and report for a completed plan whose binary plan was written to {stem}.tfplan
+pub fn save_plan_artifacts(module_path: &str, plan_dir: &str, stem: &str, workspace: Option<&str>, output_lines: &[String], format: PlanFormat, data_dir: Option<&Path>) -> PlanArtifacts {
+    let mut artifacts = PlanArtifacts {
+        plan_file: Some(format!("{}.tfplan", stem)),
+        ..Default::default()
+    };
+    
+    match export_plan_json(module_path, plan_dir, stem, data_dir) {
+        Ok(plan_json) => artifacts.plan_json = Some(plan_json),
+        Err(e) => eprintln!("Warning: Failed to export plan JSON: {}", e),
+    }
+    
+    if let Some(plan_json) = &artifacts.plan_json {
+        match save_plan_summary(plan_dir, stem, plan_json) {
+            Ok((summary, summary_file)) => {
+                artifacts.summary = Some(summary);
+                artifacts.summary_file = Some(summary_file);
+            }
+            Err(e) => eprintln!("Warning: Failed to summarize plan: {}", e),
+        }
+    }
+    
+    match save_plan_output(module_path, plan_dir, stem, workspace, output_lines, artifacts.summary.as_ref(), format) {
+        Ok(report) => artifacts.report = Some(report),
+        Err(e) => eprintln!("Warning: Failed to save plan output: {}", e),
+    }
+    
+    save_latest_artifacts(plan_dir, stem, &artifacts);
+    artifacts
+}
+
+/// Check whether terraform reported that the plan has no changes
+pub fn plan_has_no_changes(output_lines: &[String]) -> bool {
+    output_lines.iter().any(|line| clean_terraform_output(line).trim_start().starts_with("No changes."))
+}
+
+/// Drop the binary plan of a run that reported no changes and mark it clean
+pub fn discard_empty_plan(plan_dir: &str, stem: &str) -> PlanArtifacts {
+    if let Ok(plan_file) = plan_dir_file(plan_dir, &format!("{}.tfplan", stem)) {
+        let _ = std::fs::remove_file(plan_file);
+    }
+    PlanArtifacts { clean: true, ..Default::default() }
+}
+
+/// Parse the resource counts from terraform's "Apply complete! Resources: X added, Y changed, Z destroyed."
+/// (or "Destroy complete! Resources: Z destroyed.") line
+pub fn parse_apply_counts(output_lines: &[String]) -> Option<ChangeCounts> {
+    let apply_re = Regex::new(r"Apply complete! Resources: (\d+) added, (\d+) changed, (\d+) destroyed").unwrap();
+    let destroy_re = Regex::new(r"Destroy complete! Resources: (\d+) destroyed").unwrap();
+
+    output_lines.iter().rev().find_map(|line| {
+        let line = clean_terraform_output(line);
+        if let Some(caps) = apply_re.captures(&line) {
+            return Some(ChangeCounts {
+                add: caps[1].parse().ok()?,
+                change: caps[2].parse().ok()?,
+                destroy: caps[3].parse().ok()?,
+            });
+        }
+        destroy_re.captures(&line).and_then(|caps| Some(ChangeCounts {
+            destroy: caps[1].parse().ok()?,
+            ..Default::default()
+        }))
+    })
 }
 
 /// Remove ANSI color codes from terraform output
@@ -147,74 +699,140 @@ pub fn clean_terraform_output(input: &str) -> String {
 }
 
 /// Run a single terraform plan operation
-pub fn run_single_plan(module_path: &str, plan_dir: Option<&str>, workspace: Option<&str>, var_files: Option<&[String]>) -> Result<bool, String> {
+#[allow(clippy::too_many_arguments)]
+pub fn run_single_plan(module_path: &str, plan_dir: Option<&str>, workspace: Option<&str>, var_files: Option<&[String]>, args: &TerraformArgs, format: PlanFormat, skip_empty: bool, data_dir: Option<&Path>) -> Result<PlanOutcome, String> {
     // Ensure module is initialized before planning
-    ensure_module_initialized(module_path)?;
+    ensure_module_initialized(module_path, data_dir, &args.backend_config, &args.extra_init_args)?;
     
-    let mut cmd = Command::new("terraform");
-    cmd.arg("plan").current_dir(module_path);
+    let mut cmd = terraform_command(module_path, data_dir);
+    cmd.arg("plan");
     
     if let Some(var_files) = var_files {
         for var_file in var_files {
             cmd.arg("-var-file").arg(var_file);
         }
     }
+    cmd.args(args.plan_args());
+    
+    // Save a binary plan alongside the report so it can be inspected and applied later
+    let stem = match plan_dir {
+        Some(plan_dir) => {
+            let stem = plan_artifact_stem(module_path, workspace)?;
+            cmd.arg(format!("-out={}", plan_dir_file(plan_dir, &format!("{}.tfplan", stem))?.display()));
+            Some(stem)
+        }
+        None => None,
+    };
 
     let output = cmd.output()
         .map_err(|e| e.to_string())?;
 
     if !output.status.success() {
-        eprintln!("{}", String::from_utf8_lossy(&output.stderr));
-        return Ok(false);
+        let stderr = redact::redact(&String::from_utf8_lossy(&output.stderr));
+        eprintln!("{}", stderr);
+        return Ok(PlanOutcome {
+            output: String::from_utf8_lossy(&output.stdout).lines().map(redact::redact).chain(stderr.lines().map(str::to_string)).collect(),
+            ..Default::default()
+        });
     }
 
+    let mut outcome = PlanOutcome {
+        success: true,
+        output: String::from_utf8_lossy(&output.stdout).lines().map(redact::redact).collect(),
+        ..Default::default()
+    };
+
     // If plan_dir is specified, save the plan output
-    if let Some(plan_dir) = plan_dir {
-        let plan_output = String::from_utf8_lossy(&output.stdout).to_string();
-        let output_lines: Vec<String> = plan_output.lines().map(|s| s.to_string()).collect();
-        if let Err(e) = save_plan_output(module_path, plan_dir, workspace, &output_lines) {
-            eprintln!("Warning: Failed to save plan output: {}", e);
+    if let (Some(plan_dir), Some(stem)) = (plan_dir, stem) {
+        if skip_empty && plan_has_no_changes(&outcome.output) {
+            outcome.artifacts = discard_empty_plan(plan_dir, &stem);
+            return Ok(outcome);
         }
+        outcome.artifacts = save_plan_artifacts(module_path, plan_dir, &stem, workspace, &outcome.output, format, data_dir);
     }
 
-    Ok(true)
+    Ok(outcome)
 }
 
-/// Run a single terraform apply operation
-pub fn run_single_apply(module_path: &str, var_files: Option<&[String]>) -> Result<bool, String> {
+/// Run a single terraform apply operation.
+/// When a saved plan is given it is applied as-is and var files and most `args` are ignored.
+/// Output is streamed to the console as terraform writes it, and saved as an apply report when a report directory is given.
+pub fn run_single_apply(module_path: &str, workspace: Option<&str>, var_files: Option<&[String]>, args: &TerraformArgs, plan_file: Option<&Path>, report_dir: Option<&str>, data_dir: Option<&Path>) -> Result<ApplyOutcome, String> {
     // Ensure module is initialized before applying
-    ensure_module_initialized(module_path)?;
+    ensure_module_initialized(module_path, data_dir, &args.backend_config, &args.extra_init_args)?;
     
-    let mut cmd = Command::new("terraform");
+    let mut cmd = terraform_command(module_path, data_dir);
     cmd.arg("apply")
        .arg("-auto-approve")
-       .arg("-input=false")  // Prevent interactive prompts
-       .current_dir(module_path);
+       .arg("-input=false");  // Prevent interactive prompts
     
-    if let Some(var_files) = var_files {
+    if let (None, Some(var_files)) = (plan_file, var_files) {
         for var_file in var_files {
             cmd.arg("-var-file").arg(var_file);
         }
     }
+    cmd.args(args.apply_args(plan_file.is_some()));
+    // The saved plan must come after all options
+    if let Some(plan_file) = plan_file {
+        cmd.arg(plan_file);
+    }
 
-    let status = cmd.status()
+    let mut child = cmd.stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let stdout = child.stdout.take().ok_or_else(|| "Failed to capture stdout".to_string())?;
+    let stderr = child.stderr.take().ok_or_else(|| "Failed to capture stderr".to_string())?;
+
+    // Stream both pipes as terraform writes them, so long applies and lock waits stay visible
+    let stderr_handle = thread::spawn(move || {
+        BufReader::new(stderr).lines()
+            .map_while(Result::ok)
+            .map(|line| {
+                let line = redact::redact(&line);
+                eprintln!("{}", line);
+                line
+            })
+            .collect::<Vec<_>>()
+    });
+    let mut output: Vec<String> = BufReader::new(stdout).lines()
+        .map_while(Result::ok)
+        .map(|line| {
+            let line = redact::redact(&line);
+            println!("{}", line);
+            line
+        })
+        .collect();
+    output.extend(stderr_handle.join().unwrap_or_default());
+    let status = child.wait()
         .map_err(|e| e.to_string())?;
 
-    Ok(status.success())
+    let mut outcome = ApplyOutcome {
+        success: status.success(),
+        output,
+        report: None,
+    };
+    if let Some(report_dir) = report_dir {
+        match save_apply_output(module_path, report_dir, workspace, &outcome.output, outcome.success) {
+            Ok(report) => outcome.report = Some(report),
+            Err(e) => eprintln!("Warning: Failed to save apply output: {}", e),
+        }
+    }
+
+    Ok(outcome)
 }
 
 
-pub fn check_state_lock_available(module_path: &str, workspace: Option<&str>) -> bool {
+pub fn check_state_lock_available(module_path: &str, workspace: Option<&str>, data_dir: Option<&Path>) -> bool {
     if let Some(ws) = workspace {
-        if let Err(_) = select_workspace(module_path, ws) {
+        if let Err(_) = select_workspace(module_path, ws, data_dir) {
             return false;
         }
     }
     
-    let result = Command::new("terraform")
+    let result = terraform_command(module_path, data_dir)
         .arg("state")
         .arg("list")
-        .current_dir(module_path)
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .status();
@@ -222,7 +840,7 @@ pub fn check_state_lock_available(module_path: &str, workspace: Option<&str>) ->
     result.is_ok() && result.unwrap().success()
 }
 
-pub fn wait_for_state_lock_release(module_path: &str, workspace: Option<&str>, max_wait: Duration) -> bool {
+pub fn wait_for_state_lock_release(module_path: &str, workspace: Option<&str>, data_dir: Option<&Path>, max_wait: Duration) -> bool {
     use std::time::Instant;
     
     let start = Instant::now();
@@ -230,7 +848,7 @@ pub fn wait_for_state_lock_release(module_path: &str, workspace: Option<&str>, m
     let max_attempts = 10;
     
     while start.elapsed() < max_wait && attempt < max_attempts {
-        if check_state_lock_available(module_path, workspace) {
+        if check_state_lock_available(module_path, workspace, data_dir) {
             return true; // State lock is available
         }
         
@@ -241,3 +859,131 @@ pub fn wait_for_state_lock_release(module_path: &str, workspace: Option<&str>, m
     
     false // Timeout reached
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_apply_counts() {
+        let output = vec![
+            "aws_vpc.main: Creation complete after 2s".to_string(),
+            "\x1b[0m\x1b[1m\x1b[32mApply complete! Resources: 2 added, 1 changed, 0 destroyed.\x1b[0m".to_string(),
+        ];
+        assert_eq!(parse_apply_counts(&output), Some(ChangeCounts { add: 2, change: 1, destroy: 0 }));
+
+        let output = vec!["Destroy complete! Resources: 3 destroyed.".to_string()];
+        assert_eq!(parse_apply_counts(&output), Some(ChangeCounts { add: 0, change: 0, destroy: 3 }));
+
+        assert_eq!(parse_apply_counts(&["Error: access denied".to_string()]), None);
+    }
+
+    #[test]
+    fn test_terraform_args() {
+        let args = TerraformArgs {
+            vars: vec!["region=eu-west-1".to_string()],
+            targets: vec!["aws_vpc.main".to_string()],
+            no_refresh: true,
+            parallelism: Some(20),
+            lock_timeout: Some("5m".to_string()),
+            destroy: false,
+            refresh_only: false,
+            backend_config: vec!["bucket=tf-state".to_string()],
+            extra_init_args: vec!["-upgrade".to_string()],
+            extra_plan_args: vec!["-compact-warnings".to_string()],
+            extra_apply_args: vec!["-no-color".to_string()],
+        };
+        assert_eq!(
+            args.to_args(),
+            vec!["-var", "region=eu-west-1", "-target=aws_vpc.main", "-refresh=false", "-parallelism=20", "-lock-timeout=5m"]
+        );
+        assert_eq!(args.plan_args().last().map(String::as_str), Some("-compact-warnings"));
+        assert_eq!(args.apply_args(true), vec!["-parallelism=20", "-lock-timeout=5m", "-no-color"]);
+        assert!(TerraformArgs::default().to_args().is_empty());
+
+        let destroy = TerraformArgs { destroy: true, ..Default::default() };
+        assert_eq!(destroy.to_args(), vec!["-destroy"]);
+        assert!(destroy.apply_args(true).is_empty());
+        assert_eq!(TerraformArgs { refresh_only: true, ..Default::default() }.to_args(), vec!["-refresh-only"]);
+    }
+
+    #[test]
+    fn test_destroy_args() {
+        let args = TerraformArgs {
+            destroy: true,
+            vars: vec!["region=eu-west-1".to_string()],
+            extra_apply_args: vec!["-no-color".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(args.plan_args(), vec!["-destroy", "-var", "region=eu-west-1"]);
+        assert_eq!(args.apply_args(false), vec!["-destroy", "-var", "region=eu-west-1", "-no-color"]);
+        // A saved destroy plan already records its mode and variables
+        assert_eq!(args.apply_args(true), vec!["-no-color"]);
+    }
+
+    #[test]
+    fn test_init_args() {
+        let dir = tempfile::tempdir().unwrap();
+        let module = dir.path().to_string_lossy().to_string();
+        let config = vec!["key=app/prod.tfstate".to_string(), "/repo/prod.tfbackend".to_string()];
+        assert_eq!(init_args(&module, None, &[], &[]), vec!["init"]);
+        assert_eq!(init_args(&module, None, &[], &["-upgrade".to_string()]), vec!["init", "-upgrade"]);
+        assert_eq!(init_args(&module, None, &config, &[]), vec!["init", "-backend-config=key=app/prod.tfstate", "-backend-config=/repo/prod.tfbackend"]);
+        std::fs::create_dir(dir.path().join(".terraform")).unwrap();
+        assert_eq!(init_args(&module, None, &config[..1], &[]), vec!["init", "-reconfigure", "-backend-config=key=app/prod.tfstate"]);
+    }
+
+    #[test]
+    fn test_with_env() {
+        let env = BTreeMap::from([("AWS_REGION".to_string(), "eu-west-1".to_string())]);
+        let envs = |cmd: &Command| -> Vec<(String, String)> {
+            cmd.get_envs()
+                .filter_map(|(key, value)| Some((key.to_string_lossy().to_string(), value?.to_string_lossy().to_string())))
+                .collect()
+        };
+        let cmd = with_env(&env, || terraform_command("stacks/app", Some(Path::new("/tmp/data"))));
+        assert_eq!(envs(&cmd), vec![
+            ("AWS_REGION".to_string(), "eu-west-1".to_string()),
+            ("TF_DATA_DIR".to_string(), "/tmp/data".to_string()),
+        ]);
+        // The environment only applies inside with_env
+        assert!(envs(&terraform_command("stacks/app", None)).is_empty());
+        
+        // With TF_WORKSPACE set, selecting runs no terraform command
+        let env = BTreeMap::from([("TF_WORKSPACE".to_string(), "prod".to_string())]);
+        assert!(with_env(&env, || select_workspace("/nonexistent", "prod", None)).is_ok());
+        assert!(with_env(&env, || select_workspace("/nonexistent", "dev", None)).is_err());
+    }
+
+    #[test]
+    fn test_isolated_data_dir() {
+        let root = std::env::current_dir().unwrap().join(DATA_DIR_ROOT);
+        assert_eq!(isolated_data_dir("stacks/app", Some("prod")), root.join("stacks/app/workspaces/prod"));
+        // Modules outside the current directory stay under the data directory root too
+        assert_eq!(isolated_data_dir("/elsewhere/stacks/app", None), root.join("elsewhere/stacks/app"));
+        assert!(isolated_data_dir("../stacks/app", None).starts_with(&root));
+    }
+
+    #[test]
+    fn test_snapshot_dir() {
+        let root = std::env::current_dir().unwrap().join(SNAPSHOT_ROOT);
+        assert_eq!(snapshot_dir("stacks/app"), root.join("stacks/app"));
+        assert_eq!(snapshot_dir("/elsewhere/stacks/app"), root.join("elsewhere/stacks/app"));
+    }
+
+    #[test]
+    fn test_artifact_stem() {
+        assert_eq!(artifact_stem(DEFAULT_ARTIFACT_TEMPLATE, "networking", "prod", 1760000000), "networking/prod/1760000000");
+        
+        // Modules sharing a directory name get artifacts and latest copies of their own
+        let prod = plan_artifact_stem("envs/prod/network", Some("default")).unwrap();
+        let dev = plan_artifact_stem("envs/dev/network", Some("default")).unwrap();
+        assert!(prod.starts_with("envs/prod/network/default/"));
+        assert!(dev.starts_with("envs/dev/network/default/"));
+        assert_ne!(Path::new(&prod).parent(), Path::new(&dev).parent());
+        assert_eq!(artifact_stem("{module}-{workspace}-{timestamp}", "networking", "prod", 1), "networking-prod-1");
+        assert!(validate_artifact_template("plans/{module}/{timestamp}").is_ok());
+        assert!(validate_artifact_template("../{module}").is_err());
+        assert!(validate_artifact_template("/tmp/{module}").is_err());
+    }
+}
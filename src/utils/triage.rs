@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::utils::plan_manifest::module_key;
+use crate::utils::terraform_operations::{OperationResult, OperationType};
+
+/// Where to write a triage bundle and the git inputs used for change detection
+#[derive(Debug, Clone)]
+pub struct TriageOptions {
+    /// Directory the `triage-<runid>.tar.gz` bundle is written to
+    pub output_dir: String,
+    pub root_dir: String,
+    pub default_branch: String,
+    pub recent_commits: u32,
+    pub all: bool,
+}
+
+/// Write `triage-<runid>.tar.gz` for a failed run. It contains results.json,
+/// per-operation logs, the resolved config, the tool versions and the git context.
+/// Returns the path of the bundle.
+pub fn write_bundle(options: &TriageOptions, command: &str, results: &[OperationResult], resolved_config: &serde_json::Value) -> Result<PathBuf, String> {
+    let run_id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| format!("Failed to get timestamp: {}", e))?
+        .as_secs();
+    let name = format!("triage-{}", run_id);
+
+    std::fs::create_dir_all(&options.output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", options.output_dir, e))?;
+    let output_dir = std::fs::canonicalize(&options.output_dir)
+        .map_err(|e| format!("Failed to resolve {}: {}", options.output_dir, e))?;
+    let staging = output_dir.join(&name);
+    let logs = staging.join("logs");
+    std::fs::create_dir_all(&logs)
+        .map_err(|e| format!("Failed to create {}: {}", logs.display(), e))?;
+
+    let written = write_contents(&staging, &logs, options, command, results, resolved_config)
+        .and_then(|_| archive(&output_dir, &name));
+    let _ = std::fs::remove_dir_all(&staging);
+    written
+}
+
+fn write_contents(
+    staging: &Path,
+    logs: &Path,
+    options: &TriageOptions,
+    command: &str,
+    results: &[OperationResult],
+    resolved_config: &serde_json::Value,
+) -> Result<(), String> {
+    write_json(&staging.join("results.json"), &serde_json::json!({
+        "command": command,
+        "results": results.iter().map(result_json).collect::<Vec<_>>(),
+    }))?;
+    write_json(&staging.join("config.json"), resolved_config)?;
+    write_json(&staging.join("environment.json"), &environment_fingerprint())?;
+    write_json(&staging.join("git-context.json"), &git_context(options))?;
+
+    for result in results {
+        let file_name = format!(
+            "{}-{}.log",
+            module_key(&result.module_path).replace('/', "_"),
+            result.workspace.as_deref().unwrap_or("default")
+        );
+        let mut content = result.output.join("\n");
+        if let Some(error) = &result.error {
+            content.push_str(&format!("\n\nerror: {}\n", error));
+        }
+        std::fs::write(logs.join(&file_name), content)
+            .map_err(|e| format!("Failed to write log {}: {}", file_name, e))?;
+    }
+
+    Ok(())
+}
+
+fn archive(output_dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let bundle = output_dir.join(format!("{}.tar.gz", name));
+    let output = Command::new("tar")
+        .arg("-czf")
+        .arg(&bundle)
+        .arg("-C")
+        .arg(output_dir)
+        .arg(name)
+        .output()
+        .map_err(|e| format!("Failed to run tar: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!("tar failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(bundle)
+}
+
+fn result_json(result: &OperationResult) -> serde_json::Value {
+    let operation = match result.operation_type {
+        OperationType::Init => "init",
+        OperationType::Plan { .. } => "plan",
+        OperationType::Apply { .. } => "apply",
+    };
+    serde_json::json!({
+        "module": module_key(&result.module_path),
+        "workspace": result.workspace.as_deref().unwrap_or("default"),
+        "operation": operation,
+        "success": result.success,
+        "cancelled": result.cancelled,
+        "error": result.error,
+        "data_dir": result.data_dir,
+        "artifacts": {
+            "report": result.artifacts.report,
+            "plan_file": result.artifacts.plan_file,
+            "plan_json": result.artifacts.plan_json,
+            "summary": result.artifacts.summary_file,
+            "state_snapshot": result.artifacts.state_snapshot,
+        },
+    })
+}
+
+/// Versions of solarboat, terraform and git, and the platform they ran on
+pub fn environment_fingerprint() -> serde_json::Value {
+    serde_json::json!({
+        "solarboat": env!("CARGO_PKG_VERSION"),
+        "terraform": command_output("terraform", &["version"]),
+        "git": command_output("git", &["--version"]),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "ci": std::env::var("CI").is_ok(),
+        "github_actions": std::env::var("GITHUB_ACTIONS").is_ok(),
+    })
+}
+
+/// Git state and detection inputs used to pick the changed modules
+pub fn git_context(options: &TriageOptions) -> serde_json::Value {
+    serde_json::json!({
+        "root_dir": options.root_dir,
+        "default_branch": options.default_branch,
+        "recent_commits": options.recent_commits,
+        "all": options.all,
+        "head": command_output("git", &["rev-parse", "HEAD"]),
+        "branch": command_output("git", &["rev-parse", "--abbrev-ref", "HEAD"]),
+        "merge_base": command_output("git", &["merge-base", "HEAD", &options.default_branch]),
+        "status": command_output("git", &["status", "--porcelain"]),
+        "recent_log": command_output("git", &["log", "--oneline", "-n", &options.recent_commits.max(1).to_string()]),
+    })
+}
+
+/// Trimmed stdout of a command, or None if it could not be run or failed
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+}
+
+fn write_json(path: &Path, value: &serde_json::Value) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    std::fs::write(path, content)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::terraform_operations::PlanArtifacts;
+
+    #[test]
+    fn test_write_bundle() {
+        let dir = std::env::temp_dir().join(format!("solarboat-triage-test-{}", std::process::id()));
+        let options = TriageOptions {
+            output_dir: dir.to_string_lossy().to_string(),
+            root_dir: ".".to_string(),
+            default_branch: "main".to_string(),
+            recent_commits: 5,
+            all: false,
+        };
+        let results = vec![OperationResult {
+            module_path: "stacks/networking".to_string(),
+            workspace: Some("prod".to_string()),
+            operation_type: OperationType::Apply { plan_file: None, report_dir: None, snapshot: false },
+            success: false,
+            error: Some("Apply failed".to_string()),
+            output: vec!["Error: access denied".to_string()],
+            artifacts: PlanArtifacts::default(),
+            data_dir: None,
+            env: Default::default(),
+            cancelled: false,
+        }];
+
+        let bundle = write_bundle(&options, "apply", &results, &serde_json::json!({})).unwrap();
+        let listing = Command::new("tar").arg("-tzf").arg(&bundle).output().unwrap();
+        let listing = String::from_utf8_lossy(&listing.stdout);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(listing.contains("results.json"));
+        assert!(listing.contains("git-context.json"));
+        assert!(listing.contains("logs/stacks_networking-prod.log"));
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::utils::logger;
+use crate::utils::module_cache::CACHE_DIR;
+use crate::utils::plan_integrity::{sha256, to_hex};
+
+/// Workspaces listed during this run, by cache key
+static LISTED: Mutex<BTreeMap<String, Vec<String>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedWorkspaces {
+    workspaces: Vec<String>,
+    /// Seconds since the Unix epoch
+    listed_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    #[serde(default)]
+    modules: BTreeMap<String, CachedWorkspaces>,
+}
+
+/// File keeping workspace listings between runs, relative to where solarboat runs
+pub fn cache_path() -> PathBuf {
+    Path::new(CACHE_DIR).join("workspaces.json")
+}
+
+/// Key of a module's workspace listing. Listings only match while the module is listed through
+/// the same data directory, backend configuration and environment variables.
+pub fn cache_key(module_path: &str, data_dir: Option<&str>, backend_config: &[String], env: &BTreeMap<String, String>) -> String {
+    let module = std::fs::canonicalize(module_path)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| module_path.to_string());
+    let mut data = Vec::new();
+    for part in [module.as_str(), data_dir.unwrap_or("")].into_iter().chain(backend_config.iter().map(String::as_str)) {
+        data.extend_from_slice(part.as_bytes());
+        data.push(0);
+    }
+    for (name, value) in env {
+        data.extend_from_slice(format!("{}={}", name, value).as_bytes());
+        data.push(0);
+    }
+    to_hex(&sha256(&data))
+}
+
+/// Workspaces of the listing with `key`, running `list` only when they aren't known yet. Listings
+/// are reused for the rest of the run, and with a `ttl` also by later runs until they are older than it.
+pub fn cached_workspaces(
+    key: &str,
+    ttl: Option<Duration>,
+    list: impl FnOnce() -> Result<Vec<String>, String>,
+) -> Result<Vec<String>, String> {
+    cached_workspaces_in(&cache_path(), key, ttl, list)
+}
+
+fn cached_workspaces_in(
+    path: &Path,
+    key: &str,
+    ttl: Option<Duration>,
+    list: impl FnOnce() -> Result<Vec<String>, String>,
+) -> Result<Vec<String>, String> {
+    if let Some(workspaces) = LISTED.lock().ok().and_then(|listed| listed.get(key).cloned()) {
+        return Ok(workspaces);
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let cache = ttl.map(|_| load(path));
+    let fresh = |cached: &CachedWorkspaces| ttl.is_some_and(|ttl| now.saturating_sub(cached.listed_at) <= ttl.as_secs());
+
+    let workspaces = match cache.as_ref().and_then(|cache| cache.modules.get(key)).filter(|cached| fresh(cached)) {
+        Some(cached) => {
+            logger::debug(&format!("Using workspaces cached {}s ago", now.saturating_sub(cached.listed_at)));
+            cached.workspaces.clone()
+        }
+        None => {
+            let workspaces = list()?;
+            if let Some(mut cache) = cache {
+                cache.modules.retain(|_, cached| fresh(cached));
+                cache.modules.insert(key.to_string(), CachedWorkspaces { workspaces: workspaces.clone(), listed_at: now });
+                if let Err(e) = save(path, &cache) {
+                    logger::warn(&format!("Failed to cache workspaces: {}", e));
+                }
+            }
+            workspaces
+        }
+    };
+
+    if let Ok(mut listed) = LISTED.lock() {
+        listed.insert(key.to_string(), workspaces.clone());
+    }
+    Ok(workspaces)
+}
+
+/// Drop the listing with `key`, for this run and later ones, after workspaces were created
+pub fn forget(key: &str) {
+    forget_in(&cache_path(), key)
+}
+
+fn forget_in(path: &Path, key: &str) {
+    if let Ok(mut listed) = LISTED.lock() {
+        listed.remove(key);
+    }
+    let mut cache = load(path);
+    if cache.modules.remove(key).is_some() {
+        if let Err(e) = save(path, &cache) {
+            logger::warn(&format!("Failed to update workspace cache: {}", e));
+        }
+    }
+}
+
+fn load(path: &Path) -> CacheFile {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(path: &Path, cache: &CacheFile) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let content = serde_json::to_string(cache)
+        .map_err(|e| format!("Failed to serialize workspace cache: {}", e))?;
+    std::fs::write(path, content)
+        .map_err(|e| format!("Failed to write workspace cache {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_workspaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("workspaces.json");
+        let key = cache_key("/repo/stacks/app", None, &["bucket=tf-state".to_string()], &BTreeMap::new());
+        assert_ne!(key, cache_key("/repo/stacks/app", None, &[], &BTreeMap::new()));
+        let listed = || Ok(vec!["default".to_string(), "prod".to_string()]);
+        let unexpected = || -> Result<Vec<String>, String> { panic!("workspaces listed again") };
+
+        let ttl = Some(Duration::from_secs(3600));
+        assert_eq!(cached_workspaces_in(&path, &key, ttl, listed).unwrap(), vec!["default", "prod"]);
+        assert_eq!(cached_workspaces_in(&path, &key, None, unexpected).unwrap(), vec!["default", "prod"]);
+
+        // A later run reads the listing from disk while it is younger than the TTL
+        LISTED.lock().unwrap().remove(&key);
+        assert_eq!(cached_workspaces_in(&path, &key, ttl, unexpected).unwrap(), vec!["default", "prod"]);
+        LISTED.lock().unwrap().remove(&key);
+        let relisted = || Ok(vec!["default".to_string()]);
+        assert_eq!(cached_workspaces_in(&path, &key, None, relisted).unwrap(), vec!["default"]);
+
+        // A forgotten listing is listed again, even within the TTL
+        forget_in(&path, &key);
+        let created = || Ok(vec!["default".to_string(), "prod".to_string(), "qa".to_string()]);
+        assert_eq!(cached_workspaces_in(&path, &key, ttl, created).unwrap(), vec!["default", "prod", "qa"]);
+    }
+}
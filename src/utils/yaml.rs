@@ -0,0 +1,47 @@
+use serde_json::Value;
+
+/// Parse a YAML document into a JSON value. Anchors, aliases and `<<` merge keys are resolved;
+/// mapping keys must be strings, and an empty document is null.
+pub fn from_str(content: &str) -> Result<Value, String> {
+    let mut document: serde_yaml_ng::Value = serde_yaml_ng::from_str(content).map_err(|e| e.to_string())?;
+    document.apply_merge().map_err(|e| e.to_string())?;
+    serde_json::to_value(document).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        let content = "# module settings\n\
+            ignore_workspaces: [dev, 'pr-*']\n\
+            tags:\n\
+            - network   # owned by the network team\n\
+            - \"prod-critical\"\n\
+            hooks:\n  pre_plan: \"make fmt\"\n\
+            vars:\n  azs:\n    - a\n    - b\n  count: 3\n  url: http://example.com\n\
+            tf_parallelism: ~\n";
+        let value = from_str(content).unwrap();
+        assert_eq!(value, serde_json::json!({
+            "ignore_workspaces": ["dev", "pr-*"],
+            "tags": ["network", "prod-critical"],
+            "hooks": {"pre_plan": "make fmt"},
+            "vars": {"azs": ["a", "b"], "count": 3, "url": "http://example.com"},
+            "tf_parallelism": null,
+        }));
+
+        let document = serde_json::json!({
+            "modules": [{"path": "stacks/app", "depends_on": ["modules/vpc"], "used_by": [], "stateful": true}],
+            "base ref": null,
+            "Yes": "N",
+            "~": "1.0",
+        });
+        assert_eq!(from_str(&serde_yaml_ng::to_string(&document).unwrap()).unwrap(), document);
+
+        let content = "base: &base\n  pre_plan: make fmt\nhooks:\n  <<: *base\n  post_plan: |\n    echo done\n";
+        assert_eq!(from_str(content).unwrap()["hooks"], serde_json::json!({"pre_plan": "make fmt", "post_plan": "echo done\n"}));
+        assert_eq!(from_str("# nothing here\n").unwrap(), Value::Null);
+        assert!(from_str("tags: [a, b\n").is_err());
+    }
+}
@@ -1,5 +1,6 @@
+use solarboat::utils::hooks::Hooks;
 use solarboat::utils::parallel_processor::ParallelProcessor;
-use solarboat::utils::terraform_operations::{TerraformOperation, OperationType};
+use solarboat::utils::terraform_operations::{TerraformOperation, OperationType, PlanFormat, TerraformArgs};
 
 #[test]
 fn test_parallel_processor_creation_and_clamping() {
@@ -28,10 +29,14 @@ fn test_single_operation() {
     let operation = TerraformOperation {
         module_path: "test_module".to_string(),
         workspace: Some("test_workspace".to_string()),
-        operation_type: OperationType::Plan { plan_dir: None },
+        operation_type: OperationType::Plan { plan_dir: None, format: PlanFormat::Markdown, skip_empty: false },
         var_files: vec!["test.tfvars".to_string()],
+        args: TerraformArgs::default(),
         watch: false,
         skip_init: true,
+        data_dir: None,
+        hooks: Hooks::default(),
+        env: Default::default(),
     };
     
     processor.add_operation(operation).expect("Failed to add operation");
@@ -56,10 +61,14 @@ fn test_multiple_operations() {
         let operation = TerraformOperation {
             module_path: format!("test_module_{}", i),
             workspace: Some(format!("test_workspace_{}", i)),
-            operation_type: OperationType::Plan { plan_dir: None },
+            operation_type: OperationType::Plan { plan_dir: None, format: PlanFormat::Markdown, skip_empty: false },
             var_files: vec!["test.tfvars".to_string()],
+            args: TerraformArgs::default(),
             watch: false,
             skip_init: true,
+            data_dir: None,
+            hooks: Hooks::default(),
+            env: Default::default(),
         };
         processor.add_operation(operation).expect("Failed to add operation");
     }
@@ -91,10 +100,14 @@ fn test_parallel_limit() {
         let operation = TerraformOperation {
             module_path: format!("test_module_{}", i),
             workspace: Some(format!("test_workspace_{}", i)),
-            operation_type: OperationType::Plan { plan_dir: None },
+            operation_type: OperationType::Plan { plan_dir: None, format: PlanFormat::Markdown, skip_empty: false },
             var_files: vec!["test.tfvars".to_string()],
+            args: TerraformArgs::default(),
             watch: false,
             skip_init: true,
+            data_dir: None,
+            hooks: Hooks::default(),
+            env: Default::default(),
         };
         processor.add_operation(operation).expect("Failed to add operation");
     }
@@ -112,10 +125,14 @@ fn test_apply_operations() {
         let operation = TerraformOperation {
             module_path: format!("test_module_{}", i),
             workspace: Some(format!("test_workspace_{}", i)),
-            operation_type: OperationType::Apply,
+            operation_type: OperationType::Apply { plan_file: None, report_dir: None, snapshot: false },
             var_files: vec!["test.tfvars".to_string()],
+            args: TerraformArgs::default(),
             watch: false,
             skip_init: true,
+            data_dir: None,
+            hooks: Hooks::default(),
+            env: Default::default(),
         };
         processor.add_operation(operation).expect("Failed to add operation");
     }
@@ -126,7 +143,7 @@ fn test_apply_operations() {
     
     for result in results {
         match result.operation_type {
-            OperationType::Apply => {},
+            OperationType::Apply { .. } => {},
             _ => panic!("Expected Apply operation"),
         }
     }
@@ -141,10 +158,14 @@ fn test_high_parallel_limit() {
         let operation = TerraformOperation {
             module_path: format!("test_module_{}", i),
             workspace: Some(format!("test_workspace_{}", i)),
-            operation_type: OperationType::Plan { plan_dir: None },
+            operation_type: OperationType::Plan { plan_dir: None, format: PlanFormat::Markdown, skip_empty: false },
             var_files: vec!["test.tfvars".to_string()],
+            args: TerraformArgs::default(),
             watch: false,
             skip_init: true,
+            data_dir: None,
+            hooks: Hooks::default(),
+            env: Default::default(),
         };
         processor.add_operation(operation).expect("Failed to add operation");
     }
@@ -163,10 +184,14 @@ fn test_module_grouping() {
         let operation = TerraformOperation {
             module_path: "shared_module".to_string(),
             workspace: Some(workspace.to_string()),
-            operation_type: OperationType::Plan { plan_dir: None },
+            operation_type: OperationType::Plan { plan_dir: None, format: PlanFormat::Markdown, skip_empty: false },
             var_files: vec!["test.tfvars".to_string()],
+            args: TerraformArgs::default(),
             watch: false,
             skip_init: true,
+            data_dir: None,
+            hooks: Hooks::default(),
+            env: Default::default(),
         };
         processor.add_operation(operation).expect("Failed to add operation");
     }
@@ -176,10 +201,14 @@ fn test_module_grouping() {
         let operation = TerraformOperation {
             module_path: module.to_string(),
             workspace: Some("default".to_string()),
-            operation_type: OperationType::Plan { plan_dir: None },
+            operation_type: OperationType::Plan { plan_dir: None, format: PlanFormat::Markdown, skip_empty: false },
             var_files: vec!["test.tfvars".to_string()],
+            args: TerraformArgs::default(),
             watch: false,
             skip_init: true,
+            data_dir: None,
+            hooks: Hooks::default(),
+            env: Default::default(),
         };
         processor.add_operation(operation).expect("Failed to add operation");
     }
@@ -218,6 +247,34 @@ fn test_module_grouping() {
     assert_eq!(another_module_results.len(), 1);
 }
 
+#[test]
+fn test_fail_fast_cancels_queued_operations() {
+    let mut processor = ParallelProcessor::new(1).with_fail_fast(true);
+    
+    // Missing modules fail to initialize
+    for i in 0..3 {
+        let operation = TerraformOperation {
+            module_path: format!("missing_module_{}", i),
+            workspace: None,
+            operation_type: OperationType::Plan { plan_dir: None, format: PlanFormat::Markdown, skip_empty: false },
+            var_files: Vec::new(),
+            args: TerraformArgs::default(),
+            watch: false,
+            skip_init: true,
+            data_dir: None,
+            hooks: Hooks::default(),
+            env: Default::default(),
+        };
+        processor.add_operation(operation).expect("Failed to add operation");
+    }
+    
+    processor.start().expect("Failed to start processor");
+    let results = processor.wait_for_completion().expect("Failed to wait for completion");
+    assert_eq!(results.len(), 3);
+    assert_eq!(results.iter().filter(|r| !r.success && !r.cancelled).count(), 1);
+    assert_eq!(results.iter().filter(|r| r.cancelled).count(), 2);
+}
+
 #[test]
 fn test_cli_parallel_argument_parsing() {
     use solarboat::cli::Args;